@@ -1,9 +1,13 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
 
 use crate::app_runtime::AppHandle;
 use crate::errors::Error;
 use crate::mod_manager::{AddonAnalyzer, AnalyzeAddonsResult};
-use vpk_parser::{VpkParseOptions, VpkParsed, VpkParser};
+use tauri::Manager;
+use vpk_parser::{
+  VpkEntryIndex, VpkIndexHit, VpkIndexQuery, VpkParseOptions, VpkParsed, VpkParser,
+};
 
 use super::state::MANAGER;
 
@@ -17,11 +21,6 @@ pub fn parse_vpk_file(
 
   let path = PathBuf::from(&file_path);
 
-  let vpk_data = std::fs::read(&path).map_err(|e| {
-    log::error!("Failed to read VPK file {file_path}: {e}");
-    e
-  })?;
-
   let metadata = std::fs::metadata(&path).map_err(|e| {
     log::error!("Failed to get metadata for {file_path}: {e}");
     e
@@ -39,9 +38,11 @@ pub fn parse_vpk_file(
     last_modified,
     include_merkle: include_merkle.unwrap_or(false),
     include_entries: true,
+    hash_archive_data: false,
+    lenient: false,
   };
 
-  let parsed = VpkParser::parse(vpk_data, options)
+  let parsed = VpkParser::parse_file(&path, options)
     .map_err(|e| Error::InvalidInput(format!("Failed to parse VPK file {file_path}: {e}")))?;
 
   log::info!(
@@ -113,3 +114,105 @@ pub async fn clear_all_mods_data() -> Result<u64, Error> {
   let mod_manager = MANAGER.lock().unwrap();
   mod_manager.clear_all_mods_data()
 }
+
+const ENTRY_INDEX_FILE: &str = "vpk-entry-index.json";
+
+/// Loaded from disk on first search and kept in memory afterwards.
+static ENTRY_INDEX: LazyLock<Mutex<Option<VpkEntryIndex>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Whether a file name is a `_NNN.vpk` companion, which holds entry data but no
+/// directory tree of its own.
+fn is_companion_archive(name: &str) -> bool {
+  name
+    .strip_suffix(".vpk")
+    .and_then(|stem| stem.rsplit_once('_'))
+    .is_some_and(|(_, suffix)| suffix.len() == 3 && suffix.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn collect_indexable_vpks(dir: &Path, out: &mut Vec<PathBuf>) {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for entry in entries.filter_map(|e| e.ok()) {
+    let path = entry.path();
+    if path.is_dir() {
+      collect_indexable_vpks(&path, out);
+    } else if let Some(name) = path.file_name().and_then(|n| n.to_str())
+      && name.ends_with(".vpk")
+      && !is_companion_archive(name)
+    {
+      out.push(path);
+    }
+  }
+}
+
+/// Search the entries of every VPK in the addons folders and the game's own
+/// `pak01_dir.vpk`. The index is refreshed before each search, so only archives
+/// added or changed since the last one are parsed again.
+#[tauri::command]
+pub async fn search_vpk_entries(
+  app_handle: AppHandle,
+  query: VpkIndexQuery,
+  limit: Option<usize>,
+) -> Result<Vec<VpkIndexHit>, Error> {
+  let game_path = {
+    let mod_manager = MANAGER.lock().unwrap();
+    match mod_manager.get_steam_manager().get_game_path() {
+      Some(path) => path.clone(),
+      None => return Err(Error::GamePathNotSet),
+    }
+  };
+  let index_path = app_handle
+    .path()
+    .app_local_data_dir()
+    .map_err(Error::Tauri)?
+    .join(ENTRY_INDEX_FILE);
+
+  tauri::async_runtime::spawn_blocking(move || {
+    let citadel_path = game_path.join("game").join("citadel");
+    let mut vpk_paths = Vec::new();
+    collect_indexable_vpks(&citadel_path.join("addons"), &mut vpk_paths);
+    let base_vpk = citadel_path.join("pak01_dir.vpk");
+    if base_vpk.exists() {
+      vpk_paths.push(base_vpk);
+    }
+
+    let mut guard = ENTRY_INDEX.lock().unwrap();
+    let index = guard.get_or_insert_with(|| VpkEntryIndex::load(&index_path));
+    let refresh = index.refresh(&vpk_paths);
+    for failed in &refresh.failed {
+      log::warn!("Skipped unreadable VPK while indexing entries: {failed}");
+    }
+    if refresh.parsed > 0 || refresh.removed > 0 {
+      log::info!(
+        "VPK entry index updated: {} parsed, {} reused, {} removed",
+        refresh.parsed,
+        refresh.reused,
+        refresh.removed
+      );
+      if let Err(e) = index.save(&index_path) {
+        log::warn!("Failed to save VPK entry index: {e}");
+      }
+    }
+
+    index
+      .search(&query, limit)
+      .map_err(|e| Error::InvalidInput(format!("Invalid VPK entry query: {e}")))
+  })
+  .await
+  .map_err(|e| Error::BackgroundTaskFailed(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+  use super::is_companion_archive;
+
+  #[test]
+  fn companion_archives_are_not_indexed() {
+    assert!(is_companion_archive("pak01_000.vpk"));
+    assert!(is_companion_archive("pak01_012.vpk"));
+    assert!(!is_companion_archive("pak01_dir.vpk"));
+    assert!(!is_companion_archive("pak01.vpk"));
+    assert!(!is_companion_archive("my_mod_v1.vpk"));
+  }
+}
@@ -163,6 +163,7 @@ pub fn run() {
       hero_detector::clear_vpk_entry_cache,
       commands::vpk::check_addons_exist,
       commands::vpk::analyze_local_addons,
+      commands::vpk::search_vpk_entries,
       commands::foundry::foundry_analyze_vpk,
       commands::foundry::foundry_analyze_default_hero,
       commands::foundry::foundry_resolve_mod_vpk,
@@ -602,10 +602,6 @@ impl AddonAnalyzer {
       ));
     }
 
-    // Read the VPK file
-    let vpk_data =
-      std::fs::read(file_path).map_err(|e| format!("Failed to read {}: {}", file_name, e))?;
-
     // Use minimal parsing options for speed
     let options = VpkParseOptions {
       include_full_file_hash: false, // Skip expensive full file hash
@@ -613,9 +609,12 @@ impl AddonAnalyzer {
       last_modified,
       include_merkle: false, // Skip merkle tree calculation for now
       include_entries: true, // Include entries for unidentified mod analysis
+      hash_archive_data: false,
+      lenient: false,
     };
 
-    let parsed = VpkParser::parse(vpk_data, options)
+    // Memory-mapped, so the directory file is hashed without a heap copy
+    let parsed = VpkParser::parse_file(file_path, options)
       .map_err(|e| format!("Failed to parse {}: {}", file_name, e))?;
 
     Ok(LocalAddonInfo {
@@ -365,6 +365,8 @@ impl FontManager {
       file_path: vpk_path.to_string_lossy().to_string(),
       last_modified: None,
       include_merkle: false,
+      hash_archive_data: false,
+      lenient: false,
     };
 
     // Move the buffer into the parser to avoid keeping two full copies in memory.
@@ -0,0 +1,933 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::{BundleConfig, ModConfig};
+use crate::error::{DmodpkgError, Result};
+use crate::format::{
+    decode_package_index, decode_resources, encode_package_index, encode_resources, BundleHeader,
+    BundleMetadataSection, CRC64, DEFAULT_COMPRESSION_LEVEL, FORMAT_VERSION, HEADER_SIZE,
+};
+use crate::reader::{read_at, read_compressed, read_section, stream_len, PackageReader};
+use crate::resolver::{enabled_layers, Selection};
+use crate::types::{BundleBuildInfo, BundlePreset, IncludedMod, PackageIndexEntry};
+use crate::writer::{build_timestamp, is_url, normalize_path, project_paths, Source};
+
+/// Name of the configuration file at the root of a bundle project
+pub const BUNDLE_CONFIG_FILE_NAME: &str = "bundle.config.json";
+
+/// Directory holding the bundled `.dmodpkg` files
+pub const MODS_DIR: &str = "mods";
+
+/// Options controlling how a bundle is built
+#[derive(Debug, Clone)]
+pub struct BundleOptions {
+    /// Zstd compression level (1-22) for metadata and resources
+    pub compression_level: i32,
+    /// Build timestamp to record; when unset it comes from `SOURCE_DATE_EPOCH`,
+    /// else the current time. Set either one for byte-identical rebuilds.
+    pub build_timestamp: Option<String>,
+}
+
+impl Default for BundleOptions {
+    fn default() -> Self {
+        Self {
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            build_timestamp: None,
+        }
+    }
+}
+
+/// Summary of a written bundle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleSummary {
+    /// Number of embedded packages
+    pub package_count: usize,
+    /// Number of embedded resources
+    pub resource_count: usize,
+    /// Size of the written bundle
+    pub bundle_size: u64,
+}
+
+#[derive(Debug, Clone)]
+struct PendingPackage {
+    filename: String,
+    source: Source,
+    size: u64,
+    sha256: [u8; 32],
+    config: ModConfig,
+}
+
+/// Builds a `.dmodbundle` from a bundle configuration and its packages
+///
+/// Every package listed in the configuration must be added. Packages are
+/// verified as they are added and embedded unchanged, in configuration
+/// order; presets are checked against the variant groups of the packages
+/// they configure.
+#[derive(Debug, Clone)]
+pub struct BundleWriter {
+    config: BundleConfig,
+    options: BundleOptions,
+    packages: Vec<PendingPackage>,
+    resources: BTreeMap<String, Vec<u8>>,
+}
+
+impl BundleWriter {
+    /// Create a writer for `config` with no packages
+    pub fn new(config: BundleConfig, options: BundleOptions) -> Result<Self> {
+        config.validate()?;
+
+        if !zstd::compression_level_range().contains(&options.compression_level) {
+            return Err(DmodpkgError::validation(format!(
+                "compression level {} is out of range",
+                options.compression_level
+            )));
+        }
+
+        Ok(Self {
+            config,
+            options,
+            packages: Vec::new(),
+            resources: BTreeMap::new(),
+        })
+    }
+
+    /// Create a writer from a project directory containing
+    /// `bundle.config.json` and the listed packages under `mods/`. `path`
+    /// may also point at the configuration file itself.
+    pub fn from_project(path: impl AsRef<Path>, options: BundleOptions) -> Result<Self> {
+        let (dir, config_path) = project_paths(path.as_ref(), BUNDLE_CONFIG_FILE_NAME);
+        let dir = dir.as_path();
+        let config = BundleConfig::from_json(&fs::read_to_string(&config_path)?)?;
+        let mut writer = Self::new(config, options)?;
+
+        let packages: Vec<String> = writer
+            .config
+            .mods
+            .iter()
+            .map(|m| m.package.clone())
+            .collect();
+        for package in packages {
+            let file = dir.join(MODS_DIR).join(&package);
+            if !file.is_file() {
+                return Err(DmodpkgError::validation(format!(
+                    "package not found: {}",
+                    file.display()
+                )));
+            }
+            writer.push_package(&package, Source::Disk(file))?;
+        }
+
+        let screenshots: Vec<String> = writer
+            .config
+            .screenshots
+            .iter()
+            .filter(|s| !is_url(s))
+            .cloned()
+            .collect();
+        for screenshot in screenshots {
+            let file = dir.join(&screenshot);
+            if !file.is_file() {
+                return Err(DmodpkgError::validation(format!(
+                    "referenced file not found: {}",
+                    screenshot
+                )));
+            }
+            writer.add_resource(&screenshot, fs::read(&file)?)?;
+        }
+
+        Ok(writer)
+    }
+
+    /// The configuration being packaged
+    pub fn config(&self) -> &BundleConfig {
+        &self.config
+    }
+
+    /// Add a package held in memory
+    pub fn add_package(&mut self, filename: &str, data: Vec<u8>) -> Result<()> {
+        self.push_package(filename, Source::Memory(data))
+    }
+
+    /// Add a package file from disk
+    pub fn add_package_file(&mut self, filename: &str, path: impl AsRef<Path>) -> Result<()> {
+        self.push_package(filename, Source::Disk(path.as_ref().to_path_buf()))
+    }
+
+    /// Embed a resource such as a screenshot
+    pub fn add_resource(&mut self, path: &str, data: Vec<u8>) -> Result<()> {
+        self.resources.insert(normalize_path(path)?, data);
+        Ok(())
+    }
+
+    fn push_package(&mut self, filename: &str, source: Source) -> Result<()> {
+        if !self.config.mods.iter().any(|m| m.package == filename) {
+            return Err(DmodpkgError::validation(format!(
+                "package '{}' is not listed in the bundle configuration",
+                filename
+            )));
+        }
+        if self.packages.iter().any(|p| p.filename == filename) {
+            return Err(DmodpkgError::invalid_structure(format!(
+                "duplicate package '{}'",
+                filename
+            )));
+        }
+
+        let config = match &source {
+            Source::Memory(data) => PackageReader::new(Cursor::new(data)).and_then(inspect_package),
+            Source::Disk(path) => PackageReader::open(path).and_then(inspect_package),
+        }
+        .map_err(|e| package_error(filename, e))?;
+
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut source.open()?, &mut hasher)?;
+
+        self.packages.push(PendingPackage {
+            filename: filename.to_string(),
+            source,
+            size,
+            sha256: hasher.finalize().into(),
+            config,
+        });
+        Ok(())
+    }
+
+    /// Write the bundle to `out`
+    pub fn write<W: Write>(mut self, out: &mut W) -> Result<BundleSummary> {
+        // Embed packages in configuration order
+        let order: Vec<&str> = self
+            .config
+            .mods
+            .iter()
+            .map(|m| m.package.as_str())
+            .collect();
+        if let Some(missing) = order
+            .iter()
+            .find(|name| !self.packages.iter().any(|p| p.filename == **name))
+        {
+            return Err(DmodpkgError::invalid_structure(format!(
+                "package '{}' was not added to the bundle",
+                missing
+            )));
+        }
+        self.packages
+            .sort_by_key(|p| order.iter().position(|name| *name == p.filename));
+
+        let mod_configs: HashMap<String, ModConfig> = self
+            .packages
+            .iter()
+            .map(|p| (p.filename.clone(), p.config.clone()))
+            .collect();
+        for preset in &self.config.presets {
+            apply_preset(&self.config, preset, &mod_configs)?;
+        }
+
+        let mut index = Vec::with_capacity(self.packages.len());
+        let mut included_mods = Vec::with_capacity(self.packages.len());
+        let mut offset = 0u64;
+        for package in &self.packages {
+            index.push(PackageIndexEntry {
+                filename: package.filename.clone(),
+                offset,
+                size: package.size,
+                sha256: package.sha256,
+            });
+            included_mods.push(IncludedMod {
+                filename: package.filename.clone(),
+                name: package.config.name.clone(),
+                version: package.config.version.clone(),
+                size_bytes: package.size,
+                checksum: format!("sha256:{}", hex(&package.sha256)),
+            });
+            offset += package.size;
+        }
+
+        let metadata = BundleMetadataSection {
+            config: serde_json::to_value(&self.config)?,
+            build_info: BundleBuildInfo {
+                builder_version: crate::version().to_string(),
+                build_timestamp: build_timestamp(self.options.build_timestamp.as_deref()),
+                included_mods,
+            },
+        };
+        let metadata_json = serde_json::to_vec(&metadata)?;
+        let metadata_compressed = self.compress(&metadata_json)?;
+
+        let package_index = encode_package_index(&index)?;
+
+        let resources: Vec<(String, Vec<u8>)> =
+            std::mem::take(&mut self.resources).into_iter().collect();
+        let resource_count = resources.len();
+        let resources = encode_resources(&resources)?;
+        let resources_compressed = self.compress(&resources)?;
+
+        let mut header = BundleHeader::new();
+        header.bundle_metadata_offset = to_u32(HEADER_SIZE)?;
+        header.bundle_metadata_compressed_size = to_u32(metadata_compressed.len())?;
+        header.bundle_metadata_uncompressed_size = to_u32(metadata_json.len())?;
+        header.package_index_offset =
+            header.bundle_metadata_offset + header.bundle_metadata_compressed_size;
+        header.package_index_size = to_u32(package_index.len())?;
+        header.resources_offset = header
+            .package_index_offset
+            .checked_add(header.package_index_size)
+            .ok_or_else(too_large)?;
+        header.resources_compressed_size = to_u32(resources_compressed.len())?;
+        header.resources_uncompressed_size = to_u32(resources.len())?;
+        header.packages_section_offset = header
+            .resources_offset
+            .checked_add(header.resources_compressed_size)
+            .ok_or_else(too_large)?;
+        header.total_bundle_size = header.packages_section_offset as u64 + offset;
+
+        let sections = [&metadata_compressed, &package_index, &resources_compressed];
+        let mut digest = CRC64.digest();
+        for section in sections {
+            digest.update(section);
+        }
+        for package in &self.packages {
+            stream(package, |bytes| {
+                digest.update(bytes);
+                Ok(())
+            })?;
+        }
+        header.bundle_crc64 = digest.finalize();
+
+        out.write_all(&header.to_bytes())?;
+        for section in sections {
+            out.write_all(section)?;
+        }
+        for package in &self.packages {
+            stream(package, |bytes| out.write_all(bytes).map_err(Into::into))?;
+        }
+        out.flush()?;
+
+        Ok(BundleSummary {
+            package_count: self.packages.len(),
+            resource_count,
+            bundle_size: header.total_bundle_size,
+        })
+    }
+
+    /// Write the bundle to a file at `path`
+    pub fn write_to_file(self, path: impl AsRef<Path>) -> Result<BundleSummary> {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+        self.write(&mut out)
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        zstd::bulk::compress(data, self.options.compression_level)
+            .map_err(|e| DmodpkgError::compression(e.to_string()))
+    }
+}
+
+/// Check a package before embedding it and return its configuration
+fn inspect_package<R: Read + Seek>(mut reader: PackageReader<R>) -> Result<ModConfig> {
+    reader.verify()?;
+    reader.config()
+}
+
+fn package_error(filename: &str, error: DmodpkgError) -> DmodpkgError {
+    match error {
+        DmodpkgError::ChecksumMismatch(msg) => {
+            DmodpkgError::checksum_mismatch(format!("{}: {}", filename, msg))
+        }
+        DmodpkgError::Format(msg) => DmodpkgError::format(format!("{}: {}", filename, msg)),
+        DmodpkgError::InvalidStructure(msg) => {
+            DmodpkgError::invalid_structure(format!("{}: {}", filename, msg))
+        }
+        other => other,
+    }
+}
+
+/// Feed a package's bytes to `f`, checking it has not changed size since it
+/// was added
+fn stream(package: &PendingPackage, mut f: impl FnMut(&[u8]) -> Result<()>) -> Result<()> {
+    let mut input = package.source.open()?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut total = 0u64;
+    loop {
+        let read = input.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+        f(&buffer[..read])?;
+    }
+
+    if total != package.size {
+        return Err(DmodpkgError::invalid_structure(format!(
+            "package '{}' changed while the bundle was being written",
+            package.filename
+        )));
+    }
+    Ok(())
+}
+
+/// Turn a preset into a variant selection for every package in the bundle
+///
+/// Packages the preset does not mention get their default variants. Each
+/// selection is checked against the package's configuration when `mods`
+/// (package filename -> configuration) has it.
+pub fn apply_preset(
+    config: &BundleConfig,
+    preset: &BundlePreset,
+    mods: &HashMap<String, ModConfig>,
+) -> Result<BTreeMap<String, Selection>> {
+    if let Some(unknown) = preset
+        .mods
+        .iter()
+        .find(|m| !config.mods.iter().any(|entry| entry.package == m.package))
+    {
+        return Err(DmodpkgError::validation(format!(
+            "preset '{}' references unknown package '{}'",
+            preset.id, unknown.package
+        )));
+    }
+
+    let mut selections = BTreeMap::new();
+    for entry in &config.mods {
+        let mut selection = Selection::default();
+        if let Some(mod_config) = preset.mods.iter().find(|m| m.package == entry.package) {
+            selection.variants = mod_config.variants.clone();
+        }
+
+        if let Some(mod_config) = mods.get(&entry.package) {
+            enabled_layers(mod_config, &selection).map_err(|e| match e {
+                DmodpkgError::Variant(msg) => DmodpkgError::variant(format!(
+                    "preset '{}', package '{}': {}",
+                    preset.id, entry.package, msg
+                )),
+                other => other,
+            })?;
+        }
+
+        selections.insert(entry.package.clone(), selection);
+    }
+
+    Ok(selections)
+}
+
+/// Conventional output file name for a bundle: `<name>-<version>.dmodbundle`
+pub fn bundle_file_name(config: &BundleConfig) -> String {
+    format!("{}-{}.dmodbundle", config.name, config.version)
+}
+
+/// Reader for `.dmodbundle` files
+///
+/// Embedded packages are read in place: [`BundleReader::package`] returns a
+/// [`PackageReader`] over the bundle itself, so nothing is copied out.
+#[derive(Debug)]
+pub struct BundleReader<R> {
+    reader: R,
+    header: BundleHeader,
+    metadata: BundleMetadataSection,
+    packages: Vec<PackageIndexEntry>,
+    resources: Vec<(String, Vec<u8>)>,
+}
+
+impl BundleReader<BufReader<fs::File>> {
+    /// Open a bundle file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(fs::File::open(path)?))
+    }
+}
+
+impl BundleReader<Cursor<Vec<u8>>> {
+    /// Read a bundle held in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Self::new(Cursor::new(bytes))
+    }
+}
+
+impl<R: Read + Seek> BundleReader<R> {
+    /// Parse the header, metadata, package index and resources of a bundle
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut bytes = [0u8; HEADER_SIZE];
+        reader.seek(SeekFrom::Start(0))?;
+        read_section(&mut reader, &mut bytes, "header")?;
+
+        let header = BundleHeader::from_bytes(&bytes)
+            .ok_or_else(|| DmodpkgError::format("truncated header"))?;
+        if !header.is_valid() {
+            return Err(DmodpkgError::format(
+                "not a .dmodbundle file (bad magic bytes)",
+            ));
+        }
+        if header.version > FORMAT_VERSION {
+            return Err(DmodpkgError::format(format!(
+                "unsupported format version {}",
+                header.version
+            )));
+        }
+
+        let metadata_json = read_compressed(
+            &mut reader,
+            header.bundle_metadata_offset,
+            header.bundle_metadata_compressed_size,
+            header.bundle_metadata_uncompressed_size,
+            "bundle metadata",
+        )?;
+        let metadata: BundleMetadataSection = serde_json::from_slice(&metadata_json)?;
+
+        let package_index = read_at(
+            &mut reader,
+            header.package_index_offset as u64,
+            header.package_index_size as u64,
+            "package index",
+        )?;
+        let packages = decode_package_index(&package_index)?;
+
+        let resources = read_compressed(
+            &mut reader,
+            header.resources_offset,
+            header.resources_compressed_size,
+            header.resources_uncompressed_size,
+            "bundle resources",
+        )?;
+        let resources = decode_resources(&resources)?;
+
+        if header.total_bundle_size > stream_len(&mut reader)? {
+            return Err(DmodpkgError::format("truncated bundle"));
+        }
+        let packages_size = header.total_bundle_size
+            - (header.packages_section_offset as u64).min(header.total_bundle_size);
+        for package in &packages {
+            if package
+                .offset
+                .checked_add(package.size)
+                .is_none_or(|end| end > packages_size)
+            {
+                return Err(DmodpkgError::invalid_structure(format!(
+                    "package '{}' lies outside the bundle",
+                    package.filename
+                )));
+            }
+        }
+
+        Ok(Self {
+            reader,
+            header,
+            metadata,
+            packages,
+            resources,
+        })
+    }
+
+    /// The bundle header
+    pub fn header(&self) -> &BundleHeader {
+        &self.header
+    }
+
+    /// The bundle metadata section
+    pub fn metadata(&self) -> &BundleMetadataSection {
+        &self.metadata
+    }
+
+    /// The bundle configuration
+    pub fn config(&self) -> Result<BundleConfig> {
+        serde_json::from_value(self.metadata.config.clone()).map_err(Into::into)
+    }
+
+    /// Every embedded package, in index order
+    pub fn packages(&self) -> &[PackageIndexEntry] {
+        &self.packages
+    }
+
+    /// Every embedded resource as (path, contents)
+    pub fn resources(&self) -> &[(String, Vec<u8>)] {
+        &self.resources
+    }
+
+    fn entry(&self, filename: &str) -> Result<PackageIndexEntry> {
+        self.packages
+            .iter()
+            .find(|p| p.filename == filename)
+            .cloned()
+            .ok_or_else(|| {
+                DmodpkgError::invalid_structure(format!(
+                    "package '{}' not found in bundle",
+                    filename
+                ))
+            })
+    }
+
+    fn embedded(&mut self, entry: &PackageIndexEntry) -> Embedded<&mut R> {
+        Embedded {
+            inner: &mut self.reader,
+            start: self.header.packages_section_offset as u64 + entry.offset,
+            len: entry.size,
+            pos: 0,
+        }
+    }
+
+    /// Open an embedded package for reading without copying it out
+    pub fn package(&mut self, filename: &str) -> Result<PackageReader<Embedded<&mut R>>> {
+        let entry = self.entry(filename)?;
+        PackageReader::new(self.embedded(&entry)).map_err(|e| package_error(filename, e))
+    }
+
+    /// Read an embedded package, checking its SHA-256
+    ///
+    /// The package's size was checked against the bundle when it was opened.
+    pub fn read_package(&mut self, filename: &str) -> Result<Vec<u8>> {
+        let entry = self.entry(filename)?;
+        let mut data = Vec::with_capacity(entry.size as usize);
+        self.embedded(&entry).read_to_end(&mut data)?;
+
+        let sha256: [u8; 32] = Sha256::digest(&data).into();
+        if sha256 != entry.sha256 {
+            return Err(DmodpkgError::checksum_mismatch(format!(
+                "SHA-256 of package '{}' does not match",
+                filename
+            )));
+        }
+        Ok(data)
+    }
+
+    /// Variant selections for every package under a preset, or under the
+    /// bundle's default preset when `preset_id` is `None`
+    ///
+    /// Without a preset every package gets its default variants.
+    pub fn apply_preset(&mut self, preset_id: Option<&str>) -> Result<BTreeMap<String, Selection>> {
+        let config = self.config()?;
+        let preset = match preset_id {
+            Some(id) => Some(
+                config
+                    .preset(id)
+                    .ok_or_else(|| DmodpkgError::variant(format!("unknown preset '{}'", id)))?,
+            ),
+            None => config.default_preset(),
+        };
+
+        let mut mods = HashMap::new();
+        for entry in self.packages.clone() {
+            let mod_config = self.package(&entry.filename)?.config()?;
+            mods.insert(entry.filename, mod_config);
+        }
+
+        match preset {
+            Some(preset) => apply_preset(&config, preset, &mods),
+            None => Ok(config
+                .mods
+                .iter()
+                .map(|m| (m.package.clone(), Selection::default()))
+                .collect()),
+        }
+    }
+
+    /// Recreate the bundle project under `dir`: `bundle.config.json`, the
+    /// packages under `mods/` and the embedded resources
+    pub fn extract(&mut self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir.join(MODS_DIR))?;
+        let config_path = dir.join(BUNDLE_CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            serde_json::to_string_pretty(&self.metadata.config)?,
+        )?;
+
+        let mut written = vec![config_path];
+        for entry in self.packages.clone() {
+            let path = dir.join(MODS_DIR).join(normalize_path(&entry.filename)?);
+            fs::write(&path, self.read_package(&entry.filename)?)?;
+            written.push(path);
+        }
+
+        for (resource, data) in &self.resources {
+            // Never trust paths from the bundle to stay inside `dir`
+            let path = dir.join(normalize_path(resource)?);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, data)?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
+    /// Check the bundle CRC64, then every embedded package in full
+    pub fn verify(&mut self) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+        let mut digest = CRC64.digest();
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read = self.reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            digest.update(&buffer[..read]);
+        }
+        if digest.finalize() != self.header.bundle_crc64 {
+            return Err(DmodpkgError::checksum_mismatch(
+                "bundle CRC64 does not match",
+            ));
+        }
+
+        for entry in self.packages.clone() {
+            self.read_package(&entry.filename)?;
+            self.package(&entry.filename)?
+                .verify()
+                .map_err(|e| package_error(&entry.filename, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A window onto one package inside a bundle
+#[derive(Debug)]
+pub struct Embedded<R> {
+    inner: R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: Read + Seek> Read for Embedded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len {
+            return Ok(0);
+        }
+        let max = (self.len - self.pos).min(buf.len() as u64) as usize;
+        self.inner.seek(SeekFrom::Start(self.start + self.pos))?;
+        let read = self.inner.read(&mut buf[..max])?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for Embedded<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of package")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn to_u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| too_large())
+}
+
+fn too_large() -> DmodpkgError {
+    DmodpkgError::format("bundle sections exceed the 4 GiB offset limit")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::{PackOptions, PackageWriter};
+
+    const SKINS: &str = r#"{
+        "name": "character-skins",
+        "display_name": "Character Skins",
+        "version": "2.0.0",
+        "description": "Skins",
+        "authors": ["Author"],
+        "variant_groups": [{
+            "id": "skin_style",
+            "name": "Skin Style",
+            "default": "light",
+            "variants": [
+                { "id": "light", "name": "Light", "layers": ["light"] },
+                { "id": "dark", "name": "Dark", "layers": ["dark"] }
+            ]
+        }],
+        "layers": [
+            { "name": "base", "priority": 0, "required": true },
+            { "name": "light", "priority": 10 },
+            { "name": "dark", "priority": 10 }
+        ]
+    }"#;
+
+    const UI: &str = r#"{
+        "name": "enhanced-ui",
+        "display_name": "Enhanced UI",
+        "version": "1.5.0",
+        "description": "UI",
+        "authors": ["Author"],
+        "layers": [{ "name": "base", "priority": 0, "required": true }]
+    }"#;
+
+    const BUNDLE: &str = r#"{
+        "name": "visual-pack",
+        "display_name": "Visual Pack",
+        "version": "1.0.0",
+        "description": "A bundle",
+        "authors": ["Curator"],
+        "mods": [
+            { "package": "character-skins-2.0.0.dmodpkg" },
+            { "package": "enhanced-ui-1.5.0.dmodpkg", "required": false }
+        ],
+        "presets": [
+            {
+                "id": "dark_theme",
+                "name": "Dark Theme",
+                "default": true,
+                "mods": [{
+                    "package": "character-skins-2.0.0.dmodpkg",
+                    "variants": { "skin_style": "dark" }
+                }]
+            },
+            {
+                "id": "broken",
+                "name": "Broken",
+                "mods": [{
+                    "package": "character-skins-2.0.0.dmodpkg",
+                    "variants": { "skin_style": "neon" }
+                }]
+            }
+        ]
+    }"#;
+
+    fn package(config: &str, layers: &[&str]) -> Vec<u8> {
+        let options = PackOptions {
+            build_timestamp: Some("2024-01-01T00:00:00Z".to_string()),
+            ..PackOptions::default()
+        };
+        let mut writer =
+            PackageWriter::new(ModConfig::from_json(config).unwrap(), options).unwrap();
+        for layer in layers {
+            writer
+                .add_file(layer, "pak01_dir.vpk", layer.as_bytes().to_vec())
+                .unwrap();
+        }
+        let mut out = Vec::new();
+        writer.write(&mut out).unwrap();
+        out
+    }
+
+    fn bundle(config: &str) -> Result<Vec<u8>> {
+        let options = BundleOptions {
+            build_timestamp: Some("2024-01-01T00:00:00Z".to_string()),
+            ..BundleOptions::default()
+        };
+        let mut writer = BundleWriter::new(BundleConfig::from_json(config).unwrap(), options)?;
+        writer.add_package("enhanced-ui-1.5.0.dmodpkg", package(UI, &["base"]))?;
+        writer.add_package(
+            "character-skins-2.0.0.dmodpkg",
+            package(SKINS, &["base", "light", "dark"]),
+        )?;
+        writer.add_resource("previews/showcase.png", b"png".to_vec())?;
+
+        let mut out = Vec::new();
+        writer.write(&mut out)?;
+        Ok(out)
+    }
+
+    fn working_bundle() -> Vec<u8> {
+        let mut config: serde_json::Value = serde_json::from_str(BUNDLE).unwrap();
+        config["presets"].as_array_mut().unwrap().pop();
+        bundle(&config.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let bytes = working_bundle();
+        assert_eq!(bytes, working_bundle());
+
+        let mut reader = BundleReader::from_bytes(bytes).unwrap();
+        reader.verify().unwrap();
+
+        let names: Vec<&str> = reader
+            .packages()
+            .iter()
+            .map(|p| p.filename.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["character-skins-2.0.0.dmodpkg", "enhanced-ui-1.5.0.dmodpkg"]
+        );
+        let included = &reader.metadata().build_info.included_mods[0];
+        assert_eq!(included.name, "character-skins");
+        assert!(included.checksum.starts_with("sha256:"));
+        assert_eq!(reader.resources()[0].0, "previews/showcase.png");
+
+        let mut skins = reader.package("character-skins-2.0.0.dmodpkg").unwrap();
+        assert_eq!(skins.read_file("dark", "pak01_dir.vpk").unwrap(), b"dark");
+
+        let dir = tempfile::tempdir().unwrap();
+        reader.extract(dir.path()).unwrap();
+        let ui = fs::read(dir.path().join("mods/enhanced-ui-1.5.0.dmodpkg")).unwrap();
+        assert_eq!(ui, package(UI, &["base"]));
+        assert!(dir.path().join("previews/showcase.png").is_file());
+    }
+
+    #[test]
+    fn test_apply_presets() {
+        let mut reader = BundleReader::from_bytes(working_bundle()).unwrap();
+
+        let selections = reader.apply_preset(None).unwrap();
+        assert_eq!(
+            selections["character-skins-2.0.0.dmodpkg"].variants["skin_style"],
+            "dark"
+        );
+        assert!(selections["enhanced-ui-1.5.0.dmodpkg"].variants.is_empty());
+
+        assert!(matches!(
+            reader.apply_preset(Some("missing")),
+            Err(DmodpkgError::Variant(_))
+        ));
+    }
+
+    #[test]
+    fn test_presets_with_unknown_variants() {
+        assert!(matches!(bundle(BUNDLE), Err(DmodpkgError::Variant(_))));
+    }
+
+    #[test]
+    fn test_incomplete_or_corrupted_bundles() {
+        let config = BundleConfig::from_json(BUNDLE).unwrap();
+        let writer = BundleWriter::new(config.clone(), BundleOptions::default()).unwrap();
+        assert!(matches!(
+            writer.write(&mut Vec::new()),
+            Err(DmodpkgError::InvalidStructure(_))
+        ));
+
+        let mut writer = BundleWriter::new(config, BundleOptions::default()).unwrap();
+        assert!(writer
+            .add_package("other.dmodpkg", package(UI, &[]))
+            .is_err());
+        assert!(writer
+            .add_package("enhanced-ui-1.5.0.dmodpkg", b"junk".to_vec())
+            .is_err());
+
+        let mut bytes = working_bundle();
+        let last = bytes.len() - 40;
+        bytes[last] ^= 0xff;
+        let mut reader = BundleReader::from_bytes(bytes).unwrap();
+        assert!(matches!(
+            reader.verify(),
+            Err(DmodpkgError::ChecksumMismatch(_))
+        ));
+        assert!(matches!(
+            reader.read_package("enhanced-ui-1.5.0.dmodpkg"),
+            Err(DmodpkgError::ChecksumMismatch(_))
+        ));
+
+        // Sizes past the end of the file are rejected before allocating
+        let mut bytes = working_bundle();
+        let mut header = BundleHeader::from_bytes(&bytes).unwrap();
+        header.total_bundle_size = u64::MAX;
+        bytes[..HEADER_SIZE].copy_from_slice(&header.to_bytes());
+        assert!(matches!(
+            BundleReader::from_bytes(bytes),
+            Err(DmodpkgError::Format(_))
+        ));
+    }
+}
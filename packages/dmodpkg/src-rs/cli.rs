@@ -0,0 +1,1171 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use dmodpkg::{
+    bundle_file_name, decode_signing_key, package_file_name, validate_bundle,
+    validate_bundle_project, validate_package, validate_project, Author, BundleOptions,
+    BundleReader, BundleWriter, DmodpkgError, Keyring, Layer, ModConfig, PackOptions,
+    PackageReader, PackageWriter, Result, Trust, ValidationReport, Variant, VariantGroup,
+    CONFIG_FILE_NAME, CONTENT_DIR, DEFAULT_COMPRESSION_LEVEL, PREVIEWS_DIR, RESOURCE_LAYER,
+    SCHEMA_VERSION,
+};
+use serde_json::json;
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success
+  1  Error (I/O, malformed package, ...)
+  2  Invalid command-line usage
+  3  Validation failed
+  4  Integrity check failed (checksum or signature mismatch)";
+
+/// Validation found errors (or warnings with --strict)
+const EXIT_VALIDATION: u8 = 3;
+
+/// A checksum or signature did not match
+const EXIT_INTEGRITY: u8 = 4;
+
+/// Create, inspect and validate Deadlock mod packages
+#[derive(Parser)]
+#[command(name = "dmodpkg", version, after_help = EXIT_CODES)]
+struct Cli {
+    /// Print machine-readable JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a new mod project
+    Init(InitArgs),
+    /// Build a .dmodpkg from a mod project
+    Pack(PackArgs),
+    /// Extract the files of a .dmodpkg
+    Extract(ExtractArgs),
+    /// Show information about a .dmodpkg
+    Info {
+        #[command(flatten)]
+        args: InfoArgs,
+        #[command(flatten)]
+        keyring: KeyringArgs,
+    },
+    /// Validate a mod project or .dmodpkg
+    Validate {
+        #[command(flatten)]
+        args: ValidateArgs,
+        #[command(flatten)]
+        keyring: KeyringArgs,
+    },
+    /// Work with .dmodbundle files
+    #[command(subcommand)]
+    Bundle(BundleCommand),
+}
+
+#[derive(Subcommand)]
+enum BundleCommand {
+    /// Build a .dmodbundle from a bundle project
+    Pack(BundlePackArgs),
+    /// Show information about a .dmodbundle
+    Info(InfoArgs),
+    /// Extract the packages of a .dmodbundle
+    Extract(BundleExtractArgs),
+    /// Validate a bundle project or .dmodbundle
+    Validate(ValidateArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Template {
+    /// A single required layer
+    Basic,
+    /// A variant group choosing between two layers
+    Advanced,
+    /// A required layer and two optional layers
+    Multilayer,
+}
+
+#[derive(Args)]
+struct InitArgs {
+    /// Project name in kebab-case; the project is created in ./<name>
+    name: Option<String>,
+    /// Project template
+    #[arg(long, value_enum, default_value = "basic")]
+    template: Template,
+    /// Author name
+    #[arg(long)]
+    author: Option<String>,
+    /// SPDX license identifier
+    #[arg(long, default_value = "MIT")]
+    license: String,
+}
+
+#[derive(Args)]
+struct PackArgs {
+    /// Path to mod.config.json
+    #[arg(long, default_value = CONFIG_FILE_NAME)]
+    config: PathBuf,
+    /// Output directory
+    #[arg(long, default_value = "build")]
+    output: PathBuf,
+    /// Zstd compression level
+    #[arg(long, default_value_t = DEFAULT_COMPRESSION_LEVEL, value_parser = clap::value_parser!(i32).range(1..=22))]
+    compression: i32,
+    /// Chunk size, e.g. 1MB or 512KB
+    #[arg(long, default_value = "1MB", value_parser = parse_size)]
+    chunk_size: usize,
+    /// Skip validation checks
+    #[arg(long)]
+    no_validate: bool,
+    /// Sign the package with the base64-encoded Ed25519 secret key in this
+    /// file
+    #[arg(long, value_name = "KEYFILE")]
+    sign: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ExtractArgs {
+    /// Package to extract
+    package: PathBuf,
+    /// Output directory (default: ./<mod-name>)
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Only extract these layers (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    layers: Option<Vec<String>>,
+    /// Verify the whole package before extracting
+    #[arg(long)]
+    verify: bool,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+    /// Package or bundle to inspect
+    path: PathBuf,
+    /// Show detailed information
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Args)]
+struct ValidateArgs {
+    /// Project directory, config file, package or bundle
+    #[arg(default_value = ".")]
+    target: PathBuf,
+    /// Treat warnings as errors
+    #[arg(long)]
+    strict: bool,
+}
+
+#[derive(Args)]
+struct KeyringArgs {
+    /// Keyring of trusted author keys to check the package signature against
+    #[arg(long, value_name = "FILE")]
+    keyring: Option<PathBuf>,
+}
+
+impl KeyringArgs {
+    fn load(&self) -> Result<Option<Keyring>> {
+        self.keyring
+            .as_ref()
+            .map(|path| Keyring::from_json(&fs::read_to_string(path)?))
+            .transpose()
+    }
+}
+
+#[derive(Args)]
+struct BundlePackArgs {
+    /// Path to bundle.config.json
+    #[arg(long, default_value = dmodpkg::BUNDLE_CONFIG_FILE_NAME)]
+    config: PathBuf,
+    /// Output directory
+    #[arg(long, default_value = "build")]
+    output: PathBuf,
+    /// Skip validation checks
+    #[arg(long)]
+    no_validate: bool,
+}
+
+#[derive(Args)]
+struct BundleExtractArgs {
+    /// Bundle to extract
+    bundle: PathBuf,
+    /// Output directory (default: ./<bundle-name>)
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Only extract these packages (comma-separated filenames)
+    #[arg(long, value_delimiter = ',', conflicts_with = "all")]
+    packages: Option<Vec<String>>,
+    /// Extract all packages and resources (default)
+    #[arg(long)]
+    all: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let json = cli.json;
+
+    let result = match cli.command {
+        Command::Init(args) => init(args, json),
+        Command::Pack(args) => pack(args, json),
+        Command::Extract(args) => extract(args, json),
+        Command::Info { args, keyring } => info(args, keyring, json),
+        Command::Validate { args, keyring } => validate(args, keyring, json),
+        Command::Bundle(BundleCommand::Pack(args)) => bundle_pack(args, json),
+        Command::Bundle(BundleCommand::Info(args)) => bundle_info(args, json),
+        Command::Bundle(BundleCommand::Extract(args)) => bundle_extract(args, json),
+        Command::Bundle(BundleCommand::Validate(args)) => bundle_validate(args, json),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            if json {
+                println!("{}", json!({ "error": e.to_string() }));
+            } else {
+                eprintln!("Error: {e}");
+            }
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn exit_code(error: &DmodpkgError) -> u8 {
+    match error {
+        e if e.is_integrity() => EXIT_INTEGRITY,
+        DmodpkgError::Validation(_) | DmodpkgError::Layer(_) | DmodpkgError::Variant(_) => {
+            EXIT_VALIDATION
+        }
+        _ => 1,
+    }
+}
+
+fn init(args: InitArgs, json: bool) -> Result<ExitCode> {
+    let dir = match &args.name {
+        Some(name) => PathBuf::from(name),
+        None => PathBuf::from("."),
+    };
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => std::env::current_dir()?
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    if dir.join(CONFIG_FILE_NAME).exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already contains {}", dir.display(), CONFIG_FILE_NAME),
+        )
+        .into());
+    }
+
+    let author = args
+        .author
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "Anonymous".to_string());
+    let config = template_config(args.template, &name, author, args.license);
+    config.validate()?;
+
+    fs::create_dir_all(&dir)?;
+    for layer in &config.layers {
+        let layer_dir = dir.join(CONTENT_DIR).join(&layer.name);
+        fs::create_dir_all(&layer_dir)?;
+        fs::write(layer_dir.join(".gitkeep"), "")?;
+    }
+    fs::create_dir_all(dir.join(PREVIEWS_DIR).join("mod"))?;
+    fs::write(
+        dir.join("README.md"),
+        format!("# {}\n\n{}\n", config.display_name, config.description),
+    )?;
+    fs::write(dir.join(CONFIG_FILE_NAME), config.to_json()? + "\n")?;
+
+    if json {
+        print_json(&json!({ "path": dir, "config": config }));
+    } else {
+        println!("Project created at: {}", dir.display());
+        println!();
+        println!("Next steps:");
+        if args.name.is_some() {
+            println!("  cd {}", dir.display());
+        }
+        println!(
+            "  # Add your VPK files to {}/{}/",
+            CONTENT_DIR, config.layers[0].name
+        );
+        println!("  dmodpkg pack");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn template_config(template: Template, name: &str, author: String, license: String) -> ModConfig {
+    let layer = |name: &str, priority: i32, required: bool, description: &str| Layer {
+        name: name.to_string(),
+        priority,
+        description: Some(description.to_string()),
+        required,
+    };
+    let mut layers = vec![layer("base", 0, true, "Core mod files")];
+    let mut variant_groups = Vec::new();
+
+    match template {
+        Template::Basic => {}
+        Template::Multilayer => {
+            layers.push(layer("hd_textures", 10, false, "High-resolution textures"));
+            layers.push(layer("optional_sounds", 5, false, "Optional sound effects"));
+        }
+        Template::Advanced => {
+            layers.push(layer("alternate", 10, false, "Alternate look"));
+            let variant = |id: &str, display: &str, layers: &[&str]| Variant {
+                id: id.to_string(),
+                name: display.to_string(),
+                description: None,
+                layers: layers.iter().map(|l| l.to_string()).collect(),
+                preview_image: None,
+                screenshots: vec![],
+            };
+            variant_groups.push(VariantGroup {
+                id: "style".to_string(),
+                name: "Style".to_string(),
+                description: None,
+                default: "default".to_string(),
+                variants: vec![
+                    variant("default", "Default", &["base"]),
+                    variant("alternate", "Alternate", &["base", "alternate"]),
+                ],
+            });
+        }
+    }
+
+    let display_name = name
+        .split('-')
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    ModConfig {
+        schema: None,
+        name: name.to_string(),
+        display_name,
+        version: "0.1.0".to_string(),
+        description: "A Deadlock mod".to_string(),
+        game_version: None,
+        authors: vec![Author::Name(author)],
+        license: Some(license),
+        readme: Some("README.md".to_string()),
+        homepage: None,
+        repository: None,
+        screenshots: vec![],
+        variant_groups,
+        layers,
+        transformers: vec![],
+        metadata: None,
+    }
+}
+
+fn pack(args: PackArgs, json: bool) -> Result<ExitCode> {
+    let report = if args.no_validate {
+        ValidationReport::default()
+    } else {
+        let report = validate_project(&args.config)?;
+        if !report.passed(false) {
+            print_report(&report, false, json);
+            return Ok(ExitCode::from(EXIT_VALIDATION));
+        }
+        report
+    };
+
+    let signing_key = match &args.sign {
+        Some(path) => Some(decode_signing_key(&fs::read_to_string(path)?)?),
+        None => None,
+    };
+    let options = PackOptions {
+        chunk_size: args.chunk_size,
+        compression_level: args.compression,
+        signing_key,
+        ..PackOptions::default()
+    };
+    let writer = PackageWriter::from_project(&args.config, options)?;
+    let config = writer.config().clone();
+    fs::create_dir_all(&args.output)?;
+    let output = args.output.join(package_file_name(&config));
+    let summary = writer.write_to_file(&output)?;
+
+    if json {
+        print_json(&json!({
+            "path": output,
+            "file_count": summary.file_count,
+            "chunk_count": summary.chunk_count,
+            "uncompressed_size": summary.uncompressed_size,
+            "package_size": summary.package_size,
+            "warnings": report.warnings().collect::<Vec<_>>(),
+        }));
+    } else {
+        println!("Packing mod: {} v{}", config.display_name, config.version);
+        println!();
+        if !args.no_validate {
+            println!("✓ Validated configuration");
+        }
+        for warning in report.warnings() {
+            println!("✗ Warning: {}", warning.message);
+        }
+        println!(
+            "✓ Processed {} files ({} layers)",
+            summary.file_count,
+            config.layers.len()
+        );
+        println!(
+            "✓ Compressed {} → {} ({})",
+            human_size(summary.uncompressed_size),
+            human_size(summary.package_size),
+            ratio(summary.package_size, summary.uncompressed_size)
+        );
+        println!("✓ Generated checksums");
+        if args.sign.is_some() {
+            println!("✓ Signed package");
+        }
+        println!();
+        println!("Package created: {}", output.display());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn extract(args: ExtractArgs, json: bool) -> Result<ExitCode> {
+    let mut reader = PackageReader::open(&args.package)?;
+    let config = reader.config()?;
+    let output = args.output.unwrap_or_else(|| PathBuf::from(&config.name));
+
+    if args.verify {
+        reader.verify()?;
+    }
+
+    let (files, layers) = match &args.layers {
+        Some(layers) => {
+            let mut files = Vec::new();
+            for layer in layers {
+                files.extend(reader.extract_layer(layer, output.join(CONTENT_DIR).join(layer))?);
+            }
+            (files, layers.clone())
+        }
+        None => {
+            let files = reader.extract_all(&output)?;
+            (
+                files,
+                config.layers.iter().map(|l| l.name.clone()).collect(),
+            )
+        }
+    };
+
+    if json {
+        print_json(&json!({ "output": output, "layers": layers, "files": files }));
+    } else {
+        println!("Extracting: {} v{}", config.display_name, config.version);
+        println!();
+        if args.verify {
+            println!("✓ Verified package integrity");
+        }
+        println!(
+            "✓ Extracted {} {}",
+            files.len(),
+            plural(files.len(), "file")
+        );
+        println!("✓ Layers: {}", layers.join(", "));
+        println!();
+        println!("Extracted to: {}", output.display());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn info(args: InfoArgs, keyring: KeyringArgs, json: bool) -> Result<ExitCode> {
+    let mut reader = PackageReader::open(&args.path)?;
+    let config = reader.config()?;
+    // Checking the signature reads the whole package, so only when asked to
+    let (trust, unverified) = match keyring.load()? {
+        Some(keyring) => {
+            let trust = reader.verify_signature(&keyring)?;
+            let unverified = keyring.unverified_authors(&config.authors, &trust);
+            (Some(trust), unverified)
+        }
+        None => (None, Vec::new()),
+    };
+    let package_size = fs::metadata(&args.path)?.len();
+    let uncompressed_size = reader.header().total_uncompressed_size;
+    let files: Vec<_> = reader
+        .files()
+        .iter()
+        .map(|f| json!({ "layer": f.layer, "path": f.path, "size": f.uncompressed_size }))
+        .collect();
+
+    if json {
+        let mut info = json!({
+            "config": reader.metadata().config,
+            "build_info": reader.metadata().build_info,
+            "signature": reader.metadata().signature,
+            "file_count": files.len(),
+            "package_size": package_size,
+            "uncompressed_size": uncompressed_size,
+        });
+        if let Some(trust) = &trust {
+            info["trust"] = json!(trust);
+            info["unverified_authors"] = json!(unverified);
+        }
+        if args.verbose {
+            info["files"] = json!(files);
+        }
+        print_json(&info);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    print_title(&format!("{} v{}", config.display_name, config.version));
+    println!("Description: {}", config.description);
+    println!("Author: {}", authors(&config.authors));
+    if let Some(license) = &config.license {
+        println!("License: {license}");
+    }
+    if let Some(game_version) = &config.game_version {
+        println!("Game Version: {game_version}");
+    }
+
+    println!();
+    println!("Layers:");
+    for layer in &config.layers {
+        let description = layer
+            .description
+            .as_deref()
+            .map(|d| format!(" - {d}"))
+            .unwrap_or_default();
+        let required = if layer.required { " [required]" } else { "" };
+        println!(
+            "  • {} (priority {}){description}{required}",
+            layer.name, layer.priority
+        );
+    }
+
+    if !config.variant_groups.is_empty() {
+        println!();
+        println!("Variant Groups:");
+        for group in &config.variant_groups {
+            println!("  {} (default: {})", group.name, group.default);
+            for variant in &group.variants {
+                let description = variant.description.as_deref().unwrap_or(&variant.name);
+                println!("    • {} - {description}", variant.id);
+            }
+        }
+    }
+
+    println!();
+    println!("Package Info:");
+    println!("  Files: {}", files.len());
+    println!("  Compressed Size: {}", human_size(package_size));
+    println!("  Uncompressed Size: {}", human_size(uncompressed_size));
+    println!(
+        "  Compression Ratio: {}",
+        ratio(package_size, uncompressed_size)
+    );
+    match &reader.metadata().signature {
+        Some(signature) => println!(
+            "  Signature: {} ({})",
+            signature.algorithm, signature.public_key
+        ),
+        None => println!("  Signature: none"),
+    }
+    match &trust {
+        Some(Trust::Trusted { author, .. }) => println!("  Signed by: {author}"),
+        Some(Trust::Untrusted { .. }) => println!("  Signed by: untrusted key"),
+        _ => {}
+    }
+    for author in &unverified {
+        println!("✗ Warning: claims to be by {author} but is not signed by them");
+    }
+
+    if args.verbose {
+        let build_info = &reader.metadata().build_info;
+        match &build_info.platform {
+            Some(platform) => println!(
+                "  Built: {} ({}, dmodpkg {})",
+                build_info.build_timestamp, platform, build_info.builder_version
+            ),
+            None => println!(
+                "  Built: {} (dmodpkg {})",
+                build_info.build_timestamp, build_info.builder_version
+            ),
+        }
+        println!();
+        println!("Files:");
+        for file in reader.files() {
+            let layer = if file.layer == RESOURCE_LAYER {
+                "resource"
+            } else {
+                &file.layer
+            };
+            println!(
+                "  {}/{} ({})",
+                layer,
+                file.path,
+                human_size(file.uncompressed_size)
+            );
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate(args: ValidateArgs, keyring: KeyringArgs, json: bool) -> Result<ExitCode> {
+    let keyring = keyring.load()?.unwrap_or_default();
+    let report = validate_target(&args, &keyring, json)?;
+    Ok(finish_validation(&report, &args, json))
+}
+
+fn validate_target(args: &ValidateArgs, keyring: &Keyring, json: bool) -> Result<ValidationReport> {
+    if args.target.is_file() && !is_config(&args.target) {
+        if !json {
+            println!("Validating package...");
+        }
+        match PackageReader::open(&args.target) {
+            Ok(mut reader) => validate_package(&mut reader, keyring),
+            Err(e) => failed_open(e),
+        }
+    } else {
+        if !json {
+            println!("Validating mod project...");
+        }
+        validate_project(&args.target)
+    }
+}
+
+fn bundle_pack(args: BundlePackArgs, json: bool) -> Result<ExitCode> {
+    let report = if args.no_validate {
+        ValidationReport::default()
+    } else {
+        let report = validate_bundle_project(&args.config)?;
+        if !report.passed(false) {
+            print_report(&report, false, json);
+            return Ok(ExitCode::from(EXIT_VALIDATION));
+        }
+        report
+    };
+
+    let writer = BundleWriter::from_project(&args.config, BundleOptions::default())?;
+    let config = writer.config().clone();
+    fs::create_dir_all(&args.output)?;
+    let output = args.output.join(bundle_file_name(&config));
+    let summary = writer.write_to_file(&output)?;
+
+    if json {
+        print_json(&json!({
+            "path": output,
+            "package_count": summary.package_count,
+            "resource_count": summary.resource_count,
+            "bundle_size": summary.bundle_size,
+            "warnings": report.warnings().collect::<Vec<_>>(),
+        }));
+    } else {
+        println!(
+            "Packing bundle: {} v{}",
+            config.display_name, config.version
+        );
+        println!();
+        if !args.no_validate {
+            println!("✓ Validated bundle configuration");
+        }
+        for warning in report.warnings() {
+            println!("✗ Warning: {}", warning.message);
+        }
+        println!("✓ Validated {} mod packages", summary.package_count);
+        println!(
+            "✓ Processed bundle resources ({} screenshots)",
+            summary.resource_count
+        );
+        println!(
+            "✓ Embedded {} mod packages (total: {})",
+            summary.package_count,
+            human_size(summary.bundle_size)
+        );
+        println!();
+        println!("Bundle created: {}", output.display());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bundle_info(args: InfoArgs, json: bool) -> Result<ExitCode> {
+    let reader = BundleReader::open(&args.path)?;
+    let config = reader.config()?;
+    let build_info = &reader.metadata().build_info;
+
+    if json {
+        let mut info = json!({
+            "config": reader.metadata().config,
+            "build_info": build_info,
+            "bundle_size": reader.header().total_bundle_size,
+        });
+        if args.verbose {
+            info["resources"] = json!(reader
+                .resources()
+                .iter()
+                .map(|(path, data)| json!({ "path": path, "size": data.len() }))
+                .collect::<Vec<_>>());
+        }
+        print_json(&info);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    print_title(&format!("{} v{}", config.display_name, config.version));
+    println!("Description: {}", config.description);
+    println!("Author: {}", authors(&config.authors));
+
+    println!();
+    println!("Included Mods ({}):", config.mods.len());
+    for entry in &config.mods {
+        let included = build_info
+            .included_mods
+            .iter()
+            .find(|m| m.filename == entry.package);
+        let (mark, kind) = if entry.required {
+            ("✓", "required")
+        } else {
+            ("○", "optional")
+        };
+        match included {
+            Some(m) => println!("  {mark} {} v{} [{kind}]", m.name, m.version),
+            None => println!("  {mark} {} [{kind}]", entry.package),
+        }
+        if let Some(description) = &entry.description {
+            println!("    {description}");
+        }
+        if args.verbose {
+            if let Some(m) = included {
+                println!(
+                    "    {} ({}, {})",
+                    m.filename,
+                    human_size(m.size_bytes),
+                    m.checksum
+                );
+            }
+        }
+    }
+
+    if !config.presets.is_empty() {
+        println!();
+        println!("Presets ({}):", config.presets.len());
+        for preset in &config.presets {
+            let default = if preset.default { " (default)" } else { "" };
+            println!("  • {}{default}", preset.name);
+            if let Some(description) = &preset.description {
+                println!("    {description}");
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "Bundle Size: {}",
+        human_size(reader.header().total_bundle_size)
+    );
+    println!("Screenshots: {}", config.screenshots.len());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bundle_extract(args: BundleExtractArgs, json: bool) -> Result<ExitCode> {
+    let mut reader = BundleReader::open(&args.bundle)?;
+    let config = reader.config()?;
+    let output = args.output.unwrap_or_else(|| PathBuf::from(&config.name));
+
+    let files = match &args.packages {
+        Some(packages) => {
+            fs::create_dir_all(&output)?;
+            let mut files = Vec::new();
+            for package in packages {
+                let path = output.join(package);
+                fs::write(&path, reader.read_package(package)?)?;
+                files.push(path);
+            }
+            files
+        }
+        None => reader.extract(&output)?,
+    };
+
+    if json {
+        print_json(&json!({ "output": output, "files": files }));
+    } else {
+        println!("Extracting bundle packages...");
+        println!();
+        for file in files
+            .iter()
+            .filter(|f| f.extension().is_some_and(|e| e == "dmodpkg"))
+        {
+            println!("✓ Extracted {}", file_name(file));
+        }
+        println!();
+        println!(
+            "Extracted {} {} to: {}",
+            files.len(),
+            plural(files.len(), "file"),
+            output.display()
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bundle_validate(args: ValidateArgs, json: bool) -> Result<ExitCode> {
+    let report = if args.target.is_file() && !is_config(&args.target) {
+        if !json {
+            println!("Validating bundle...");
+        }
+        match BundleReader::open(&args.target) {
+            Ok(mut reader) => validate_bundle(&mut reader)?,
+            Err(e) => failed_open(e)?,
+        }
+    } else {
+        if !json {
+            println!("Validating bundle project...");
+        }
+        validate_bundle_project(&args.target)?
+    };
+
+    Ok(finish_validation(&report, &args, json))
+}
+
+/// A file that cannot even be opened fails validation, unless reading it
+/// failed
+fn failed_open(error: DmodpkgError) -> Result<ValidationReport> {
+    if let DmodpkgError::Io(e) = error {
+        return Err(e.into());
+    }
+    let mut report = ValidationReport {
+        integrity_failed: error.is_integrity(),
+        ..ValidationReport::default()
+    };
+    report.error(error.to_string());
+    Ok(report)
+}
+
+fn finish_validation(report: &ValidationReport, args: &ValidateArgs, json: bool) -> ExitCode {
+    print_report(report, args.strict, json);
+    ExitCode::from(validation_exit_code(report, args.strict))
+}
+
+fn validation_exit_code(report: &ValidationReport, strict: bool) -> u8 {
+    if report.integrity_failed {
+        EXIT_INTEGRITY
+    } else if report.passed(strict) {
+        0
+    } else {
+        EXIT_VALIDATION
+    }
+}
+
+fn print_report(report: &ValidationReport, strict: bool, json: bool) {
+    if json {
+        print_json(&report_json(report, strict));
+        return;
+    }
+
+    println!();
+    for issue in report.errors() {
+        println!("✗ Error: {}", issue.message);
+    }
+    for issue in report.warnings() {
+        println!("✗ Warning: {}", issue.message);
+    }
+
+    let errors = report.errors().count();
+    let warnings = report.warnings().count();
+    if !report.issues.is_empty() {
+        println!();
+    }
+    match (report.passed(strict), warnings) {
+        (true, 0) => println!("Validation passed."),
+        (true, n) => println!("Validation passed with {n} {}.", plural(n, "warning")),
+        (false, _) => println!(
+            "Validation failed with {errors} {} and {warnings} {}.",
+            plural(errors, "error"),
+            plural(warnings, "warning")
+        ),
+    }
+}
+
+fn report_json(report: &ValidationReport, strict: bool) -> serde_json::Value {
+    json!({
+        "passed": report.passed(strict),
+        "strict": strict,
+        "schema": SCHEMA_VERSION,
+        "issues": report.issues,
+    })
+}
+
+fn is_config(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+}
+
+fn print_json(value: &impl serde::Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{json}"),
+        Err(e) => eprintln!("Error serializing to JSON: {e}"),
+    }
+}
+
+fn print_title(title: &str) {
+    println!("{title}");
+    println!("{}", "━".repeat(41));
+    println!();
+}
+
+fn authors(authors: &[Author]) -> String {
+    authors
+        .iter()
+        .map(|a| match a {
+            Author::Name(name) => name.as_str(),
+            Author::Detailed { name, .. } => name.as_str(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        word.to_string()
+    } else {
+        format!("{word}s")
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+fn ratio(compressed: u64, uncompressed: u64) -> String {
+    if uncompressed == 0 {
+        return "n/a".to_string();
+    }
+    format!("{:.1}%", compressed as f64 / uncompressed as f64 * 100.0)
+}
+
+/// Parse sizes such as `1048576`, `512KB`, `1MB` or `16M`
+fn parse_size(value: &str) -> std::result::Result<usize, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("invalid size '{value}'"))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        _ => return Err(format!("invalid size unit in '{value}' (use KB or MB)")),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{value}' is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dmodpkg::{encode_signing_key, SigningKey};
+
+    /// `validate --json <package>` for a freshly built package, optionally
+    /// with its last byte flipped
+    fn validate_package_json(tamper: bool) -> (serde_json::Value, u8) {
+        let config = template_config(
+            Template::Basic,
+            "test-mod",
+            "TestAuthor".to_string(),
+            "MIT".to_string(),
+        );
+        let mut writer = PackageWriter::new(config, PackOptions::default()).unwrap();
+        writer
+            .add_file("base", "pak01_dir.vpk", b"vpk".to_vec())
+            .unwrap();
+        let mut bytes = Vec::new();
+        writer.write(&mut bytes).unwrap();
+        if tamper {
+            let last = bytes.len() - 1;
+            bytes[last] ^= 0xff;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test-mod-0.1.0.dmodpkg");
+        fs::write(&path, bytes).unwrap();
+
+        let cli = Cli::try_parse_from([
+            "dmodpkg".as_ref(),
+            "validate".as_ref(),
+            path.as_os_str(),
+            "--json".as_ref(),
+        ])
+        .unwrap();
+        assert!(cli.json);
+        let Command::Validate { args, keyring } = cli.command else {
+            panic!("expected the validate command");
+        };
+        assert!(keyring.keyring.is_none());
+        let report = validate_target(&args, &Keyring::new(), cli.json).unwrap();
+        (
+            report_json(&report, args.strict),
+            validation_exit_code(&report, args.strict),
+        )
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Ok(1048576));
+        assert_eq!(parse_size("512KB"), Ok(512 * 1024));
+        assert_eq!(parse_size("1MB"), Ok(1024 * 1024));
+        assert_eq!(parse_size(" 16m "), Ok(16 * 1024 * 1024));
+        assert_eq!(parse_size("4 MiB"), Ok(4 * 1024 * 1024));
+        assert_eq!(parse_size("2B"), Ok(2));
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("1GB").is_err());
+        assert!(parse_size("1.5MB").is_err());
+        assert!(parse_size(&format!("{}MB", usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_human_size_and_ratio() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1024), "1.0 KB");
+        assert_eq!(human_size(1536), "1.5 KB");
+        assert_eq!(human_size(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(human_size(u64::MAX), "16777216.0 TB");
+
+        assert_eq!(ratio(50, 200), "25.0%");
+        assert_eq!(ratio(300, 200), "150.0%");
+        assert_eq!(ratio(10, 0), "n/a");
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(&DmodpkgError::checksum_mismatch("crc")), 4);
+        assert_eq!(exit_code(&DmodpkgError::signature("key")), 4);
+        assert_eq!(exit_code(&DmodpkgError::validation("name")), 3);
+        assert_eq!(exit_code(&DmodpkgError::layer("base")), 3);
+        assert_eq!(exit_code(&DmodpkgError::variant("dark")), 3);
+        assert_eq!(exit_code(&DmodpkgError::format("magic")), 1);
+        assert_eq!(exit_code(&std::io::Error::other("disk").into()), 1);
+    }
+
+    #[test]
+    fn test_validate_json() {
+        let (json, code) = validate_package_json(false);
+        assert_eq!(code, 0);
+        assert_eq!(json["passed"], true);
+        assert_eq!(json["strict"], false);
+        assert_eq!(json["schema"], SCHEMA_VERSION);
+        assert_eq!(json["issues"], json!([]));
+
+        let (json, code) = validate_package_json(true);
+        assert_eq!(code, EXIT_INTEGRITY);
+        assert_eq!(json["passed"], false);
+        assert_eq!(json["issues"][0]["severity"], "error");
+        assert!(json["issues"][0]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Checksum mismatch"));
+    }
+
+    #[test]
+    fn test_signing_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("test-mod");
+        let config = template_config(
+            Template::Basic,
+            "test-mod",
+            "TestAuthor".to_string(),
+            "MIT".to_string(),
+        );
+        fs::create_dir_all(project.join("content/base")).unwrap();
+        fs::write(project.join("content/base/pak01_dir.vpk"), b"vpk").unwrap();
+        fs::write(project.join("README.md"), "# Test Mod\n").unwrap();
+        fs::write(project.join(CONFIG_FILE_NAME), config.to_json().unwrap()).unwrap();
+
+        let key = SigningKey::from_bytes(&[5; 32]);
+        let keyfile = dir.path().join("author.key");
+        fs::write(&keyfile, encode_signing_key(&key) + "\n").unwrap();
+        let mut keyring = Keyring::new();
+        keyring.add("TestAuthor", &key.verifying_key());
+        let keyring_path = dir.path().join("keyring.json");
+        fs::write(&keyring_path, keyring.to_json().unwrap()).unwrap();
+
+        let output = dir.path().join("build");
+        let cli = Cli::try_parse_from([
+            "dmodpkg".as_ref(),
+            "pack".as_ref(),
+            "--config".as_ref(),
+            project.join(CONFIG_FILE_NAME).as_os_str(),
+            "--output".as_ref(),
+            output.as_os_str(),
+            "--sign".as_ref(),
+            keyfile.as_os_str(),
+        ])
+        .unwrap();
+        let Command::Pack(args) = cli.command else {
+            panic!("expected the pack command");
+        };
+        pack(args, true).unwrap();
+
+        let package = output.join("test-mod-0.1.0.dmodpkg");
+        let cli = Cli::try_parse_from([
+            "dmodpkg".as_ref(),
+            "validate".as_ref(),
+            package.as_os_str(),
+            "--keyring".as_ref(),
+            keyring_path.as_os_str(),
+        ])
+        .unwrap();
+        let Command::Validate { args, keyring } = cli.command else {
+            panic!("expected the validate command");
+        };
+        let keyring = keyring.load().unwrap().unwrap();
+        let report = validate_target(&args, &keyring, true).unwrap();
+        assert!(report.passed(true), "{:?}", report.issues);
+
+        let mut reader = PackageReader::open(&package).unwrap();
+        assert_eq!(
+            reader.verify_signature(&keyring).unwrap().author(),
+            Some("TestAuthor")
+        );
+
+        // The same package is flagged against a keyring that trusts someone
+        // else's key for the claimed author
+        let mut other = Keyring::new();
+        other.add(
+            "TestAuthor",
+            &SigningKey::from_bytes(&[6; 32]).verifying_key(),
+        );
+        let report = validate_target(&args, &other, true).unwrap();
+        assert!(report.passed(false));
+        assert!(!report.passed(true));
+    }
+}
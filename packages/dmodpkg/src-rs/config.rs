@@ -220,6 +220,16 @@ impl BundleConfig {
         serde_json::to_string_pretty(self).map_err(Into::into)
     }
 
+    /// Find a preset by ID
+    pub fn preset(&self, id: &str) -> Option<&BundlePreset> {
+        self.presets.iter().find(|p| p.id == id)
+    }
+
+    /// The preset marked as default, if any
+    pub fn default_preset(&self) -> Option<&BundlePreset> {
+        self.presets.iter().find(|p| p.default)
+    }
+
     /// Validate configuration
     pub fn validate(&self) -> Result<()> {
         // Validate name format (kebab-case)
@@ -34,6 +34,10 @@ pub enum DmodpkgError {
     #[error("Invalid package structure: {0}")]
     InvalidStructure(String),
 
+    /// Missing, malformed or non-matching package signatures
+    #[error("Signature error: {0}")]
+    Signature(String),
+
     /// Layer errors (missing layer, conflicting layers, etc.)
     #[error("Layer error: {0}")]
     Layer(String),
@@ -48,6 +52,11 @@ pub enum DmodpkgError {
 }
 
 impl DmodpkgError {
+    /// Whether a checksum or signature did not match
+    pub fn is_integrity(&self) -> bool {
+        matches!(self, Self::ChecksumMismatch(_) | Self::Signature(_))
+    }
+
     /// Create a validation error
     pub fn validation(msg: impl Into<String>) -> Self {
         Self::Validation(msg.into())
@@ -73,6 +82,11 @@ impl DmodpkgError {
         Self::InvalidStructure(msg.into())
     }
 
+    /// Create a signature error
+    pub fn signature(msg: impl Into<String>) -> Self {
+        Self::Signature(msg.into())
+    }
+
     /// Create a layer error
     pub fn layer(msg: impl Into<String>) -> Self {
         Self::Layer(msg.into())
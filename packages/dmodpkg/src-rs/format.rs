@@ -1,5 +1,9 @@
+use crc::{Crc, CRC_32_ISO_HDLC, CRC_64_XZ};
 use serde::{Deserialize, Serialize};
 
+use crate::error::{DmodpkgError, Result};
+use crate::types::{BundleBuildInfo, ChunkMetadata, FileEntry, PackageIndexEntry};
+
 /// Magic bytes for .dmodpkg files
 pub const DMODPKG_MAGIC: &[u8; 8] = b"DMODPKG\0";
 
@@ -21,6 +25,18 @@ pub const MIN_CHUNK_SIZE: usize = 256 * 1024;
 /// Maximum chunk size (16MB)
 pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
 
+/// Default Zstd compression level
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 9;
+
+/// Size of one chunk table entry in bytes
+pub const CHUNK_ENTRY_SIZE: usize = 20;
+
+/// CRC-32 of each chunk's compressed bytes
+pub(crate) const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// CRC-64 of everything after the header
+pub(crate) const CRC64: Crc<u64> = Crc::<u64>::new(&CRC_64_XZ);
+
 /// Package header structure
 #[derive(Debug, Clone)]
 pub struct PackageHeader {
@@ -190,6 +206,55 @@ impl BundleHeader {
         }
     }
 
+    /// Serialize header to bytes
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+
+        bytes[0..8].copy_from_slice(&self.magic);
+        bytes[8..10].copy_from_slice(&self.version.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.flags.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.bundle_metadata_offset.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.bundle_metadata_compressed_size.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.bundle_metadata_uncompressed_size.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.package_index_offset.to_le_bytes());
+        bytes[28..32].copy_from_slice(&self.package_index_size.to_le_bytes());
+        bytes[32..36].copy_from_slice(&self.resources_offset.to_le_bytes());
+        bytes[36..40].copy_from_slice(&self.resources_compressed_size.to_le_bytes());
+        bytes[40..44].copy_from_slice(&self.resources_uncompressed_size.to_le_bytes());
+        bytes[44..48].copy_from_slice(&self.packages_section_offset.to_le_bytes());
+        bytes[48..56].copy_from_slice(&self.total_bundle_size.to_le_bytes());
+        bytes[56..64].copy_from_slice(&self.bundle_crc64.to_le_bytes());
+
+        bytes
+    }
+
+    /// Parse header from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE {
+            return None;
+        }
+
+        let mut magic = [0u8; 8];
+        magic.copy_from_slice(&bytes[0..8]);
+
+        Some(Self {
+            magic,
+            version: u16::from_le_bytes(bytes[8..10].try_into().ok()?),
+            flags: u16::from_le_bytes(bytes[10..12].try_into().ok()?),
+            bundle_metadata_offset: u32::from_le_bytes(bytes[12..16].try_into().ok()?),
+            bundle_metadata_compressed_size: u32::from_le_bytes(bytes[16..20].try_into().ok()?),
+            bundle_metadata_uncompressed_size: u32::from_le_bytes(bytes[20..24].try_into().ok()?),
+            package_index_offset: u32::from_le_bytes(bytes[24..28].try_into().ok()?),
+            package_index_size: u32::from_le_bytes(bytes[28..32].try_into().ok()?),
+            resources_offset: u32::from_le_bytes(bytes[32..36].try_into().ok()?),
+            resources_compressed_size: u32::from_le_bytes(bytes[36..40].try_into().ok()?),
+            resources_uncompressed_size: u32::from_le_bytes(bytes[40..44].try_into().ok()?),
+            packages_section_offset: u32::from_le_bytes(bytes[44..48].try_into().ok()?),
+            total_bundle_size: u64::from_le_bytes(bytes[48..56].try_into().ok()?),
+            bundle_crc64: u64::from_le_bytes(bytes[56..64].try_into().ok()?),
+        })
+    }
+
     /// Validate header magic bytes
     pub fn is_valid(&self) -> bool {
         self.magic == *DMODBUNDLE_MAGIC
@@ -214,6 +279,263 @@ pub struct MetadataSection {
     pub signature: Option<crate::types::Signature>,
 }
 
+/// Bundle metadata section (stored as compressed JSON)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleMetadataSection {
+    /// Original bundle configuration
+    pub config: serde_json::Value,
+    /// Build information
+    pub build_info: BundleBuildInfo,
+}
+
+/// Encode the file index (before compression)
+pub fn encode_file_index(entries: &[FileEntry]) -> Result<Vec<u8>> {
+    let count = u32::try_from(entries.len())
+        .map_err(|_| DmodpkgError::format("too many files for the file index"))?;
+    let mut out = Vec::new();
+    out.extend_from_slice(&count.to_le_bytes());
+
+    for entry in entries {
+        let path_len = u16::try_from(entry.path.len())
+            .map_err(|_| DmodpkgError::format(format!("path is too long: {}", entry.path)))?;
+        let layer_len = u8::try_from(entry.layer.len()).map_err(|_| {
+            DmodpkgError::format(format!("layer name is too long: {}", entry.layer))
+        })?;
+        let chunk_count = u16::try_from(entry.chunk_indices.len()).map_err(|_| {
+            DmodpkgError::format(format!("file has too many chunks: {}", entry.path))
+        })?;
+
+        out.extend_from_slice(&path_len.to_le_bytes());
+        out.extend_from_slice(entry.path.as_bytes());
+        out.push(layer_len);
+        out.extend_from_slice(entry.layer.as_bytes());
+        out.extend_from_slice(&entry.uncompressed_size.to_le_bytes());
+        out.extend_from_slice(&chunk_count.to_le_bytes());
+        for index in &entry.chunk_indices {
+            out.extend_from_slice(&index.to_le_bytes());
+        }
+        out.extend_from_slice(&entry.sha256);
+    }
+
+    Ok(out)
+}
+
+/// Encode the chunk table
+pub fn encode_chunk_table(chunks: &[ChunkMetadata]) -> Result<Vec<u8>> {
+    let count = u32::try_from(chunks.len())
+        .map_err(|_| DmodpkgError::format("too many chunks for the chunk table"))?;
+    let mut out = Vec::with_capacity(4 + chunks.len() * CHUNK_ENTRY_SIZE);
+    out.extend_from_slice(&count.to_le_bytes());
+
+    for chunk in chunks {
+        out.extend_from_slice(&chunk.offset.to_le_bytes());
+        out.extend_from_slice(&chunk.compressed_size.to_le_bytes());
+        out.extend_from_slice(&chunk.uncompressed_size.to_le_bytes());
+        out.extend_from_slice(&chunk.crc32.to_le_bytes());
+    }
+
+    Ok(out)
+}
+
+/// Decode a file index written by [`encode_file_index`]
+pub fn decode_file_index(bytes: &[u8]) -> Result<Vec<FileEntry>> {
+    let mut input = ByteReader::new(bytes, "file index");
+    let count = input.u32()?;
+    let mut entries = Vec::new();
+
+    for _ in 0..count {
+        let path_len = input.u16()? as usize;
+        let path = input.string(path_len)?;
+        let layer_len = input.u8()? as usize;
+        let layer = input.string(layer_len)?;
+        let uncompressed_size = input.u64()?;
+        let chunk_count = input.u16()?;
+        let chunk_indices = (0..chunk_count)
+            .map(|_| input.u32())
+            .collect::<Result<Vec<_>>>()?;
+        let mut sha256 = [0u8; 32];
+        sha256.copy_from_slice(input.bytes(32)?);
+
+        entries.push(FileEntry {
+            path,
+            layer,
+            uncompressed_size,
+            chunk_indices,
+            sha256,
+        });
+    }
+
+    input.finish()?;
+    Ok(entries)
+}
+
+/// Decode a chunk table written by [`encode_chunk_table`]
+pub fn decode_chunk_table(bytes: &[u8]) -> Result<Vec<ChunkMetadata>> {
+    let mut input = ByteReader::new(bytes, "chunk table");
+    let count = input.u32()?;
+    let mut chunks = Vec::new();
+
+    for _ in 0..count {
+        chunks.push(ChunkMetadata {
+            offset: input.u64()?,
+            compressed_size: input.u32()?,
+            uncompressed_size: input.u32()?,
+            crc32: input.u32()?,
+        });
+    }
+
+    input.finish()?;
+    Ok(chunks)
+}
+
+/// Encode a bundle's package index
+pub fn encode_package_index(entries: &[PackageIndexEntry]) -> Result<Vec<u8>> {
+    let count = u32::try_from(entries.len())
+        .map_err(|_| DmodpkgError::format("too many packages for the package index"))?;
+    let mut out = Vec::new();
+    out.extend_from_slice(&count.to_le_bytes());
+
+    for entry in entries {
+        let name_len = u16::try_from(entry.filename.len()).map_err(|_| {
+            DmodpkgError::format(format!("filename is too long: {}", entry.filename))
+        })?;
+        out.extend_from_slice(&name_len.to_le_bytes());
+        out.extend_from_slice(entry.filename.as_bytes());
+        out.extend_from_slice(&entry.offset.to_le_bytes());
+        out.extend_from_slice(&entry.size.to_le_bytes());
+        out.extend_from_slice(&entry.sha256);
+    }
+
+    Ok(out)
+}
+
+/// Decode a package index written by [`encode_package_index`]
+pub fn decode_package_index(bytes: &[u8]) -> Result<Vec<PackageIndexEntry>> {
+    let mut input = ByteReader::new(bytes, "package index");
+    let count = input.u32()?;
+    let mut entries = Vec::new();
+
+    for _ in 0..count {
+        let name_len = input.u16()? as usize;
+        entries.push(PackageIndexEntry {
+            filename: input.string(name_len)?,
+            offset: input.u64()?,
+            size: input.u64()?,
+            sha256: input.array()?,
+        });
+    }
+
+    input.finish()?;
+    Ok(entries)
+}
+
+/// Encode bundle resources (before compression)
+///
+/// The RFC leaves the layout open; it is a u32 count followed by, per
+/// resource, a u16 path length, the UTF-8 path, a u32 size and the bytes.
+pub fn encode_resources(resources: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let count = u32::try_from(resources.len())
+        .map_err(|_| DmodpkgError::format("too many bundle resources"))?;
+    let mut out = Vec::new();
+    out.extend_from_slice(&count.to_le_bytes());
+
+    for (path, data) in resources {
+        let path_len = u16::try_from(path.len())
+            .map_err(|_| DmodpkgError::format(format!("path is too long: {}", path)))?;
+        let size = u32::try_from(data.len())
+            .map_err(|_| DmodpkgError::format(format!("resource is too large: {}", path)))?;
+        out.extend_from_slice(&path_len.to_le_bytes());
+        out.extend_from_slice(path.as_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    Ok(out)
+}
+
+/// Decode bundle resources written by [`encode_resources`]
+pub fn decode_resources(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut input = ByteReader::new(bytes, "bundle resources");
+    let count = input.u32()?;
+    let mut resources = Vec::new();
+
+    for _ in 0..count {
+        let path_len = input.u16()? as usize;
+        let path = input.string(path_len)?;
+        let size = input.u32()? as usize;
+        resources.push((path, input.bytes(size)?.to_vec()));
+    }
+
+    input.finish()?;
+    Ok(resources)
+}
+
+/// Little-endian cursor over a section, failing with a format error when the
+/// section ends early
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    section: &'static str,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8], section: &'static str) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            section,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| DmodpkgError::format(format!("truncated {}", self.section)))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String> {
+        String::from_utf8(self.bytes(len)?.to_vec())
+            .map_err(|_| DmodpkgError::format(format!("invalid UTF-8 in {}", self.section)))
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.pos != self.bytes.len() {
+            return Err(DmodpkgError::format(format!(
+                "unexpected trailing bytes in {}",
+                self.section
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -227,6 +549,41 @@ mod tests {
         assert_eq!(header.magic, parsed.magic);
         assert_eq!(header.version, parsed.version);
         assert!(parsed.is_valid());
+
+        let mut bundle = BundleHeader::new();
+        bundle.packages_section_offset = 1234;
+        bundle.bundle_crc64 = u64::MAX;
+        let parsed = BundleHeader::from_bytes(&bundle.to_bytes()).unwrap();
+        assert!(parsed.is_valid());
+        assert_eq!(parsed.packages_section_offset, 1234);
+        assert_eq!(parsed.bundle_crc64, u64::MAX);
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let entries = vec![FileEntry {
+            path: "pak01_dir.vpk".to_string(),
+            layer: "base".to_string(),
+            uncompressed_size: 42,
+            chunk_indices: vec![0, 3],
+            sha256: [9; 32],
+        }];
+        let chunks = vec![ChunkMetadata {
+            offset: 7,
+            compressed_size: 10,
+            uncompressed_size: 20,
+            crc32: 0xdead_beef,
+        }];
+
+        let decoded = decode_file_index(&encode_file_index(&entries).unwrap()).unwrap();
+        assert_eq!(decoded[0].path, "pak01_dir.vpk");
+        assert_eq!(decoded[0].chunk_indices, vec![0, 3]);
+        assert_eq!(decoded[0].sha256, [9; 32]);
+
+        let table = encode_chunk_table(&chunks).unwrap();
+        assert_eq!(table.len(), 4 + CHUNK_ENTRY_SIZE);
+        assert_eq!(decode_chunk_table(&table).unwrap()[0].crc32, 0xdead_beef);
+        assert!(decode_chunk_table(&table[..table.len() - 1]).is_err());
     }
 
     #[test]
@@ -1,16 +1,42 @@
+mod bundle;
 mod config;
 mod error;
 mod format;
+mod reader;
+mod resolver;
+mod signing;
 mod types;
+mod validate;
+mod writer;
 
+pub use bundle::{
+    apply_preset, bundle_file_name, BundleOptions, BundleReader, BundleSummary, BundleWriter,
+    Embedded, BUNDLE_CONFIG_FILE_NAME, MODS_DIR,
+};
 pub use config::{BundleConfig, ModConfig};
 pub use error::{DmodpkgError, Result};
 pub use format::{
-    BundleHeader, PackageHeader, MetadataSection,
+    BundleHeader, PackageHeader, MetadataSection, BundleMetadataSection,
     DMODPKG_MAGIC, DMODBUNDLE_MAGIC, FORMAT_VERSION,
-    DEFAULT_CHUNK_SIZE, MIN_CHUNK_SIZE, MAX_CHUNK_SIZE,
+    DEFAULT_CHUNK_SIZE, MIN_CHUNK_SIZE, MAX_CHUNK_SIZE, DEFAULT_COMPRESSION_LEVEL,
+};
+pub use reader::PackageReader;
+pub use resolver::{enabled_layers, resolve, Override, Resolution, ResolvedFile, Selection};
+pub use signing::{
+    decode_public_key, decode_signing_key, encode_public_key, encode_signing_key, Keyring, Trust,
+    TrustedKey, SIGNATURE_ALGORITHM,
 };
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
 pub use types::*;
+pub use validate::{
+    validate_bundle, validate_bundle_project, validate_package, validate_project, Issue,
+    Severity, ValidationReport, IMAGE_EXTENSIONS, IMAGE_WARNING_SIZE, MAX_IMAGE_SIZE,
+    MIN_SCREENSHOT_SIZE, PREVIEWS_DIR, PREVIEW_IMAGE_SIZES, SCHEMA_VERSION,
+};
+pub use writer::{
+    package_file_name, PackOptions, PackageSummary, PackageWriter,
+    CONFIG_FILE_NAME, CONTENT_DIR, RESOURCE_LAYER,
+};
 
 /// Get library version
 pub fn version() -> &'static str {
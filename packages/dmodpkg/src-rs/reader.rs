@@ -0,0 +1,686 @@
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::ModConfig;
+use crate::error::{DmodpkgError, Result};
+use crate::format::{
+    decode_chunk_table, decode_file_index, MetadataSection, PackageHeader, CRC32, CRC64,
+    FORMAT_VERSION, HEADER_SIZE, MAX_CHUNK_SIZE,
+};
+use crate::resolver::{resolve, Resolution, Selection};
+use crate::signing::{self, Keyring, Trust};
+use crate::types::{ChunkMetadata, FileEntry};
+use crate::writer::{normalize_path, CONFIG_FILE_NAME, CONTENT_DIR, RESOURCE_LAYER};
+
+/// Random-access reader for `.dmodpkg` files
+///
+/// Opening a package reads only the header, metadata, file index and chunk
+/// table. File data is decompressed on demand, one referenced chunk at a
+/// time, and every chunk's CRC32 and every file's SHA-256 are checked as it
+/// is read. The package CRC64 covers the whole file, so it is only checked by
+/// [`PackageReader::verify`].
+#[derive(Debug)]
+pub struct PackageReader<R> {
+    reader: R,
+    header: PackageHeader,
+    metadata: MetadataSection,
+    files: Vec<FileEntry>,
+    chunks: Vec<ChunkMetadata>,
+}
+
+impl PackageReader<BufReader<fs::File>> {
+    /// Open a package file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(fs::File::open(path)?))
+    }
+}
+
+impl PackageReader<Cursor<Vec<u8>>> {
+    /// Read a package held in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Self::new(Cursor::new(bytes))
+    }
+}
+
+impl<R: Read + Seek> PackageReader<R> {
+    /// Parse the header and index sections of a package
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut bytes = [0u8; HEADER_SIZE];
+        reader.seek(SeekFrom::Start(0))?;
+        read_section(&mut reader, &mut bytes, "header")?;
+
+        let header = PackageHeader::from_bytes(&bytes)
+            .ok_or_else(|| DmodpkgError::format("truncated header"))?;
+        if !header.is_valid() {
+            return Err(DmodpkgError::format(
+                "not a .dmodpkg file (bad magic bytes)",
+            ));
+        }
+        if header.version > FORMAT_VERSION {
+            return Err(DmodpkgError::format(format!(
+                "unsupported format version {}",
+                header.version
+            )));
+        }
+
+        let metadata_json = read_compressed(
+            &mut reader,
+            header.metadata_offset,
+            header.metadata_compressed_size,
+            header.metadata_uncompressed_size,
+            "metadata",
+        )?;
+        let metadata: MetadataSection = serde_json::from_slice(&metadata_json)?;
+
+        let file_index = read_compressed(
+            &mut reader,
+            header.file_index_offset,
+            header.file_index_compressed_size,
+            header.file_index_uncompressed_size,
+            "file index",
+        )?;
+        let files = decode_file_index(&file_index)?;
+
+        let chunk_table = read_at(
+            &mut reader,
+            header.chunk_table_offset as u64,
+            header.chunk_table_size as u64,
+            "chunk table",
+        )?;
+        let chunks = decode_chunk_table(&chunk_table)?;
+        if let Some(index) = chunks
+            .iter()
+            .position(|c| c.uncompressed_size as usize > MAX_CHUNK_SIZE)
+        {
+            return Err(DmodpkgError::format(format!(
+                "chunk {} is larger than the maximum chunk size",
+                index
+            )));
+        }
+
+        for file in &files {
+            if let Some(index) = file
+                .chunk_indices
+                .iter()
+                .find(|&&i| i as usize >= chunks.len())
+            {
+                return Err(DmodpkgError::invalid_structure(format!(
+                    "file '{}' references missing chunk {}",
+                    file.path, index
+                )));
+            }
+        }
+
+        Ok(Self {
+            reader,
+            header,
+            metadata,
+            files,
+            chunks,
+        })
+    }
+
+    /// The package header
+    pub fn header(&self) -> &PackageHeader {
+        &self.header
+    }
+
+    /// The metadata section
+    pub fn metadata(&self) -> &MetadataSection {
+        &self.metadata
+    }
+
+    /// The packaged mod configuration
+    pub fn config(&self) -> Result<ModConfig> {
+        serde_json::from_value(self.metadata.config.clone()).map_err(Into::into)
+    }
+
+    /// Every file in the package, in index order
+    pub fn files(&self) -> &[FileEntry] {
+        &self.files
+    }
+
+    /// The chunk table
+    pub fn chunks(&self) -> &[ChunkMetadata] {
+        &self.chunks
+    }
+
+    /// Look up a file by layer and path
+    pub fn file(&self, layer: &str, path: &str) -> Option<&FileEntry> {
+        let path = normalize_path(path).ok()?;
+        self.files
+            .iter()
+            .find(|f| f.layer == layer && f.path == path)
+    }
+
+    /// Files belonging to `layer`
+    pub fn layer_files<'a>(&'a self, layer: &'a str) -> impl Iterator<Item = &'a FileEntry> + 'a {
+        self.files.iter().filter(move |f| f.layer == layer)
+    }
+
+    /// Work out which files `selection` installs from this package
+    pub fn resolve(&self, selection: &Selection) -> Result<Resolution> {
+        resolve(&self.config()?, selection, &self.files)
+    }
+
+    /// Read and verify the contents of a single file
+    pub fn read_file(&mut self, layer: &str, path: &str) -> Result<Vec<u8>> {
+        let entry = self.file(layer, path).cloned().ok_or_else(|| {
+            DmodpkgError::invalid_structure(format!(
+                "file '{}' not found in layer '{}'",
+                path, layer
+            ))
+        })?;
+        self.read_entry(&entry)
+    }
+
+    /// Read and verify the contents of a file from the index
+    pub fn read_entry(&mut self, entry: &FileEntry) -> Result<Vec<u8>> {
+        // The buffer grows as chunks decode rather than trusting the index's
+        // size up front, so a crafted index cannot demand a huge allocation
+        let mut data = Vec::new();
+        self.copy_entry(entry, &mut data)?;
+        Ok(data)
+    }
+
+    /// Decode a file from the index into `out` one chunk at a time, checking
+    /// its size and SHA-256 once every chunk has been written
+    pub fn copy_entry<W: Write>(&mut self, entry: &FileEntry, out: &mut W) -> Result<()> {
+        // Only trust the size from the index once its chunks account for it
+        let chunks_size = entry
+            .chunk_indices
+            .iter()
+            .map(|&i| {
+                self.chunks
+                    .get(i as usize)
+                    .map_or(0, |c| c.uncompressed_size as u64)
+            })
+            .sum::<u64>();
+        if chunks_size != entry.uncompressed_size {
+            return Err(DmodpkgError::format(format!(
+                "file '{}' is {} bytes, but its chunks hold {}",
+                entry.path, entry.uncompressed_size, chunks_size
+            )));
+        }
+
+        let mut hasher = Sha256::new();
+        let mut written = 0u64;
+        for &index in &entry.chunk_indices {
+            let chunk = self.read_chunk(index)?;
+            hasher.update(&chunk);
+            out.write_all(&chunk)?;
+            written += chunk.len() as u64;
+        }
+
+        if written != entry.uncompressed_size {
+            return Err(DmodpkgError::invalid_structure(format!(
+                "file '{}' is {} bytes, expected {}",
+                entry.path, written, entry.uncompressed_size
+            )));
+        }
+
+        let sha256: [u8; 32] = hasher.finalize().into();
+        if sha256 != entry.sha256 {
+            return Err(DmodpkgError::checksum_mismatch(format!(
+                "SHA-256 of file '{}' does not match",
+                entry.path
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Read, verify and decompress a single chunk
+    pub fn read_chunk(&mut self, index: u32) -> Result<Vec<u8>> {
+        let chunk =
+            self.chunks.get(index as usize).cloned().ok_or_else(|| {
+                DmodpkgError::invalid_structure(format!("missing chunk {}", index))
+            })?;
+
+        let offset = (self.header.data_section_offset as u64)
+            .checked_add(chunk.offset)
+            .ok_or_else(|| {
+                DmodpkgError::invalid_structure(format!("chunk {} is out of range", index))
+            })?;
+        let compressed = read_at(
+            &mut self.reader,
+            offset,
+            chunk.compressed_size as u64,
+            "data chunk",
+        )?;
+
+        if CRC32.checksum(&compressed) != chunk.crc32 {
+            return Err(DmodpkgError::checksum_mismatch(format!(
+                "CRC32 of chunk {} does not match",
+                index
+            )));
+        }
+
+        decompress(&compressed, chunk.uncompressed_size, "data chunk")
+    }
+
+    /// Write the files of `layer` under `dir`, returning the written paths
+    pub fn extract_layer(&mut self, layer: &str, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let entries: Vec<FileEntry> = self.layer_files(layer).cloned().collect();
+        if entries.is_empty() && !self.has_layer(layer) {
+            return Err(DmodpkgError::layer(format!("unknown layer '{}'", layer)));
+        }
+
+        entries
+            .iter()
+            .map(|entry| self.extract_entry(entry, dir.as_ref()))
+            .collect()
+    }
+
+    /// Recreate the mod project under `dir`: `mod.config.json`, a
+    /// `content/<layer>/` folder per layer and the embedded resources
+    pub fn extract_all(&mut self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let config_path = dir.join(CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            serde_json::to_string_pretty(&self.metadata.config)?,
+        )?;
+
+        let mut written = vec![config_path];
+        for entry in self.files.clone() {
+            let root = if entry.layer == RESOURCE_LAYER {
+                dir.to_path_buf()
+            } else {
+                dir.join(CONTENT_DIR).join(&entry.layer)
+            };
+            written.push(self.extract_entry(&entry, &root)?);
+        }
+
+        Ok(written)
+    }
+
+    fn extract_entry(&mut self, entry: &FileEntry, dir: &Path) -> Result<PathBuf> {
+        // Never trust paths from the index to stay inside `dir`
+        let path = dir.join(normalize_path(&entry.path)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Streamed straight to disk; a file that fails its checks is removed
+        let mut out = BufWriter::new(fs::File::create(&path)?);
+        let result = self
+            .copy_entry(entry, &mut out)
+            .and_then(|()| out.flush().map_err(Into::into));
+        drop(out);
+        if let Err(e) = result {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        Ok(path)
+    }
+
+    fn has_layer(&self, layer: &str) -> bool {
+        self.config()
+            .map(|config| config.layers.iter().any(|l| l.name == layer))
+            .unwrap_or(false)
+    }
+
+    /// Check the package CRC64, then every chunk and every file
+    pub fn verify(&mut self) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+        let mut digest = CRC64.digest();
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read = self.reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            digest.update(&buffer[..read]);
+        }
+        if digest.finalize() != self.header.package_crc64 {
+            return Err(DmodpkgError::checksum_mismatch(
+                "package CRC64 does not match",
+            ));
+        }
+
+        for index in 0..self.chunks.len() {
+            self.read_chunk(index as u32)?;
+        }
+        for entry in self.files.clone() {
+            self.copy_entry(&entry, &mut io::sink())?;
+        }
+
+        Ok(())
+    }
+
+    /// Verify the package and its signature, and look the signer up in
+    /// `keyring`
+    ///
+    /// Runs [`PackageReader::verify`] first and then recomputes the signed
+    /// digest, so a tampered section or chunk is an error even when the
+    /// package is unsigned or its signer is not trusted.
+    pub fn verify_signature(&mut self, keyring: &Keyring) -> Result<Trust> {
+        self.verify()?;
+
+        let Some(signature) = self.metadata.signature.clone() else {
+            return Ok(Trust::Unsigned);
+        };
+        let digest = self.signed_digest()?;
+        let key = signing::verify(&signature, &digest)?;
+        Ok(keyring.trust(&key))
+    }
+
+    fn signed_digest(&mut self) -> Result<[u8; 32]> {
+        let file_index = read_compressed(
+            &mut self.reader,
+            self.header.file_index_offset,
+            self.header.file_index_compressed_size,
+            self.header.file_index_uncompressed_size,
+            "file index",
+        )?;
+
+        let chunk_table = read_at(
+            &mut self.reader,
+            self.header.chunk_table_offset as u64,
+            self.header.chunk_table_size as u64,
+            "chunk table",
+        )?;
+
+        let mut data = Sha256::new();
+        self.reader
+            .seek(SeekFrom::Start(self.header.data_section_offset as u64))?;
+        std::io::copy(&mut self.reader, &mut data)?;
+
+        signing::package_digest(
+            &self.header,
+            &self.metadata,
+            &file_index,
+            &chunk_table,
+            &data.finalize().into(),
+        )
+    }
+}
+
+pub(crate) fn read_section<R: Read>(
+    reader: &mut R,
+    buffer: &mut [u8],
+    section: &str,
+) -> Result<()> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => DmodpkgError::format(format!("truncated {}", section)),
+        _ => e.into(),
+    })
+}
+
+/// Length of the whole stream, for checking sizes read from it
+pub(crate) fn stream_len<R: Seek>(reader: &mut R) -> Result<u64> {
+    Ok(reader.seek(SeekFrom::End(0))?)
+}
+
+/// Read `size` bytes at `offset`, checking that they lie within the stream
+/// before allocating for them
+pub(crate) fn read_at<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    size: u64,
+    section: &str,
+) -> Result<Vec<u8>> {
+    let len = stream_len(reader)?;
+    if offset.checked_add(size).is_none_or(|end| end > len) {
+        return Err(DmodpkgError::format(format!("truncated {}", section)));
+    }
+
+    let mut buffer = vec![0u8; size as usize];
+    reader.seek(SeekFrom::Start(offset))?;
+    read_section(reader, &mut buffer, section)?;
+    Ok(buffer)
+}
+
+pub(crate) fn read_compressed<R: Read + Seek>(
+    reader: &mut R,
+    offset: u32,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    section: &str,
+) -> Result<Vec<u8>> {
+    let compressed = read_at(reader, offset as u64, compressed_size as u64, section)?;
+    decompress(&compressed, uncompressed_size, section)
+}
+
+fn decompress(compressed: &[u8], uncompressed_size: u32, section: &str) -> Result<Vec<u8>> {
+    // A frame that records its size must agree before the buffer is sized
+    if let Ok(Some(size)) = zstd::zstd_safe::get_frame_content_size(compressed) {
+        if size != uncompressed_size as u64 {
+            return Err(DmodpkgError::format(format!(
+                "{} holds {} bytes, expected {}",
+                section, size, uncompressed_size
+            )));
+        }
+    }
+    let data = zstd::bulk::decompress(compressed, uncompressed_size as usize)
+        .map_err(|e| DmodpkgError::compression(format!("{}: {}", section, e)))?;
+    if data.len() != uncompressed_size as usize {
+        return Err(DmodpkgError::compression(format!(
+            "{} decompressed to {} bytes, expected {}",
+            section,
+            data.len(),
+            uncompressed_size
+        )));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::MIN_CHUNK_SIZE;
+    use crate::writer::{PackOptions, PackageWriter};
+    use ed25519_dalek::SigningKey;
+
+    const CONFIG: &str = r#"{
+        "name": "test-mod",
+        "display_name": "Test Mod",
+        "version": "1.0.0",
+        "description": "A test mod",
+        "authors": ["TestAuthor"],
+        "layers": [
+            { "name": "base", "priority": 0, "required": true },
+            { "name": "extra", "priority": 10 }
+        ]
+    }"#;
+
+    fn big_file() -> Vec<u8> {
+        (0..700 * 1024u32).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn package() -> Vec<u8> {
+        signed_package(None)
+    }
+
+    fn signed_package(signing_key: Option<SigningKey>) -> Vec<u8> {
+        let options = PackOptions {
+            chunk_size: MIN_CHUNK_SIZE,
+            build_timestamp: Some("2024-01-01T00:00:00Z".to_string()),
+            signing_key,
+            ..PackOptions::default()
+        };
+        let mut writer =
+            PackageWriter::new(ModConfig::from_json(CONFIG).unwrap(), options).unwrap();
+        writer
+            .add_file("base", "pak01_dir.vpk", big_file())
+            .unwrap();
+        writer
+            .add_file("base", "pak01_000.vpk", b"hello".to_vec())
+            .unwrap();
+        writer
+            .add_file("extra", "pak02_dir.vpk", b"extra".to_vec())
+            .unwrap();
+
+        let mut out = Vec::new();
+        writer.write(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_read_files() {
+        let mut reader = PackageReader::from_bytes(package()).unwrap();
+
+        assert_eq!(reader.files().len(), 3);
+        assert_eq!(reader.config().unwrap().name, "test-mod");
+        assert_eq!(
+            reader.read_file("base", "pak01_dir.vpk").unwrap(),
+            big_file()
+        );
+        assert_eq!(reader.read_file("base", "pak01_000.vpk").unwrap(), b"hello");
+        assert!(reader.read_file("extra", "pak01_000.vpk").is_err());
+        reader.verify().unwrap();
+
+        let resolution = reader.resolve(&Selection::default()).unwrap();
+        assert_eq!(resolution.layers, vec!["base"]);
+        assert_eq!(resolution.files.len(), 2);
+    }
+
+    #[test]
+    fn test_extract_layers_and_projects() {
+        let mut reader = PackageReader::from_bytes(package()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let written = reader
+            .extract_layer("extra", dir.path().join("extra"))
+            .unwrap();
+        assert_eq!(written, vec![dir.path().join("extra/pak02_dir.vpk")]);
+
+        reader.extract_all(dir.path().join("project")).unwrap();
+        let project = dir.path().join("project");
+        assert_eq!(
+            fs::read(project.join("content/base/pak01_000.vpk")).unwrap(),
+            b"hello"
+        );
+        assert!(project.join(CONFIG_FILE_NAME).is_file());
+
+        assert!(matches!(
+            reader.extract_layer("missing", dir.path()),
+            Err(DmodpkgError::Layer(_))
+        ));
+    }
+
+    #[test]
+    fn test_corrupted_chunks() {
+        let mut bytes = package();
+        let header = PackageHeader::from_bytes(&bytes).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(header.data_section_offset as usize <= last);
+
+        let mut reader = PackageReader::from_bytes(bytes).unwrap();
+        assert!(matches!(
+            reader.verify(),
+            Err(DmodpkgError::ChecksumMismatch(_))
+        ));
+        // The last chunk belongs to the last file written
+        assert!(matches!(
+            reader.read_file("extra", "pak02_dir.vpk"),
+            Err(DmodpkgError::ChecksumMismatch(_))
+        ));
+        assert_eq!(reader.read_file("base", "pak01_000.vpk").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_malformed_packages() {
+        let mut bytes = package();
+        bytes[0] = b'X';
+        assert!(matches!(
+            PackageReader::from_bytes(bytes),
+            Err(DmodpkgError::Format(_))
+        ));
+
+        let bytes = package();
+        assert!(matches!(
+            PackageReader::from_bytes(bytes[..HEADER_SIZE + 4].to_vec()),
+            Err(DmodpkgError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn test_tampered_sizes() {
+        let bytes = package();
+        let header = PackageHeader::from_bytes(&bytes).unwrap();
+
+        let mut tampered = header.clone();
+        tampered.chunk_table_size = u32::MAX;
+        let mut oversized = bytes.clone();
+        oversized[..HEADER_SIZE].copy_from_slice(&tampered.to_bytes());
+        assert!(matches!(
+            PackageReader::from_bytes(oversized),
+            Err(DmodpkgError::Format(_))
+        ));
+
+        // Compressed size of the first chunk, after the count and its offset
+        let mut oversized = bytes.clone();
+        let at = header.chunk_table_offset as usize + 4 + 8;
+        oversized[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = PackageReader::from_bytes(oversized).unwrap();
+        assert!(matches!(reader.read_chunk(0), Err(DmodpkgError::Format(_))));
+
+        let mut reader = PackageReader::from_bytes(bytes).unwrap();
+        let mut entry = reader.files()[0].clone();
+        entry.uncompressed_size = u64::MAX;
+        assert!(matches!(
+            reader.read_entry(&entry),
+            Err(DmodpkgError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn test_crafted_file_sizes() {
+        // One chunk claiming the largest size, listed 65535 times: about 1 TiB
+        let mut reader = PackageReader::from_bytes(package()).unwrap();
+        reader.chunks[0].uncompressed_size = MAX_CHUNK_SIZE as u32;
+        let mut entry = reader.files()[0].clone();
+        entry.chunk_indices = vec![0; 65535];
+        entry.uncompressed_size = 65535 * MAX_CHUNK_SIZE as u64;
+        reader.files = vec![entry.clone()];
+
+        assert!(reader.read_entry(&entry).is_err());
+        assert!(reader.verify().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(reader.extract_layer(&entry.layer, dir.path()).is_err());
+        assert!(!dir.path().join(&entry.path).exists());
+    }
+
+    #[test]
+    fn test_signature_verification() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut keyring = Keyring::new();
+
+        let mut reader = PackageReader::from_bytes(package()).unwrap();
+        assert_eq!(reader.verify_signature(&keyring).unwrap(), Trust::Unsigned);
+
+        let signed = signed_package(Some(key.clone()));
+        assert_eq!(signed, signed_package(Some(key.clone())));
+        let mut reader = PackageReader::from_bytes(signed.clone()).unwrap();
+        assert!(matches!(
+            reader.verify_signature(&keyring).unwrap(),
+            Trust::Untrusted { .. }
+        ));
+        keyring.add("TestAuthor", &key.verifying_key());
+        assert_eq!(
+            reader.verify_signature(&keyring).unwrap().author(),
+            Some("TestAuthor")
+        );
+
+        // A re-encoded metadata section no longer matches the signature
+        reader.metadata.config["description"] = "Totally safe".into();
+        assert!(matches!(
+            reader.verify_signature(&keyring),
+            Err(DmodpkgError::Signature(_))
+        ));
+
+        let mut bytes = signed;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let mut reader = PackageReader::from_bytes(bytes).unwrap();
+        assert!(reader.verify_signature(&keyring).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::ModConfig;
+use crate::error::{DmodpkgError, Result};
+use crate::types::{FileEntry, Layer};
+use crate::writer::RESOURCE_LAYER;
+
+/// What the user picked: one variant per group and on/off toggles for
+/// optional layers
+///
+/// Groups left out use their default variant. Optional layers that no
+/// variant controls are off unless toggled on; layers listed by a variant
+/// follow the variant selection and cannot be toggled directly.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Selection {
+    /// Variant choices (variant_group_id -> variant_id)
+    #[serde(default)]
+    pub variants: HashMap<String, String>,
+    /// Optional layer toggles (layer name -> enabled)
+    #[serde(default)]
+    pub layers: HashMap<String, bool>,
+}
+
+/// A file that will be installed
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedFile {
+    /// File path within its layer
+    pub path: String,
+    /// Layer the installed copy comes from
+    pub layer: String,
+    /// Priority of that layer
+    pub priority: i32,
+    /// Position of the file in the file list given to [`resolve`]
+    pub index: usize,
+}
+
+/// A file hidden by the same path in a higher-priority layer
+#[derive(Debug, Clone, Serialize)]
+pub struct Override {
+    /// Path present in both layers
+    pub path: String,
+    /// Layer whose copy is installed
+    pub winner: String,
+    /// Priority of the winning layer
+    pub winner_priority: i32,
+    /// Layer whose copy is skipped
+    pub overridden: String,
+    /// Priority of the skipped layer
+    pub overridden_priority: i32,
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: '{}' (priority {}) overrides '{}' (priority {})",
+            self.path, self.winner, self.winner_priority, self.overridden, self.overridden_priority
+        )?;
+        if self.winner_priority == self.overridden_priority {
+            write!(f, ", declared later")?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of resolving a selection against a file list
+#[derive(Debug, Clone, Serialize)]
+pub struct Resolution {
+    /// Effective variant for every group, defaults included
+    pub variants: BTreeMap<String, String>,
+    /// Enabled layers, lowest priority first
+    pub layers: Vec<String>,
+    /// Files to install, sorted by path
+    pub files: Vec<ResolvedFile>,
+    /// Every override between enabled layers, sorted by path
+    pub overrides: Vec<Override>,
+}
+
+/// Validate `selection` against `config` and work out which layers it enables
+///
+/// Returns the effective variant per group and the enabled layers, lowest
+/// priority first; layers with equal priority keep their declaration order.
+pub fn enabled_layers(
+    config: &ModConfig,
+    selection: &Selection,
+) -> Result<(BTreeMap<String, String>, Vec<String>)> {
+    config.validate()?;
+
+    for (group_id, variant_id) in &selection.variants {
+        let group = config
+            .variant_groups
+            .iter()
+            .find(|g| &g.id == group_id)
+            .ok_or_else(|| {
+                DmodpkgError::variant(format!("unknown variant group '{}'", group_id))
+            })?;
+        if !group.variants.iter().any(|v| &v.id == variant_id) {
+            return Err(DmodpkgError::variant(format!(
+                "unknown variant '{}' in group '{}'",
+                variant_id, group_id
+            )));
+        }
+    }
+
+    // Layers listed by any variant belong to that variant's group
+    let mut controlled: HashMap<&str, &str> = HashMap::new();
+    let mut chosen_layers: HashSet<&str> = HashSet::new();
+    let mut variants = BTreeMap::new();
+    for group in &config.variant_groups {
+        let chosen = selection.variants.get(&group.id).unwrap_or(&group.default);
+        for variant in &group.variants {
+            for layer in &variant.layers {
+                controlled.entry(layer).or_insert(&group.id);
+                if &variant.id == chosen {
+                    chosen_layers.insert(layer);
+                }
+            }
+        }
+        variants.insert(group.id.clone(), chosen.clone());
+    }
+
+    for (name, &enabled) in &selection.layers {
+        let layer = find_layer(config, name)?;
+        if layer.required {
+            if !enabled {
+                return Err(DmodpkgError::layer(format!(
+                    "required layer '{}' cannot be disabled",
+                    name
+                )));
+            }
+        } else if let Some(group) = controlled.get(name.as_str()) {
+            return Err(DmodpkgError::layer(format!(
+                "layer '{}' is selected through variant group '{}'",
+                name, group
+            )));
+        }
+    }
+
+    let mut layers: Vec<&Layer> = config
+        .layers
+        .iter()
+        .filter(|l| {
+            l.required
+                || chosen_layers.contains(l.name.as_str())
+                || selection.layers.get(&l.name).copied().unwrap_or(false)
+        })
+        .collect();
+    layers.sort_by_key(|l| l.priority);
+
+    Ok((
+        variants,
+        layers.into_iter().map(|l| l.name.clone()).collect(),
+    ))
+}
+
+/// Resolve `selection` against the files of a package
+///
+/// Every path is installed from the highest-priority enabled layer that has
+/// it. When priorities are equal the layer declared later in the
+/// configuration wins. Resources (files outside any layer) are ignored.
+pub fn resolve(
+    config: &ModConfig,
+    selection: &Selection,
+    files: &[FileEntry],
+) -> Result<Resolution> {
+    let (variants, layers) = enabled_layers(config, selection)?;
+
+    // Rank by priority, then by declaration order
+    let rank: HashMap<&str, (i32, usize)> = config
+        .layers
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| layers.contains(&l.name))
+        .map(|(i, l)| (l.name.as_str(), (l.priority, i)))
+        .collect();
+
+    let mut by_path: BTreeMap<&str, Vec<(usize, &FileEntry)>> = BTreeMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if file.layer == RESOURCE_LAYER {
+            continue;
+        }
+        if !config.layers.iter().any(|l| l.name == file.layer) {
+            return Err(DmodpkgError::layer(format!(
+                "file '{}' belongs to unknown layer '{}'",
+                file.path, file.layer
+            )));
+        }
+        if rank.contains_key(file.layer.as_str()) {
+            by_path.entry(&file.path).or_default().push((index, file));
+        }
+    }
+
+    let mut resolved = Vec::with_capacity(by_path.len());
+    let mut overrides = Vec::new();
+    for (path, mut candidates) in by_path {
+        candidates.sort_by_key(|(_, f)| rank[f.layer.as_str()]);
+        let (index, winner) = candidates.pop().expect("at least one candidate per path");
+        let winner_priority = rank[winner.layer.as_str()].0;
+
+        for (_, loser) in candidates.iter().rev() {
+            overrides.push(Override {
+                path: path.to_string(),
+                winner: winner.layer.clone(),
+                winner_priority,
+                overridden: loser.layer.clone(),
+                overridden_priority: rank[loser.layer.as_str()].0,
+            });
+        }
+
+        resolved.push(ResolvedFile {
+            path: path.to_string(),
+            layer: winner.layer.clone(),
+            priority: winner_priority,
+            index,
+        });
+    }
+
+    Ok(Resolution {
+        variants,
+        layers,
+        files: resolved,
+        overrides,
+    })
+}
+
+fn find_layer<'a>(config: &'a ModConfig, name: &str) -> Result<&'a Layer> {
+    config
+        .layers
+        .iter()
+        .find(|l| l.name == name)
+        .ok_or_else(|| DmodpkgError::layer(format!("unknown layer '{}'", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "name": "test-mod",
+        "display_name": "Test Mod",
+        "version": "1.0.0",
+        "description": "A test mod",
+        "authors": ["TestAuthor"],
+        "variant_groups": [{
+            "id": "quality",
+            "name": "Graphics Quality",
+            "default": "medium",
+            "variants": [
+                { "id": "low", "name": "Low", "layers": ["base"] },
+                { "id": "medium", "name": "Medium", "layers": ["base", "med_quality"] },
+                { "id": "high", "name": "High", "layers": ["base", "high_quality"] }
+            ]
+        }],
+        "layers": [
+            { "name": "base", "priority": 0, "required": true },
+            { "name": "med_quality", "priority": 10 },
+            { "name": "high_quality", "priority": 10 },
+            { "name": "enhanced_audio", "priority": 5 },
+            { "name": "bonus_effects", "priority": 5 }
+        ]
+    }"#;
+
+    fn config() -> ModConfig {
+        ModConfig::from_json(CONFIG).unwrap()
+    }
+
+    fn files() -> Vec<FileEntry> {
+        [
+            ("base", "characters.vpk"),
+            ("base", "sounds.vpk"),
+            ("base", "maps.vpk"),
+            ("med_quality", "characters.vpk"),
+            ("high_quality", "characters.vpk"),
+            ("enhanced_audio", "sounds.vpk"),
+            ("bonus_effects", "sounds.vpk"),
+            ("", "README.md"),
+        ]
+        .into_iter()
+        .map(|(layer, path)| FileEntry {
+            path: path.to_string(),
+            layer: layer.to_string(),
+            uncompressed_size: 0,
+            chunk_indices: vec![],
+            sha256: [0; 32],
+        })
+        .collect()
+    }
+
+    fn winners(resolution: &Resolution) -> Vec<(&str, &str)> {
+        resolution
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.layer.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_default_selection() {
+        let resolution = resolve(&config(), &Selection::default(), &files()).unwrap();
+
+        assert_eq!(resolution.variants["quality"], "medium");
+        assert_eq!(resolution.layers, vec!["base", "med_quality"]);
+        assert_eq!(
+            winners(&resolution),
+            vec![
+                ("characters.vpk", "med_quality"),
+                ("maps.vpk", "base"),
+                ("sounds.vpk", "base")
+            ]
+        );
+        assert_eq!(
+            resolution.overrides[0].to_string(),
+            "characters.vpk: 'med_quality' (priority 10) overrides 'base' (priority 0)"
+        );
+    }
+
+    #[test]
+    fn test_variants_and_toggles() {
+        let selection = Selection {
+            variants: HashMap::from([("quality".to_string(), "high".to_string())]),
+            layers: HashMap::from([
+                ("enhanced_audio".to_string(), true),
+                ("bonus_effects".to_string(), true),
+                ("base".to_string(), true),
+            ]),
+        };
+
+        let resolution = resolve(&config(), &selection, &files()).unwrap();
+
+        assert_eq!(
+            resolution.layers,
+            vec!["base", "enhanced_audio", "bonus_effects", "high_quality"]
+        );
+        let sounds = resolution
+            .files
+            .iter()
+            .find(|f| f.path == "sounds.vpk")
+            .unwrap();
+        // Equal priority: the layer declared later wins
+        assert_eq!(sounds.layer, "bonus_effects");
+        assert_eq!(sounds.index, 6);
+
+        let explained: Vec<String> = resolution
+            .overrides
+            .iter()
+            .filter(|o| o.path == "sounds.vpk")
+            .map(|o| o.to_string())
+            .collect();
+        assert_eq!(
+            explained,
+            vec![
+                "sounds.vpk: 'bonus_effects' (priority 5) overrides 'enhanced_audio' (priority 5), declared later",
+                "sounds.vpk: 'bonus_effects' (priority 5) overrides 'base' (priority 0)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_selections() {
+        let check = |variants: &[(&str, &str)], layers: &[(&str, bool)]| {
+            let selection = Selection {
+                variants: variants
+                    .iter()
+                    .map(|(g, v)| (g.to_string(), v.to_string()))
+                    .collect(),
+                layers: layers.iter().map(|(l, on)| (l.to_string(), *on)).collect(),
+            };
+            resolve(&config(), &selection, &files()).unwrap_err()
+        };
+
+        assert!(matches!(
+            check(&[("colour", "red")], &[]),
+            DmodpkgError::Variant(_)
+        ));
+        assert!(matches!(
+            check(&[("quality", "ultra")], &[]),
+            DmodpkgError::Variant(_)
+        ));
+        assert!(matches!(
+            check(&[], &[("base", false)]),
+            DmodpkgError::Layer(_)
+        ));
+        assert!(matches!(
+            check(&[], &[("missing", true)]),
+            DmodpkgError::Layer(_)
+        ));
+        assert!(matches!(
+            check(&[], &[("high_quality", true)]),
+            DmodpkgError::Layer(_)
+        ));
+    }
+}
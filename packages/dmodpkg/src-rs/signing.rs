@@ -0,0 +1,369 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{DmodpkgError, Result};
+use crate::format::{MetadataSection, PackageHeader};
+use crate::types::{Author, Signature};
+
+/// Algorithm recorded in package signatures
+pub const SIGNATURE_ALGORITHM: &str = "ed25519";
+
+/// Domain separator hashed ahead of the signed package contents
+const DIGEST_CONTEXT: &[u8] = b"dmodpkg signature v1\0";
+
+/// The digest a package signature is made over
+///
+/// Covers the header fields describing the contents (magic, version, flags
+/// and total uncompressed size), the metadata without its signature, the
+/// uncompressed file index, the chunk table and the SHA-256 of the data
+/// section. Section offsets, sizes and the package CRC64 are left out since
+/// adding the signature changes them; the sections themselves are hashed.
+pub(crate) fn package_digest(
+    header: &PackageHeader,
+    metadata: &MetadataSection,
+    file_index: &[u8],
+    chunk_table: &[u8],
+    data_sha256: &[u8; 32],
+) -> Result<[u8; 32]> {
+    let mut unsigned = metadata.clone();
+    unsigned.signature = None;
+    let metadata_json = serde_json::to_vec(&unsigned)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(DIGEST_CONTEXT);
+    hasher.update(header.magic);
+    hasher.update(header.version.to_le_bytes());
+    hasher.update(header.flags.to_le_bytes());
+    hasher.update(header.total_uncompressed_size.to_le_bytes());
+    for section in [metadata_json.as_slice(), file_index, chunk_table] {
+        hasher.update((section.len() as u64).to_le_bytes());
+        hasher.update(section);
+    }
+    hasher.update(data_sha256);
+    Ok(hasher.finalize().into())
+}
+
+/// Sign a package digest
+pub(crate) fn sign(key: &SigningKey, digest: &[u8; 32]) -> Signature {
+    Signature {
+        algorithm: SIGNATURE_ALGORITHM.to_string(),
+        public_key: encode_public_key(&key.verifying_key()),
+        signature: STANDARD.encode(key.sign(digest).to_bytes()),
+    }
+}
+
+/// Check `signature` over a package digest and return the key that made it
+pub(crate) fn verify(signature: &Signature, digest: &[u8; 32]) -> Result<VerifyingKey> {
+    if signature.algorithm != SIGNATURE_ALGORITHM {
+        return Err(DmodpkgError::signature(format!(
+            "unsupported signature algorithm '{}'",
+            signature.algorithm
+        )));
+    }
+
+    let key = decode_public_key(&signature.public_key)?;
+    let bytes = STANDARD
+        .decode(&signature.signature)
+        .map_err(|e| DmodpkgError::signature(format!("invalid signature encoding: {}", e)))?;
+    let signature = ed25519_dalek::Signature::from_slice(&bytes)
+        .map_err(|_| DmodpkgError::signature("signature must be 64 bytes"))?;
+
+    key.verify_strict(digest, &signature)
+        .map_err(|_| DmodpkgError::signature("signature does not match the package contents"))?;
+    Ok(key)
+}
+
+/// Base64-encode a public key the way package signatures store it
+pub fn encode_public_key(key: &VerifyingKey) -> String {
+    STANDARD.encode(key.as_bytes())
+}
+
+/// Decode a base64-encoded public key
+pub fn decode_public_key(value: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = STANDARD
+        .decode(value.trim())
+        .map_err(|e| DmodpkgError::signature(format!("invalid public key encoding: {}", e)))?
+        .try_into()
+        .map_err(|_| DmodpkgError::signature("public key must be 32 bytes"))?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| DmodpkgError::signature("public key is not a valid ed25519 key"))
+}
+
+/// Base64-encode a signing key's 32-byte secret, the format `dmodpkg pack
+/// --sign` reads key files in
+pub fn encode_signing_key(key: &SigningKey) -> String {
+    STANDARD.encode(key.to_bytes())
+}
+
+/// Decode a base64-encoded signing key secret
+pub fn decode_signing_key(value: &str) -> Result<SigningKey> {
+    let bytes: [u8; 32] = STANDARD
+        .decode(value.trim())
+        .map_err(|e| DmodpkgError::signature(format!("invalid signing key encoding: {}", e)))?
+        .try_into()
+        .map_err(|_| DmodpkgError::signature("signing key must be 32 bytes"))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Who signed a package, as far as a [`Keyring`] can tell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Trust {
+    /// The package has no signature
+    Unsigned,
+    /// Validly signed by a key the keyring does not know
+    Untrusted {
+        /// Base64-encoded public key
+        public_key: String,
+    },
+    /// Validly signed by a trusted author
+    Trusted {
+        /// Author the key belongs to
+        author: String,
+        /// Base64-encoded public key
+        public_key: String,
+    },
+}
+
+impl Trust {
+    /// The trusted author who signed the package, if any
+    pub fn author(&self) -> Option<&str> {
+        match self {
+            Trust::Trusted { author, .. } => Some(author),
+            _ => None,
+        }
+    }
+}
+
+/// A trusted author's public key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    /// Author name, as used in mod configurations
+    pub author: String,
+    /// Base64-encoded public key
+    pub public_key: String,
+}
+
+/// Public keys of trusted authors
+///
+/// Serializes as `{ "keys": [{ "author": ..., "public_key": ... }] }`. One
+/// author may have several keys; each key belongs to a single author.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Keyring {
+    keys: Vec<TrustedKey>,
+}
+
+impl Keyring {
+    /// Create an empty keyring
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a keyring from JSON, rejecting invalid or duplicate keys
+    pub fn from_json(json: &str) -> Result<Self> {
+        let parsed: Keyring = serde_json::from_str(json)?;
+        let mut keyring = Keyring::new();
+        for entry in parsed.keys {
+            let key = decode_public_key(&entry.public_key)?;
+            if keyring.author(&key).is_some() {
+                return Err(DmodpkgError::signature(format!(
+                    "public key {} is listed more than once",
+                    entry.public_key
+                )));
+            }
+            keyring.add(entry.author, &key);
+        }
+        Ok(keyring)
+    }
+
+    /// Serialize the keyring to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(Into::into)
+    }
+
+    /// Trust `key` as belonging to `author`, returning the author it
+    /// previously belonged to
+    pub fn add(&mut self, author: impl Into<String>, key: &VerifyingKey) -> Option<String> {
+        let public_key = encode_public_key(key);
+        let author = author.into();
+        match self.keys.iter_mut().find(|k| k.public_key == public_key) {
+            Some(entry) => Some(std::mem::replace(&mut entry.author, author)),
+            None => {
+                self.keys.push(TrustedKey { author, public_key });
+                None
+            }
+        }
+    }
+
+    /// Stop trusting `key`, returning the author it belonged to
+    pub fn remove(&mut self, key: &VerifyingKey) -> Option<String> {
+        let public_key = encode_public_key(key);
+        let index = self.keys.iter().position(|k| k.public_key == public_key)?;
+        Some(self.keys.remove(index).author)
+    }
+
+    /// The author `key` belongs to
+    pub fn author(&self, key: &VerifyingKey) -> Option<&str> {
+        let public_key = encode_public_key(key);
+        self.keys
+            .iter()
+            .find(|k| k.public_key == public_key)
+            .map(|k| k.author.as_str())
+    }
+
+    /// Every trusted key, in insertion order
+    pub fn keys(&self) -> &[TrustedKey] {
+        &self.keys
+    }
+
+    /// How far a valid signature by `key` can be trusted
+    pub fn trust(&self, key: &VerifyingKey) -> Trust {
+        let public_key = encode_public_key(key);
+        match self.author(key) {
+            Some(author) => Trust::Trusted {
+                author: author.to_string(),
+                public_key,
+            },
+            None => Trust::Untrusted { public_key },
+        }
+    }
+
+    /// Authors a package claims that the keyring knows but that did not sign
+    /// it
+    ///
+    /// A non-empty result means the package names a trusted author without
+    /// carrying that author's signature, which callers should warn about.
+    /// Names are compared case-insensitively.
+    pub fn unverified_authors(&self, authors: &[Author], trust: &Trust) -> Vec<String> {
+        authors
+            .iter()
+            .map(|a| match a {
+                Author::Name(name) => name,
+                Author::Detailed { name, .. } => name,
+            })
+            .filter(|name| {
+                self.keys
+                    .iter()
+                    .any(|k| k.author.to_lowercase() == name.to_lowercase())
+            })
+            .filter(|name| {
+                trust
+                    .author()
+                    .is_none_or(|signer| signer.to_lowercase() != name.to_lowercase())
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_signing_key_round_trip() {
+        let encoded = encode_signing_key(&key(3));
+        assert_eq!(decode_signing_key(&format!("{encoded}\n")).unwrap(), key(3));
+        assert!(matches!(
+            decode_signing_key("not base64!"),
+            Err(DmodpkgError::Signature(_))
+        ));
+        assert!(matches!(
+            decode_signing_key(&encode_public_key(&key(3).verifying_key())[..20]),
+            Err(DmodpkgError::Signature(_))
+        ));
+    }
+
+    #[test]
+    fn test_sign_and_verify_digests() {
+        let digest = [1u8; 32];
+        let signature = sign(&key(1), &digest);
+        assert_eq!(verify(&signature, &digest).unwrap(), key(1).verifying_key());
+
+        let mut other = digest;
+        other[0] ^= 1;
+        assert!(matches!(
+            verify(&signature, &other),
+            Err(DmodpkgError::Signature(_))
+        ));
+
+        let forged = Signature {
+            public_key: encode_public_key(&key(2).verifying_key()),
+            ..signature.clone()
+        };
+        assert!(verify(&forged, &digest).is_err());
+
+        let unknown = Signature {
+            algorithm: "rsa".to_string(),
+            ..signature
+        };
+        assert!(verify(&unknown, &digest).is_err());
+    }
+
+    #[test]
+    fn test_keyring_management() {
+        let mut keyring = Keyring::new();
+        assert_eq!(keyring.add("Alice", &key(1).verifying_key()), None);
+        assert_eq!(keyring.add("Bob", &key(2).verifying_key()), None);
+        assert_eq!(
+            keyring.add("Robert", &key(2).verifying_key()),
+            Some("Bob".to_string())
+        );
+
+        let keyring = Keyring::from_json(&keyring.to_json().unwrap()).unwrap();
+        assert_eq!(keyring.keys().len(), 2);
+        assert_eq!(keyring.author(&key(2).verifying_key()), Some("Robert"));
+        assert!(matches!(
+            keyring.trust(&key(3).verifying_key()),
+            Trust::Untrusted { .. }
+        ));
+
+        let mut copy = keyring.clone();
+        assert_eq!(
+            copy.remove(&key(1).verifying_key()),
+            Some("Alice".to_string())
+        );
+        assert_eq!(copy.author(&key(1).verifying_key()), None);
+
+        let duplicate = format!(
+            r#"{{ "keys": [
+                {{ "author": "Alice", "public_key": "{0}" }},
+                {{ "author": "Mallory", "public_key": "{0}" }}
+            ] }}"#,
+            encode_public_key(&key(1).verifying_key())
+        );
+        assert!(Keyring::from_json(&duplicate).is_err());
+        assert!(
+            Keyring::from_json(r#"{ "keys": [{ "author": "A", "public_key": "AAAA" }] }"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_unverified_author_claims() {
+        let mut keyring = Keyring::new();
+        keyring.add("Alice", &key(1).verifying_key());
+        let authors = vec![
+            Author::Name("alice".to_string()),
+            Author::Name("Carol".to_string()),
+        ];
+
+        assert_eq!(
+            keyring.unverified_authors(&authors, &Trust::Unsigned),
+            vec!["alice"]
+        );
+        let untrusted = keyring.trust(&key(2).verifying_key());
+        assert_eq!(
+            keyring.unverified_authors(&authors, &untrusted),
+            vec!["alice"]
+        );
+        let trusted = keyring.trust(&key(1).verifying_key());
+        assert!(keyring.unverified_authors(&authors, &trusted).is_empty());
+    }
+}
@@ -101,8 +101,10 @@ pub struct BuildInfo {
     pub builder_version: String,
     /// Build timestamp (ISO 8601)
     pub build_timestamp: String,
-    /// Build platform
-    pub platform: String,
+    /// Build platform. Not recorded by this builder, as it would make
+    /// packages built on different systems differ.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
     /// Checksum algorithm used
     pub checksum_algorithm: String,
 }
@@ -146,6 +148,45 @@ pub struct ChunkMetadata {
     pub crc32: u32,
 }
 
+/// Package embedded in a bundle, as recorded in the bundle metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncludedMod {
+    /// Filename of the .dmodpkg file
+    pub filename: String,
+    /// Mod name from the package metadata
+    pub name: String,
+    /// Mod version from the package metadata
+    pub version: String,
+    /// Package size in bytes
+    pub size_bytes: u64,
+    /// Package checksum ("sha256:<hex>")
+    pub checksum: String,
+}
+
+/// Bundle build information (added during packaging)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleBuildInfo {
+    /// Version of the builder tool
+    pub builder_version: String,
+    /// Build timestamp (ISO 8601)
+    pub build_timestamp: String,
+    /// Embedded packages
+    pub included_mods: Vec<IncludedMod>,
+}
+
+/// Entry in a bundle's package index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageIndexEntry {
+    /// Filename of the .dmodpkg file
+    pub filename: String,
+    /// Offset in the packages section
+    pub offset: u64,
+    /// Package size in bytes
+    pub size: u64,
+    /// SHA256 checksum of the package
+    pub sha256: [u8; 32],
+}
+
 /// Mod entry in a bundle
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BundleModEntry {
@@ -0,0 +1,792 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::bundle::{apply_preset, BundleReader, BUNDLE_CONFIG_FILE_NAME, MODS_DIR};
+use crate::config::{BundleConfig, ModConfig};
+use crate::error::{DmodpkgError, Result};
+use crate::reader::PackageReader;
+use crate::signing::Keyring;
+use crate::types::FileEntry;
+use crate::writer::{is_url, is_vpk, project_paths, CONFIG_FILE_NAME, CONTENT_DIR, RESOURCE_LAYER};
+
+/// Image formats accepted for previews and screenshots
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp"];
+
+/// Images above this size get a warning (2MB)
+pub const IMAGE_WARNING_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Images above this size are rejected (10MB)
+pub const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Directory embedded images are expected to live under
+pub const PREVIEWS_DIR: &str = "previews";
+
+/// Recommended dimensions for variant preview images
+pub const PREVIEW_IMAGE_SIZES: &[(u32, u32)] = &[(1280, 720), (1920, 1080)];
+
+/// Smallest recommended dimensions for screenshots
+pub const MIN_SCREENSHOT_SIZE: (u32, u32) = (1920, 1080);
+
+/// Configuration schema version the validation rules implement
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How serious a validation finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The project or package cannot be used as is
+    Error,
+    /// Allowed, but probably a mistake
+    Warning,
+}
+
+/// A single validation finding
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    /// How serious the finding is
+    pub severity: Severity,
+    /// What was found
+    pub message: String,
+}
+
+/// Findings from validating a project, package or bundle
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationReport {
+    /// Every finding, in the order it was found
+    pub issues: Vec<Issue>,
+    /// Whether one of the errors is a checksum or signature mismatch
+    pub integrity_failed: bool,
+}
+
+impl ValidationReport {
+    /// Record an error
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.issues.push(Issue {
+            severity: Severity::Error,
+            message: message.into(),
+        });
+    }
+
+    /// Record a warning
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.issues.push(Issue {
+            severity: Severity::Warning,
+            message: message.into(),
+        });
+    }
+
+    /// Record a failed check as an error, passing other failures through
+    fn check(&mut self, result: Result<()>) -> Result<bool> {
+        match result {
+            Ok(()) => Ok(true),
+            Err(DmodpkgError::Io(e)) => Err(e.into()),
+            Err(e) => {
+                self.integrity_failed |= e.is_integrity();
+                self.error(e.to_string());
+                Ok(false)
+            }
+        }
+    }
+
+    /// Findings with error severity
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    /// Findings with warning severity
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == Severity::Warning)
+    }
+
+    /// Whether validation passed; in strict mode warnings count as failures
+    pub fn passed(&self, strict: bool) -> bool {
+        self.errors().next().is_none() && (!strict || self.warnings().next().is_none())
+    }
+}
+
+/// Validate a mod project directory
+///
+/// Checks the configuration, that every layer has a flat folder of VPK files
+/// under `content/`, and that every preview image and screenshot follows the
+/// image rules: an existing PNG, JPEG or WebP file under `previews/` of at
+/// most 10MB (warning above 2MB), or an HTTPS URL. Preview images should be
+/// 1280x720 or 1920x1080 and screenshots at least 1920x1080; other sizes get
+/// a warning. `path` is the project directory or its `mod.config.json`.
+pub fn validate_project(path: impl AsRef<Path>) -> Result<ValidationReport> {
+    let (dir, config_path) = project_paths(path.as_ref(), CONFIG_FILE_NAME);
+    let dir = dir.as_path();
+    let mut report = ValidationReport::default();
+
+    let config = match fs::read_to_string(&config_path) {
+        Ok(json) => ModConfig::from_json(&json),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            report.error(format!("{} not found", config_path.display()));
+            return Ok(report);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            report.error(e.to_string());
+            return Ok(report);
+        }
+    };
+    report.check(config.validate())?;
+    check_schema(&mut report, config.schema.as_deref());
+
+    let content = dir.join(CONTENT_DIR);
+    for layer in &config.layers {
+        let layer_dir = content.join(&layer.name);
+        if !layer_dir.is_dir() {
+            report.error(format!(
+                "Layer '{}' has no directory at {}/{}",
+                layer.name, CONTENT_DIR, layer.name
+            ));
+            continue;
+        }
+
+        let mut files = 0;
+        for entry in fs::read_dir(&layer_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                report.warning(format!(
+                    "Layer '{}' contains a subdirectory '{}'; layers are flat, so it will not be packed",
+                    layer.name, name
+                ));
+                continue;
+            }
+            if !is_vpk(&name) {
+                report.warning(format!(
+                    "Layer '{}' contains '{}', which is not a VPK file and will not be packed",
+                    layer.name, name
+                ));
+                continue;
+            }
+            files += 1;
+        }
+        if files == 0 {
+            report.warning(format!("Layer '{}' is empty", layer.name));
+        }
+    }
+
+    if content.is_dir() {
+        for entry in fs::read_dir(&content)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if !name.starts_with('.') && !config.layers.iter().any(|l| l.name == name) {
+                report.warning(format!(
+                    "{}/{} does not match any layer and will not be packed",
+                    CONTENT_DIR, name
+                ));
+            }
+        }
+    }
+
+    if let Some(readme) = config.readme.as_deref().filter(|r| !is_url(r)) {
+        if !dir.join(readme).is_file() {
+            report.error(format!("README not found: {}", readme));
+        }
+    }
+
+    for_each_image(&config, |image, kind| {
+        check_image(&mut report, image, kind, |path| project_image(dir, path))
+    })?;
+    for variant in config.variant_groups.iter().flat_map(|g| &g.variants) {
+        if variant.preview_image.is_none() {
+            report.warning(format!("Variant '{}' has no preview image", variant.id));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Validate a built package: its integrity and signature, configuration,
+/// layers and embedded images
+///
+/// Authors the package claims that `keyring` trusts but that did not sign it
+/// get a warning.
+pub fn validate_package<R: Read + Seek>(
+    reader: &mut PackageReader<R>,
+    keyring: &Keyring,
+) -> Result<ValidationReport> {
+    let mut report = ValidationReport::default();
+    let mut trust = None;
+    report.check(reader.verify_signature(keyring).map(|t| trust = Some(t)))?;
+
+    let config = match reader.config() {
+        Ok(config) => config,
+        Err(e) => {
+            report.error(e.to_string());
+            return Ok(report);
+        }
+    };
+    report.check(config.validate())?;
+    check_schema(&mut report, config.schema.as_deref());
+    if let Some(trust) = &trust {
+        for author in keyring.unverified_authors(&config.authors, trust) {
+            report.warning(format!(
+                "Package claims to be by {} but is not signed by them",
+                author
+            ));
+        }
+    }
+
+    for layer in &config.layers {
+        if reader.layer_files(&layer.name).next().is_none() {
+            report.warning(format!("Layer '{}' is empty", layer.name));
+        }
+    }
+    for file in reader.files() {
+        if file.layer != RESOURCE_LAYER && !config.layers.iter().any(|l| l.name == file.layer) {
+            report.error(format!(
+                "File '{}' belongs to unknown layer '{}'",
+                file.path, file.layer
+            ));
+        }
+    }
+
+    let resources: Vec<FileEntry> = reader.layer_files(RESOURCE_LAYER).cloned().collect();
+    for_each_image(&config, |image, kind| {
+        check_image(&mut report, image, kind, |path| {
+            let Some(entry) = resources.iter().find(|f| f.path == path) else {
+                return Ok(None);
+            };
+            // A corrupt resource has already failed the integrity check
+            ImageFile::new(entry.uncompressed_size, || {
+                Ok(reader.read_entry(entry).unwrap_or_default())
+            })
+            .map(Some)
+        })
+    })?;
+
+    Ok(report)
+}
+
+/// Validate a bundle project; `path` is the project directory or its
+/// `bundle.config.json`
+pub fn validate_bundle_project(path: impl AsRef<Path>) -> Result<ValidationReport> {
+    let (dir, config_path) = project_paths(path.as_ref(), BUNDLE_CONFIG_FILE_NAME);
+    let dir = dir.as_path();
+    let mut report = ValidationReport::default();
+
+    let config = match fs::read_to_string(&config_path) {
+        Ok(json) => BundleConfig::from_json(&json),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            report.error(format!("{} not found", config_path.display()));
+            return Ok(report);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            report.error(e.to_string());
+            return Ok(report);
+        }
+    };
+    if !report.check(config.validate())? {
+        return Ok(report);
+    }
+    check_schema(&mut report, config.schema.as_deref());
+
+    let mut mods = HashMap::new();
+    for entry in &config.mods {
+        let path = dir.join(MODS_DIR).join(&entry.package);
+        if !path.is_file() {
+            report.error(format!("Package not found: {}/{}", MODS_DIR, entry.package));
+            continue;
+        }
+
+        let checked = PackageReader::open(&path).and_then(|mut reader| {
+            reader.verify()?;
+            reader.config()
+        });
+        match checked {
+            Ok(mod_config) => {
+                mods.insert(entry.package.clone(), mod_config);
+            }
+            Err(DmodpkgError::Io(e)) => return Err(e.into()),
+            Err(e) => {
+                report.integrity_failed |= e.is_integrity();
+                report.error(format!("{}: {}", entry.package, e));
+            }
+        }
+    }
+
+    check_presets(&mut report, &config, &mods)?;
+    for screenshot in &config.screenshots {
+        check_image(&mut report, screenshot, ImageKind::Screenshot, |path| {
+            project_image(dir, path)
+        })?;
+    }
+
+    Ok(report)
+}
+
+/// Validate a built bundle: its integrity, configuration, embedded packages
+/// and presets
+pub fn validate_bundle<R: Read + Seek>(reader: &mut BundleReader<R>) -> Result<ValidationReport> {
+    let mut report = ValidationReport::default();
+    report.check(reader.verify())?;
+
+    let config = match reader.config() {
+        Ok(config) => config,
+        Err(e) => {
+            report.error(e.to_string());
+            return Ok(report);
+        }
+    };
+    if !report.check(config.validate())? {
+        return Ok(report);
+    }
+    check_schema(&mut report, config.schema.as_deref());
+
+    let mut mods = HashMap::new();
+    for entry in reader.packages().to_vec() {
+        match reader.package(&entry.filename).and_then(|p| p.config()) {
+            Ok(mod_config) => {
+                mods.insert(entry.filename, mod_config);
+            }
+            Err(DmodpkgError::Io(e)) => return Err(e.into()),
+            Err(e) => report.error(e.to_string()),
+        }
+    }
+    for entry in &config.mods {
+        if !reader
+            .packages()
+            .iter()
+            .any(|p| p.filename == entry.package)
+        {
+            report.error(format!("Package '{}' is not embedded", entry.package));
+        }
+    }
+
+    check_presets(&mut report, &config, &mods)?;
+    let resources: HashMap<&str, &[u8]> = reader
+        .resources()
+        .iter()
+        .map(|(path, data)| (path.as_str(), data.as_slice()))
+        .collect();
+    for screenshot in &config.screenshots {
+        check_image(&mut report, screenshot, ImageKind::Screenshot, |path| {
+            resources
+                .get(path)
+                .map(|data| ImageFile::new(data.len() as u64, || Ok(data.to_vec())))
+                .transpose()
+        })?;
+    }
+
+    Ok(report)
+}
+
+fn check_presets(
+    report: &mut ValidationReport,
+    config: &BundleConfig,
+    mods: &HashMap<String, ModConfig>,
+) -> Result<()> {
+    for preset in &config.presets {
+        for mod_config in &preset.mods {
+            let has_variants = mods
+                .get(&mod_config.package)
+                .is_some_and(|m| !m.variant_groups.is_empty());
+            if mods.contains_key(&mod_config.package) && !has_variants {
+                report.warning(format!(
+                    "Mod '{}' has no variants but is referenced in preset '{}'",
+                    mod_config.package, preset.id
+                ));
+            }
+        }
+        report.check(apply_preset(config, preset, mods).map(|_| ()))?;
+    }
+    Ok(())
+}
+
+/// Warn when the configuration's `$schema` names a version other than
+/// [`SCHEMA_VERSION`], e.g. `.../mod-config-v2.json`
+fn check_schema(report: &mut ValidationReport, schema: Option<&str>) {
+    let Some(version) = schema.and_then(schema_version) else {
+        return;
+    };
+    if version != SCHEMA_VERSION {
+        report.warning(format!(
+            "Configuration uses schema version {}, but only version {} is supported",
+            version, SCHEMA_VERSION
+        ));
+    }
+}
+
+/// The version in a schema URL ending in `-v<version>.json`
+fn schema_version(schema: &str) -> Option<u32> {
+    let (_, version) = schema.strip_suffix(".json")?.rsplit_once("-v")?;
+    version.parse().ok()
+}
+
+/// Which dimension recommendation an image falls under
+#[derive(Debug, Clone, Copy)]
+enum ImageKind {
+    Preview,
+    Screenshot,
+}
+
+/// An embedded image as far as the image rules care
+struct ImageFile {
+    size: u64,
+    /// `None` when the image is too large to read or not a PNG, JPEG or WebP
+    dimensions: Option<(u32, u32)>,
+}
+
+impl ImageFile {
+    /// `read` is only called when the image is within [`MAX_IMAGE_SIZE`]
+    fn new(size: u64, read: impl FnOnce() -> Result<Vec<u8>>) -> Result<Self> {
+        let dimensions = if size <= MAX_IMAGE_SIZE {
+            image_dimensions(&read()?)
+        } else {
+            None
+        };
+        Ok(Self { size, dimensions })
+    }
+}
+
+/// Look up an image in a project directory
+fn project_image(dir: &Path, path: &str) -> Result<Option<ImageFile>> {
+    let file = dir.join(path);
+    if !file.is_file() {
+        return Ok(None);
+    }
+    ImageFile::new(fs::metadata(&file)?.len(), || Ok(fs::read(&file)?)).map(Some)
+}
+
+/// Call `f` with every preview image and screenshot path in `config`
+fn for_each_image(
+    config: &ModConfig,
+    mut f: impl FnMut(&str, ImageKind) -> Result<()>,
+) -> Result<()> {
+    for screenshot in &config.screenshots {
+        f(screenshot, ImageKind::Screenshot)?;
+    }
+    for variant in config.variant_groups.iter().flat_map(|g| &g.variants) {
+        if let Some(image) = &variant.preview_image {
+            f(image, ImageKind::Preview)?;
+        }
+        for screenshot in &variant.screenshots {
+            f(screenshot, ImageKind::Screenshot)?;
+        }
+    }
+    Ok(())
+}
+
+/// Apply the image rules to `image`; `lookup` returns an embedded image, or
+/// `None` when it does not exist
+fn check_image(
+    report: &mut ValidationReport,
+    image: &str,
+    kind: ImageKind,
+    lookup: impl FnOnce(&str) -> Result<Option<ImageFile>>,
+) -> Result<()> {
+    if is_url(image) {
+        if !image.starts_with("https://") {
+            report.error(format!("Image URL must use HTTPS: {}", image));
+        }
+        return Ok(());
+    }
+
+    let extension = Path::new(image)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    let supported = extension.is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.as_str()));
+    if !supported {
+        report.error(format!(
+            "Unsupported image format (use PNG, JPEG or WebP): {}",
+            image
+        ));
+    }
+    if !image.starts_with(&format!("{}/", PREVIEWS_DIR)) {
+        report.warning(format!(
+            "Image should be under {}/: {}",
+            PREVIEWS_DIR, image
+        ));
+    }
+
+    let Some(file) = lookup(image)? else {
+        report.error(format!("Image not found: {}", image));
+        return Ok(());
+    };
+    if file.size > MAX_IMAGE_SIZE {
+        report.error(format!("Image is larger than 10MB: {}", image));
+        return Ok(());
+    }
+    if file.size > IMAGE_WARNING_SIZE {
+        report.warning(format!(
+            "Image is larger than 2MB and may load slowly: {}",
+            image
+        ));
+    }
+
+    match (file.dimensions, kind) {
+        (None, _) if supported => report.error(format!(
+            "Image is not a valid PNG, JPEG or WebP file: {}",
+            image
+        )),
+        (None, _) => {}
+        (Some((width, height)), ImageKind::Preview)
+            if !PREVIEW_IMAGE_SIZES.contains(&(width, height)) =>
+        {
+            report.warning(format!(
+                "Preview image is {}x{}; 1280x720 or 1920x1080 is recommended: {}",
+                width, height, image
+            ))
+        }
+        (Some((width, height)), ImageKind::Screenshot)
+            if width < MIN_SCREENSHOT_SIZE.0 || height < MIN_SCREENSHOT_SIZE.1 =>
+        {
+            report.warning(format!(
+                "Screenshot is {}x{}; 1920x1080 or larger is recommended: {}",
+                width, height, image
+            ))
+        }
+        (Some(_), _) => {}
+    }
+
+    Ok(())
+}
+
+/// Width and height from a PNG, JPEG or WebP header
+fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let le16 = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let le24 = |at: usize| {
+        let b = data.get(at..at + 3)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], 0]))
+    };
+
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") && data.get(12..16) == Some(b"IHDR") {
+        let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+        let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+        return Some((width, height));
+    }
+
+    if data.starts_with(&[0xff, 0xd8]) {
+        // Walk the segments up to the start-of-frame marker
+        let mut at = 2;
+        loop {
+            if *data.get(at)? != 0xff {
+                return None;
+            }
+            let marker = *data.get(at + 1)?;
+            match marker {
+                0xff => at += 1,
+                0x01 | 0xd0..=0xd7 => at += 2,
+                0xd9 | 0xda => return None,
+                0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                    return Some((be16(at + 7)? as u32, be16(at + 5)? as u32));
+                }
+                _ => at += 2 + be16(at + 2)? as usize,
+            }
+        }
+    }
+
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return match data.get(12..16)? {
+            b"VP8 " if data.get(23..26) == Some(&[0x9d, 0x01, 0x2a]) => {
+                Some(((le16(26)? & 0x3fff) as u32, (le16(28)? & 0x3fff) as u32))
+            }
+            b"VP8L" if data.get(20) == Some(&0x2f) => {
+                let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::{PackOptions, PackageWriter};
+    use ed25519_dalek::SigningKey;
+
+    const CONFIG: &str = r#"{
+        "name": "test-mod",
+        "display_name": "Test Mod",
+        "version": "1.0.0",
+        "description": "A test mod",
+        "authors": ["TestAuthor"],
+        "screenshots": ["previews/mod/shot.png", "http://example.com/a.png", "shot.gif"],
+        "variant_groups": [{
+            "id": "skin",
+            "name": "Skin",
+            "default": "dark",
+            "variants": [
+                { "id": "dark", "name": "Dark", "layers": ["dark"], "preview_image": "previews/skin/dark/main.png" },
+                { "id": "light", "name": "Light", "layers": ["base"] }
+            ]
+        }],
+        "layers": [
+            { "name": "base", "priority": 0, "required": true },
+            { "name": "dark", "priority": 10 }
+        ]
+    }"#;
+
+    /// A PNG header for a `width` x `height` image, padded to `len` bytes
+    fn png(width: u32, height: u32, len: usize) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.resize(len, 0);
+        data
+    }
+
+    fn messages(report: &ValidationReport, severity: Severity) -> Vec<&str> {
+        report
+            .issues
+            .iter()
+            .filter(|i| i.severity == severity)
+            .map(|i| i.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_project_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(CONFIG_FILE_NAME), CONFIG).unwrap();
+        fs::create_dir_all(root.join("content/base")).unwrap();
+        fs::create_dir_all(root.join("content/dark")).unwrap();
+        fs::create_dir_all(root.join("content/stray")).unwrap();
+        fs::create_dir_all(root.join("previews/mod")).unwrap();
+        fs::write(root.join("content/base/pak01_dir.vpk"), "vpk").unwrap();
+        fs::write(root.join("content/base/notes.txt"), "txt").unwrap();
+        fs::write(
+            root.join("previews/mod/shot.png"),
+            png(1280, 720, 3 * 1024 * 1024),
+        )
+        .unwrap();
+
+        let report = validate_project(root).unwrap();
+
+        assert_eq!(
+            messages(&report, Severity::Error),
+            vec![
+                "Image URL must use HTTPS: http://example.com/a.png",
+                "Unsupported image format (use PNG, JPEG or WebP): shot.gif",
+                "Image not found: shot.gif",
+                "Image not found: previews/skin/dark/main.png",
+            ]
+        );
+        assert_eq!(
+            messages(&report, Severity::Warning),
+            vec![
+                "Layer 'base' contains 'notes.txt', which is not a VPK file and will not be packed",
+                "Layer 'dark' is empty",
+                "content/stray does not match any layer and will not be packed",
+                "Image is larger than 2MB and may load slowly: previews/mod/shot.png",
+                "Screenshot is 1280x720; 1920x1080 or larger is recommended: previews/mod/shot.png",
+                "Image should be under previews/: shot.gif",
+                "Variant 'light' has no preview image",
+            ]
+        );
+        assert!(!report.passed(false));
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        assert_eq!(image_dimensions(&png(1920, 1080, 64)), Some((1920, 1080)));
+
+        // SOI, an APP0 segment, then a baseline SOF0 frame
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, 0xff, 0xc0, 0x00, 0x11, 0x08, 0x02,
+            0xd0, 0x05, 0x00,
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((1280, 720)));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        webp.extend_from_slice(&[0x7f, 0x07, 0x00, 0x37, 0x04, 0x00]);
+        assert_eq!(image_dimensions(&webp), Some((1920, 1080)));
+
+        assert_eq!(image_dimensions(&[0u8; 64]), None);
+        assert_eq!(image_dimensions(&jpeg[..12]), None);
+    }
+
+    #[test]
+    fn test_other_schema_versions() {
+        let mut report = ValidationReport::default();
+        check_schema(
+            &mut report,
+            Some("https://deadlockmods.app/schemas/mod-config-v1.json"),
+        );
+        assert!(report.issues.is_empty());
+
+        check_schema(
+            &mut report,
+            Some("https://deadlockmods.app/schemas/mod-config-v2.json"),
+        );
+        assert_eq!(
+            messages(&report, Severity::Warning),
+            vec!["Configuration uses schema version 2, but only version 1 is supported"]
+        );
+    }
+
+    #[test]
+    fn test_missing_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = validate_project(dir.path()).unwrap();
+        assert_eq!(report.errors().count(), 1);
+    }
+
+    #[test]
+    fn test_package_validation() {
+        let mut config = ModConfig::from_json(CONFIG).unwrap();
+        config.screenshots.clear();
+        config.variant_groups.clear();
+        let mut writer = PackageWriter::new(config, PackOptions::default()).unwrap();
+        writer
+            .add_file("base", "pak01_dir.vpk", b"vpk".to_vec())
+            .unwrap();
+        let mut bytes = Vec::new();
+        writer.write(&mut bytes).unwrap();
+
+        let mut reader = PackageReader::from_bytes(bytes.clone()).unwrap();
+        let report = validate_package(&mut reader, &Keyring::new()).unwrap();
+        assert_eq!(
+            messages(&report, Severity::Warning),
+            vec!["Layer 'dark' is empty"]
+        );
+        assert!(report.passed(false));
+        assert!(!report.passed(true));
+
+        let mut keyring = Keyring::new();
+        keyring.add(
+            "TestAuthor",
+            &SigningKey::from_bytes(&[1; 32]).verifying_key(),
+        );
+        let mut reader = PackageReader::from_bytes(bytes.clone()).unwrap();
+        let report = validate_package(&mut reader, &keyring).unwrap();
+        assert_eq!(
+            messages(&report, Severity::Warning),
+            vec![
+                "Package claims to be by TestAuthor but is not signed by them",
+                "Layer 'dark' is empty"
+            ]
+        );
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let mut reader = PackageReader::from_bytes(bytes).unwrap();
+        let report = validate_package(&mut reader, &Keyring::new()).unwrap();
+        assert!(!report.passed(false));
+        assert!(report.integrity_failed);
+    }
+}
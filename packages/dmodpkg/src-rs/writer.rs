@@ -0,0 +1,657 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::SigningKey;
+use sha2::{Digest, Sha256};
+
+use crate::config::ModConfig;
+use crate::error::{DmodpkgError, Result};
+use crate::format::{
+    encode_chunk_table, encode_file_index, MetadataSection, PackageHeader, CRC32, CRC64,
+    DEFAULT_CHUNK_SIZE, DEFAULT_COMPRESSION_LEVEL, HEADER_SIZE, MAX_CHUNK_SIZE, MIN_CHUNK_SIZE,
+};
+use crate::signing;
+use crate::types::{BuildInfo, ChunkMetadata, FileEntry};
+
+/// Name of the configuration file at the root of a mod project
+pub const CONFIG_FILE_NAME: &str = "mod.config.json";
+
+/// Directory holding one folder of files per layer
+pub const CONTENT_DIR: &str = "content";
+
+/// Layer name given to embedded resources (README, previews, screenshots)
+pub const RESOURCE_LAYER: &str = "";
+
+/// Options controlling how a package is built
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Uncompressed size of each data chunk
+    pub chunk_size: usize,
+    /// Zstd compression level (1-22)
+    pub compression_level: i32,
+    /// Build timestamp to record; when unset it comes from `SOURCE_DATE_EPOCH`,
+    /// else the current time. Set either one for byte-identical rebuilds.
+    pub build_timestamp: Option<String>,
+    /// Key to sign the package with; signatures are deterministic, so signed
+    /// rebuilds stay byte-identical too
+    pub signing_key: Option<SigningKey>,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            build_timestamp: None,
+            signing_key: None,
+        }
+    }
+}
+
+/// Summary of a written package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSummary {
+    /// Number of files in the file index
+    pub file_count: usize,
+    /// Number of distinct data chunks
+    pub chunk_count: usize,
+    /// Total size of all files before compression
+    pub uncompressed_size: u64,
+    /// Size of the written package
+    pub package_size: u64,
+}
+
+/// Where a file's contents come from
+#[derive(Debug, Clone)]
+pub(crate) enum Source {
+    Memory(Vec<u8>),
+    Disk(PathBuf),
+}
+
+impl Source {
+    pub(crate) fn open(&self) -> Result<Box<dyn Read + '_>> {
+        Ok(match self {
+            Source::Memory(data) => Box::new(data.as_slice()),
+            Source::Disk(path) => Box::new(BufReader::new(fs::File::open(path)?)),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PendingFile {
+    layer: String,
+    path: String,
+    source: Source,
+}
+
+/// Builds a `.dmodpkg` from a mod configuration and its files
+///
+/// Files are written in layer order (as declared in the configuration) and
+/// then by path, with embedded resources last. Each file is split into
+/// chunks of `chunk_size` bytes; identical chunks are stored once.
+#[derive(Debug, Clone)]
+pub struct PackageWriter {
+    config: ModConfig,
+    options: PackOptions,
+    files: Vec<PendingFile>,
+    paths: HashSet<(String, String)>,
+}
+
+impl PackageWriter {
+    /// Create a writer for `config` with no files
+    pub fn new(config: ModConfig, options: PackOptions) -> Result<Self> {
+        config.validate()?;
+
+        if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&options.chunk_size) {
+            return Err(DmodpkgError::validation(format!(
+                "chunk size must be between {} and {} bytes",
+                MIN_CHUNK_SIZE, MAX_CHUNK_SIZE
+            )));
+        }
+
+        if !zstd::compression_level_range().contains(&options.compression_level) {
+            return Err(DmodpkgError::validation(format!(
+                "compression level {} is out of range",
+                options.compression_level
+            )));
+        }
+
+        Ok(Self {
+            config,
+            options,
+            files: Vec::new(),
+            paths: HashSet::new(),
+        })
+    }
+
+    /// Create a writer from a project directory containing `mod.config.json`
+    /// and a `content/<layer>/` folder for every layer. `path` may also point
+    /// at the configuration file itself.
+    ///
+    /// Layers are flat folders of VPKs: only the VPK files at the root of each
+    /// layer folder are packed. Subdirectories and other files are skipped,
+    /// and `validate` warns about them.
+    pub fn from_project(path: impl AsRef<Path>, options: PackOptions) -> Result<Self> {
+        let (dir, config_path) = project_paths(path.as_ref(), CONFIG_FILE_NAME);
+        let dir = dir.as_path();
+        let config = ModConfig::from_json(&fs::read_to_string(&config_path)?)?;
+        let mut writer = Self::new(config, options)?;
+
+        let layers: Vec<String> = writer
+            .config
+            .layers
+            .iter()
+            .map(|l| l.name.clone())
+            .collect();
+        for layer in layers {
+            let layer_dir = dir.join(CONTENT_DIR).join(&layer);
+            if !layer_dir.is_dir() {
+                return Err(DmodpkgError::layer(format!(
+                    "missing directory for layer '{}': {}",
+                    layer,
+                    layer_dir.display()
+                )));
+            }
+
+            for file in layer_files(&layer_dir)? {
+                let relative = relative_path(&layer_dir, &file)?;
+                writer.add_path(&layer, &relative, file)?;
+            }
+        }
+
+        for resource in writer.resource_paths() {
+            let file = dir.join(&resource);
+            if !file.is_file() {
+                return Err(DmodpkgError::validation(format!(
+                    "referenced file not found: {}",
+                    resource
+                )));
+            }
+            writer.add_path(RESOURCE_LAYER, &resource, file)?;
+        }
+
+        Ok(writer)
+    }
+
+    /// The configuration being packaged
+    pub fn config(&self) -> &ModConfig {
+        &self.config
+    }
+
+    /// Add a file held in memory to `layer`. Layer files must be VPKs at the
+    /// layer root; embedded resources (`RESOURCE_LAYER`) may be any file.
+    pub fn add_file(&mut self, layer: &str, path: &str, data: Vec<u8>) -> Result<()> {
+        self.push(layer, path, Source::Memory(data))
+    }
+
+    fn add_path(&mut self, layer: &str, path: &str, file: PathBuf) -> Result<()> {
+        self.push(layer, path, Source::Disk(file))
+    }
+
+    fn push(&mut self, layer: &str, path: &str, source: Source) -> Result<()> {
+        if layer != RESOURCE_LAYER && !self.config.layers.iter().any(|l| l.name == layer) {
+            return Err(DmodpkgError::layer(format!("unknown layer '{}'", layer)));
+        }
+
+        let path = normalize_path(path)?;
+        if layer != RESOURCE_LAYER && (path.contains('/') || !is_vpk(&path)) {
+            return Err(DmodpkgError::invalid_structure(format!(
+                "layer '{}' may only hold VPK files at its root: {}",
+                layer, path
+            )));
+        }
+        if !self.paths.insert((layer.to_string(), path.clone())) {
+            // Resources may be referenced more than once
+            if layer == RESOURCE_LAYER {
+                return Ok(());
+            }
+            return Err(DmodpkgError::invalid_structure(format!(
+                "duplicate file '{}' in layer '{}'",
+                path, layer
+            )));
+        }
+
+        self.files.push(PendingFile {
+            layer: layer.to_string(),
+            path,
+            source,
+        });
+        Ok(())
+    }
+
+    /// Local files referenced by the configuration that get embedded
+    fn resource_paths(&self) -> Vec<String> {
+        let config = &self.config;
+        let variants = config.variant_groups.iter().flat_map(|g| &g.variants);
+
+        config
+            .readme
+            .iter()
+            .chain(&config.screenshots)
+            .chain(variants.clone().filter_map(|v| v.preview_image.as_ref()))
+            .chain(variants.flat_map(|v| &v.screenshots))
+            .filter(|path| !is_url(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Write the package to `out`
+    pub fn write<W: Write>(mut self, out: &mut W) -> Result<PackageSummary> {
+        let layer_order: HashMap<&str, usize> = self
+            .config
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (l.name.as_str(), i))
+            .collect();
+        self.files.sort_by(|a, b| {
+            let rank = |f: &PendingFile| {
+                layer_order
+                    .get(f.layer.as_str())
+                    .copied()
+                    .unwrap_or(usize::MAX)
+            };
+            rank(a).cmp(&rank(b)).then_with(|| a.path.cmp(&b.path))
+        });
+
+        // Chunk and compress every file
+        let mut entries = Vec::with_capacity(self.files.len());
+        let mut chunks: Vec<ChunkMetadata> = Vec::new();
+        let mut chunk_data: Vec<Vec<u8>> = Vec::new();
+        let mut seen: HashMap<[u8; 32], u32> = HashMap::new();
+        let mut data_size = 0u64;
+        let mut uncompressed_size = 0u64;
+
+        for file in &self.files {
+            let data = match &file.source {
+                Source::Memory(data) => data.clone(),
+                Source::Disk(path) => fs::read(path)?,
+            };
+
+            let mut chunk_indices = Vec::new();
+            for piece in data.chunks(self.options.chunk_size) {
+                let hash: [u8; 32] = Sha256::digest(piece).into();
+                if let Some(&index) = seen.get(&hash) {
+                    chunk_indices.push(index);
+                    continue;
+                }
+
+                let compressed = zstd::bulk::compress(piece, self.options.compression_level)
+                    .map_err(|e| DmodpkgError::compression(e.to_string()))?;
+                let index = u32::try_from(chunks.len())
+                    .map_err(|_| DmodpkgError::format("too many chunks"))?;
+                chunks.push(ChunkMetadata {
+                    offset: data_size,
+                    compressed_size: to_u32(compressed.len())?,
+                    uncompressed_size: to_u32(piece.len())?,
+                    crc32: CRC32.checksum(&compressed),
+                });
+                data_size += compressed.len() as u64;
+                chunk_data.push(compressed);
+                seen.insert(hash, index);
+                chunk_indices.push(index);
+            }
+
+            uncompressed_size += data.len() as u64;
+            entries.push(FileEntry {
+                path: file.path.clone(),
+                layer: file.layer.clone(),
+                uncompressed_size: data.len() as u64,
+                chunk_indices,
+                sha256: Sha256::digest(&data).into(),
+            });
+        }
+
+        let mut metadata = MetadataSection {
+            config: serde_json::to_value(&self.config)?,
+            build_info: BuildInfo {
+                builder_version: crate::version().to_string(),
+                build_timestamp: build_timestamp(self.options.build_timestamp.as_deref()),
+                platform: None,
+                checksum_algorithm: "SHA256".to_string(),
+            },
+            signature: None,
+        };
+        let file_index = encode_file_index(&entries)?;
+        let chunk_table = encode_chunk_table(&chunks)?;
+
+        let mut header = PackageHeader::new();
+        header.total_uncompressed_size = uncompressed_size;
+
+        if let Some(key) = &self.options.signing_key {
+            let mut data = Sha256::new();
+            for chunk in &chunk_data {
+                data.update(chunk);
+            }
+            let digest = signing::package_digest(
+                &header,
+                &metadata,
+                &file_index,
+                &chunk_table,
+                &data.finalize().into(),
+            )?;
+            metadata.signature = Some(signing::sign(key, &digest));
+        }
+
+        let metadata_json = serde_json::to_vec(&metadata)?;
+        let metadata_compressed = self.compress(&metadata_json)?;
+        let file_index_compressed = self.compress(&file_index)?;
+
+        header.metadata_offset = to_u32(HEADER_SIZE)?;
+        header.metadata_compressed_size = to_u32(metadata_compressed.len())?;
+        header.metadata_uncompressed_size = to_u32(metadata_json.len())?;
+        header.file_index_offset = header.metadata_offset + header.metadata_compressed_size;
+        header.file_index_compressed_size = to_u32(file_index_compressed.len())?;
+        header.file_index_uncompressed_size = to_u32(file_index.len())?;
+        header.chunk_table_offset = header
+            .file_index_offset
+            .checked_add(header.file_index_compressed_size)
+            .ok_or_else(too_large)?;
+        header.chunk_table_size = to_u32(chunk_table.len())?;
+        header.data_section_offset = header
+            .chunk_table_offset
+            .checked_add(header.chunk_table_size)
+            .ok_or_else(too_large)?;
+
+        let sections = [&metadata_compressed, &file_index_compressed, &chunk_table]
+            .into_iter()
+            .chain(&chunk_data);
+        let mut digest = CRC64.digest();
+        for section in sections.clone() {
+            digest.update(section);
+        }
+        header.package_crc64 = digest.finalize();
+
+        out.write_all(&header.to_bytes())?;
+        for section in sections {
+            out.write_all(section)?;
+        }
+        out.flush()?;
+
+        Ok(PackageSummary {
+            file_count: entries.len(),
+            chunk_count: chunks.len(),
+            uncompressed_size,
+            package_size: header.data_section_offset as u64 + data_size,
+        })
+    }
+
+    /// Write the package to a file at `path`
+    pub fn write_to_file(self, path: impl AsRef<Path>) -> Result<PackageSummary> {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+        self.write(&mut out)
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        zstd::bulk::compress(data, self.options.compression_level)
+            .map_err(|e| DmodpkgError::compression(e.to_string()))
+    }
+}
+
+/// The timestamp to record for a build: an explicit value, else
+/// `SOURCE_DATE_EPOCH`, else the current time
+pub(crate) fn build_timestamp(explicit: Option<&str>) -> String {
+    if let Some(timestamp) = explicit {
+        return timestamp.to_string();
+    }
+
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+    format_timestamp(seconds)
+}
+
+/// Split a project path into its directory and configuration file; `path`
+/// is either the directory or the configuration file
+pub(crate) fn project_paths(path: &Path, config_name: &str) -> (PathBuf, PathBuf) {
+    if path.is_dir() {
+        return (path.to_path_buf(), path.join(config_name));
+    }
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    (dir, path.to_path_buf())
+}
+
+/// Conventional output file name for a package: `<name>-<version>.dmodpkg`
+pub fn package_file_name(config: &ModConfig) -> String {
+    format!("{}-{}.dmodpkg", config.name, config.version)
+}
+
+/// Whether a configured resource refers to a URL rather than a project file
+pub(crate) fn is_url(value: &str) -> bool {
+    value.starts_with("https://") || value.starts_with("http://")
+}
+
+/// Normalize a path inside a package to forward slashes, rejecting anything
+/// that could escape the package root
+pub(crate) fn normalize_path(path: &str) -> Result<String> {
+    let path = path.replace('\\', "/");
+    if path.starts_with('/') || path.contains(':') {
+        return Err(DmodpkgError::invalid_structure(format!(
+            "path must be relative: {}",
+            path
+        )));
+    }
+
+    let parts: Vec<&str> = path
+        .split('/')
+        .filter(|p| !p.is_empty() && *p != ".")
+        .collect();
+    if parts.is_empty() || parts.contains(&"..") {
+        return Err(DmodpkgError::invalid_structure(format!(
+            "invalid path: {}",
+            path
+        )));
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// Whether a file name has the `.vpk` extension, in any case
+pub(crate) fn is_vpk(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".vpk")
+}
+
+/// The VPK files directly inside a layer directory, in sorted order. Hidden
+/// entries, subdirectories and other files are not part of the layer.
+fn layer_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || !is_vpk(&name) {
+            continue;
+        }
+
+        let path = entry.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+fn relative_path(root: &Path, file: &Path) -> Result<String> {
+    let relative = file.strip_prefix(root).map_err(|_| {
+        DmodpkgError::invalid_structure(format!("{} is outside {}", file.display(), root.display()))
+    })?;
+    relative.to_str().map(str::to_string).ok_or_else(|| {
+        DmodpkgError::invalid_structure(format!("path is not UTF-8: {}", file.display()))
+    })
+}
+
+fn to_u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| too_large())
+}
+
+fn too_large() -> DmodpkgError {
+    DmodpkgError::format("package sections exceed the 4 GiB offset limit")
+}
+
+/// Format seconds since the Unix epoch as an ISO 8601 UTC timestamp
+fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let rem = seconds % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "name": "test-mod",
+        "display_name": "Test Mod",
+        "version": "1.0.0",
+        "description": "A test mod",
+        "authors": ["TestAuthor"],
+        "readme": "README.md",
+        "screenshots": ["https://example.com/shot.png"],
+        "layers": [
+            { "name": "base", "priority": 0, "required": true },
+            { "name": "extra", "priority": 10 }
+        ]
+    }"#;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(CONFIG_FILE_NAME), CONFIG).unwrap();
+        fs::write(root.join("README.md"), "# Test").unwrap();
+        fs::create_dir_all(root.join("content/base/sub")).unwrap();
+        fs::create_dir_all(root.join("content/extra")).unwrap();
+        fs::write(
+            root.join("content/base/pak01_dir.vpk"),
+            vec![7u8; 600 * 1024],
+        )
+        .unwrap();
+        fs::write(root.join("content/base/sub/b.vpk"), "b").unwrap();
+        fs::write(root.join("content/base/notes.txt"), "notes").unwrap();
+        fs::write(root.join("content/base/.DS_Store"), "junk").unwrap();
+        fs::write(
+            root.join("content/extra/pak02_dir.vpk"),
+            vec![7u8; 300 * 1024],
+        )
+        .unwrap();
+        dir
+    }
+
+    fn build(dir: &Path) -> Vec<u8> {
+        let options = PackOptions {
+            chunk_size: MIN_CHUNK_SIZE,
+            build_timestamp: Some("2024-01-01T00:00:00Z".to_string()),
+            ..PackOptions::default()
+        };
+        let mut out = Vec::new();
+        PackageWriter::from_project(dir, options)
+            .unwrap()
+            .write(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_reproducible_builds() {
+        let dir = project();
+        assert_eq!(build(dir.path()), build(dir.path()));
+    }
+
+    #[test]
+    fn test_package_layout() {
+        let dir = project();
+        let bytes = build(dir.path());
+        let header = PackageHeader::from_bytes(&bytes).unwrap();
+
+        assert!(header.is_valid());
+        assert_eq!(header.package_crc64, CRC64.checksum(&bytes[HEADER_SIZE..]));
+        assert_eq!(header.total_uncompressed_size, 900 * 1024 + 6);
+
+        let start = header.metadata_offset as usize;
+        let end = start + header.metadata_compressed_size as usize;
+        let json = zstd::decode_all(&bytes[start..end]).unwrap();
+        let metadata: MetadataSection = serde_json::from_slice(&json).unwrap();
+        assert_eq!(metadata.config["name"], "test-mod");
+        assert_eq!(metadata.build_info.build_timestamp, "2024-01-01T00:00:00Z");
+        assert!(metadata.build_info.platform.is_none());
+
+        // Three files: pak01 (3 chunks), pak02 (2 chunks) and the README; the
+        // nested VPK and the text file are not part of the layer. The 256 KiB
+        // runs of sevens are all the same chunk.
+        let table = &bytes[header.chunk_table_offset as usize..header.data_section_offset as usize];
+        let chunk_count = u32::from_le_bytes(table[0..4].try_into().unwrap());
+        assert_eq!(chunk_count, 4);
+    }
+
+    #[test]
+    fn test_missing_layer_directory() {
+        let dir = project();
+        fs::remove_dir_all(dir.path().join("content/extra")).unwrap();
+
+        let err = PackageWriter::from_project(dir.path(), PackOptions::default()).unwrap_err();
+        assert!(matches!(err, DmodpkgError::Layer(_)));
+    }
+
+    #[test]
+    fn test_paths_outside_the_package() {
+        let config = ModConfig::from_json(CONFIG).unwrap();
+        let mut writer = PackageWriter::new(config, PackOptions::default()).unwrap();
+
+        assert!(writer.add_file("base", "../escape.vpk", vec![]).is_err());
+        assert!(writer.add_file("base", "/abs.vpk", vec![]).is_err());
+        assert!(writer.add_file("missing", "a.vpk", vec![]).is_err());
+        writer.add_file("base", ".\\a.vpk", vec![]).unwrap();
+        assert!(writer.add_file("base", "a.vpk", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_layer_root_vpks_only() {
+        let config = ModConfig::from_json(CONFIG).unwrap();
+        let mut writer = PackageWriter::new(config, PackOptions::default()).unwrap();
+
+        assert!(writer.add_file("base", "dir/a.vpk", vec![]).is_err());
+        assert!(writer.add_file("base", "notes.txt", vec![]).is_err());
+        writer.add_file("base", "PAK01_DIR.VPK", vec![]).unwrap();
+        writer
+            .add_file(RESOURCE_LAYER, "docs/notes.txt", vec![])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_timestamp_formatting() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1_709_251_199), "2024-02-29T23:59:59Z");
+    }
+}
@@ -0,0 +1,374 @@
+use sha1::{Digest, Sha1};
+
+use crate::binary::{BinaryObject, BinaryParser, BinarySerializer, StringTable, write_cstr};
+use crate::error::{KvError, Result};
+
+/// `appinfo.vdf` before and after Steam added the binary data checksum
+/// (v28) and moved keys into a string table (v29)
+pub const APPINFO_V27: u32 = 0x0756_4427;
+pub const APPINFO_V28: u32 = 0x0756_4428;
+pub const APPINFO_V29: u32 = 0x0756_4429;
+
+/// `packageinfo.vdf` without and with the PICS token per package
+pub const PACKAGEINFO_V27: u32 = 0x0656_5527;
+pub const PACKAGEINFO_V28: u32 = 0x0656_5528;
+
+/// Steam's app metadata cache, `appcache/appinfo.vdf`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppInfo {
+    /// Format magic, one of the `APPINFO_V*` constants
+    pub magic: u32,
+    pub universe: u32,
+    pub apps: Vec<AppInfoEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppInfoEntry {
+    pub app_id: u32,
+    pub info_state: u32,
+    pub last_updated: u32,
+    pub pics_token: u64,
+    /// SHA-1 of the app's info in text KeyValues form
+    pub text_sha1: [u8; 20],
+    pub change_number: u32,
+    /// SHA-1 of the binary `data`, v28 and later. Recomputed on write.
+    pub binary_sha1: Option<[u8; 20]>,
+    pub data: BinaryObject,
+}
+
+/// Steam's package (license) cache, `appcache/packageinfo.vdf`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageInfo {
+    /// Format magic, one of the `PACKAGEINFO_V*` constants
+    pub magic: u32,
+    pub universe: u32,
+    pub packages: Vec<PackageInfoEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageInfoEntry {
+    pub package_id: u32,
+    pub sha1: [u8; 20],
+    pub change_number: u32,
+    /// PICS access token, v28 only
+    pub pics_token: Option<u64>,
+    pub data: BinaryObject,
+}
+
+/// Size of the fixed fields between an app's size field and its data
+fn app_header_len(magic: u32) -> usize {
+    let base = 4 + 4 + 8 + 20 + 4;
+    if magic >= APPINFO_V28 {
+        base + 20
+    } else {
+        base
+    }
+}
+
+impl AppInfo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BinaryParser::new(bytes);
+        let magic = reader.read_u32()?;
+        if !(APPINFO_V27..=APPINFO_V29).contains(&magic) {
+            reader.seek(0);
+            return Err(reader.error(&format!("unsupported appinfo magic 0x{magic:08x}")));
+        }
+        let universe = reader.read_u32()?;
+
+        let strings = if magic >= APPINFO_V29 {
+            let table_offset = usize::try_from(reader.read_u64()?)
+                .ok()
+                .filter(|&offset| offset <= bytes.len())
+                .ok_or_else(|| reader.error("string table offset is past the end of the file"))?;
+            let apps_start = reader.position();
+            reader.seek(table_offset);
+            let count = reader.read_u32()?;
+            let strings = (0..count)
+                .map(|_| reader.read_cstr())
+                .collect::<Result<Vec<_>>>()?;
+            reader.seek(apps_start);
+            Some(strings)
+        } else {
+            None
+        };
+
+        let mut apps = Vec::new();
+        loop {
+            let app_id = reader.read_u32()?;
+            if app_id == 0 {
+                break;
+            }
+            let size = reader.read_u32()?;
+            let end = usize::try_from(size)
+                .ok()
+                .and_then(|size| reader.position().checked_add(size))
+                .ok_or_else(|| reader.error(&format!("app {app_id} has an invalid size {size}")))?;
+            let info_state = reader.read_u32()?;
+            let last_updated = reader.read_u32()?;
+            let pics_token = reader.read_u64()?;
+            let text_sha1 = reader.read_array()?;
+            let change_number = reader.read_u32()?;
+            let binary_sha1 = if magic >= APPINFO_V28 {
+                Some(reader.read_array()?)
+            } else {
+                None
+            };
+
+            let data_start = reader.position();
+            if end < data_start || end > bytes.len() {
+                return Err(reader.error(&format!("app {app_id} has an invalid size {size}")));
+            }
+            let blob = &bytes[data_start..end];
+            let data = match &strings {
+                Some(strings) => {
+                    let mut parser = BinaryParser::with_string_table(blob, strings);
+                    parser.read_object(false)
+                }
+                None => BinaryParser::new(blob).read_object(false),
+            }
+            .map_err(|e| offset_error(e, data_start))?;
+            reader.seek(end);
+
+            apps.push(AppInfoEntry {
+                app_id,
+                info_state,
+                last_updated,
+                pics_token,
+                text_sha1,
+                change_number,
+                binary_sha1,
+                data,
+            });
+        }
+
+        Ok(AppInfo {
+            magic,
+            universe,
+            apps,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.magic.to_le_bytes());
+        out.extend_from_slice(&self.universe.to_le_bytes());
+        let table_offset_at = out.len();
+        if self.magic >= APPINFO_V29 {
+            out.extend_from_slice(&0u64.to_le_bytes());
+        }
+
+        let mut strings = StringTable::default();
+        for app in &self.apps {
+            let mut blob = Vec::new();
+            let table = (self.magic >= APPINFO_V29).then_some(&mut strings);
+            BinarySerializer::write_object(&mut blob, &app.data, table);
+
+            out.extend_from_slice(&app.app_id.to_le_bytes());
+            let size = app_header_len(self.magic) + blob.len();
+            out.extend_from_slice(&(size as u32).to_le_bytes());
+            out.extend_from_slice(&app.info_state.to_le_bytes());
+            out.extend_from_slice(&app.last_updated.to_le_bytes());
+            out.extend_from_slice(&app.pics_token.to_le_bytes());
+            out.extend_from_slice(&app.text_sha1);
+            out.extend_from_slice(&app.change_number.to_le_bytes());
+            if self.magic >= APPINFO_V28 {
+                out.extend_from_slice(&Sha1::digest(&blob));
+            }
+            out.extend_from_slice(&blob);
+        }
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        if self.magic >= APPINFO_V29 {
+            let table_offset = out.len() as u64;
+            out[table_offset_at..table_offset_at + 8].copy_from_slice(&table_offset.to_le_bytes());
+            out.extend_from_slice(&(strings.strings.len() as u32).to_le_bytes());
+            for key in &strings.strings {
+                write_cstr(&mut out, key);
+            }
+        }
+        out
+    }
+
+    pub fn app(&self, app_id: u32) -> Option<&AppInfoEntry> {
+        self.apps.iter().find(|app| app.app_id == app_id)
+    }
+}
+
+impl PackageInfo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BinaryParser::new(bytes);
+        let magic = reader.read_u32()?;
+        if !(PACKAGEINFO_V27..=PACKAGEINFO_V28).contains(&magic) {
+            reader.seek(0);
+            return Err(reader.error(&format!("unsupported packageinfo magic 0x{magic:08x}")));
+        }
+        let universe = reader.read_u32()?;
+
+        let mut packages = Vec::new();
+        loop {
+            let package_id = reader.read_u32()?;
+            if package_id == u32::MAX {
+                break;
+            }
+            let sha1 = reader.read_array()?;
+            let change_number = reader.read_u32()?;
+            let pics_token = if magic >= PACKAGEINFO_V28 {
+                Some(reader.read_u64()?)
+            } else {
+                None
+            };
+            let data = reader.read_object(false)?;
+
+            packages.push(PackageInfoEntry {
+                package_id,
+                sha1,
+                change_number,
+                pics_token,
+                data,
+            });
+        }
+
+        Ok(PackageInfo {
+            magic,
+            universe,
+            packages,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.magic.to_le_bytes());
+        out.extend_from_slice(&self.universe.to_le_bytes());
+        for package in &self.packages {
+            out.extend_from_slice(&package.package_id.to_le_bytes());
+            out.extend_from_slice(&package.sha1);
+            out.extend_from_slice(&package.change_number.to_le_bytes());
+            if self.magic >= PACKAGEINFO_V28 {
+                out.extend_from_slice(&package.pics_token.unwrap_or_default().to_le_bytes());
+            }
+            BinarySerializer::write_object(&mut out, &package.data, None);
+        }
+        out.extend_from_slice(&u32::MAX.to_le_bytes());
+        out
+    }
+}
+
+/// Shift the offset of an error from an app's data blob to the whole file
+fn offset_error(error: KvError, base: usize) -> KvError {
+    match error {
+        KvError::BinaryFormat { offset, message } => KvError::BinaryFormat {
+            offset: base + offset,
+            message,
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::BinaryValue;
+
+    fn deadlock(change_number: u32) -> AppInfoEntry {
+        AppInfoEntry {
+            app_id: 1_422_450,
+            info_state: 2,
+            last_updated: 1_700_000_000,
+            pics_token: 0,
+            text_sha1: [7; 20],
+            change_number,
+            binary_sha1: Some([0; 20]),
+            data: vec![(
+                "appinfo".to_string(),
+                BinaryValue::Object(vec![
+                    ("appid".to_string(), BinaryValue::Int32(1_422_450)),
+                    (
+                        "common".to_string(),
+                        BinaryValue::Object(vec![(
+                            "name".to_string(),
+                            BinaryValue::String("Deadlock".to_string()),
+                        )]),
+                    ),
+                ]),
+            )],
+        }
+    }
+
+    #[test]
+    fn test_appinfo_round_trip_with_string_table() {
+        let info = AppInfo {
+            magic: APPINFO_V29,
+            universe: 1,
+            apps: vec![deadlock(1), deadlock(2)],
+        };
+
+        let bytes = info.serialize();
+        let parsed = AppInfo::parse(&bytes).unwrap();
+
+        assert_eq!(parsed.apps.len(), 2);
+        assert_eq!(parsed.apps[1].change_number, 2);
+        assert_eq!(parsed.app(1_422_450).unwrap().data, info.apps[0].data);
+        assert_eq!(
+            parsed.apps[0].binary_sha1,
+            Some(parsed.apps[1].binary_sha1.unwrap()),
+            "checksum covers the data only"
+        );
+        assert_eq!(parsed.serialize(), bytes);
+    }
+
+    #[test]
+    fn test_appinfo_v27_has_inline_keys() {
+        let mut app = deadlock(5);
+        app.binary_sha1 = None;
+        let info = AppInfo {
+            magic: APPINFO_V27,
+            universe: 1,
+            apps: vec![app],
+        };
+
+        let bytes = info.serialize();
+
+        assert!(bytes.windows(7).any(|w| w == b"appinfo"));
+        assert_eq!(AppInfo::parse(&bytes).unwrap(), info);
+    }
+
+    #[test]
+    fn test_appinfo_with_out_of_range_offsets_is_an_error() {
+        let info = AppInfo {
+            magic: APPINFO_V29,
+            universe: 1,
+            apps: vec![deadlock(1)],
+        };
+        let bytes = info.serialize();
+
+        let mut bad_table = bytes.clone();
+        bad_table[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(AppInfo::parse(&bad_table).is_err());
+
+        let mut bad_size = bytes.clone();
+        bad_size[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(AppInfo::parse(&bad_size).is_err());
+    }
+
+    #[test]
+    fn test_packageinfo_round_trip() {
+        let info = PackageInfo {
+            magic: PACKAGEINFO_V28,
+            universe: 1,
+            packages: vec![PackageInfoEntry {
+                package_id: 0,
+                sha1: [1; 20],
+                change_number: 9,
+                pics_token: Some(3),
+                data: vec![(
+                    "0".to_string(),
+                    BinaryValue::Object(vec![("packageid".to_string(), BinaryValue::Int32(0))]),
+                )],
+            }],
+        };
+
+        let bytes = info.serialize();
+
+        assert_eq!(PackageInfo::parse(&bytes).unwrap(), info);
+    }
+}
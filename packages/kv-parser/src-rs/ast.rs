@@ -1,5 +1,6 @@
 // Re-export AST types from types.rs for convenience
 pub use crate::types::{
-    AstNode, CommentNode, ConditionalNode, DocumentNode, KeyValueNode, NodeType, NumberNode,
-    ObjectNode, Position, StringNode, TokenNode, ValueNode, WhitespaceNode,
+    AstNode, CommentNode, ConditionalNode, DirectiveKind, DirectiveNode, DocumentNode,
+    KeyValueNode, NodeType, NumberNode, ObjectNode, Position, StringNode, TokenNode, ValueNode,
+    WhitespaceNode,
 };
@@ -0,0 +1,482 @@
+use std::collections::HashMap;
+
+use crate::error::{KvError, Result};
+use crate::types::{KeyValuesObject, KeyValuesValue};
+
+/// Type byte in front of every binary key-value
+mod tag {
+    pub const OBJECT: u8 = 0x00;
+    pub const STRING: u8 = 0x01;
+    pub const INT32: u8 = 0x02;
+    pub const FLOAT32: u8 = 0x03;
+    pub const POINTER: u8 = 0x04;
+    pub const WIDE_STRING: u8 = 0x05;
+    pub const COLOR: u8 = 0x06;
+    pub const UINT64: u8 = 0x07;
+    pub const END: u8 = 0x08;
+    pub const INT64: u8 = 0x0A;
+    /// End marker some writers use instead of `END`
+    pub const ALT_END: u8 = 0x0B;
+}
+
+/// How deeply objects may nest before the parser gives up, so a crafted file
+/// cannot exhaust the stack
+pub(crate) const MAX_DEPTH: usize = 256;
+
+/// Key-values of a binary object in file order, repeated keys included
+pub type BinaryObject = Vec<(String, BinaryValue)>;
+
+/// A binary KeyValues value with its wire type preserved, so files can be
+/// written back byte for byte
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryValue {
+    Object(BinaryObject),
+    String(String),
+    Int32(i32),
+    Float32(f32),
+    Pointer(i32),
+    WideString(String),
+    /// RGBA
+    Color([u8; 4]),
+    UInt64(u64),
+    Int64(i64),
+}
+
+impl BinaryValue {
+    /// The value in the text data model. Numbers become `Number`, so 64-bit
+    /// integers above 2^53 lose precision; colors become `"r g b a"` strings
+    /// like in text KeyValues.
+    pub fn to_data(&self) -> KeyValuesValue {
+        match self {
+            BinaryValue::Object(obj) => KeyValuesValue::Object(object_to_data(obj)),
+            BinaryValue::String(s) | BinaryValue::WideString(s) => {
+                KeyValuesValue::String(s.clone())
+            }
+            BinaryValue::Int32(n) | BinaryValue::Pointer(n) => KeyValuesValue::Number(*n as f64),
+            BinaryValue::Float32(n) => KeyValuesValue::Number(*n as f64),
+            BinaryValue::UInt64(n) => KeyValuesValue::Number(*n as f64),
+            BinaryValue::Int64(n) => KeyValuesValue::Number(*n as f64),
+            BinaryValue::Color([r, g, b, a]) => KeyValuesValue::String(format!("{r} {g} {b} {a}")),
+        }
+    }
+
+    /// Pick a wire type for a data value: whole numbers that fit become
+    /// `Int32`, larger ones `UInt64` or `Int64`, and the rest `Float32`
+    pub fn from_data(value: &KeyValuesValue) -> Result<Self> {
+        Ok(match value {
+            KeyValuesValue::String(s) => BinaryValue::String(s.clone()),
+            KeyValuesValue::Number(n) if n.fract() == 0.0 && n.is_finite() => {
+                if *n >= i32::MIN as f64 && *n <= i32::MAX as f64 {
+                    BinaryValue::Int32(*n as i32)
+                } else if *n >= 0.0 {
+                    BinaryValue::UInt64(*n as u64)
+                } else {
+                    BinaryValue::Int64(*n as i64)
+                }
+            }
+            KeyValuesValue::Number(n) => BinaryValue::Float32(*n as f32),
+            KeyValuesValue::Object(obj) => BinaryValue::Object(object_from_data(obj)?),
+            KeyValuesValue::Array(_) => return Err(KvError::ArraySerializationError),
+        })
+    }
+}
+
+/// Convert a binary object to the text data model, see `BinaryValue::to_data`
+pub fn object_to_data(obj: &BinaryObject) -> KeyValuesObject {
+    let mut data = KeyValuesObject::new();
+    for (key, value) in obj {
+        data.append(key.clone(), value.to_data());
+    }
+    data
+}
+
+/// Convert data to a binary object, see `BinaryValue::from_data`
+pub fn object_from_data(data: &KeyValuesObject) -> Result<BinaryObject> {
+    data.entries()
+        .into_iter()
+        .map(|(key, value)| Ok((key.to_string(), BinaryValue::from_data(value)?)))
+        .collect()
+}
+
+/// Reads binary KeyValues, as found in `shortcuts.vdf` and inside
+/// `appinfo.vdf` and `packageinfo.vdf`
+///
+/// Every key-value is a type byte, a null-terminated key (or, in files with
+/// a string table, a 32-bit index into it) and the value; objects end with
+/// an `0x08` byte.
+pub struct BinaryParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    strings: Option<&'a [String]>,
+    depth: usize,
+}
+
+impl<'a> BinaryParser<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            strings: None,
+            depth: 0,
+        }
+    }
+
+    /// Parser for key-values whose keys are indexes into `strings`
+    pub fn with_string_table(bytes: &'a [u8], strings: &'a [String]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            strings: Some(strings),
+            depth: 0,
+        }
+    }
+
+    /// Parse a whole binary KeyValues buffer, keeping value types
+    pub fn parse(bytes: &[u8]) -> Result<BinaryObject> {
+        let mut parser = BinaryParser::new(bytes);
+        let document = parser.read_object(true)?;
+        if parser.pos < bytes.len() {
+            return Err(parser.error("trailing data after the root object"));
+        }
+        Ok(document)
+    }
+
+    /// Parse a whole binary KeyValues buffer into the text data model
+    pub fn parse_data(bytes: &[u8]) -> Result<KeyValuesObject> {
+        Self::parse(bytes).map(|document| object_to_data(&document))
+    }
+
+    /// Read key-values up to the end of the current object. At the root the
+    /// end marker may also be left out.
+    pub(crate) fn read_object(&mut self, root: bool) -> Result<BinaryObject> {
+        let mut obj = Vec::new();
+        loop {
+            if root && self.pos == self.bytes.len() {
+                return Ok(obj);
+            }
+            let start = self.pos;
+            let value_type = self.read_u8()?;
+            if value_type == tag::END || value_type == tag::ALT_END {
+                return Ok(obj);
+            }
+
+            let key = self.read_key()?;
+            let value = match value_type {
+                tag::OBJECT => {
+                    if self.depth == MAX_DEPTH {
+                        return Err(
+                            self.error(&format!("objects nested more than {MAX_DEPTH} deep"))
+                        );
+                    }
+                    self.depth += 1;
+                    let nested = self.read_object(false);
+                    self.depth -= 1;
+                    BinaryValue::Object(nested?)
+                }
+                tag::STRING => BinaryValue::String(self.read_cstr()?),
+                tag::INT32 => BinaryValue::Int32(i32::from_le_bytes(self.read_array()?)),
+                tag::FLOAT32 => BinaryValue::Float32(f32::from_le_bytes(self.read_array()?)),
+                tag::POINTER => BinaryValue::Pointer(i32::from_le_bytes(self.read_array()?)),
+                tag::WIDE_STRING => BinaryValue::WideString(self.read_wide_str()?),
+                tag::COLOR => BinaryValue::Color(self.read_array()?),
+                tag::UINT64 => BinaryValue::UInt64(self.read_u64()?),
+                tag::INT64 => BinaryValue::Int64(i64::from_le_bytes(self.read_array()?)),
+                other => {
+                    self.pos = start;
+                    return Err(self.error(&format!("unknown value type 0x{other:02x}")));
+                }
+            };
+            obj.push((key, value));
+        }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self
+            .pos
+            .checked_add(N)
+            .and_then(|end| self.bytes.get(self.pos..end))
+            .ok_or_else(|| self.error("unexpected end of data"))?;
+        self.pos += N;
+        Ok(bytes.try_into().expect("slice has N bytes"))
+    }
+
+    pub(crate) fn read_cstr(&mut self) -> Result<String> {
+        let len = self.bytes[self.pos.min(self.bytes.len())..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| self.error("unterminated string"))?;
+        let s = std::str::from_utf8(&self.bytes[self.pos..self.pos + len])
+            .map_err(|_| self.error("string is not valid UTF-8"))?
+            .to_string();
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    fn read_wide_str(&mut self) -> Result<String> {
+        let start = self.pos;
+        let mut units = Vec::new();
+        loop {
+            match u16::from_le_bytes(self.read_array()?) {
+                0 => break,
+                unit => units.push(unit),
+            }
+        }
+        String::from_utf16(&units).map_err(|_| {
+            self.pos = start;
+            self.error("wide string is not valid UTF-16")
+        })
+    }
+
+    fn read_key(&mut self) -> Result<String> {
+        let Some(strings) = self.strings else {
+            return self.read_cstr();
+        };
+        let index = self.read_u32()?;
+        strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| self.error(&format!("key index {index} is not in the string table")))
+    }
+
+    pub(crate) fn error(&self, message: &str) -> KvError {
+        KvError::BinaryFormat {
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Keys collected while writing a file with a string table
+#[derive(Debug, Default)]
+pub(crate) struct StringTable {
+    pub(crate) strings: Vec<String>,
+    index: HashMap<String, u32>,
+}
+
+impl StringTable {
+    fn intern(&mut self, key: &str) -> u32 {
+        if let Some(&index) = self.index.get(key) {
+            return index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(key.to_string());
+        self.index.insert(key.to_string(), index);
+        index
+    }
+}
+
+/// Writes binary KeyValues
+pub struct BinarySerializer;
+
+impl BinarySerializer {
+    /// Serialize a binary object, ending the root with an end marker the
+    /// way Steam writes `shortcuts.vdf`
+    pub fn serialize(obj: &BinaryObject) -> Vec<u8> {
+        let mut out = Vec::new();
+        Self::write_object(&mut out, obj, None);
+        out
+    }
+
+    /// Serialize data, choosing value types with `BinaryValue::from_data`
+    pub fn serialize_data(data: &KeyValuesObject) -> Result<Vec<u8>> {
+        Ok(Self::serialize(&object_from_data(data)?))
+    }
+
+    /// Write `obj` and its end marker, with keys either inline or as
+    /// indexes into `strings`
+    pub(crate) fn write_object(
+        out: &mut Vec<u8>,
+        obj: &BinaryObject,
+        mut strings: Option<&mut StringTable>,
+    ) {
+        for (key, value) in obj {
+            let value_type = match value {
+                BinaryValue::Object(_) => tag::OBJECT,
+                BinaryValue::String(_) => tag::STRING,
+                BinaryValue::Int32(_) => tag::INT32,
+                BinaryValue::Float32(_) => tag::FLOAT32,
+                BinaryValue::Pointer(_) => tag::POINTER,
+                BinaryValue::WideString(_) => tag::WIDE_STRING,
+                BinaryValue::Color(_) => tag::COLOR,
+                BinaryValue::UInt64(_) => tag::UINT64,
+                BinaryValue::Int64(_) => tag::INT64,
+            };
+            out.push(value_type);
+
+            match strings.as_deref_mut() {
+                Some(table) => out.extend_from_slice(&table.intern(key).to_le_bytes()),
+                None => write_cstr(out, key),
+            }
+
+            match value {
+                BinaryValue::Object(child) => {
+                    Self::write_object(out, child, strings.as_deref_mut())
+                }
+                BinaryValue::String(s) => write_cstr(out, s),
+                BinaryValue::Int32(n) | BinaryValue::Pointer(n) => {
+                    out.extend_from_slice(&n.to_le_bytes())
+                }
+                BinaryValue::Float32(n) => out.extend_from_slice(&n.to_le_bytes()),
+                BinaryValue::WideString(s) => {
+                    for unit in s.encode_utf16().chain([0]) {
+                        out.extend_from_slice(&unit.to_le_bytes());
+                    }
+                }
+                BinaryValue::Color(rgba) => out.extend_from_slice(rgba),
+                BinaryValue::UInt64(n) => out.extend_from_slice(&n.to_le_bytes()),
+                BinaryValue::Int64(n) => out.extend_from_slice(&n.to_le_bytes()),
+            }
+        }
+        out.push(tag::END);
+    }
+}
+
+pub(crate) fn write_cstr(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(s.as_bytes());
+    out.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `shortcuts.vdf` with one non-Steam game, as Steam writes it
+    fn shortcuts() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(b"\x00shortcuts\x00");
+        bytes.extend(b"\x000\x00");
+        bytes.extend(b"\x02appid\x00");
+        bytes.extend((-1_520_000_000i32).to_le_bytes());
+        bytes.extend(b"\x01AppName\x00Deadlock Modded\x00");
+        bytes.extend(b"\x01LaunchOptions\x00-novid +map street_test\x00");
+        bytes.extend(b"\x02IsHidden\x00");
+        bytes.extend(0i32.to_le_bytes());
+        bytes.extend(b"\x00tags\x00");
+        bytes.extend(b"\x010\x00mods\x00");
+        bytes.extend(b"\x011\x00favorite\x00");
+        bytes.extend(b"\x08\x08\x08\x08");
+        bytes
+    }
+
+    #[test]
+    fn test_parse_shortcuts() {
+        let data = BinaryParser::parse_data(&shortcuts()).unwrap();
+
+        let KeyValuesValue::Object(list) = &data["shortcuts"] else {
+            panic!("expected object");
+        };
+        let KeyValuesValue::Object(game) = &list["0"] else {
+            panic!("expected object");
+        };
+        assert_eq!(game["appid"], KeyValuesValue::Number(-1_520_000_000.0));
+        assert_eq!(
+            game["LaunchOptions"],
+            KeyValuesValue::String("-novid +map street_test".to_string())
+        );
+        let KeyValuesValue::Object(tags) = &game["tags"] else {
+            panic!("expected object");
+        };
+        assert_eq!(tags.len(), 2);
+    }
+
+    #[test]
+    fn test_round_trip_is_byte_identical() {
+        let bytes = shortcuts();
+
+        let parsed = BinaryParser::parse(&bytes).unwrap();
+        assert_eq!(BinarySerializer::serialize(&parsed), bytes);
+
+        let data = BinaryParser::parse_data(&bytes).unwrap();
+        assert_eq!(BinarySerializer::serialize_data(&data).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_every_value_type_round_trips() {
+        let obj: BinaryObject = vec![
+            ("s".to_string(), BinaryValue::String("text".to_string())),
+            ("i".to_string(), BinaryValue::Int32(-7)),
+            ("f".to_string(), BinaryValue::Float32(1.5)),
+            ("p".to_string(), BinaryValue::Pointer(42)),
+            ("w".to_string(), BinaryValue::WideString("wïde".to_string())),
+            ("c".to_string(), BinaryValue::Color([255, 128, 0, 255])),
+            ("u".to_string(), BinaryValue::UInt64(76_561_198_000_000_000)),
+            ("l".to_string(), BinaryValue::Int64(-5_000_000_000)),
+            ("dup".to_string(), BinaryValue::Int32(1)),
+            ("dup".to_string(), BinaryValue::Int32(2)),
+        ];
+
+        let bytes = BinarySerializer::serialize(&obj);
+
+        assert_eq!(BinaryParser::parse(&bytes).unwrap(), obj);
+        let data = object_to_data(&obj);
+        assert_eq!(
+            data["c"],
+            KeyValuesValue::String("255 128 0 255".to_string())
+        );
+        assert_eq!(data.get_all("dup").len(), 2);
+    }
+
+    #[test]
+    fn test_string_table_keys() {
+        let strings = vec!["appinfo".to_string(), "name".to_string()];
+        let mut table = StringTable::default();
+        let obj: BinaryObject = vec![(
+            "appinfo".to_string(),
+            BinaryValue::Object(vec![(
+                "name".to_string(),
+                BinaryValue::String("Deadlock".to_string()),
+            )]),
+        )];
+
+        let mut bytes = Vec::new();
+        BinarySerializer::write_object(&mut bytes, &obj, Some(&mut table));
+
+        assert_eq!(table.strings, strings);
+        let mut parser = BinaryParser::with_string_table(&bytes, &strings);
+        assert_eq!(parser.read_object(false).unwrap(), obj);
+    }
+
+    #[test]
+    fn test_truncated_and_unknown_data_is_an_error() {
+        let bytes = shortcuts();
+
+        let truncated = BinaryParser::parse(&bytes[..20]).unwrap_err();
+        assert!(matches!(truncated, KvError::BinaryFormat { .. }));
+
+        let unknown = BinaryParser::parse(b"\x09key\x00").unwrap_err();
+        assert!(matches!(unknown, KvError::BinaryFormat { offset: 0, .. }));
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        let nested = |depth: usize| {
+            let mut bytes = b"\x00k\x00".repeat(depth);
+            bytes.extend(vec![tag::END; depth]);
+            bytes
+        };
+
+        assert!(BinaryParser::parse(&nested(MAX_DEPTH)).is_ok());
+        let error = BinaryParser::parse(&nested(100_000)).unwrap_err();
+        assert!(error.to_string().contains("nested"), "{error}");
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+
+use crate::parser::Parser;
+use crate::types::{
+    AstNode, ConditionalKey, ConditionalNode, DocumentNode, KeyValuesObject, ValueNode,
+};
+
+/// Symbols defined when evaluating `[$SYMBOL]` conditionals
+///
+/// Conditionals follow the engine's syntax: `$` before a symbol is optional,
+/// `!` negates a term, and terms combine with `&&` and `||`, with `&&`
+/// binding tighter. Symbols are matched case-insensitively and undefined
+/// symbols are false.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConditionSymbols {
+    symbols: HashSet<String>,
+}
+
+fn normalize_symbol(symbol: &str) -> String {
+    symbol.trim().trim_start_matches('$').to_ascii_uppercase()
+}
+
+impl ConditionSymbols {
+    /// No symbols defined
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Symbols of a Windows build
+    pub fn windows() -> Self {
+        Self::from_iter(["WINDOWS", "WIN32", "WIN64"])
+    }
+
+    /// Symbols of a Linux build
+    pub fn linux() -> Self {
+        Self::from_iter(["LINUX", "POSIX"])
+    }
+
+    /// Symbols of a macOS build
+    pub fn osx() -> Self {
+        Self::from_iter(["OSX", "POSIX"])
+    }
+
+    /// Symbols of the platform this library was built for
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::windows()
+        } else if cfg!(target_os = "macos") {
+            Self::osx()
+        } else {
+            Self::linux()
+        }
+    }
+
+    pub fn define(&mut self, symbol: &str) {
+        self.symbols.insert(normalize_symbol(symbol));
+    }
+
+    pub fn undefine(&mut self, symbol: &str) {
+        self.symbols.remove(&normalize_symbol(symbol));
+    }
+
+    pub fn is_defined(&self, symbol: &str) -> bool {
+        self.symbols.contains(&normalize_symbol(symbol))
+    }
+
+    /// Evaluate a conditional such as `[!$X360]` or `$LINUX||$OSX`
+    pub fn evaluate(&self, condition: &str) -> bool {
+        let condition = condition.trim();
+        let condition = condition
+            .strip_prefix('[')
+            .and_then(|c| c.strip_suffix(']'))
+            .unwrap_or(condition);
+
+        condition
+            .split("||")
+            .any(|clause| clause.split("&&").all(|term| self.evaluate_term(term)))
+    }
+
+    fn evaluate_term(&self, term: &str) -> bool {
+        let mut term = term.trim();
+        let mut negated = false;
+        while let Some(rest) = term.strip_prefix('!') {
+            negated = !negated;
+            term = rest.trim_start();
+        }
+
+        let defined = !term.is_empty() && self.is_defined(term);
+        defined != negated
+    }
+
+    /// Whether the key-value a conditional node is attached to applies
+    pub fn matches(&self, conditional: &ConditionalNode) -> bool {
+        self.evaluate(&conditional.raw)
+    }
+
+    /// Data view of a document with key-values whose conditional is false
+    /// left out. Key-values without a conditional are always kept.
+    pub fn filter(&self, ast: &DocumentNode) -> KeyValuesObject {
+        Parser::ast_to_data_where(ast, &|kv| {
+            kv.conditional.as_ref().is_none_or(|c| self.matches(c))
+        })
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for ConditionSymbols {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Self {
+            symbols: iter
+                .into_iter()
+                .map(|symbol| normalize_symbol(symbol.as_ref()))
+                .collect(),
+        }
+    }
+}
+
+/// Every key in a document that carries a conditional, in document order.
+/// Keys nested under a conditional object are not listed separately.
+pub fn conditional_keys(ast: &DocumentNode) -> Vec<ConditionalKey> {
+    let mut keys = Vec::new();
+    collect_conditional_keys(&ast.children, "", &mut keys);
+    keys
+}
+
+fn collect_conditional_keys(children: &[AstNode], prefix: &str, out: &mut Vec<ConditionalKey>) {
+    for child in children {
+        let AstNode::KeyValue(kv) = child else {
+            continue;
+        };
+        let path = if prefix.is_empty() {
+            kv.key.value.clone()
+        } else {
+            format!("{prefix}.{}", kv.key.value)
+        };
+
+        match &kv.conditional {
+            Some(conditional) => out.push(ConditionalKey {
+                path,
+                condition: conditional.raw.clone(),
+            }),
+            None => {
+                if let ValueNode::Object(obj) = &kv.value {
+                    collect_conditional_keys(&obj.children, &path, out);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::KvDocument;
+    use crate::types::{KeyValuesValue, ParseOptions};
+
+    const GAMEINFO: &str = r#""GameInfo"
+{
+    "FileSystem"
+    {
+        "SearchPaths"
+        {
+            "Game"    "citadel/addons"
+            "Game"    "citadel/win"    [$WIN32]
+            "Game"    "citadel/posix"  [$LINUX||$OSX]
+            "Game"    "core"
+        }
+    }
+    "ConVars"
+    {
+        "r_vsync"    "1"    [!$LINUX]
+        "mat_fast"   "1"    [$LINUX&&!$DECK]
+    }
+}
+"#;
+
+    fn s(value: &str) -> KeyValuesValue {
+        KeyValuesValue::String(value.to_string())
+    }
+
+    #[test]
+    fn test_evaluate_operators() {
+        let linux = ConditionSymbols::linux();
+
+        assert!(linux.evaluate("[$LINUX]"));
+        assert!(linux.evaluate("[$linux]"));
+        assert!(!linux.evaluate("[$WIN32]"));
+        assert!(linux.evaluate("[!$X360]"));
+        assert!(linux.evaluate("[$WIN32||$POSIX]"));
+        assert!(!linux.evaluate("[$LINUX&&$DECK]"));
+        assert!(linux.evaluate("[$LINUX&&!$DECK]"));
+        assert!(linux.evaluate("[$WIN32&&$DECK||$LINUX]"));
+        assert!(!linux.evaluate("[]"));
+    }
+
+    #[test]
+    fn test_filter_by_platform() {
+        let parsed = Parser::parse(GAMEINFO, ParseOptions::default()).unwrap();
+        let mut doc = KvDocument::new();
+        doc.load_from_string(GAMEINFO).unwrap();
+
+        let linux = ConditionSymbols::linux().filter(&parsed.ast);
+        let windows = ConditionSymbols::windows().filter(&parsed.ast);
+
+        let paths = |data: &KeyValuesObject| -> Vec<KeyValuesValue> {
+            let KeyValuesValue::Object(game_info) = &data["GameInfo"] else {
+                panic!("expected object");
+            };
+            let KeyValuesValue::Object(fs) = &game_info["FileSystem"] else {
+                panic!("expected object");
+            };
+            let KeyValuesValue::Object(search) = &fs["SearchPaths"] else {
+                panic!("expected object");
+            };
+            search.get_all("Game").into_iter().cloned().collect()
+        };
+
+        assert_eq!(
+            paths(&linux),
+            [s("citadel/addons"), s("citadel/posix"), s("core")]
+        );
+        assert_eq!(
+            paths(&windows),
+            [s("citadel/addons"), s("citadel/win"), s("core")]
+        );
+        assert_eq!(
+            doc.view(&ConditionSymbols::linux()),
+            Some(linux),
+            "document view matches the filtered AST"
+        );
+
+        doc.set("GameInfo.game", s("citadel")).unwrap();
+        assert_eq!(
+            doc.view(&ConditionSymbols::linux()),
+            None,
+            "edits drop the conditionals a view filters on"
+        );
+    }
+
+    #[test]
+    fn test_conditional_keys() {
+        let parsed = Parser::parse(GAMEINFO, ParseOptions::default()).unwrap();
+
+        let keys = conditional_keys(&parsed.ast);
+
+        let listed: Vec<(&str, &str)> = keys
+            .iter()
+            .map(|k| (k.path.as_str(), k.condition.as_str()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("GameInfo.FileSystem.SearchPaths.Game", "[$WIN32]"),
+                ("GameInfo.FileSystem.SearchPaths.Game", "[$LINUX||$OSX]"),
+                ("GameInfo.ConVars.r_vsync", "[!$LINUX]"),
+                ("GameInfo.ConVars.mat_fast", "[$LINUX&&!$DECK]"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,706 @@
+//! Deserializing typed values from KeyValues text with serde.
+//!
+//! KeyValues has no types of its own, so scalars are read from their text:
+//! `"1"` is as good an integer as `1`. A key that repeats deserializes into a
+//! sequence, and so does an object whose keys are indices (`"0" { } "1" { }`
+//! in libraryfolders.vdf). The deserializer walks the AST rather than the data
+//! model so that errors can point at the value they are about.
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+
+use crate::error::{KvError, Result};
+use crate::parser::Parser;
+use crate::types::{AstNode, KeyValueNode, ObjectNode, ParseOptions, Position, ValueNode};
+
+/// Deserialize the value under the document's root key, e.g. the block under
+/// `"libraryfolders"`
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T> {
+    from_str_with_key(input).map(|(value, _)| value)
+}
+
+/// Deserialize the value under the document's root key, returning the key too
+pub fn from_str_with_key<T: DeserializeOwned>(input: &str) -> Result<(T, String)> {
+    let ast = Parser::parse(input, ParseOptions::default())?.ast;
+    let mut roots = key_values(&ast.children);
+    let root = roots
+        .next()
+        .ok_or_else(|| error("Expected a root key", ast.start))?;
+    if let Some(extra) = roots.next() {
+        return Err(error(
+            format!(
+                "Expected a single root key, found '{}' after '{}'",
+                extra.key.value, root.key.value
+            ),
+            extra.start,
+        ));
+    }
+
+    let value = T::deserialize(ValueDeserializer::new(vec![&root.value]))?;
+    Ok((value, root.key.value.clone()))
+}
+
+fn error(message: impl Into<String>, position: Position) -> KvError {
+    KvError::Data {
+        message: message.into(),
+        position: Some(position),
+    }
+}
+
+fn key_values(children: &[AstNode]) -> impl Iterator<Item = &KeyValueNode> {
+    children.iter().filter_map(|child| match child {
+        AstNode::KeyValue(kv) => Some(kv.as_ref()),
+        _ => None,
+    })
+}
+
+/// Whether the object's keys are `"0"`, `"1"`, ... in order, which makes it
+/// a list rather than a single item. An empty object is an empty list.
+fn is_index_keyed(object: &ObjectNode) -> bool {
+    key_values(&object.children)
+        .enumerate()
+        .all(|(index, kv)| kv.key.value == index.to_string())
+}
+
+fn start(node: &ValueNode) -> Position {
+    match node {
+        ValueNode::String(s) => s.start,
+        ValueNode::Number(n) => n.start,
+        ValueNode::Object(o) => o.start,
+    }
+}
+
+/// Scalar text, with a quoted number's quotes removed
+fn text(node: &ValueNode) -> Option<&str> {
+    match node {
+        ValueNode::String(s) => Some(&s.value),
+        ValueNode::Number(n) => Some(n.raw.trim_matches('"')),
+        ValueNode::Object(_) => None,
+    }
+}
+
+/// A scalar read from its text: a value or a key
+struct TextDeserializer<'a> {
+    text: &'a str,
+    position: Position,
+}
+
+impl TextDeserializer<'_> {
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T> {
+        self.text.trim().parse().map_err(|_| {
+            error(
+                format!("Expected {expected}, found '{}'", self.text),
+                self.position,
+            )
+        })
+    }
+
+    fn invalid(&self, expected: &str) -> KvError {
+        error(
+            format!("Expected {expected}, found '{}'", self.text),
+            self.position,
+        )
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident: $ty:ty, $expected:literal;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                let value: $ty = self.parse($expected)?;
+                visitor.$visit(value).map_err(|e: KvError| e.at(self.position))
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for TextDeserializer<'_> {
+    type Error = KvError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = match self.text.trim() {
+            "1" | "true" => true,
+            "0" | "false" => false,
+            _ => return Err(self.invalid("a boolean")),
+        };
+        visitor
+            .visit_bool(value)
+            .map_err(|e: KvError| e.at(self.position))
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8: i8, "an integer";
+        deserialize_i16 => visit_i16: i16, "an integer";
+        deserialize_i32 => visit_i32: i32, "an integer";
+        deserialize_i64 => visit_i64: i64, "an integer";
+        deserialize_u8 => visit_u8: u8, "an unsigned integer";
+        deserialize_u16 => visit_u16: u16, "an unsigned integer";
+        deserialize_u32 => visit_u32: u32, "an unsigned integer";
+        deserialize_u64 => visit_u64: u64, "an unsigned integer";
+        deserialize_f32 => visit_f32: f32, "a number";
+        deserialize_f64 => visit_f64: f64, "a number";
+        deserialize_char => visit_char: char, "a single character";
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor
+            .visit_str(self.text)
+            .map_err(|e: KvError| e.at(self.position))
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor
+            .visit_bytes(self.text.as_bytes())
+            .map_err(|e: KvError| e.at(self.position))
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor
+            .visit_unit()
+            .map_err(|e: KvError| e.at(self.position))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(self.invalid("a list"))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(self.invalid("an object"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let variant: de::value::StrDeserializer<'_, KvError> = self.text.into_deserializer();
+        visitor
+            .visit_enum(variant)
+            .map_err(|e: KvError| e.at(self.position))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// Every occurrence of one key: a single value, or several if the key repeats
+struct ValueDeserializer<'a> {
+    nodes: Vec<&'a ValueNode>,
+}
+
+impl<'a> ValueDeserializer<'a> {
+    fn new(nodes: Vec<&'a ValueNode>) -> Self {
+        Self { nodes }
+    }
+
+    fn position(&self) -> Position {
+        start(self.nodes[0])
+    }
+
+    /// The only occurrence, for anything that cannot take a list
+    fn single(&self) -> Result<&'a ValueNode> {
+        match self.nodes.as_slice() {
+            [node] => Ok(node),
+            [_, second, ..] => Err(error(
+                format!(
+                    "Expected a single value, found {} values for the same key",
+                    self.nodes.len()
+                ),
+                start(second),
+            )),
+            [] => unreachable!("a key always has a value"),
+        }
+    }
+
+    fn text(&self) -> Result<TextDeserializer<'a>> {
+        let node = self.single()?;
+        text(node)
+            .map(|text| TextDeserializer {
+                text,
+                position: start(node),
+            })
+            .ok_or_else(|| error("Expected a value, found an object", start(node)))
+    }
+
+    fn object(&self) -> Result<&'a ObjectNode> {
+        match self.single()? {
+            ValueNode::Object(object) => Ok(object),
+            node => Err(error(
+                format!(
+                    "Expected an object, found '{}'",
+                    text(node).unwrap_or_default()
+                ),
+                start(node),
+            )),
+        }
+    }
+}
+
+macro_rules! forward_to_text {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                self.text()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_> {
+    type Error = KvError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.nodes.len() > 1 {
+            return self.deserialize_seq(visitor);
+        }
+        let position = self.position();
+        match self.nodes[0] {
+            ValueNode::Object(_) => self.deserialize_map(visitor),
+            ValueNode::Number(number) if !number.is_float => match number.raw.parse::<i64>() {
+                Ok(value) => visitor.visit_i64(value),
+                Err(_) => visitor.visit_f64(number.value),
+            }
+            .map_err(|e: KvError| e.at(position)),
+            ValueNode::Number(number) => visitor
+                .visit_f64(number.value)
+                .map_err(|e: KvError| e.at(position)),
+            ValueNode::String(string) => visitor
+                .visit_str(&string.value)
+                .map_err(|e: KvError| e.at(position)),
+        }
+    }
+
+    forward_to_text! {
+        deserialize_bool
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_identifier
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Repeated keys, the values of an index-keyed object, or a lone value
+    /// as a list of one
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let position = self.position();
+        let items: Vec<&ValueNode> = match self.nodes.as_slice() {
+            [ValueNode::Object(object)] if is_index_keyed(object) => {
+                key_values(&object.children).map(|kv| &kv.value).collect()
+            }
+            nodes => nodes.to_vec(),
+        };
+        visitor
+            .visit_seq(SeqDeserializer {
+                items: items.into_iter(),
+            })
+            .map_err(|e: KvError| e.at(position))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let object = self.object()?;
+        visitor
+            .visit_map(MapDeserializer::new(object))
+            .map_err(|e: KvError| e.at(object.start))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    /// Unit variants are written as their name, other variants as an object
+    /// with the variant name as its only key
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let node = self.single()?;
+        let ValueNode::Object(object) = node else {
+            return self.text()?.deserialize_enum(name, variants, visitor);
+        };
+
+        let mut entries = key_values(&object.children);
+        match (entries.next(), entries.next()) {
+            (Some(entry), None) => visitor
+                .visit_enum(EnumDeserializer { entry })
+                .map_err(|e: KvError| e.at(object.start)),
+            _ => Err(error(
+                "Expected an object with a single key naming the variant",
+                object.start,
+            )),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqDeserializer<'a> {
+    items: std::vec::IntoIter<&'a ValueNode>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer<'_> {
+    type Error = KvError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.items
+            .next()
+            .map(|node| seed.deserialize(ValueDeserializer::new(vec![node])))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// An object's keys, with the occurrences of a repeated key grouped under
+/// its first appearance
+struct MapDeserializer<'a> {
+    entries: std::vec::IntoIter<(&'a KeyValueNode, Vec<&'a ValueNode>)>,
+    value: Option<Vec<&'a ValueNode>>,
+}
+
+impl<'a> MapDeserializer<'a> {
+    fn new(object: &'a ObjectNode) -> Self {
+        let mut entries: Vec<(&KeyValueNode, Vec<&ValueNode>)> = Vec::new();
+        for kv in key_values(&object.children) {
+            match entries
+                .iter_mut()
+                .find(|(first, _)| first.key.value == kv.key.value)
+            {
+                Some((_, values)) => values.push(&kv.value),
+                None => entries.push((kv, vec![&kv.value])),
+            }
+        }
+        Self {
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer<'_> {
+    type Error = KvError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((kv, values)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(values);
+        seed.deserialize(TextDeserializer {
+            text: &kv.key.value,
+            position: kv.key.start,
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let values = self
+            .value
+            .take()
+            .expect("next_value_seed is called after next_key_seed");
+        seed.deserialize(ValueDeserializer::new(values))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumDeserializer<'a> {
+    entry: &'a KeyValueNode,
+}
+
+impl<'de, 'a> EnumAccess<'de> for EnumDeserializer<'a> {
+    type Error = KvError;
+    type Variant = ValueDeserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(TextDeserializer {
+            text: &self.entry.key.value,
+            position: self.entry.key.start,
+        })?;
+        Ok((variant, ValueDeserializer::new(vec![&self.entry.value])))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ValueDeserializer<'_> {
+    type Error = KvError;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct LibraryFolder {
+        path: String,
+        #[serde(rename = "totalsize")]
+        total_size: u64,
+        apps: BTreeMap<u32, u64>,
+    }
+
+    #[test]
+    fn test_numbers_from_strings_and_index_keyed_lists() {
+        let input = r#""libraryfolders"
+{
+    "0"
+    {
+        "path"      "C:\\Program Files (x86)\\Steam"
+        "totalsize" "0"
+        "apps"
+        {
+            "1422450"   "30618112457"
+        }
+    }
+    "1"
+    {
+        "path"      "D:\\SteamLibrary"
+        "totalsize" 2000396742656
+        "apps" { }
+    }
+}
+"#;
+
+        let (folders, key): (Vec<LibraryFolder>, String) = from_str_with_key(input).unwrap();
+
+        assert_eq!(key, "libraryfolders");
+        assert_eq!(folders.len(), 2);
+        assert_eq!(folders[0].path, r"C:\Program Files (x86)\Steam");
+        assert_eq!(folders[0].apps.get(&1422450), Some(&30618112457));
+        assert_eq!(folders[1].total_size, 2000396742656);
+        assert!(folders[1].apps.is_empty());
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct SearchPaths {
+        game: Vec<String>,
+        #[serde(rename = "Mod")]
+        mods: Option<Vec<String>>,
+        write: Option<String>,
+    }
+
+    #[test]
+    fn test_repeated_keys_and_single_values_as_lists() {
+        let paths: SearchPaths =
+            from_str("SearchPaths { Game citadel/addons Game citadel Game core Write citadel }")
+                .unwrap();
+
+        assert_eq!(paths.game, ["citadel/addons", "citadel", "core"]);
+        assert_eq!(paths.mods, None);
+        assert_eq!(paths.write.as_deref(), Some("citadel"));
+
+        let single: SearchPaths = from_str("SearchPaths { Game core }").unwrap();
+        assert_eq!(single.game, ["core"]);
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Root {
+        item: Vec<Item>,
+    }
+
+    #[test]
+    fn test_single_object_as_list_of_one() {
+        let root: Root = from_str("root { item { name a } }").unwrap();
+        assert_eq!(root.item, [Item { name: "a".into() }]);
+
+        let root: Root = from_str("root { item { 0 { name a } 1 { name b } } }").unwrap();
+        assert_eq!(root.item.len(), 2);
+
+        let root: Root = from_str("root { item { } }").unwrap();
+        assert!(root.item.is_empty());
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Kind {
+        Multiplayer,
+        Singleplayer,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct User {
+        #[serde(rename = "AccountName")]
+        account_name: String,
+        #[serde(rename = "MostRecent")]
+        most_recent: bool,
+        kind: Kind,
+    }
+
+    #[test]
+    fn test_booleans_and_unit_variants_from_text() {
+        let users: BTreeMap<u64, User> = from_str(
+            r#""users" { "76561197960287930" { "AccountName" "gaben" "MostRecent" "1" "kind" "multiplayer" } }"#,
+        )
+        .unwrap();
+
+        let user = &users[&76561197960287930];
+        assert_eq!(user.account_name, "gaben");
+        assert!(user.most_recent);
+        assert_eq!(user.kind, Kind::Multiplayer);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let err =
+            from_str::<BTreeMap<String, u32>>("root\n{\n    count \"many\"\n}\n").unwrap_err();
+
+        let KvError::Data { message, position } = err else {
+            panic!("expected a data error, got {err:?}");
+        };
+        assert_eq!(message, "Expected an unsigned integer, found 'many'");
+        let position = position.unwrap();
+        assert_eq!((position.line, position.column), (3, 11));
+    }
+
+    #[test]
+    fn test_missing_field_position() {
+        let err = from_str::<User>("user\n{\n    AccountName a\n}\n").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Data error at line 2, column 1: missing field `MostRecent`"
+        );
+    }
+
+    #[test]
+    fn test_several_root_keys() {
+        let err = from_str::<String>("a 1\nb 2\n").unwrap_err();
+
+        assert!(err.to_string().contains("found 'b' after 'a'"));
+    }
+}
@@ -1,3 +1,11 @@
+// Older code in this module predates these lints; kept as written.
+#![allow(
+    clippy::collapsible_if,
+    clippy::collapsible_match,
+    clippy::useless_format,
+    clippy::ptr_arg
+)]
+
 use crate::ast::*;
 use crate::error::{KvError, Result};
 use crate::types::{
@@ -231,6 +239,16 @@ impl DiffApplicator {
 
     fn apply_change(data: &mut KeyValuesObject, change: &DiffEntry) -> Result<()> {
         let path_parts: Vec<&str> = change.path.split('.').collect();
+        Self::apply_change_at(data, &path_parts, change)
+    }
+
+    /// Apply a data change at an already split key path, for callers whose
+    /// keys may themselves contain dots
+    pub(crate) fn apply_change_at(
+        data: &mut KeyValuesObject,
+        path_parts: &[&str],
+        change: &DiffEntry,
+    ) -> Result<()> {
         let last_key = path_parts.last().ok_or_else(|| KvError::InvalidPath {
             path: change.path.clone(),
         })?;
@@ -238,14 +256,14 @@ impl DiffApplicator {
         // Navigate to parent object
         let mut current = data;
         for part in &path_parts[..path_parts.len() - 1] {
-            if !current.contains_key(*part) {
+            if !current.contains_key(part) {
                 current.insert(
                     part.to_string(),
                     KeyValuesValue::Object(KeyValuesObject::new()),
                 );
             }
 
-            match current.get_mut(*part) {
+            match current.get_mut(part) {
                 Some(KeyValuesValue::Object(obj)) => {
                     current = obj;
                 }
@@ -266,7 +284,7 @@ impl DiffApplicator {
                 }
             }
             DiffOp::Remove => {
-                current.remove(*last_key);
+                current.remove(last_key);
             }
         }
 
@@ -324,16 +342,24 @@ impl DiffApplicator {
 
         // Handle regular data operations
         let path_parts: Vec<&str> = change.path.split('.').collect();
+        Self::apply_change_to_ast_at(ast, &path_parts, change)
+    }
 
+    /// Apply a data change to the AST at an already split key path
+    pub(crate) fn apply_change_to_ast_at(
+        ast: &mut DocumentNode,
+        path_parts: &[&str],
+        change: &DiffEntry,
+    ) -> Result<()> {
         match change.op {
             DiffOp::Replace => {
-                Self::update_keyvalue_in_ast_path(ast, &path_parts, change.new_value.as_ref())?;
+                Self::update_keyvalue_in_ast_path(ast, path_parts, change.new_value.as_ref())?;
             }
             DiffOp::Add => {
-                Self::add_keyvalue_to_ast_path(ast, &path_parts, change.new_value.as_ref())?;
+                Self::add_keyvalue_to_ast_path(ast, path_parts, change.new_value.as_ref())?;
             }
             DiffOp::Remove => {
-                Self::remove_keyvalue_from_ast_path(ast, &path_parts)?;
+                Self::remove_keyvalue_from_ast_path(ast, path_parts)?;
             }
         }
 
@@ -861,72 +887,31 @@ impl DiffApplicator {
                 return Self::add_multiple_keyvalues(children, key, arr);
             }
 
-            // Single value - existing logic
-            // Detect indentation from existing whitespace, or use default
-            let indent = if let Some(last) = children.last() {
-                if let AstNode::Whitespace(ws) = last {
-                    // Extract indentation (tabs) from existing whitespace
-                    let tabs: String = ws
-                        .raw
-                        .chars()
-                        .skip_while(|c| *c != '\n' && *c != '\r')
-                        .skip_while(|c| *c == '\n' || *c == '\r')
-                        .take_while(|c| *c == '\t')
-                        .collect();
-                    if !tabs.is_empty() {
-                        format!("\n{}", tabs)
-                    } else {
-                        "\n\t\t\t".to_string()
-                    }
-                } else {
-                    // Look backwards for whitespace to detect indentation
-                    let mut found_indent = None;
-                    for child in children.iter().rev() {
-                        if let AstNode::Whitespace(ws) = child {
-                            let tabs: String = ws
-                                .raw
-                                .chars()
-                                .skip_while(|c| *c != '\n' && *c != '\r')
-                                .skip_while(|c| *c == '\n' || *c == '\r')
-                                .take_while(|c| *c == '\t')
-                                .collect();
-                            if !tabs.is_empty() {
-                                found_indent = Some(format!("\n{}", tabs));
-                                break;
-                            }
-                        } else if let AstNode::KeyValue(_) = child {
-                            break;
-                        }
-                    }
-                    found_indent.unwrap_or_else(|| "\n\t\t\t".to_string())
-                }
+            // Single value - keep the whitespace before the closing brace at
+            // the end and indent the new entry like its last sibling
+            let trailing_ws = if matches!(children.last(), Some(AstNode::Whitespace(_))) {
+                children.pop()
             } else {
-                "\n\t\t\t".to_string()
+                None
             };
+            let indent = Self::sibling_indent(children).unwrap_or_else(|| "\n\t\t\t".to_string());
 
-            // Only add whitespace if the last node is not already whitespace
-            if !children
-                .last()
-                .map(|c| matches!(c, AstNode::Whitespace(_)))
-                .unwrap_or(false)
-            {
-                let ws_node = AstNode::Whitespace(WhitespaceNode {
-                    node_type: NodeType::Whitespace,
-                    start: Position {
-                        offset: 0,
-                        line: 0,
-                        column: 0,
-                    },
-                    end: Position {
-                        offset: 0,
-                        line: 0,
-                        column: 0,
-                    },
-                    raw: indent.clone(),
-                    value: indent.clone(),
-                });
-                children.push(ws_node);
-            }
+            let ws_node = AstNode::Whitespace(WhitespaceNode {
+                node_type: NodeType::Whitespace,
+                start: Position {
+                    offset: 0,
+                    line: 0,
+                    column: 0,
+                },
+                end: Position {
+                    offset: 0,
+                    line: 0,
+                    column: 0,
+                },
+                raw: indent.clone(),
+                value: indent,
+            });
+            children.push(ws_node);
 
             let kv_node = KeyValueNode {
                 node_type: NodeType::KeyValue,
@@ -978,10 +963,31 @@ impl DiffApplicator {
                 conditional: None,
             };
             children.push(AstNode::KeyValue(Box::new(kv_node)));
+
+            if let Some(ws) = trailing_ws {
+                children.push(ws);
+            }
         }
         Ok(())
     }
 
+    /// Line break and indentation in front of the last key-value in `children`
+    fn sibling_indent(children: &[AstNode]) -> Option<String> {
+        let last_kv = children
+            .iter()
+            .rposition(|c| matches!(c, AstNode::KeyValue(_)))?;
+        let AstNode::Whitespace(ws) = children.get(last_kv.checked_sub(1)?)? else {
+            return None;
+        };
+        let line_start = ws.raw.rfind('\n')?;
+        let line_start = if ws.raw[..line_start].ends_with('\r') {
+            line_start - 1
+        } else {
+            line_start
+        };
+        Some(ws.raw[line_start..].to_string())
+    }
+
     fn remove_keyvalue_from_ast_path(ast: &mut DocumentNode, path_parts: &[&str]) -> Result<()> {
         if path_parts.is_empty() {
             return Err(KvError::InvalidPath {
@@ -1058,7 +1064,7 @@ impl DiffApplicator {
     /// Note: Arrays are not supported in the AST representation and will return an error.
     /// KeyValues format does not natively support arrays, and arrays are only used
     /// internally when duplicate keys are encountered during parsing.
-    fn create_value_node(value: &KeyValuesValue) -> Result<ValueNode> {
+    pub(crate) fn create_value_node(value: &KeyValuesValue) -> Result<ValueNode> {
         match value {
             KeyValuesValue::String(s) => Ok(ValueNode::String(StringNode {
                 node_type: NodeType::String,
@@ -1635,7 +1641,7 @@ fn check_add_already_applied(
 
     let mut current = data;
     for part in &path_parts[..path_parts.len() - 1] {
-        match current.get(*part) {
+        match current.get(part) {
             Some(KeyValuesValue::Object(obj)) => {
                 current = obj;
             }
@@ -1694,7 +1700,7 @@ fn check_replace_already_applied(
 
     let mut current = data;
     for part in &path_parts[..path_parts.len() - 1] {
-        match current.get(*part) {
+        match current.get(part) {
             Some(KeyValuesValue::Object(obj)) => {
                 current = obj;
             }
@@ -1739,7 +1745,7 @@ fn check_remove_already_applied(
 
     let mut current = data;
     for part in &path_parts[..path_parts.len() - 1] {
-        match current.get(*part) {
+        match current.get(part) {
             Some(KeyValuesValue::Object(obj)) => {
                 current = obj;
             }
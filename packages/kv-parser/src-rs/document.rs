@@ -1,14 +1,26 @@
 use std::fmt;
 
 use crate::ast::DocumentNode;
+use crate::conditional::{ConditionSymbols, conditional_keys};
 use crate::diff::{DiffApplicator, DiffGenerator};
 use crate::error::{KvError, Result};
+use crate::merge::{MergeChange, merge3_changes};
 use crate::parser::Parser;
+use crate::query::KvQuery;
 use crate::serializer::Serializer;
 use crate::types::{
-    DiffStats, DocumentDiff, KeyValuesObject, KeyValuesValue, ParseOptions, SerializeOptions,
+    ConditionalKey, DiffStats, DocumentDiff, InsertPosition, KeyValuesObject, KeyValuesValue,
+    MergeConflict, ParseOptions, QueryEdit, QueryMatch, SerializeOptions,
 };
 
+/// Result of `KvDocument::merge3`
+pub struct Merge3Result {
+    /// Our document with their non-conflicting changes applied
+    pub document: KvDocument,
+    /// Keys both sides changed differently; the document keeps our value
+    pub conflicts: Vec<MergeConflict>,
+}
+
 /// Document API for load → modify → save workflows
 pub struct KvDocument {
     data: KeyValuesObject,
@@ -60,8 +72,37 @@ impl KvDocument {
         current_value
     }
 
+    /// Get every value stored under a repeated key, in document order
+    pub fn get_all(&self, path: &str) -> Vec<&KeyValuesValue> {
+        match self.get(path) {
+            Some(KeyValuesValue::Array(items)) => items.iter().collect(),
+            Some(value) => vec![value],
+            None => Vec::new(),
+        }
+    }
+
     /// Set a value by path (creates intermediate objects if needed)
+    ///
+    /// An existing key keeps its position in the document.
     pub fn set(&mut self, path: &str, value: KeyValuesValue) -> Result<()> {
+        let (parent, last_part) = self.parent_object_mut(path)?;
+        parent.insert(last_part, value);
+        self.ast = None; // Clear AST since data was modified
+        Ok(())
+    }
+
+    /// Add another occurrence of the key at `path` after its existing ones
+    /// (creates intermediate objects if needed)
+    pub fn append(&mut self, path: &str, value: KeyValuesValue) -> Result<()> {
+        let (parent, last_part) = self.parent_object_mut(path)?;
+        parent.append(last_part, value);
+        self.ast = None; // Clear AST since data was modified
+        Ok(())
+    }
+
+    /// Navigate to the object holding the last segment of `path`, creating
+    /// intermediate objects along the way
+    fn parent_object_mut(&mut self, path: &str) -> Result<(&mut KeyValuesObject, String)> {
         let parts: Vec<&str> = path.split('.').collect();
         let last_part = parts.last().ok_or_else(|| KvError::InvalidPath {
             path: path.to_string(),
@@ -71,14 +112,14 @@ impl KvDocument {
 
         // Navigate/create path
         for part in &parts[..parts.len() - 1] {
-            if !current.contains_key(*part) {
+            if !current.contains_key(part) {
                 current.insert(
                     part.to_string(),
                     KeyValuesValue::Object(KeyValuesObject::new()),
                 );
             }
 
-            match current.get_mut(*part) {
+            match current.get_mut(part) {
                 Some(KeyValuesValue::Object(obj)) => {
                     current = obj;
                 }
@@ -90,9 +131,7 @@ impl KvDocument {
             }
         }
 
-        current.insert(last_part.to_string(), value);
-        self.ast = None; // Clear AST since data was modified
-        Ok(())
+        Ok((current, last_part.to_string()))
     }
 
     /// Delete a value by path
@@ -106,7 +145,7 @@ impl KvDocument {
 
         // Navigate to parent
         for part in &parts[..parts.len() - 1] {
-            match current.get_mut(*part) {
+            match current.get_mut(part) {
                 Some(KeyValuesValue::Object(obj)) => {
                     current = obj;
                 }
@@ -114,13 +153,101 @@ impl KvDocument {
             }
         }
 
-        let removed = current.remove(*last_part).is_some();
+        let removed = current.remove(last_part).is_some();
         if removed {
             self.ast = None; // Clear AST since data was modified
         }
         Ok(removed)
     }
 
+    /// Every key-value a query selects, in document order. See `KvQuery`
+    /// for the syntax.
+    pub fn query(&self, query: &str) -> Result<Vec<QueryMatch>> {
+        Ok(KvQuery::parse(query)?.select(&self.data))
+    }
+
+    /// Replace the value of every key-value a query selects
+    ///
+    /// Query edits go through the AST when the document has one, so
+    /// comments and formatting are kept. Returns the number of matches.
+    pub fn query_set(&mut self, query: &str, value: KeyValuesValue) -> Result<usize> {
+        self.edit_with_query(
+            query,
+            |query, ast| query.set_ast(ast, &value),
+            |query, data| query.set(data, &value),
+        )
+    }
+
+    /// Add `key` before or after every key-value a query selects
+    pub fn query_insert(
+        &mut self,
+        query: &str,
+        position: InsertPosition,
+        key: &str,
+        value: KeyValuesValue,
+    ) -> Result<usize> {
+        self.edit_with_query(
+            query,
+            |query, ast| query.insert_ast(ast, position, key, &value),
+            |query, data| query.insert(data, position, key, &value),
+        )
+    }
+
+    /// Remove every key-value a query selects
+    pub fn query_delete(&mut self, query: &str) -> Result<usize> {
+        self.edit_with_query(
+            query,
+            |query, ast| query.delete_ast(ast),
+            |query, data| query.delete(data),
+        )
+    }
+
+    /// Apply a serialized query edit, returning the number of matches
+    pub fn apply_query_edit(&mut self, edit: &QueryEdit) -> Result<usize> {
+        match edit {
+            QueryEdit::Set { query, value } => self.query_set(query, value.clone()),
+            QueryEdit::Insert {
+                query,
+                position,
+                key,
+                value,
+            } => self.query_insert(query, *position, key, value.clone()),
+            QueryEdit::Delete { query } => self.query_delete(query),
+        }
+    }
+
+    fn edit_with_query(
+        &mut self,
+        query: &str,
+        on_ast: impl FnOnce(&KvQuery, &mut DocumentNode) -> Result<usize>,
+        on_data: impl FnOnce(&KvQuery, &mut KeyValuesObject) -> Result<usize>,
+    ) -> Result<usize> {
+        let query = KvQuery::parse(query)?;
+        match &mut self.ast {
+            Some(ast) => {
+                let count = on_ast(&query, ast)?;
+                self.data = Parser::ast_to_data_where(ast, &|_| true);
+                Ok(count)
+            }
+            None => on_data(&query, &mut self.data),
+        }
+    }
+
+    /// Data as seen on a platform: key-values whose `[$SYMBOL]` conditional
+    /// is false for `symbols` are left out
+    ///
+    /// Conditionals live in the AST, so this is `None` for a document that was
+    /// never loaded or whose data has been modified with `set`, `append`,
+    /// `delete`, `merge` or `clear`.
+    pub fn view(&self, symbols: &ConditionSymbols) -> Option<KeyValuesObject> {
+        self.ast.as_ref().map(|ast| symbols.filter(ast))
+    }
+
+    /// Keys whose presence depends on a conditional
+    pub fn conditional_keys(&self) -> Vec<ConditionalKey> {
+        self.ast.as_ref().map(conditional_keys).unwrap_or_default()
+    }
+
     /// Check if a path exists
     pub fn has(&self, path: &str) -> bool {
         self.get(path).is_some()
@@ -194,14 +321,14 @@ impl KvDocument {
                 let mut current = &mut self.data;
 
                 for part in &parts {
-                    if !current.contains_key(*part) {
+                    if !current.contains_key(part) {
                         current.insert(
                             part.to_string(),
                             KeyValuesValue::Object(KeyValuesObject::new()),
                         );
                     }
 
-                    match current.get_mut(*part) {
+                    match current.get_mut(part) {
                         Some(KeyValuesValue::Object(target)) => {
                             current = target;
                         }
@@ -243,6 +370,45 @@ impl KvDocument {
         Ok(())
     }
 
+    /// Three-way merge: apply the changes `theirs` made to `base` on top of
+    /// `ours`
+    ///
+    /// The merged document starts from our AST, so our comments, formatting
+    /// and hand edits survive. Keys both sides changed differently are
+    /// reported as conflicts and keep our value. Without an AST on our side
+    /// only the data is merged.
+    pub fn merge3(
+        base: &KvDocument,
+        ours: &KvDocument,
+        theirs: &KvDocument,
+    ) -> Result<Merge3Result> {
+        let (changes, conflicts) = merge3_changes(&base.data, &ours.data, &theirs.data);
+
+        let mut document = KvDocument::with_options(ours.options.clone());
+        match &ours.ast {
+            Some(ast) => {
+                let mut merged = ast.clone();
+                for MergeChange { path, change } in &changes {
+                    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+                    DiffApplicator::apply_change_to_ast_at(&mut merged, &path, change)?;
+                }
+                document.load_from_string(&Serializer::serialize_ast(&merged))?;
+            }
+            None => {
+                document.data = ours.data.clone();
+                for MergeChange { path, change } in &changes {
+                    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+                    DiffApplicator::apply_change_at(&mut document.data, &path, change)?;
+                }
+            }
+        }
+
+        Ok(Merge3Result {
+            document,
+            conflicts,
+        })
+    }
+
     /// Compare with another document
     pub fn equals(&self, other: &KvDocument) -> bool {
         let diff = self.diff(other);
@@ -319,6 +485,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_document_append_keeps_duplicates() {
+        let mut doc = KvDocument::new();
+        doc.load_from_string(
+            r#""GameInfo" { "SearchPaths" { "Game" "citadel" "Mod" "citadel" } }"#,
+        )
+        .unwrap();
+
+        doc.append(
+            "GameInfo.SearchPaths.Game",
+            KeyValuesValue::String("core".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.get_all("GameInfo.SearchPaths.Game"),
+            vec![
+                &KeyValuesValue::String("citadel".to_string()),
+                &KeyValuesValue::String("core".to_string()),
+            ]
+        );
+        let serialized = doc.serialize().unwrap();
+        let game = serialized.find("citadel").unwrap();
+        let modded = serialized.find("Mod").unwrap();
+        let core = serialized.find("core").unwrap();
+        assert!(game < modded && modded < core);
+    }
+
     #[test]
     fn test_document_delete() {
         let mut doc = KvDocument::new();
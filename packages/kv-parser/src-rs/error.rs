@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::types::Position;
+
 pub type Result<T> = std::result::Result<T, KvError>;
 
 #[derive(Debug, Error)]
@@ -70,6 +72,24 @@ pub enum KvError {
     #[error("Cannot set property on non-object value at path: {path}")]
     CannotSetOnNonObject { path: String },
 
+    #[error("Invalid query '{query}': {message}")]
+    InvalidQuery { query: String, message: String },
+
+    #[error("Invalid binary VDF at byte {offset}: {message}")]
+    BinaryFormat { offset: usize, message: String },
+
+    #[error("Include cycle: {chain}")]
+    IncludeCycle { chain: String },
+
+    #[error("Failed to load included file {path}: {message}")]
+    IncludeLoad { path: String, message: String },
+
+    #[error("Data error{}: {message}", at_position(.position))]
+    Data {
+        message: String,
+        position: Option<Position>,
+    },
+
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
@@ -85,3 +105,43 @@ pub enum KvError {
     #[error("{0}")]
     Other(String),
 }
+
+impl KvError {
+    /// Attach `position` to a data error that does not have one yet
+    pub(crate) fn at(self, position: Position) -> Self {
+        match self {
+            Self::Data {
+                message,
+                position: None,
+            } => Self::Data {
+                message,
+                position: Some(position),
+            },
+            other => other,
+        }
+    }
+}
+
+impl serde::de::Error for KvError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Data {
+            message: msg.to_string(),
+            position: None,
+        }
+    }
+}
+
+impl serde::ser::Error for KvError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Data {
+            message: msg.to_string(),
+            position: None,
+        }
+    }
+}
+
+fn at_position(position: &Option<Position>) -> String {
+    position
+        .map(|p| format!(" at line {}, column {}", p.line, p.column))
+        .unwrap_or_default()
+}
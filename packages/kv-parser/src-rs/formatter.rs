@@ -0,0 +1,334 @@
+use crate::ast::*;
+use crate::error::Result;
+use crate::parser::Parser;
+use crate::serializer::Serializer;
+use crate::tokenizer::Tokenizer;
+use crate::types::{ParseOptions, SerializeOptions};
+
+/// Lays a document out canonically while keeping everything a person wrote:
+/// comments, conditionals, directives and the blank lines that group keys.
+///
+/// Each key goes on its own line, indented per `SerializeOptions`, objects
+/// open on the line after their key, runs of blank lines collapse to one and
+/// quoting follows the options' policy. Comments stay where they were, either
+/// on their own line or after the value they trail. Formatting formatted
+/// output changes nothing.
+pub struct Formatter {
+    options: SerializeOptions,
+    quoting: Serializer,
+}
+
+impl Formatter {
+    pub fn new(options: SerializeOptions) -> Self {
+        Self {
+            quoting: Serializer::new(options.clone()),
+            options,
+        }
+    }
+
+    /// Parse and format `input`
+    pub fn format(&self, input: &str) -> Result<String> {
+        let result = Parser::parse(input, ParseOptions::default())?;
+        Ok(self.format_ast(&result.ast))
+    }
+
+    pub fn format_ast(&self, ast: &DocumentNode) -> String {
+        let mut out = String::new();
+        self.write_block(&mut out, &ast.children, 0, false);
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    fn indent(&self, out: &mut String, depth: usize) {
+        if self.options.use_tabs {
+            out.extend(std::iter::repeat_n('\t', depth));
+        } else {
+            out.extend(std::iter::repeat_n(' ', depth * self.options.indent_size));
+        }
+    }
+
+    /// Write `children` one line each, without a trailing newline. Inside an
+    /// object the opening brace is already on the current line.
+    fn write_block(&self, out: &mut String, children: &[AstNode], depth: usize, in_object: bool) {
+        let widths = self.alignment(children);
+        // Line breaks since the last thing written
+        let mut newlines = 0;
+        let mut line_started = in_object;
+        let mut first = true;
+
+        for (child, width) in children.iter().zip(widths) {
+            if let AstNode::Whitespace(ws) = child {
+                newlines += ws.value.matches('\n').count();
+                continue;
+            }
+
+            // A comment on the same line as what came before trails it
+            if let AstNode::Comment(comment) = child
+                && newlines == 0
+                && line_started
+            {
+                out.push(' ');
+                out.push_str(comment.raw.trim_end());
+                continue;
+            }
+
+            if line_started {
+                out.push('\n');
+                if newlines > 1 && !first {
+                    out.push('\n');
+                }
+            }
+            self.indent(out, depth);
+
+            match child {
+                AstNode::KeyValue(kv) => self.write_key_value(out, kv, depth, width),
+                AstNode::Comment(comment) => out.push_str(comment.raw.trim_end()),
+                AstNode::Conditional(conditional) => out.push_str(conditional.raw.trim_end()),
+                AstNode::Directive(directive) => {
+                    let name = match directive.kind {
+                        DirectiveKind::Include => "#include",
+                        DirectiveKind::Base => "#base",
+                    };
+                    out.push_str(&format!("{name} \"{}\"", directive.path));
+                }
+                AstNode::Whitespace(_) => unreachable!(),
+            }
+
+            newlines = 0;
+            line_started = true;
+            first = false;
+        }
+    }
+
+    fn write_key_value(&self, out: &mut String, kv: &KeyValueNode, depth: usize, width: usize) {
+        let key = self.string_text(&kv.key);
+        out.push_str(&key);
+
+        match &kv.value {
+            ValueNode::Object(object) => {
+                out.push('\n');
+                self.indent(out, depth);
+                out.push('{');
+                self.write_block(out, &object.children, depth + 1, true);
+                out.push('\n');
+                self.indent(out, depth);
+                out.push('}');
+            }
+            value => {
+                self.separator(out, key.chars().count(), width);
+                out.push_str(&self.value_text(value));
+            }
+        }
+
+        if let Some(conditional) = &kv.conditional {
+            out.push(' ');
+            out.push_str(conditional.raw.trim_end());
+        }
+    }
+
+    /// Space between a key and its value, reaching `width` when aligning
+    fn separator(&self, out: &mut String, key_width: usize, width: usize) {
+        if !self.options.align_values {
+            out.push(if self.options.use_tabs { '\t' } else { ' ' });
+            return;
+        }
+
+        if self.options.use_tabs {
+            // Tabs advance to the next stop; the key starts on one
+            let tab = self.options.indent_size.max(1);
+            let target = (width / tab + 1) * tab;
+            let tabs = target / tab - key_width / tab;
+            out.extend(std::iter::repeat_n('\t', tabs));
+        } else {
+            out.extend(std::iter::repeat_n(' ', width + 1 - key_width));
+        }
+    }
+
+    /// The widest key in each child's alignment group: a run of scalar keys
+    /// not broken up by a blank line, an object or a directive
+    fn alignment(&self, children: &[AstNode]) -> Vec<usize> {
+        let mut widths = vec![0; children.len()];
+        if !self.options.align_values {
+            return widths;
+        }
+
+        let mut run: Vec<usize> = Vec::new();
+        let mut widest = 0;
+        let mut close = |run: &mut Vec<usize>, widest: &mut usize| {
+            for &i in run.iter() {
+                widths[i] = *widest;
+            }
+            run.clear();
+            *widest = 0;
+        };
+
+        for (i, child) in children.iter().enumerate() {
+            match child {
+                AstNode::KeyValue(kv) if !matches!(kv.value, ValueNode::Object(_)) => {
+                    widest = widest.max(self.string_text(&kv.key).chars().count());
+                    run.push(i);
+                }
+                AstNode::Whitespace(ws) if ws.value.matches('\n').count() < 2 => {}
+                AstNode::Comment(_) | AstNode::Conditional(_) => {}
+                _ => close(&mut run, &mut widest),
+            }
+        }
+        close(&mut run, &mut widest);
+        widths
+    }
+
+    fn needs_quotes(&self, value: &str) -> bool {
+        // An unquoted token ends where a comment would start
+        self.quoting.needs_quotes(value) || value.contains("//") || value.contains("/*")
+    }
+
+    fn string_text(&self, node: &StringNode) -> String {
+        if !self.needs_quotes(&node.value) {
+            return node.value.clone();
+        }
+
+        // Keep a quoted string's escapes as they were written, unless the
+        // parser recovered it from malformed text and it would not read back
+        if node.quoted && reads_back(&node.raw, &node.value) {
+            return node.raw.clone();
+        }
+        format!("\"{}\"", self.quoting.escape_string(&node.value))
+    }
+
+    fn value_text(&self, value: &ValueNode) -> String {
+        match value {
+            ValueNode::String(string) => self.string_text(string),
+            ValueNode::Number(number) => {
+                let raw = number.raw.trim_matches('"');
+                if self.options.quote_all_strings {
+                    format!("\"{raw}\"")
+                } else {
+                    raw.to_string()
+                }
+            }
+            ValueNode::Object(_) => unreachable!("objects are written as blocks"),
+        }
+    }
+}
+
+/// Whether `raw` is a complete quoted string that parses to `value`
+fn reads_back(raw: &str, value: &str) -> bool {
+    // A token after the string shows whether its closing quote really ends it
+    let text = format!("{raw} x");
+    Tokenizer::new(&text)
+        .next_token()
+        .is_ok_and(|token| token.raw == raw && token.value == value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(input: &str, options: SerializeOptions) -> String {
+        Formatter::new(options).format(input).unwrap()
+    }
+
+    fn tabs() -> SerializeOptions {
+        SerializeOptions {
+            use_tabs: true,
+            ..SerializeOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_format_layout_and_comments() {
+        let input = "// Header\n\n\n\"Root\" {   \"a\"   \"1\" // one\n   b   x\n\n\n\n  // Group\n  \"c\" \"two words\"\n  Child { d 2 } [$WIN32]\n}\n";
+
+        let formatted = format(input, tabs());
+
+        assert_eq!(
+            formatted,
+            "// Header\n\nRoot\n{\n\ta\t\"1\" // one\n\tb\tx\n\n\t// Group\n\tc\t\"two words\"\n\tChild\n\t{\n\t\td\t2\n\t} [$WIN32]\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        let input = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/data/gameinfo.gi"
+        ))
+        .unwrap();
+
+        for options in [
+            tabs(),
+            SerializeOptions {
+                quote_all_strings: true,
+                align_values: true,
+                ..SerializeOptions::default()
+            },
+        ] {
+            let once = format(&input, options.clone());
+            assert_eq!(format(&once, options), once);
+        }
+    }
+
+    #[test]
+    fn test_align_values_within_groups() {
+        let options = SerializeOptions {
+            quote_all_strings: true,
+            align_values: true,
+            indent_size: 2,
+            ..SerializeOptions::default()
+        };
+
+        let formatted = format("r { a 1 long 2\n\nmid 3 }", options);
+
+        assert_eq!(
+            formatted,
+            "\"r\"\n{\n  \"a\"    \"1\"\n  \"long\" \"2\"\n\n  \"mid\" \"3\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_align_with_tab_stops() {
+        let options = SerializeOptions {
+            align_values: true,
+            ..tabs()
+        };
+
+        let formatted = format("r { a 1 abcdef 2 }", options);
+
+        assert_eq!(formatted, "r\n{\n\ta\t\t1\n\tabcdef\t2\n}\n");
+    }
+
+    #[test]
+    fn test_format_idempotent_on_malformed_input() {
+        let inputs = [
+            "[",
+            "r { a b } [$X",
+            "a \"unterminated\\\"",
+            "r { a \"x\\qy\" b \"tab\\there\" }",
+            "r { \"\" \"\" }",
+        ];
+
+        for input in inputs {
+            let once = format(input, tabs());
+            let twice = Formatter::new(tabs())
+                .format(&once)
+                .unwrap_or_else(|e| panic!("{input:?} formatted as unparseable {once:?}: {e}"));
+            assert_eq!(twice, once, "{input:?}");
+            assert_eq!(
+                Parser::parse(&once, ParseOptions::default()).unwrap().data,
+                Parser::parse(input, ParseOptions::default()).unwrap().data,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_quote_ambiguous_strings() {
+        let formatted = format(r#"r { url "http://x" path "a\\b" c "//d" }"#, tabs());
+
+        assert!(formatted.contains("url\t\"http://x\""));
+        assert!(formatted.contains(r#"path	a\b"#));
+        assert!(formatted.contains("c\t\"//d\""));
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{KvError, Result};
+use crate::parser::Parser;
+use crate::types::{
+    AstNode, DirectiveKind, DocumentNode, IncludedDocument, KeySource, KeyValuesObject,
+    KeyValuesValue, ParseOptions, ResolvedDocument,
+};
+
+/// Loads the contents of files referenced by `#include` and `#base`
+///
+/// Implemented for any `FnMut(&Path) -> io::Result<String>`, so tests and
+/// callers reading from archives can pass a closure.
+pub trait IncludeLoader {
+    fn load(&mut self, path: &Path) -> io::Result<String>;
+}
+
+impl<F> IncludeLoader for F
+where
+    F: FnMut(&Path) -> io::Result<String>,
+{
+    fn load(&mut self, path: &Path) -> io::Result<String> {
+        self(path)
+    }
+}
+
+/// Loader that reads included files from disk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsLoader;
+
+impl IncludeLoader for FsLoader {
+    fn load(&mut self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Resolves `#include` and `#base` directives into a single document
+///
+/// Directive paths are relative to the directory of the file that contains
+/// them. Included files are resolved recursively; a file that (directly or
+/// indirectly) includes itself is reported as `KvError::IncludeCycle`.
+///
+/// Precedence follows the engine's KeyValues loader:
+/// - `#include` appends the included file's keys after the including file's
+///   own keys.
+/// - `#base` only fills in keys the including file does not define, merging
+///   nested objects key by key. Earlier `#base` files win over later ones.
+pub struct IncludeResolver<L: IncludeLoader> {
+    loader: L,
+    options: ParseOptions,
+    stack: Vec<PathBuf>,
+}
+
+/// One file after its own directives have been resolved
+struct ResolvedFile {
+    ast: DocumentNode,
+    data: KeyValuesObject,
+    sources: Vec<KeySource>,
+}
+
+impl<L: IncludeLoader> IncludeResolver<L> {
+    pub fn new(loader: L) -> Self {
+        Self::with_options(loader, ParseOptions::default())
+    }
+
+    pub fn with_options(loader: L, options: ParseOptions) -> Self {
+        Self {
+            loader,
+            options,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Load the file at `path` through the loader and resolve it
+    pub fn resolve_file(&mut self, path: impl AsRef<Path>) -> Result<ResolvedDocument> {
+        let path = normalize_path(path.as_ref());
+        let content = self.load(&path)?;
+        self.resolve_str(&content, path)
+    }
+
+    /// Resolve already loaded `content` as if it was read from `path`
+    pub fn resolve_str(
+        &mut self,
+        content: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<ResolvedDocument> {
+        let path = normalize_path(path.as_ref());
+        self.stack.clear();
+        let resolved = self.resolve_document(content, &path)?;
+
+        Ok(ResolvedDocument {
+            file: display_path(&path),
+            data: resolved.data,
+            ast: resolved.ast,
+            sources: resolved.sources,
+        })
+    }
+
+    fn load(&mut self, path: &Path) -> Result<String> {
+        self.loader.load(path).map_err(|e| KvError::IncludeLoad {
+            path: display_path(path),
+            message: e.to_string(),
+        })
+    }
+
+    fn resolve_document(&mut self, content: &str, path: &Path) -> Result<ResolvedFile> {
+        let file = display_path(path);
+        let parsed = Parser::parse(content, self.options.clone())?;
+        let mut ast = parsed.ast;
+        let mut data = parsed.data;
+        let mut sources = Vec::new();
+        collect_sources(&data, "", &file, &mut sources);
+
+        self.stack.push(path.to_path_buf());
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let mut includes = Vec::new();
+        let mut bases = Vec::new();
+
+        for child in &mut ast.children {
+            let AstNode::Directive(directive) = child else {
+                continue;
+            };
+
+            let target = normalize_path(&base_dir.join(&directive.path));
+            if self.stack.contains(&target) {
+                let chain = self
+                    .stack
+                    .iter()
+                    .chain(std::iter::once(&target))
+                    .map(|p| display_path(p))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                return Err(KvError::IncludeCycle { chain });
+            }
+
+            let content = self.load(&target)?;
+            let included = self.resolve_document(&content, &target)?;
+            directive.resolved = Some(Box::new(IncludedDocument {
+                file: display_path(&target),
+                ast: included.ast,
+            }));
+
+            match directive.kind {
+                DirectiveKind::Include => includes.push((included.data, included.sources)),
+                DirectiveKind::Base => bases.push((included.data, included.sources)),
+            }
+        }
+        self.stack.pop();
+
+        for (included, included_sources) in includes {
+            for (key, value) in included.entries() {
+                data.append(key.to_string(), value.clone());
+            }
+            sources.extend(included_sources);
+        }
+        for (base, base_sources) in bases {
+            merge_base(&mut data, &base, "", &base_sources, &mut sources);
+        }
+
+        Ok(ResolvedFile { ast, data, sources })
+    }
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+fn collect_sources(obj: &KeyValuesObject, prefix: &str, file: &str, out: &mut Vec<KeySource>) {
+    for (key, value) in obj.entries() {
+        let path = join_path(prefix, key);
+        out.push(KeySource {
+            path: path.clone(),
+            file: file.to_string(),
+        });
+        if let KeyValuesValue::Object(child) = value {
+            collect_sources(child, &path, file, out);
+        }
+    }
+}
+
+/// Fill in keys from `base` that `target` does not define
+fn merge_base(
+    target: &mut KeyValuesObject,
+    base: &KeyValuesObject,
+    prefix: &str,
+    base_sources: &[KeySource],
+    sources: &mut Vec<KeySource>,
+) {
+    let own_keys: HashSet<String> = target.keys().cloned().collect();
+    let mut added = HashSet::new();
+
+    for (key, base_value) in base.entries() {
+        let path = join_path(prefix, key);
+
+        if !own_keys.contains(key) {
+            target.append(key.to_string(), base_value.clone());
+            if added.insert(key) {
+                let nested = format!("{path}.");
+                sources.extend(
+                    base_sources
+                        .iter()
+                        .filter(|source| source.path == path || source.path.starts_with(&nested))
+                        .cloned(),
+                );
+            }
+            continue;
+        }
+
+        if let (Some(KeyValuesValue::Object(own)), KeyValuesValue::Object(base_child)) =
+            (target.get_mut(key), base_value)
+        {
+            merge_base(own, base_child, &path, base_sources, sources);
+        }
+    }
+}
+
+/// Lexically normalize a path so `a/../b.vdf` and `b.vdf` compare equal
+/// without touching the filesystem
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn display_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::Serializer;
+    use std::collections::HashMap;
+
+    fn loader(files: &[(&str, &str)]) -> impl FnMut(&Path) -> io::Result<String> + use<> {
+        let files: HashMap<String, String> = files
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.to_string()))
+            .collect();
+        move |path: &Path| {
+            files
+                .get(&display_path(path))
+                .cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file"))
+        }
+    }
+
+    fn s(value: &str) -> KeyValuesValue {
+        KeyValuesValue::String(value.to_string())
+    }
+
+    #[test]
+    fn test_include_appends_after_own_keys() {
+        let mut resolver = IncludeResolver::new(loader(&[
+            (
+                "scripts/main.vdf",
+                "#include \"extra/more.vdf\"\n\"Weapon\" { \"name\" \"main\" }\n",
+            ),
+            (
+                "scripts/extra/more.vdf",
+                "\"Weapon\" { \"name\" \"more\" }\n",
+            ),
+        ]));
+
+        let resolved = resolver.resolve_file("scripts/main.vdf").unwrap();
+
+        let weapons = resolved.data.get_all("Weapon");
+        assert_eq!(weapons.len(), 2);
+        let KeyValuesValue::Object(first) = weapons[0] else {
+            panic!("expected object");
+        };
+        assert_eq!(first.get("name"), Some(&s("main")));
+    }
+
+    #[test]
+    fn test_base_only_fills_missing_keys() {
+        let mut resolver = IncludeResolver::new(loader(&[
+            (
+                "ui/hud.res",
+                "#base \"hud_base.res\"\n\"Hud\" { \"Health\" { \"xpos\" \"10\" } }\n",
+            ),
+            (
+                "ui/hud_base.res",
+                "\"Hud\" { \"Health\" { \"xpos\" \"0\" \"ypos\" \"5\" } \"Ammo\" { \"xpos\" \"20\" } }\n",
+            ),
+        ]));
+
+        let resolved = resolver.resolve_file("ui/hud.res").unwrap();
+
+        let KeyValuesValue::Object(hud) = &resolved.data["Hud"] else {
+            panic!("expected object");
+        };
+        let KeyValuesValue::Object(health) = &hud["Health"] else {
+            panic!("expected object");
+        };
+        assert_eq!(health.get("xpos"), Some(&s("10")));
+        assert_eq!(health.get("ypos"), Some(&s("5")));
+        assert!(hud.contains_key("Ammo"));
+
+        assert_eq!(resolved.source_of("Hud.Health.xpos"), Some("ui/hud.res"));
+        assert_eq!(
+            resolved.source_of("Hud.Health.ypos"),
+            Some("ui/hud_base.res")
+        );
+        assert_eq!(resolved.source_of("Hud.Ammo.xpos"), Some("ui/hud_base.res"));
+    }
+
+    #[test]
+    fn test_include_cycle_is_reported() {
+        let mut resolver = IncludeResolver::new(loader(&[
+            ("a.vdf", "#include \"sub/b.vdf\"\n\"A\" \"1\"\n"),
+            ("sub/b.vdf", "#base \"../a.vdf\"\n\"B\" \"2\"\n"),
+        ]));
+
+        let err = resolver.resolve_file("a.vdf").unwrap_err();
+
+        match err {
+            KvError::IncludeCycle { chain } => assert_eq!(chain, "a.vdf -> sub/b.vdf -> a.vdf"),
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_missing_include_is_an_error() {
+        let mut resolver = IncludeResolver::new(loader(&[("a.vdf", "#include \"gone.vdf\"\n")]));
+
+        let err = resolver.resolve_file("a.vdf").unwrap_err();
+
+        assert!(matches!(err, KvError::IncludeLoad { path, .. } if path == "gone.vdf"));
+    }
+
+    #[test]
+    fn test_ast_keeps_each_file_for_write_back() {
+        let main = "#base \"base.vdf\"\n\"Root\" { \"own\" \"1\" }\n";
+        let mut resolver = IncludeResolver::new(loader(&[
+            ("cfg/main.vdf", main),
+            ("cfg/base.vdf", "\"Root\" { \"inherited\" \"2\" }\n"),
+        ]));
+
+        let resolved = resolver.resolve_file("cfg/main.vdf").unwrap();
+
+        // The root file still serializes to exactly what was read
+        assert_eq!(Serializer::serialize_ast(&resolved.ast), main);
+
+        let Some(AstNode::Directive(directive)) = resolved.ast.children.first() else {
+            panic!("expected directive");
+        };
+        let included = directive.resolved.as_ref().unwrap();
+        assert_eq!(included.file, "cfg/base.vdf");
+        assert_eq!(
+            Serializer::serialize_ast(&included.ast),
+            "\"Root\" { \"inherited\" \"2\" }\n"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use super::{Kv3Document, Kv3Header, Kv3Value};
+use crate::types::Position;
+
+/// A parsed KV3 text document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kv3Ast {
+    pub header: Kv3Header,
+    pub root: Kv3Node,
+    /// Comments after the root object
+    pub trailing_comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kv3Node {
+    pub start: Position,
+    pub end: Position,
+    /// Comments on the lines before this node, delimiters included
+    pub comments: Vec<String>,
+    /// A comment on the same line after this node
+    pub line_comment: Option<String>,
+    pub value: Kv3NodeValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kv3NodeValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    /// Doubles keep their source text so `1.000000` is written back as is
+    Double {
+        value: f64,
+        raw: String,
+    },
+    String {
+        value: String,
+        /// Written as a `"""` block
+        multiline: bool,
+    },
+    Binary(Vec<u8>),
+    Array {
+        items: Vec<Kv3Node>,
+        /// Comments between the last item and `]`
+        trailing_comments: Vec<String>,
+    },
+    Object {
+        members: Vec<(String, Kv3Node)>,
+        /// Comments between the last member and `}`
+        trailing_comments: Vec<String>,
+    },
+    Flagged {
+        flag: String,
+        value: Box<Kv3Node>,
+    },
+}
+
+impl Kv3Ast {
+    pub fn to_document(&self) -> Kv3Document {
+        Kv3Document {
+            header: self.header.clone(),
+            root: self.root.to_value(),
+        }
+    }
+}
+
+impl Kv3Document {
+    pub fn to_ast(&self) -> Kv3Ast {
+        Kv3Ast {
+            header: self.header.clone(),
+            root: Kv3Node::from_value(&self.root),
+            trailing_comments: Vec::new(),
+        }
+    }
+}
+
+impl Kv3Node {
+    /// A node with no position or comments
+    pub fn new(value: Kv3NodeValue) -> Self {
+        Self {
+            start: Position::default(),
+            end: Position::default(),
+            comments: Vec::new(),
+            line_comment: None,
+            value,
+        }
+    }
+
+    pub fn to_value(&self) -> Kv3Value {
+        match &self.value {
+            Kv3NodeValue::Null => Kv3Value::Null,
+            Kv3NodeValue::Bool(b) => Kv3Value::Bool(*b),
+            Kv3NodeValue::Int(i) => Kv3Value::Int(*i),
+            Kv3NodeValue::UInt(u) => Kv3Value::UInt(*u),
+            Kv3NodeValue::Double { value, .. } => Kv3Value::Double(*value),
+            Kv3NodeValue::String { value, .. } => Kv3Value::String(value.clone()),
+            Kv3NodeValue::Binary(bytes) => Kv3Value::Binary(bytes.clone()),
+            Kv3NodeValue::Array { items, .. } => {
+                Kv3Value::Array(items.iter().map(Kv3Node::to_value).collect())
+            }
+            Kv3NodeValue::Object { members, .. } => Kv3Value::Object(
+                members
+                    .iter()
+                    .map(|(key, node)| (key.clone(), node.to_value()))
+                    .collect(),
+            ),
+            Kv3NodeValue::Flagged { flag, value } => Kv3Value::Flagged {
+                flag: flag.clone(),
+                value: Box::new(value.to_value()),
+            },
+        }
+    }
+
+    pub fn from_value(value: &Kv3Value) -> Self {
+        let value = match value {
+            Kv3Value::Null => Kv3NodeValue::Null,
+            Kv3Value::Bool(b) => Kv3NodeValue::Bool(*b),
+            Kv3Value::Int(i) => Kv3NodeValue::Int(*i),
+            Kv3Value::UInt(u) => Kv3NodeValue::UInt(*u),
+            // KV3 has no literal for NaN
+            Kv3Value::Double(d) if d.is_nan() => Kv3NodeValue::Null,
+            Kv3Value::Double(d) => Kv3NodeValue::Double {
+                value: *d,
+                raw: format_double(*d),
+            },
+            Kv3Value::String(s) => Kv3NodeValue::String {
+                value: s.clone(),
+                multiline: s.contains('\n') && !s.contains("\"\"\""),
+            },
+            Kv3Value::Binary(bytes) => Kv3NodeValue::Binary(bytes.clone()),
+            Kv3Value::Array(items) => Kv3NodeValue::Array {
+                items: items.iter().map(Kv3Node::from_value).collect(),
+                trailing_comments: Vec::new(),
+            },
+            Kv3Value::Object(pairs) => Kv3NodeValue::Object {
+                members: pairs
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Kv3Node::from_value(value)))
+                    .collect(),
+                trailing_comments: Vec::new(),
+            },
+            Kv3Value::Flagged { flag, value } => Kv3NodeValue::Flagged {
+                flag: flag.clone(),
+                value: Box::new(Kv3Node::from_value(value)),
+            },
+        };
+        Self::new(value)
+    }
+}
+
+/// Valve's six decimal places when they are exact, the shortest
+/// round-tripping form otherwise
+///
+/// Infinities are written as an out-of-range exponent, which parses back to
+/// the same infinity.
+fn format_double(value: f64) -> String {
+    if value.is_infinite() {
+        return if value > 0.0 { "1e999" } else { "-1e999" }.to_string();
+    }
+    let fixed = format!("{value:.6}");
+    if fixed.parse::<f64>() == Ok(value) {
+        fixed
+    } else {
+        format!("{value:?}")
+    }
+}
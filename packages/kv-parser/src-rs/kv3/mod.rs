@@ -0,0 +1,402 @@
+//! KeyValues3 text, the source form of Source 2 assets (`.vdata`, `.vpcf`,
+//! `.vsndevts`, ...).
+//!
+//! A document is a `<!-- kv3 ... -->` header followed by a root object.
+//! `Kv3Parser` reads text into a `Kv3Ast` that keeps comments and the exact
+//! spelling of doubles, `Kv3Serializer` writes one back, and `Kv3Document`
+//! is the plain data view that maps onto binary KV3 values.
+
+mod ast;
+mod parser;
+mod serializer;
+mod tokenizer;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::error::Result;
+
+pub use ast::{Kv3Ast, Kv3Node, Kv3NodeValue};
+pub use parser::Kv3Parser;
+pub use serializer::Kv3Serializer;
+
+/// Encoding GUID Valve's tools write for KV3 text
+pub const KV3_ENCODING_TEXT: &str = "e21c7f3c-8a33-41c5-9977-a76d3a32aa0d";
+/// Format GUID of schema-less ("generic") KV3
+pub const KV3_FORMAT_GENERIC: &str = "7412167c-06e9-4698-aff2-e63eb59037e7";
+
+/// One `name:version{guid}` part of the header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct Kv3Format {
+    pub name: String,
+    pub version: String,
+}
+
+/// `<!-- kv3 encoding:text:version{...} format:generic:version{...} -->`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct Kv3Header {
+    pub encoding: Kv3Format,
+    pub format: Kv3Format,
+}
+
+impl Default for Kv3Header {
+    fn default() -> Self {
+        Self {
+            encoding: Kv3Format {
+                name: "text".to_string(),
+                version: KV3_ENCODING_TEXT.to_string(),
+            },
+            format: Kv3Format {
+                name: "generic".to_string(),
+                version: KV3_FORMAT_GENERIC.to_string(),
+            },
+        }
+    }
+}
+
+impl Kv3Header {
+    /// Parse the inside of the header comment, e.g.
+    /// `kv3 encoding:text:version{...} format:generic:version{...}`
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.split_whitespace();
+        if parts.next()? != "kv3" {
+            return None;
+        }
+
+        let mut encoding = None;
+        let mut format = None;
+        for part in parts {
+            let (field, rest) = part.split_once(':')?;
+            let (name, version) = rest.split_once(":version{")?;
+            let parsed = Kv3Format {
+                name: name.to_string(),
+                version: version.strip_suffix('}')?.to_string(),
+            };
+            match field {
+                "encoding" => encoding = Some(parsed),
+                "format" => format = Some(parsed),
+                _ => return None,
+            }
+        }
+
+        Some(Self {
+            encoding: encoding?,
+            format: format?,
+        })
+    }
+}
+
+impl fmt::Display for Kv3Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<!-- kv3 encoding:{}:version{{{}}} format:{}:version{{{}}} -->",
+            self.encoding.name, self.encoding.version, self.format.name, self.format.version
+        )
+    }
+}
+
+/// A KV3 value. Mirrors the binary KV3 value tree, plus the flags
+/// (`resource:`, `soundevent:`, `subclass:`, ...) text KV3 can attach to a
+/// value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/")]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum Kv3Value {
+    Null,
+    Bool(bool),
+    Int(#[ts(type = "number")] i64),
+    /// Text KV3 has no unsigned syntax, so only values above `i64::MAX` read
+    /// back from text as `UInt`; smaller ones come back as `Int`
+    UInt(#[ts(type = "number")] u64),
+    Double(f64),
+    String(String),
+    Binary(
+        #[serde(with = "crate::types::base64_bytes")]
+        #[ts(type = "string")]
+        Vec<u8>,
+    ),
+    Array(Vec<Kv3Value>),
+    /// Insertion-ordered key/value pairs
+    Object(Vec<(String, Kv3Value)>),
+    Flagged {
+        flag: String,
+        value: Box<Kv3Value>,
+    },
+}
+
+impl Kv3Value {
+    /// First child with `key`, looking through flags
+    pub fn get(&self, key: &str) -> Option<&Kv3Value> {
+        match self.unflagged() {
+            Self::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Kv3Value> {
+        match self {
+            Self::Object(pairs) => pairs.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+            Self::Flagged { value, .. } => value.get_mut(key),
+            _ => None,
+        }
+    }
+
+    /// The value with any flag removed
+    pub fn unflagged(&self) -> &Kv3Value {
+        match self {
+            Self::Flagged { value, .. } => value.unflagged(),
+            other => other,
+        }
+    }
+
+    pub fn flag(&self) -> Option<&str> {
+        match self {
+            Self::Flagged { flag, .. } => Some(flag),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.unflagged() {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self.unflagged() {
+            Self::Double(d) => Some(d),
+            Self::Int(i) => Some(i as f64),
+            Self::UInt(u) => Some(u as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Kv3Value]> {
+        match self.unflagged() {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// A KV3 text document as plain data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct Kv3Document {
+    pub header: Kv3Header,
+    pub root: Kv3Value,
+}
+
+impl Kv3Document {
+    pub fn new(root: Kv3Value) -> Self {
+        Self {
+            header: Kv3Header::default(),
+            root,
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Self> {
+        Ok(Kv3Parser::parse(input)?.to_document())
+    }
+
+    pub fn to_text(&self) -> String {
+        Kv3Serializer::serialize(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VDATA: &str = r#"<!-- kv3 encoding:text:version{e21c7f3c-8a33-41c5-9977-a76d3a32aa0d} format:generic:version{7412167c-06e9-4698-aff2-e63eb59037e7} -->
+{
+	// Hero card
+	hero_astro =
+	{
+		_class = "CCitadelHeroData"
+		m_bDisabled = false
+		m_flScale = 1.150000
+		m_nHeroID = 47
+		m_strIcon = resource:"panorama/images/heroes/astro_card_psd.vtex"
+		m_strSelect = soundevent:"Astro.Select"
+		m_vecOffset = [ 0.0, -2.5, 10.0 ]
+		m_Abilities =
+		[
+			"ability_astro_01",
+			"ability_astro_02", // trailing comma is fine
+		]
+		"quoted key" = null
+		m_Blob = #[ 00 7F ff ]
+		m_Lore = """
+Line one
+Line "two"
+"""
+		m_Sub = subclass:
+		{
+			_class = "CAbilityData"
+		}
+	}
+}
+"#;
+
+    #[test]
+    fn test_parse_vdata() {
+        let document = Kv3Document::parse(VDATA).unwrap();
+        let hero = document.root.get("hero_astro").unwrap();
+
+        assert_eq!(document.header, Kv3Header::default());
+        assert_eq!(
+            hero.get("_class").unwrap().as_str(),
+            Some("CCitadelHeroData")
+        );
+        assert_eq!(hero.get("m_bDisabled"), Some(&Kv3Value::Bool(false)));
+        assert_eq!(hero.get("m_nHeroID"), Some(&Kv3Value::Int(47)));
+        assert_eq!(hero.get("m_flScale").unwrap().as_f64(), Some(1.15));
+
+        let icon = hero.get("m_strIcon").unwrap();
+        assert_eq!(icon.flag(), Some("resource"));
+        assert_eq!(
+            icon.as_str(),
+            Some("panorama/images/heroes/astro_card_psd.vtex")
+        );
+        assert_eq!(hero.get("m_strSelect").unwrap().flag(), Some("soundevent"));
+
+        let offset = hero.get("m_vecOffset").unwrap().as_array().unwrap();
+        assert_eq!(offset[1], Kv3Value::Double(-2.5));
+        assert_eq!(
+            hero.get("m_Abilities").unwrap().as_array().unwrap().len(),
+            2
+        );
+        assert_eq!(hero.get("quoted key"), Some(&Kv3Value::Null));
+        assert_eq!(
+            hero.get("m_Blob"),
+            Some(&Kv3Value::Binary(vec![0, 0x7f, 0xff]))
+        );
+        assert_eq!(
+            hero.get("m_Lore").unwrap().as_str(),
+            Some("Line one\nLine \"two\"")
+        );
+        assert_eq!(
+            hero.get("m_Sub").unwrap().get("_class").unwrap().as_str(),
+            Some("CAbilityData")
+        );
+    }
+
+    #[test]
+    fn test_ast_round_trip_keeps_comments_and_doubles() {
+        let ast = Kv3Parser::parse(VDATA).unwrap();
+        let text = Kv3Serializer::serialize_ast(&ast);
+
+        assert!(text.contains("{\n\t// Hero card\n\thero_astro =\n"));
+        assert!(text.contains("m_flScale = 1.150000\n"));
+        assert!(text.contains("m_vecOffset = [ 0.0, -2.5, 10.0 ]\n"));
+        assert!(text.contains("\"ability_astro_02\", // trailing comma is fine\n"));
+        assert_eq!(
+            Kv3Serializer::serialize_ast(&Kv3Parser::parse(&text).unwrap()),
+            text
+        );
+        assert_eq!(
+            Kv3Document::parse(&text).unwrap(),
+            Kv3Document::parse(VDATA).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_document_round_trip() {
+        let document = Kv3Document::new(Kv3Value::Object(vec![
+            ("big".to_string(), Kv3Value::UInt(u64::MAX)),
+            ("negative".to_string(), Kv3Value::Int(-3)),
+            ("third".to_string(), Kv3Value::Double(1.0 / 3.0)),
+            (
+                "text".to_string(),
+                Kv3Value::String("a\tb \"c\"".to_string()),
+            ),
+            ("lines".to_string(), Kv3Value::String("a\nb".to_string())),
+            ("empty".to_string(), Kv3Value::Array(Vec::new())),
+            ("blob".to_string(), Kv3Value::Binary(vec![1, 2, 3])),
+            (
+                "nested".to_string(),
+                Kv3Value::Array(vec![Kv3Value::Object(vec![(
+                    "m_Model".to_string(),
+                    Kv3Value::Flagged {
+                        flag: "resource_name".to_string(),
+                        value: Box::new(Kv3Value::String("models/a.vmdl".to_string())),
+                    },
+                )])]),
+            ),
+        ]));
+
+        let text = document.to_text();
+
+        assert!(text.starts_with("<!-- kv3 encoding:text:version{"));
+        assert_eq!(Kv3Document::parse(&text).unwrap(), document);
+    }
+
+    #[test]
+    fn test_non_finite_doubles() {
+        let document = Kv3Document::new(Kv3Value::Object(vec![
+            ("inf".to_string(), Kv3Value::Double(f64::INFINITY)),
+            ("neg_inf".to_string(), Kv3Value::Double(f64::NEG_INFINITY)),
+            ("nan".to_string(), Kv3Value::Double(f64::NAN)),
+        ]));
+
+        let parsed = Kv3Document::parse(&document.to_text()).unwrap();
+
+        assert_eq!(
+            parsed.root.get("inf"),
+            Some(&Kv3Value::Double(f64::INFINITY))
+        );
+        assert_eq!(
+            parsed.root.get("neg_inf"),
+            Some(&Kv3Value::Double(f64::NEG_INFINITY))
+        );
+        assert_eq!(parsed.root.get("nan"), Some(&Kv3Value::Null));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let header = Kv3Header::default().to_string();
+
+        assert!(
+            Kv3Document::parse("{ a = 1 }").is_err(),
+            "header is required"
+        );
+        assert!(Kv3Document::parse(&format!("{header}\n{{ a = }}")).is_err());
+        assert!(Kv3Document::parse(&format!("{header}\n{{ a = [ 1 2 ] }}")).is_err());
+        assert!(Kv3Document::parse(&format!("{header}\n{{ a = #[ 0g ] }}")).is_err());
+        assert!(Kv3Document::parse(&format!("{header}\n{{ a = 1 }} b")).is_err());
+    }
+
+    #[test]
+    fn test_small_unsigned_integers_read_back_as_int() {
+        let document = Kv3Document::new(Kv3Value::Object(vec![
+            ("small".to_string(), Kv3Value::UInt(5)),
+            ("big".to_string(), Kv3Value::UInt(u64::MAX)),
+        ]));
+
+        let parsed = Kv3Document::parse(&document.to_text()).unwrap();
+
+        assert_eq!(parsed.root.get("small"), Some(&Kv3Value::Int(5)));
+        assert_eq!(parsed.root.get("big"), Some(&Kv3Value::UInt(u64::MAX)));
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        let header = Kv3Header::default().to_string();
+        let nested =
+            |depth: usize| format!("{header}\n{}1{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert!(Kv3Document::parse(&nested(200)).is_ok());
+        let error = Kv3Document::parse(&nested(100_000)).unwrap_err();
+        assert!(error.to_string().contains("nested"), "{error}");
+    }
+}
@@ -0,0 +1,249 @@
+use super::Kv3Header;
+use super::ast::{Kv3Ast, Kv3Node, Kv3NodeValue};
+use super::tokenizer::{Kv3Token, Kv3TokenKind, Kv3Tokenizer};
+use crate::error::{KvError, Result};
+
+/// How deeply objects, arrays and flags may nest before parsing fails, so a
+/// crafted file cannot exhaust the stack
+const MAX_DEPTH: usize = 256;
+
+pub struct Kv3Parser {
+    tokens: Vec<Kv3Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Kv3Parser {
+    pub fn parse(input: &str) -> Result<Kv3Ast> {
+        let tokens = Kv3Tokenizer::new(input).tokenize()?;
+        let mut parser = Self {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        parser.parse_document()
+    }
+
+    fn current(&self) -> &Kv3Token {
+        &self.tokens[self.pos.min(self.tokens.len() - 1)]
+    }
+
+    fn advance(&mut self) -> Kv3Token {
+        let token = self.current().clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, token: &Kv3Token, message: &str) -> KvError {
+        KvError::ParseError {
+            line: token.start.line,
+            column: token.start.column,
+            message: message.to_string(),
+        }
+    }
+
+    fn unexpected(&self, token: &Kv3Token, expected: &str) -> KvError {
+        let found = match &token.kind {
+            Kv3TokenKind::Eof => "end of input".to_string(),
+            Kv3TokenKind::Identifier(name) => format!("'{name}'"),
+            Kv3TokenKind::Number(raw) => format!("'{raw}'"),
+            Kv3TokenKind::String(_) | Kv3TokenKind::MultilineString(_) => "a string".to_string(),
+            other => format!("{other:?}"),
+        };
+        self.error(token, &format!("Expected {expected}, found {found}"))
+    }
+
+    fn take_comments(&mut self) -> Vec<String> {
+        let mut comments = Vec::new();
+        while let Kv3TokenKind::Comment(comment) = &self.current().kind {
+            comments.push(comment.clone());
+            self.advance();
+        }
+        comments
+    }
+
+    /// A comment that starts on the line `node` ends on belongs to it
+    fn take_line_comment(&mut self, node: &mut Kv3Node) {
+        if let Kv3TokenKind::Comment(comment) = &self.current().kind
+            && self.current().start.line == node.end.line
+        {
+            node.line_comment = Some(comment.clone());
+            self.advance();
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Kv3Ast> {
+        let header_token = self.advance();
+        let header = match &header_token.kind {
+            Kv3TokenKind::Header(text) => Kv3Header::parse(text)
+                .ok_or_else(|| self.error(&header_token, "Invalid KV3 header"))?,
+            _ => return Err(self.error(&header_token, "Missing <!-- kv3 ... --> header")),
+        };
+
+        let comments = self.take_comments();
+        let mut root = self.parse_value()?;
+        root.comments = comments;
+        let trailing_comments = self.take_comments();
+
+        if self.current().kind != Kv3TokenKind::Eof {
+            return Err(self.unexpected(self.current(), "end of input"));
+        }
+
+        Ok(Kv3Ast {
+            header,
+            root,
+            trailing_comments,
+        })
+    }
+
+    fn parse_value(&mut self) -> Result<Kv3Node> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(
+                self.current(),
+                &format!("Values nested more than {MAX_DEPTH} deep"),
+            ));
+        }
+        self.depth += 1;
+        let node = self.parse_nested_value();
+        self.depth -= 1;
+        node
+    }
+
+    fn parse_nested_value(&mut self) -> Result<Kv3Node> {
+        let token = self.advance();
+        let value = match token.kind {
+            Kv3TokenKind::OpenBrace => return self.parse_object(token),
+            Kv3TokenKind::OpenBracket => return self.parse_array(token),
+            Kv3TokenKind::Identifier(name) if self.current().kind == Kv3TokenKind::Colon => {
+                self.advance();
+                let mut node = Kv3Node::new(Kv3NodeValue::Flagged {
+                    flag: name,
+                    value: Box::new(self.parse_value()?),
+                });
+                if let Kv3NodeValue::Flagged { value, .. } = &node.value {
+                    node.end = value.end;
+                }
+                node.start = token.start;
+                return Ok(node);
+            }
+            Kv3TokenKind::Identifier(ref name) => match name.as_str() {
+                "null" => Kv3NodeValue::Null,
+                "true" => Kv3NodeValue::Bool(true),
+                "false" => Kv3NodeValue::Bool(false),
+                _ => return Err(self.error(&token, &format!("Unknown value '{name}'"))),
+            },
+            Kv3TokenKind::Number(ref raw) => self.parse_number(&token, raw)?,
+            Kv3TokenKind::String(value) => Kv3NodeValue::String {
+                value,
+                multiline: false,
+            },
+            Kv3TokenKind::MultilineString(value) => Kv3NodeValue::String {
+                value,
+                multiline: true,
+            },
+            Kv3TokenKind::Blob(bytes) => Kv3NodeValue::Binary(bytes),
+            _ => return Err(self.unexpected(&token, "a value")),
+        };
+
+        let mut node = Kv3Node::new(value);
+        node.start = token.start;
+        node.end = token.end;
+        Ok(node)
+    }
+
+    fn parse_number(&self, token: &Kv3Token, raw: &str) -> Result<Kv3NodeValue> {
+        let invalid = || self.error(token, &format!("Invalid number '{raw}'"));
+        if raw.contains(['.', 'e', 'E']) {
+            let value = raw.parse::<f64>().map_err(|_| invalid())?;
+            return Ok(Kv3NodeValue::Double {
+                value,
+                raw: raw.to_string(),
+            });
+        }
+
+        if let Ok(value) = raw.parse::<i64>() {
+            Ok(Kv3NodeValue::Int(value))
+        } else {
+            raw.parse::<u64>()
+                .map(Kv3NodeValue::UInt)
+                .map_err(|_| invalid())
+        }
+    }
+
+    fn parse_object(&mut self, open: Kv3Token) -> Result<Kv3Node> {
+        let mut members = Vec::new();
+        loop {
+            let comments = self.take_comments();
+            let token = self.advance();
+            let key = match token.kind {
+                Kv3TokenKind::CloseBrace => {
+                    let mut node = Kv3Node::new(Kv3NodeValue::Object {
+                        members,
+                        trailing_comments: comments,
+                    });
+                    node.start = open.start;
+                    node.end = token.end;
+                    return Ok(node);
+                }
+                Kv3TokenKind::Identifier(key) | Kv3TokenKind::String(key) => key,
+                _ => return Err(self.unexpected(&token, "a key or '}'")),
+            };
+
+            if self.current().kind != Kv3TokenKind::Equals {
+                return Err(self.unexpected(self.current(), "'='"));
+            }
+            self.advance();
+
+            let mut value = self.parse_value()?;
+            value.comments = comments;
+            self.take_line_comment(&mut value);
+            members.push((key, value));
+        }
+    }
+
+    fn parse_array(&mut self, open: Kv3Token) -> Result<Kv3Node> {
+        let mut items = Vec::new();
+        loop {
+            let comments = self.take_comments();
+            if self.current().kind == Kv3TokenKind::CloseBracket {
+                return Ok(self.close_array(open, items, comments));
+            }
+
+            let mut item = self.parse_value()?;
+            item.comments = comments;
+            let has_comma = self.current().kind == Kv3TokenKind::Comma;
+            if has_comma {
+                self.advance();
+            }
+            self.take_line_comment(&mut item);
+            items.push(item);
+
+            // Only the last item may go without a comma
+            if !has_comma {
+                let trailing_comments = self.take_comments();
+                if self.current().kind != Kv3TokenKind::CloseBracket {
+                    return Err(self.unexpected(self.current(), "',' or ']'"));
+                }
+                return Ok(self.close_array(open, items, trailing_comments));
+            }
+        }
+    }
+
+    fn close_array(
+        &mut self,
+        open: Kv3Token,
+        items: Vec<Kv3Node>,
+        trailing_comments: Vec<String>,
+    ) -> Kv3Node {
+        let close = self.advance();
+        let mut node = Kv3Node::new(Kv3NodeValue::Array {
+            items,
+            trailing_comments,
+        });
+        node.start = open.start;
+        node.end = close.end;
+        node
+    }
+}
@@ -0,0 +1,216 @@
+use super::Kv3Document;
+use super::ast::{Kv3Ast, Kv3Node, Kv3NodeValue};
+
+/// Writes KV3 text the way Valve's tools lay it out: tab indentation, nested
+/// objects and arrays opening on the line after their key, short numeric
+/// arrays (vectors, colors) on one line
+pub struct Kv3Serializer;
+
+/// Longest numeric array that is written on a single line
+const MAX_INLINE_ARRAY: usize = 4;
+
+impl Kv3Serializer {
+    pub fn serialize(document: &Kv3Document) -> String {
+        Self::serialize_ast(&document.to_ast())
+    }
+
+    pub fn serialize_ast(ast: &Kv3Ast) -> String {
+        let mut out = ast.header.to_string();
+        out.push('\n');
+        for comment in &ast.root.comments {
+            out.push_str(comment);
+            out.push('\n');
+        }
+        Self::write_value(&mut out, &ast.root, 0);
+        Self::write_line_comment(&mut out, &ast.root);
+        out.push('\n');
+        for comment in &ast.trailing_comments {
+            out.push_str(comment);
+            out.push('\n');
+        }
+        out
+    }
+
+    fn indent(out: &mut String, depth: usize) {
+        out.extend(std::iter::repeat_n('\t', depth));
+    }
+
+    fn write_comments(out: &mut String, comments: &[String], depth: usize) {
+        for comment in comments {
+            Self::indent(out, depth);
+            out.push_str(comment);
+            out.push('\n');
+        }
+    }
+
+    fn write_line_comment(out: &mut String, node: &Kv3Node) {
+        if let Some(comment) = &node.line_comment {
+            out.push(' ');
+            out.push_str(comment);
+        }
+    }
+
+    /// Objects and arrays that are laid out over several lines
+    fn is_block(node: &Kv3Node) -> bool {
+        match &node.value {
+            Kv3NodeValue::Object {
+                members,
+                trailing_comments,
+            } => !members.is_empty() || !trailing_comments.is_empty(),
+            Kv3NodeValue::Array { .. } => !Self::is_inline_array(node),
+            Kv3NodeValue::Flagged { value, .. } => Self::is_block(value),
+            _ => false,
+        }
+    }
+
+    fn is_inline_array(node: &Kv3Node) -> bool {
+        let Kv3NodeValue::Array {
+            items,
+            trailing_comments,
+        } = &node.value
+        else {
+            return false;
+        };
+        trailing_comments.is_empty()
+            && items.len() <= MAX_INLINE_ARRAY
+            && items.iter().all(|item| {
+                item.comments.is_empty()
+                    && item.line_comment.is_none()
+                    && matches!(
+                        item.value,
+                        Kv3NodeValue::Int(_)
+                            | Kv3NodeValue::UInt(_)
+                            | Kv3NodeValue::Double { .. }
+                            | Kv3NodeValue::Bool(_)
+                    )
+            })
+    }
+
+    /// Write `node` starting at the current position, with nested lines
+    /// indented to `depth`
+    fn write_value(out: &mut String, node: &Kv3Node, depth: usize) {
+        match &node.value {
+            Kv3NodeValue::Null => out.push_str("null"),
+            Kv3NodeValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Kv3NodeValue::Int(i) => out.push_str(&i.to_string()),
+            Kv3NodeValue::UInt(u) => out.push_str(&u.to_string()),
+            Kv3NodeValue::Double { raw, .. } => out.push_str(raw),
+            Kv3NodeValue::String { value, multiline } => {
+                if *multiline && !value.contains("\"\"\"") {
+                    out.push_str("\"\"\"\n");
+                    out.push_str(value);
+                    out.push_str("\n\"\"\"");
+                } else {
+                    Self::write_string(out, value);
+                }
+            }
+            Kv3NodeValue::Binary(bytes) => {
+                out.push_str("#[");
+                for byte in bytes {
+                    out.push_str(&format!(" {byte:02X}"));
+                }
+                out.push_str(if bytes.is_empty() { "]" } else { " ]" });
+            }
+            Kv3NodeValue::Flagged { flag, value } => {
+                out.push_str(flag);
+                out.push(':');
+                if Self::is_block(value) {
+                    out.push('\n');
+                    Self::indent(out, depth);
+                }
+                Self::write_value(out, value, depth);
+            }
+            Kv3NodeValue::Array { items, .. } if Self::is_inline_array(node) => {
+                if items.is_empty() {
+                    out.push_str("[]");
+                    return;
+                }
+                out.push_str("[ ");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    Self::write_value(out, item, depth);
+                }
+                out.push_str(" ]");
+            }
+            Kv3NodeValue::Array {
+                items,
+                trailing_comments,
+            } => {
+                out.push_str("[\n");
+                for item in items {
+                    Self::write_comments(out, &item.comments, depth + 1);
+                    Self::indent(out, depth + 1);
+                    Self::write_value(out, item, depth + 1);
+                    out.push(',');
+                    Self::write_line_comment(out, item);
+                    out.push('\n');
+                }
+                Self::write_comments(out, trailing_comments, depth + 1);
+                Self::indent(out, depth);
+                out.push(']');
+            }
+            Kv3NodeValue::Object {
+                members,
+                trailing_comments,
+            } => {
+                if !Self::is_block(node) {
+                    out.push_str("{}");
+                    return;
+                }
+                out.push_str("{\n");
+                for (key, value) in members {
+                    Self::write_comments(out, &value.comments, depth + 1);
+                    Self::indent(out, depth + 1);
+                    Self::write_key(out, key);
+                    if Self::is_block(value) && value.value.is_container() {
+                        out.push_str(" =\n");
+                        Self::indent(out, depth + 1);
+                    } else {
+                        out.push_str(" = ");
+                    }
+                    Self::write_value(out, value, depth + 1);
+                    Self::write_line_comment(out, value);
+                    out.push('\n');
+                }
+                Self::write_comments(out, trailing_comments, depth + 1);
+                Self::indent(out, depth);
+                out.push('}');
+            }
+        }
+    }
+
+    fn write_key(out: &mut String, key: &str) {
+        let mut chars = key.chars();
+        let is_identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+            && !matches!(key, "null" | "true" | "false");
+        if is_identifier {
+            out.push_str(key);
+        } else {
+            Self::write_string(out, key);
+        }
+    }
+
+    fn write_string(out: &mut String, value: &str) {
+        out.push('"');
+        for ch in value.chars() {
+            match ch {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                '\r' => out.push_str("\\r"),
+                _ => out.push(ch),
+            }
+        }
+        out.push('"');
+    }
+}
+
+impl Kv3NodeValue {
+    fn is_container(&self) -> bool {
+        matches!(self, Self::Object { .. } | Self::Array { .. })
+    }
+}
@@ -0,0 +1,284 @@
+use crate::error::{KvError, Result};
+use crate::types::Position;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Kv3TokenKind {
+    /// `<!-- ... -->`, with the delimiters stripped
+    Header(String),
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Equals,
+    Comma,
+    Colon,
+    Identifier(String),
+    /// Numbers are validated by the parser, which knows whether it wants an
+    /// integer or a double
+    Number(String),
+    String(String),
+    MultilineString(String),
+    Blob(Vec<u8>),
+    /// A comment including its `//` or `/* */` delimiters
+    Comment(String),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Kv3Token {
+    pub kind: Kv3TokenKind,
+    pub start: Position,
+    pub end: Position,
+}
+
+pub(crate) struct Kv3Tokenizer {
+    input: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Kv3Tokenizer {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.chars().collect(),
+            pos: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn current_char(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, ch)| self.peek(i) == Some(ch))
+    }
+
+    fn advance(&mut self) {
+        if let Some(ch) = self.current_char() {
+            if ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn advance_by(&mut self, n: usize) {
+        for _ in 0..n {
+            self.advance();
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            offset: self.pos,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn error(&self, start: Position, message: &str) -> KvError {
+        KvError::ParseError {
+            line: start.line,
+            column: start.column,
+            message: message.to_string(),
+        }
+    }
+
+    /// Consume everything up to and including `terminator`
+    fn read_until(&mut self, terminator: &str, start: Position, what: &str) -> Result<String> {
+        let mut value = String::new();
+        loop {
+            if self.starts_with(terminator) {
+                self.advance_by(terminator.chars().count());
+                return Ok(value);
+            }
+            let Some(ch) = self.current_char() else {
+                return Err(self.error(start, &format!("Unterminated {what}")));
+            };
+            value.push(ch);
+            self.advance();
+        }
+    }
+
+    fn read_string(&mut self, start: Position) -> Result<Kv3TokenKind> {
+        self.advance(); // Skip opening quote
+        let mut value = String::new();
+        loop {
+            match self.current_char() {
+                None | Some('\n') => return Err(self.error(start, "Unterminated string")),
+                Some('"') => {
+                    self.advance();
+                    return Ok(Kv3TokenKind::String(value));
+                }
+                Some('\\') => {
+                    self.advance();
+                    let escaped = match self.current_char() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some(other) => {
+                            // Unknown escapes are kept as written
+                            value.push('\\');
+                            other
+                        }
+                        None => return Err(self.error(start, "Unterminated string")),
+                    };
+                    value.push(escaped);
+                    self.advance();
+                }
+                Some(ch) => {
+                    value.push(ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// `"""` strings run verbatim up to the next `"""`. The line breaks
+    /// right after the opening and before the closing quotes are not part
+    /// of the value.
+    fn read_multiline_string(&mut self, start: Position) -> Result<Kv3TokenKind> {
+        self.advance_by(3);
+        let raw = self.read_until("\"\"\"", start, "multi-line string")?;
+        let value = raw
+            .strip_prefix("\r\n")
+            .or_else(|| raw.strip_prefix('\n'))
+            .unwrap_or(&raw);
+        let value = value
+            .strip_suffix("\r\n")
+            .or_else(|| value.strip_suffix('\n'))
+            .unwrap_or(value);
+        Ok(Kv3TokenKind::MultilineString(value.to_string()))
+    }
+
+    fn read_blob(&mut self, start: Position) -> Result<Kv3TokenKind> {
+        self.advance_by(2); // Skip #[
+        let mut bytes = Vec::new();
+        loop {
+            match self.current_char() {
+                Some(']') => {
+                    self.advance();
+                    return Ok(Kv3TokenKind::Blob(bytes));
+                }
+                Some(ch) if ch.is_whitespace() => self.advance(),
+                Some(hi) => {
+                    let byte = self
+                        .peek(1)
+                        .and_then(|lo| u8::from_str_radix(&format!("{hi}{lo}"), 16).ok())
+                        .ok_or_else(|| {
+                            self.error(self.position(), "Invalid byte in binary blob")
+                        })?;
+                    bytes.push(byte);
+                    self.advance_by(2);
+                }
+                None => return Err(self.error(start, "Unterminated binary blob")),
+            }
+        }
+    }
+
+    fn read_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let mut value = String::new();
+        while let Some(ch) = self.current_char().filter(|&ch| accept(ch)) {
+            value.push(ch);
+            self.advance();
+        }
+        value
+    }
+
+    fn is_number_start(&self, ch: char) -> bool {
+        let next_is_digit = |offset| self.peek(offset).is_some_and(|c: char| c.is_ascii_digit());
+        ch.is_ascii_digit()
+            || (ch == '.' && next_is_digit(1))
+            || ((ch == '-' || ch == '+')
+                && (next_is_digit(1) || (self.peek(1) == Some('.') && next_is_digit(2))))
+    }
+
+    pub fn next_token(&mut self) -> Result<Kv3Token> {
+        while self.current_char().is_some_and(char::is_whitespace) {
+            self.advance();
+        }
+
+        let start = self.position();
+        let Some(ch) = self.current_char() else {
+            return Ok(Kv3Token {
+                kind: Kv3TokenKind::Eof,
+                start,
+                end: start,
+            });
+        };
+
+        let single = match ch {
+            '{' => Some(Kv3TokenKind::OpenBrace),
+            '}' => Some(Kv3TokenKind::CloseBrace),
+            '[' => Some(Kv3TokenKind::OpenBracket),
+            ']' => Some(Kv3TokenKind::CloseBracket),
+            '=' => Some(Kv3TokenKind::Equals),
+            ',' => Some(Kv3TokenKind::Comma),
+            ':' => Some(Kv3TokenKind::Colon),
+            _ => None,
+        };
+
+        let kind = if let Some(kind) = single {
+            self.advance();
+            kind
+        } else if self.starts_with("<!--") {
+            self.advance_by(4);
+            let header = self.read_until("-->", start, "header")?;
+            Kv3TokenKind::Header(header.trim().to_string())
+        } else if self.starts_with("//") {
+            Kv3TokenKind::Comment(self.read_while(|c| c != '\n').trim_end().to_string())
+        } else if self.starts_with("/*") {
+            self.advance_by(2);
+            let body = self.read_until("*/", start, "comment")?;
+            Kv3TokenKind::Comment(format!("/*{body}*/"))
+        } else if self.starts_with("\"\"\"") {
+            self.read_multiline_string(start)?
+        } else if ch == '"' {
+            self.read_string(start)?
+        } else if self.starts_with("#[") {
+            self.read_blob(start)?
+        } else if self.is_number_start(ch) {
+            Kv3TokenKind::Number(
+                self.read_while(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')),
+            )
+        } else if ch.is_alphabetic() || ch == '_' {
+            Kv3TokenKind::Identifier(
+                self.read_while(|c| c.is_alphanumeric() || matches!(c, '_' | '.')),
+            )
+        } else {
+            return Err(self.error(start, &format!("Unexpected character '{ch}'")));
+        };
+
+        Ok(Kv3Token {
+            kind,
+            start,
+            end: self.position(),
+        })
+    }
+
+    pub fn tokenize(&mut self) -> Result<Vec<Kv3Token>> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            let is_eof = token.kind == Kv3TokenKind::Eof;
+            tokens.push(token);
+            if is_eof {
+                return Ok(tokens);
+            }
+        }
+    }
+}
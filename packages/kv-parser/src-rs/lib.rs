@@ -1,8 +1,19 @@
+mod appinfo;
 mod ast;
+mod binary;
+mod conditional;
+mod de;
 mod diff;
 mod document;
 mod error;
+mod formatter;
+mod include;
+mod kv3;
+mod merge;
+mod object;
 mod parser;
+mod query;
+mod ser;
 mod serializer;
 mod tokenizer;
 mod types;
@@ -11,10 +22,27 @@ use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::slice;
 
+pub use appinfo::{
+    APPINFO_V27, APPINFO_V28, APPINFO_V29, AppInfo, AppInfoEntry, PACKAGEINFO_V27, PACKAGEINFO_V28,
+    PackageInfo, PackageInfoEntry,
+};
+pub use binary::{
+    BinaryObject, BinaryParser, BinarySerializer, BinaryValue, object_from_data, object_to_data,
+};
+pub use conditional::{ConditionSymbols, conditional_keys};
+pub use de::{from_str, from_str_with_key};
 pub use diff::{is_patch_already_applied, DiffApplicator, DiffGenerator};
-pub use document::KvDocument;
+pub use document::{KvDocument, Merge3Result};
 pub use error::{KvError, Result};
+pub use formatter::Formatter;
+pub use include::{FsLoader, IncludeLoader, IncludeResolver};
+pub use kv3::{
+    KV3_ENCODING_TEXT, KV3_FORMAT_GENERIC, Kv3Ast, Kv3Document, Kv3Format, Kv3Header, Kv3Node,
+    Kv3NodeValue, Kv3Parser, Kv3Serializer, Kv3Value,
+};
 pub use parser::Parser;
+pub use query::KvQuery;
+pub use ser::{to_string, to_string_with_key};
 pub use serializer::Serializer;
 pub use tokenizer::Tokenizer;
 pub use types::*;
@@ -27,6 +55,15 @@ fn to_c_string(s: String) -> *mut c_char {
     }
 }
 
+// Endpoints that produce text wrap it in a `TextOutput`, so callers never
+// have to tell a result from an error by looking at the text
+fn text_output(result: std::result::Result<String, String>) -> *mut c_char {
+    match serde_json::to_string(&TextOutput::from_result(result)) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
 /// # Safety
 /// The caller must ensure `ptr` is a valid pointer returned by `to_c_string()`.
 #[unsafe(no_mangle)]
@@ -89,6 +126,91 @@ pub unsafe extern "C" fn kv_parse(
     }
 }
 
+/// # Safety
+/// The caller must ensure `buffer` points to `buffer_len` bytes and `options_json` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_parse_with_diagnostics(
+    buffer: *const u8,
+    buffer_len: usize,
+    options_json: *const c_char,
+) -> *mut c_char {
+    if buffer.is_null() || buffer_len == 0 {
+        return to_c_string(r#"{"error": "Invalid buffer"}"#.to_string());
+    }
+
+    let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
+    let input = match String::from_utf8(buffer_slice.to_vec()) {
+        Ok(s) => s,
+        Err(e) => return to_c_string(format!(r#"{{"error": "Invalid UTF-8: {e}"}}"#)),
+    };
+
+    let options = if options_json.is_null() {
+        ParseOptions::default()
+    } else {
+        let options_str = unsafe {
+            match CStr::from_ptr(options_json).to_str() {
+                Ok(s) => s,
+                Err(_) => return to_c_string(r#"{"error": "Invalid options string"}"#.to_string()),
+            }
+        };
+
+        match serde_json::from_str::<ParseOptions>(options_str) {
+            Ok(opts) => opts,
+            Err(e) => return to_c_string(format!(r#"{{"error": "Invalid options JSON: {e}"}}"#)),
+        }
+    };
+
+    let report = Parser::parse_with_diagnostics(&input, options);
+    match serde_json::to_string(&report) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `file_path` is a valid C string and `options_json` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_parse_file(
+    file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    if file_path.is_null() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
+    }
+
+    let path = unsafe {
+        match CStr::from_ptr(file_path).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid file path string"}"#.to_string()),
+        }
+    };
+
+    let options = if options_json.is_null() {
+        ParseOptions::default()
+    } else {
+        let options_str = unsafe {
+            match CStr::from_ptr(options_json).to_str() {
+                Ok(s) => s,
+                Err(_) => return to_c_string(r#"{"error": "Invalid options string"}"#.to_string()),
+            }
+        };
+
+        match serde_json::from_str::<ParseOptions>(options_str) {
+            Ok(opts) => opts,
+            Err(e) => return to_c_string(format!(r#"{{"error": "Invalid options JSON: {e}"}}"#)),
+        }
+    };
+
+    // Parse the file and everything it pulls in through #include / #base
+    match IncludeResolver::with_options(FsLoader, options).resolve_file(path) {
+        Ok(result) => match serde_json::to_string(&result) {
+            Ok(json) => to_c_string(json),
+            Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+        },
+        Err(e) => to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    }
+}
+
 /// # Safety
 /// The caller must ensure `ast_json` is null or a valid C string.
 #[unsafe(no_mangle)]
@@ -160,6 +282,46 @@ pub unsafe extern "C" fn kv_serialize_data(
     }
 }
 
+/// # Safety
+/// The caller must ensure `buffer` points to `buffer_len` bytes and `options_json` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_format(
+    buffer: *const u8,
+    buffer_len: usize,
+    options_json: *const c_char,
+) -> *mut c_char {
+    if buffer.is_null() {
+        return to_c_string(r#"{"error": "Invalid buffer"}"#.to_string());
+    }
+
+    let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
+    let input = match std::str::from_utf8(buffer_slice) {
+        Ok(s) => s,
+        Err(e) => return to_c_string(format!(r#"{{"error": "Invalid UTF-8: {e}"}}"#)),
+    };
+
+    let options = if options_json.is_null() {
+        SerializeOptions::default()
+    } else {
+        let options_str = unsafe {
+            match CStr::from_ptr(options_json).to_str() {
+                Ok(s) => s,
+                Err(_) => return to_c_string(r#"{"error": "Invalid options string"}"#.to_string()),
+            }
+        };
+
+        match serde_json::from_str::<SerializeOptions>(options_str) {
+            Ok(opts) => opts,
+            Err(e) => return to_c_string(format!(r#"{{"error": "Invalid options JSON: {e}"}}"#)),
+        }
+    };
+
+    let result = Formatter::new(options)
+        .format(input)
+        .map_err(|e| format!("Parse failed: {e}"));
+    text_output(result)
+}
+
 /// # Safety
 /// The caller must ensure `source_json` and `target_json` are null or valid C strings.
 #[unsafe(no_mangle)]
@@ -274,3 +436,310 @@ pub unsafe extern "C" fn kv_diff_stats(diff_json: *const c_char) -> *mut c_char
         Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
     }
 }
+
+/// # Safety
+/// The caller must ensure `base`, `ours` and `theirs` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_merge3(
+    base: *const c_char,
+    ours: *const c_char,
+    theirs: *const c_char,
+) -> *mut c_char {
+    if base.is_null() || ours.is_null() || theirs.is_null() {
+        return to_c_string(r#"{"error": "Invalid input"}"#.to_string());
+    }
+
+    let mut documents = Vec::with_capacity(3);
+    for (name, content) in [("base", base), ("ours", ours), ("theirs", theirs)] {
+        let content = unsafe {
+            match CStr::from_ptr(content).to_str() {
+                Ok(s) => s,
+                Err(_) => return to_c_string(format!(r#"{{"error": "Invalid {name} string"}}"#)),
+            }
+        };
+
+        let mut document = KvDocument::new();
+        if let Err(e) = document.load_from_string(content) {
+            return to_c_string(
+                serde_json::json!({ "error": format!("Failed to parse {name}: {e}") }).to_string(),
+            );
+        }
+        documents.push(document);
+    }
+
+    let result = match KvDocument::merge3(&documents[0], &documents[1], &documents[2]) {
+        Ok(result) => result,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    let merged = match result.document.serialize() {
+        Ok(merged) => merged,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    let output = MergeOutput {
+        merged,
+        conflicts: result.conflicts,
+    };
+
+    match serde_json::to_string(&output) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `ast_json` and `symbols_json` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_evaluate_conditionals(
+    ast_json: *const c_char,
+    symbols_json: *const c_char,
+) -> *mut c_char {
+    if ast_json.is_null() || symbols_json.is_null() {
+        return to_c_string(r#"{"error": "Invalid input"}"#.to_string());
+    }
+
+    let ast_str = unsafe {
+        match CStr::from_ptr(ast_json).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid AST string"}"#.to_string()),
+        }
+    };
+
+    let symbols_str = unsafe {
+        match CStr::from_ptr(symbols_json).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid symbols string"}"#.to_string()),
+        }
+    };
+
+    let ast: DocumentNode = match serde_json::from_str(ast_str) {
+        Ok(a) => a,
+        Err(e) => return to_c_string(format!(r#"{{"error": "Invalid AST JSON: {e}"}}"#)),
+    };
+
+    let symbols: Vec<String> = match serde_json::from_str(symbols_str) {
+        Ok(s) => s,
+        Err(e) => return to_c_string(format!(r#"{{"error": "Invalid symbols JSON: {e}"}}"#)),
+    };
+
+    let data = ConditionSymbols::from_iter(symbols).filter(&ast);
+
+    match serde_json::to_string(&data) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `ast_json` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_conditional_keys(ast_json: *const c_char) -> *mut c_char {
+    if ast_json.is_null() {
+        return to_c_string(r#"{"error": "Invalid AST JSON"}"#.to_string());
+    }
+
+    let ast_str = unsafe {
+        match CStr::from_ptr(ast_json).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid AST string"}"#.to_string()),
+        }
+    };
+
+    let ast: DocumentNode = match serde_json::from_str(ast_str) {
+        Ok(a) => a,
+        Err(e) => return to_c_string(format!(r#"{{"error": "Invalid AST JSON: {e}"}}"#)),
+    };
+
+    match serde_json::to_string(&conditional_keys(&ast)) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `content` and `query` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_query(content: *const c_char, query: *const c_char) -> *mut c_char {
+    if content.is_null() || query.is_null() {
+        return to_c_string(r#"{"error": "Invalid input"}"#.to_string());
+    }
+
+    let content_str = unsafe {
+        match CStr::from_ptr(content).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid content string"}"#.to_string()),
+        }
+    };
+
+    let query_str = unsafe {
+        match CStr::from_ptr(query).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid query string"}"#.to_string()),
+        }
+    };
+
+    let mut document = KvDocument::new();
+    if let Err(e) = document.load_from_string(content_str) {
+        return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string());
+    }
+
+    let matches = match document.query(query_str) {
+        Ok(matches) => matches,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    match serde_json::to_string(&matches) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `content` and `edit_json` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_query_edit(
+    content: *const c_char,
+    edit_json: *const c_char,
+) -> *mut c_char {
+    if content.is_null() || edit_json.is_null() {
+        return to_c_string(r#"{"error": "Invalid input"}"#.to_string());
+    }
+
+    let content_str = unsafe {
+        match CStr::from_ptr(content).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid content string"}"#.to_string()),
+        }
+    };
+
+    let edit_str = unsafe {
+        match CStr::from_ptr(edit_json).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid edit string"}"#.to_string()),
+        }
+    };
+
+    let edit: QueryEdit = match serde_json::from_str(edit_str) {
+        Ok(edit) => edit,
+        Err(e) => return to_c_string(format!(r#"{{"error": "Invalid edit JSON: {e}"}}"#)),
+    };
+
+    let mut document = KvDocument::new();
+    if let Err(e) = document.load_from_string(content_str) {
+        return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string());
+    }
+
+    let count = match document.apply_query_edit(&edit) {
+        Ok(count) => count,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    let content = match document.serialize() {
+        Ok(content) => content,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    match serde_json::to_string(&QueryEditOutput { content, count }) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `buffer` is a valid pointer to `buffer_len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_parse_binary(buffer: *const u8, buffer_len: usize) -> *mut c_char {
+    if buffer.is_null() || buffer_len == 0 {
+        return to_c_string(r#"{"error": "Invalid buffer"}"#.to_string());
+    }
+
+    let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
+    let data = match BinaryParser::parse_data(buffer_slice) {
+        Ok(data) => data,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    match serde_json::to_string(&data) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `data_json` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_serialize_binary(data_json: *const c_char) -> *mut c_char {
+    if data_json.is_null() {
+        return to_c_string(r#"{"error": "Invalid data JSON"}"#.to_string());
+    }
+
+    let data_str = unsafe {
+        match CStr::from_ptr(data_json).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid data string"}"#.to_string()),
+        }
+    };
+
+    let data: KeyValuesObject = match serde_json::from_str(data_str) {
+        Ok(d) => d,
+        Err(e) => return to_c_string(format!(r#"{{"error": "Invalid data JSON: {e}"}}"#)),
+    };
+
+    let bytes = match BinarySerializer::serialize_data(&data) {
+        Ok(bytes) => bytes,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    match serde_json::to_string(&BinaryOutput { bytes }) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `content` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_parse_kv3(content: *const c_char) -> *mut c_char {
+    if content.is_null() {
+        return to_c_string(r#"{"error": "Invalid input"}"#.to_string());
+    }
+
+    let content_str = unsafe {
+        match CStr::from_ptr(content).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid content string"}"#.to_string()),
+        }
+    };
+
+    let document = match Kv3Document::parse(content_str) {
+        Ok(document) => document,
+        Err(e) => return to_c_string(serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+
+    match serde_json::to_string(&document) {
+        Ok(json) => to_c_string(json),
+        Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+    }
+}
+
+/// # Safety
+/// The caller must ensure `document_json` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kv_serialize_kv3(document_json: *const c_char) -> *mut c_char {
+    if document_json.is_null() {
+        return to_c_string(r#"{"error": "Invalid document JSON"}"#.to_string());
+    }
+
+    let document_str = unsafe {
+        match CStr::from_ptr(document_json).to_str() {
+            Ok(s) => s,
+            Err(_) => return to_c_string(r#"{"error": "Invalid document string"}"#.to_string()),
+        }
+    };
+
+    let result = serde_json::from_str::<Kv3Document>(document_str)
+        .map(|document| document.to_text())
+        .map_err(|e| format!("Invalid document JSON: {e}"));
+    text_output(result)
+}
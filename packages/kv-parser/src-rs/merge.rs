@@ -0,0 +1,433 @@
+use crate::types::{DiffEntry, DiffOp, KeyValuesObject, KeyValuesValue, MergeConflict};
+
+/// One change of theirs that can be applied on top of our document
+///
+/// `path` holds the key segments as they appear in the document, so keys
+/// that contain dots are addressed exactly; `change.path` is the same path
+/// joined with dots, for display.
+pub(crate) struct MergeChange {
+    pub(crate) path: Vec<String>,
+    pub(crate) change: DiffEntry,
+}
+
+#[derive(Default)]
+struct Merge {
+    changes: Vec<MergeChange>,
+    conflicts: Vec<MergeConflict>,
+}
+
+/// Split their changes against `base` into the ones that can be applied on
+/// top of `ours` and the ones that conflict with our own changes
+///
+/// A change of theirs conflicts when we changed the same key, a key inside
+/// it or an object containing it, and the two sides ended up with different
+/// values. Changes both sides made identically are neither applied again nor
+/// reported. Repeated keys are merged entry by entry, so entries added on
+/// each side in different places are all kept.
+pub(crate) fn merge3_changes(
+    base: &KeyValuesObject,
+    ours: &KeyValuesObject,
+    theirs: &KeyValuesObject,
+) -> (Vec<MergeChange>, Vec<MergeConflict>) {
+    let mut merge = Merge::default();
+    merge_objects(&mut Vec::new(), Some(base), ours, theirs, &mut merge);
+    (merge.changes, merge.conflicts)
+}
+
+fn merge_objects(
+    path: &mut Vec<String>,
+    base: Option<&KeyValuesObject>,
+    ours: &KeyValuesObject,
+    theirs: &KeyValuesObject,
+    merge: &mut Merge,
+) {
+    let mut keys: Vec<&String> = Vec::new();
+    for key in base
+        .into_iter()
+        .flat_map(|obj| obj.keys())
+        .chain(ours.keys())
+        .chain(theirs.keys())
+    {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    for key in keys {
+        path.push(key.clone());
+        merge_values(
+            path,
+            base.and_then(|obj| obj.get(key)),
+            ours.get(key),
+            theirs.get(key),
+            merge,
+        );
+        path.pop();
+    }
+}
+
+fn merge_values(
+    path: &mut Vec<String>,
+    base: Option<&KeyValuesValue>,
+    ours: Option<&KeyValuesValue>,
+    theirs: Option<&KeyValuesValue>,
+    merge: &mut Merge,
+) {
+    if theirs == base || ours == theirs {
+        return;
+    }
+
+    match (ours, theirs) {
+        (Some(KeyValuesValue::Object(our_obj)), Some(KeyValuesValue::Object(their_obj))) => {
+            let base_obj = match base {
+                Some(KeyValuesValue::Object(obj)) => Some(obj),
+                _ => None,
+            };
+            merge_objects(path, base_obj, our_obj, their_obj, merge);
+        }
+        _ if ours == base => merge.changes.push(change(path, ours, theirs.cloned())),
+        _ if [base, ours, theirs]
+            .iter()
+            .any(|value| matches!(value, Some(KeyValuesValue::Array(_)))) =>
+        {
+            match merge_entries(&entries(base), &entries(ours), &entries(theirs)) {
+                Some(merged) => {
+                    let merged = match merged.len() {
+                        0 => None,
+                        1 if !matches!(ours, Some(KeyValuesValue::Array(_))) => {
+                            merged.into_iter().next()
+                        }
+                        _ => Some(KeyValuesValue::Array(merged)),
+                    };
+                    if merged.as_ref() != ours {
+                        merge.changes.push(change(path, ours, merged));
+                    }
+                }
+                None => merge.conflicts.push(conflict(path, base, ours, theirs)),
+            }
+        }
+        _ => merge.conflicts.push(conflict(path, base, ours, theirs)),
+    }
+}
+
+fn change(
+    path: &[String],
+    ours: Option<&KeyValuesValue>,
+    new: Option<KeyValuesValue>,
+) -> MergeChange {
+    let op = match (ours, &new) {
+        (None, _) => DiffOp::Add,
+        (_, None) => DiffOp::Remove,
+        _ => DiffOp::Replace,
+    };
+    MergeChange {
+        path: path.to_vec(),
+        change: DiffEntry {
+            op,
+            path: path.join("."),
+            old_value: ours.cloned(),
+            new_value: new,
+            comment: None,
+            old_comment: None,
+            new_comment: None,
+            comment_position: None,
+        },
+    }
+}
+
+fn conflict(
+    path: &[String],
+    base: Option<&KeyValuesValue>,
+    ours: Option<&KeyValuesValue>,
+    theirs: Option<&KeyValuesValue>,
+) -> MergeConflict {
+    MergeConflict {
+        path: path.join("."),
+        base: base.cloned(),
+        ours: ours.cloned(),
+        theirs: theirs.cloned(),
+    }
+}
+
+/// The entries of a possibly repeated key, in document order
+fn entries(value: Option<&KeyValuesValue>) -> Vec<&KeyValuesValue> {
+    match value {
+        None => Vec::new(),
+        Some(KeyValuesValue::Array(items)) => items.iter().collect(),
+        Some(value) => vec![value],
+    }
+}
+
+/// Three-way merge of the entries of a repeated key
+///
+/// Entries both sides kept from `base` anchor the merge; between two anchors
+/// the side that changed something wins, and both sides changing the same
+/// stretch differently is a conflict (`None`).
+fn merge_entries(
+    base: &[&KeyValuesValue],
+    ours: &[&KeyValuesValue],
+    theirs: &[&KeyValuesValue],
+) -> Option<Vec<KeyValuesValue>> {
+    let in_ours = matching(base, ours);
+    let in_theirs = matching(base, theirs);
+
+    let mut merged = Vec::new();
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    loop {
+        let anchor = (b..base.len())
+            .find_map(|i| Some((i, in_ours[i]?, in_theirs[i]?)))
+            .unwrap_or((base.len(), ours.len(), theirs.len()));
+        let (base_run, our_run, their_run) =
+            (&base[b..anchor.0], &ours[o..anchor.1], &theirs[t..anchor.2]);
+
+        let run = if our_run == base_run || our_run == their_run {
+            their_run
+        } else if their_run == base_run {
+            our_run
+        } else {
+            return None;
+        };
+        merged.extend(run.iter().map(|value| (*value).clone()));
+
+        if anchor.0 == base.len() {
+            return Some(merged);
+        }
+        merged.push(base[anchor.0].clone());
+        (b, o, t) = (anchor.0 + 1, anchor.1 + 1, anchor.2 + 1);
+    }
+}
+
+/// For each entry of `base`, the index of the entry it lines up with in
+/// `other` along a longest common subsequence
+fn matching(base: &[&KeyValuesValue], other: &[&KeyValuesValue]) -> Vec<Option<usize>> {
+    let mut lengths = vec![vec![0usize; other.len() + 1]; base.len() + 1];
+    for i in (0..base.len()).rev() {
+        for j in (0..other.len()).rev() {
+            lengths[i][j] = if base[i] == other[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut matched = vec![None; base.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < base.len() && j < other.len() {
+        if base[i] == other[j] {
+            matched[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::KvDocument;
+
+    const BASE: &str = r#""GameInfo"
+{
+    "game"    "citadel"
+    "Version"    "1"
+    "Tools"    "0"
+    "FileSystem"
+    {
+        "SearchPaths"
+        {
+            "Game"    "citadel"
+            "Game"    "core"
+        }
+    }
+}
+"#;
+
+    const OURS: &str = r#""GameInfo"
+{
+    "game"    "citadel"
+    "Version"    "1"
+    // Enabled for mod debugging
+    "Tools"    "1"
+    "FileSystem"
+    {
+        "SearchPaths"
+        {
+            "Game"    "citadel/addons"
+            "Game"    "citadel"
+            "Game"    "core"
+        }
+    }
+}
+"#;
+
+    const THEIRS: &str = r#""GameInfo"
+{
+    "game"    "citadel"
+    "Version"    "2"
+    "Tools"    "2"
+    "FileSystem"
+    {
+        "SearchPaths"
+        {
+            "Game"    "citadel"
+            "Game"    "core"
+        }
+    }
+    "Telemetry"    "off"
+}
+"#;
+
+    fn doc(content: &str) -> KvDocument {
+        let mut doc = KvDocument::new();
+        doc.load_from_string(content).unwrap();
+        doc
+    }
+
+    fn s(value: &str) -> KeyValuesValue {
+        KeyValuesValue::String(value.to_string())
+    }
+
+    #[test]
+    fn test_merge3_keeps_our_edits_and_takes_their_updates() {
+        let result = KvDocument::merge3(&doc(BASE), &doc(OURS), &doc(THEIRS)).unwrap();
+        let merged = result.document;
+
+        assert_eq!(merged.get("GameInfo.Version"), Some(&s("2")));
+        assert_eq!(merged.get("GameInfo.Telemetry"), Some(&s("off")));
+        assert_eq!(
+            merged.get_all("GameInfo.FileSystem.SearchPaths.Game"),
+            vec![&s("citadel/addons"), &s("citadel"), &s("core")]
+        );
+
+        let text = merged.serialize().unwrap();
+        assert!(text.contains("// Enabled for mod debugging"));
+        assert!(text.contains("\"Version\"    \"2\""));
+        assert!(text.ends_with("    }\n    \"Telemetry\"    \"off\"\n}\n"));
+    }
+
+    #[test]
+    fn test_merge3_reports_conflicts_and_keeps_ours() {
+        let result = KvDocument::merge3(&doc(BASE), &doc(OURS), &doc(THEIRS)).unwrap();
+
+        assert_eq!(
+            result.conflicts,
+            vec![MergeConflict {
+                path: "GameInfo.Tools".to_string(),
+                base: Some(s("0")),
+                ours: Some(s("1")),
+                theirs: Some(s("2")),
+            }]
+        );
+        assert_eq!(result.document.get("GameInfo.Tools"), Some(&s("1")));
+    }
+
+    #[test]
+    fn test_identical_changes_do_not_conflict() {
+        let (changes, conflicts) = merge3_changes(
+            doc(BASE).get_data(),
+            doc(THEIRS).get_data(),
+            doc(THEIRS).get_data(),
+        );
+
+        assert!(changes.is_empty());
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_their_change_to_a_dotted_key_is_applied() {
+        let base = "\"Root\"\n{\n    \"sv.cheats\"    \"0\"\n    \"name\"    \"base\"\n}\n";
+        let ours = base.replace("\"base\"", "\"ours\"");
+        let theirs = base.replace("\"0\"", "\"1\"");
+
+        let (changes, conflicts) = merge3_changes(
+            doc(base).get_data(),
+            doc(&ours).get_data(),
+            doc(&theirs).get_data(),
+        );
+        assert!(conflicts.is_empty());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, ["Root", "sv.cheats"]);
+
+        let merged = KvDocument::merge3(&doc(base), &doc(&ours), &doc(&theirs))
+            .unwrap()
+            .document;
+        let root = match merged.get("Root") {
+            Some(KeyValuesValue::Object(root)) => root,
+            other => panic!("expected an object, got {other:?}"),
+        };
+        assert_eq!(root.get("sv.cheats"), Some(&s("1")));
+        assert_eq!(root.get("name"), Some(&s("ours")));
+    }
+
+    #[test]
+    fn test_repeated_keys_merge_entry_by_entry() {
+        let theirs = THEIRS.replace(
+            "            \"Game\"    \"core\"\n",
+            "            \"Game\"    \"core\"\n            \"Game\"    \"core/extra\"\n",
+        );
+
+        let result = KvDocument::merge3(&doc(BASE), &doc(OURS), &doc(&theirs)).unwrap();
+
+        assert!(
+            result
+                .conflicts
+                .iter()
+                .all(|conflict| conflict.path == "GameInfo.Tools")
+        );
+        assert_eq!(
+            result
+                .document
+                .get_all("GameInfo.FileSystem.SearchPaths.Game"),
+            vec![
+                &s("citadel/addons"),
+                &s("citadel"),
+                &s("core"),
+                &s("core/extra")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_keys_changed_in_the_same_place_conflict() {
+        let ours = BASE.replace("\"core\"", "\"core/ours\"");
+        let theirs = BASE.replace("\"core\"", "\"core/theirs\"");
+
+        let result = KvDocument::merge3(&doc(BASE), &doc(&ours), &doc(&theirs)).unwrap();
+
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(
+            result.conflicts[0].path,
+            "GameInfo.FileSystem.SearchPaths.Game"
+        );
+        assert_eq!(
+            result
+                .document
+                .get_all("GameInfo.FileSystem.SearchPaths.Game"),
+            vec![&s("citadel"), &s("core/ours")]
+        );
+    }
+
+    #[test]
+    fn test_removing_a_section_we_edited_conflicts() {
+        let theirs = BASE.replace(
+            "        \"SearchPaths\"\n        {\n            \"Game\"    \"citadel\"\n            \"Game\"    \"core\"\n        }\n",
+            "",
+        );
+
+        let (_, conflicts) = merge3_changes(
+            doc(BASE).get_data(),
+            doc(OURS).get_data(),
+            doc(&theirs).get_data(),
+        );
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "GameInfo.FileSystem.SearchPaths");
+        assert!(conflicts[0].theirs.is_none());
+    }
+}
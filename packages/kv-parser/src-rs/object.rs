@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Index;
+
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::types::KeyValuesValue;
+
+/// KeyValues object - an ordered multimap of string keys to values
+///
+/// Keys are grouped the way the data model has always exposed them: a key that
+/// appears more than once maps to a `KeyValuesValue::Array` of its values, so
+/// `get`, `insert` and `remove` behave like a map. Underneath, the object also
+/// remembers the document order of every occurrence, so interleaved repeats
+/// such as `Game`, `Mod`, `Game` in gameinfo.gi SearchPaths come back out of
+/// `entries` (and the data serializer) in the order they were read.
+#[derive(Debug, Clone, Default)]
+pub struct KeyValuesObject {
+    /// One `(key, value)` per distinct key, in order of first appearance
+    groups: Vec<(String, KeyValuesValue)>,
+    /// Position of each key in `groups`
+    index: HashMap<String, usize>,
+    /// Group of every occurrence, in document order
+    sequence: Vec<usize>,
+}
+
+/// Number of occurrences a grouped value stands for
+fn occurrences(value: &KeyValuesValue) -> usize {
+    match value {
+        KeyValuesValue::Array(items) => items.len(),
+        _ => 1,
+    }
+}
+
+/// The `occurrence`-th value of a group, if it has that many
+fn occurrence(value: &KeyValuesValue, occurrence: usize) -> Option<&KeyValuesValue> {
+    match value {
+        KeyValuesValue::Array(items) => items.get(occurrence),
+        _ => (occurrence == 0).then_some(value),
+    }
+}
+
+impl KeyValuesObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct keys
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// The value for `key`, or an array of every value if the key repeats
+    pub fn get(&self, key: &str) -> Option<&KeyValuesValue> {
+        self.index.get(key).map(|&group| &self.groups[group].1)
+    }
+
+    /// Mutable access to the grouped value for `key`. Growing or shrinking a
+    /// repeated key's array through this keeps the surviving occurrences in
+    /// place and puts new ones right after the key's last occurrence.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut KeyValuesValue> {
+        self.index.get(key).map(|&group| &mut self.groups[group].1)
+    }
+
+    /// Every value of `key` in document order
+    pub fn get_all(&self, key: &str) -> Vec<&KeyValuesValue> {
+        match self.get(key) {
+            Some(KeyValuesValue::Array(items)) => items.iter().collect(),
+            Some(value) => vec![value],
+            None => Vec::new(),
+        }
+    }
+
+    /// Set the grouped value for `key`, keeping its position if it exists.
+    /// An array value stands for that many occurrences of the key.
+    pub fn insert(&mut self, key: String, value: KeyValuesValue) -> Option<KeyValuesValue> {
+        let Some(&group) = self.index.get(&key) else {
+            let group = self.groups.len();
+            self.sequence
+                .extend(std::iter::repeat_n(group, occurrences(&value)));
+            self.index.insert(key.clone(), group);
+            self.groups.push((key, value));
+            return None;
+        };
+
+        let new_count = occurrences(&value);
+        let mut slots: Vec<usize> = self
+            .sequence
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| **g == group)
+            .map(|(position, _)| position)
+            .collect();
+
+        if new_count < slots.len() {
+            for position in slots.split_off(new_count).into_iter().rev() {
+                self.sequence.remove(position);
+            }
+        } else if new_count > slots.len() {
+            let after = slots.last().map_or(self.sequence.len(), |last| last + 1);
+            self.sequence.splice(
+                after..after,
+                std::iter::repeat_n(group, new_count - slots.len()),
+            );
+        }
+
+        Some(std::mem::replace(&mut self.groups[group].1, value))
+    }
+
+    /// Add one more occurrence of `key` at the end of the object, turning an
+    /// existing value into an array of values
+    pub fn append(&mut self, key: String, value: KeyValuesValue) {
+        let Some(&group) = self.index.get(&key) else {
+            self.insert(key, value);
+            return;
+        };
+
+        let existing = &mut self.groups[group].1;
+        match existing {
+            KeyValuesValue::Array(items) => items.push(value),
+            _ => {
+                let first = std::mem::replace(existing, KeyValuesValue::Array(Vec::new()));
+                *existing = KeyValuesValue::Array(vec![first, value]);
+            }
+        }
+        self.sequence.push(group);
+    }
+
+    /// Remove every occurrence of `key`
+    pub fn remove(&mut self, key: &str) -> Option<KeyValuesValue> {
+        let group = self.index.remove(key)?;
+        let (_, value) = self.groups.remove(group);
+
+        for (key, _) in &self.groups[group..] {
+            if let Some(position) = self.index.get_mut(key) {
+                *position -= 1;
+            }
+        }
+        self.sequence.retain(|&g| g != group);
+        for g in &mut self.sequence {
+            if *g > group {
+                *g -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.index.clear();
+        self.sequence.clear();
+    }
+
+    /// Distinct keys in order of first appearance
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.groups.iter().map(|(key, _)| key)
+    }
+
+    /// Grouped values in order of their key's first appearance
+    pub fn values(&self) -> impl Iterator<Item = &KeyValuesValue> {
+        self.groups.iter().map(|(_, value)| value)
+    }
+
+    /// `(key, grouped value)` pairs in order of first appearance
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &KeyValuesValue)> {
+        self.groups.iter().map(|(key, value)| (key, value))
+    }
+
+    /// Every occurrence as a `(key, value)` pair in document order, with
+    /// repeated keys expanded back into separate entries
+    pub fn entries(&self) -> Vec<(&str, &KeyValuesValue)> {
+        self.slots()
+            .into_iter()
+            .filter_map(|(group, n)| {
+                let (key, value) = &self.groups[group];
+                occurrence(value, n).map(|item| (key.as_str(), item))
+            })
+            .collect()
+    }
+
+    /// The `n`-th occurrence of `key` in document order
+    pub fn get_occurrence(&self, key: &str, n: usize) -> Option<&KeyValuesValue> {
+        occurrence(self.get(key)?, n)
+    }
+
+    pub fn get_occurrence_mut(&mut self, key: &str, n: usize) -> Option<&mut KeyValuesValue> {
+        match self.get_mut(key)? {
+            KeyValuesValue::Array(items) => items.get_mut(n),
+            value => (n == 0).then_some(value),
+        }
+    }
+
+    /// Position of the `n`-th occurrence of `key` in `entries`
+    pub fn entry_index(&self, key: &str, n: usize) -> Option<usize> {
+        let group = *self.index.get(key)?;
+        self.slots().iter().position(|&slot| slot == (group, n))
+    }
+
+    /// Insert one occurrence of `key` so it ends up at position `index` of
+    /// `entries`, shifting later entries back
+    pub fn insert_entry(&mut self, index: usize, key: String, value: KeyValuesValue) {
+        self.sync_sequence();
+        let index = index.min(self.sequence.len());
+
+        let Some(&group) = self.index.get(&key) else {
+            let group = self.groups.len();
+            self.index.insert(key.clone(), group);
+            self.groups.push((key, value));
+            self.sequence.insert(index, group);
+            return;
+        };
+
+        let before = self.sequence[..index]
+            .iter()
+            .filter(|&&g| g == group)
+            .count();
+        let existing = &mut self.groups[group].1;
+        match existing {
+            KeyValuesValue::Array(items) => items.insert(before, value),
+            _ => {
+                let first = std::mem::replace(existing, KeyValuesValue::Array(Vec::new()));
+                let items = if before == 0 {
+                    vec![value, first]
+                } else {
+                    vec![first, value]
+                };
+                *existing = KeyValuesValue::Array(items);
+            }
+        }
+        self.sequence.insert(index, group);
+    }
+
+    /// Remove only the `n`-th occurrence of `key`. A key left with a single
+    /// occurrence goes back to holding a plain value.
+    pub fn remove_occurrence(&mut self, key: &str, n: usize) -> Option<KeyValuesValue> {
+        let group = *self.index.get(key)?;
+        if occurrences(&self.groups[group].1) <= 1 {
+            return if n == 0 { self.remove(key) } else { None };
+        }
+
+        self.sync_sequence();
+        let KeyValuesValue::Array(items) = &mut self.groups[group].1 else {
+            return None;
+        };
+        if n >= items.len() {
+            return None;
+        }
+        let removed = items.remove(n);
+        if items.len() == 1 {
+            let last = items.remove(0);
+            self.groups[group].1 = last;
+        }
+
+        let position = self
+            .sequence
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| **g == group)
+            .nth(n)
+            .map(|(position, _)| position);
+        if let Some(position) = position {
+            self.sequence.remove(position);
+        }
+        Some(removed)
+    }
+
+    /// `(group, occurrence)` of every entry in document order
+    fn slots(&self) -> Vec<(usize, usize)> {
+        let mut counts = vec![0usize; self.groups.len()];
+        for &group in &self.sequence {
+            counts[group] += 1;
+        }
+
+        let mut seen = vec![0usize; self.groups.len()];
+        let mut slots = Vec::with_capacity(self.sequence.len());
+        let emit_rest = |slots: &mut Vec<_>, group: usize, from: usize| {
+            for n in from..occurrences(&self.groups[group].1) {
+                slots.push((group, n));
+            }
+        };
+
+        for &group in &self.sequence {
+            let n = seen[group];
+            seen[group] += 1;
+            if n < occurrences(&self.groups[group].1) {
+                slots.push((group, n));
+            }
+            // Values added through `get_mut` have no slot of their own; keep
+            // them next to the key's last known occurrence.
+            if seen[group] == counts[group] {
+                emit_rest(&mut slots, group, n + 1);
+            }
+        }
+        for (group, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                emit_rest(&mut slots, group, 0);
+            }
+        }
+
+        slots
+    }
+
+    /// Bring `sequence` back in line with the grouped values after they were
+    /// resized through `get_mut`
+    fn sync_sequence(&mut self) {
+        self.sequence = self.slots().into_iter().map(|(group, _)| group).collect();
+    }
+}
+
+/// Objects are equal when they hold the same keys with equal values; the
+/// order keys appear in does not matter.
+impl PartialEq for KeyValuesObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: AsRef<str> + ?Sized> Index<&K> for KeyValuesObject {
+    type Output = KeyValuesValue;
+
+    fn index(&self, key: &K) -> &KeyValuesValue {
+        self.get(key.as_ref())
+            .expect("key not found in KeyValuesObject")
+    }
+}
+
+impl FromIterator<(String, KeyValuesValue)> for KeyValuesObject {
+    fn from_iter<I: IntoIterator<Item = (String, KeyValuesValue)>>(iter: I) -> Self {
+        let mut object = Self::new();
+        for (key, value) in iter {
+            object.insert(key, value);
+        }
+        object
+    }
+}
+
+impl IntoIterator for KeyValuesObject {
+    type Item = (String, KeyValuesValue);
+    type IntoIter = std::vec::IntoIter<(String, KeyValuesValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.groups.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a KeyValuesObject {
+    type Item = (&'a String, &'a KeyValuesValue);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, KeyValuesValue)>,
+        fn(&'a (String, KeyValuesValue)) -> (&'a String, &'a KeyValuesValue),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.groups.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl Serialize for KeyValuesObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyValuesObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct ObjectVisitor;
+
+        impl<'de> Visitor<'de> for ObjectVisitor {
+            type Value = KeyValuesObject;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a KeyValues object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut access: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let mut object = KeyValuesObject::new();
+                while let Some((key, value)) = access.next_entry::<String, KeyValuesValue>()? {
+                    object.insert(key, value);
+                }
+                Ok(object)
+            }
+        }
+
+        deserializer.deserialize_map(ObjectVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(value: &str) -> KeyValuesValue {
+        KeyValuesValue::String(value.to_string())
+    }
+
+    fn search_paths() -> KeyValuesObject {
+        let mut object = KeyValuesObject::new();
+        object.append("Game".to_string(), s("citadel/addons"));
+        object.append("Mod".to_string(), s("citadel"));
+        object.append("Game".to_string(), s("citadel"));
+        object.append("Write".to_string(), s("citadel"));
+        object.append("Game".to_string(), s("core"));
+        object
+    }
+
+    fn entry_keys(object: &KeyValuesObject) -> Vec<&str> {
+        object.entries().into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn test_repeated_keys_keep_document_order() {
+        let object = search_paths();
+
+        assert_eq!(object.len(), 3);
+        assert_eq!(
+            entry_keys(&object),
+            ["Game", "Mod", "Game", "Write", "Game"]
+        );
+        assert_eq!(
+            object.get("Game"),
+            Some(&KeyValuesValue::Array(vec![
+                s("citadel/addons"),
+                s("citadel"),
+                s("core")
+            ]))
+        );
+        assert_eq!(object.get_all("Mod"), vec![&s("citadel")]);
+    }
+
+    #[test]
+    fn test_insert_keeps_existing_positions() {
+        let mut object = search_paths();
+
+        object.insert("Mod".to_string(), s("core"));
+        object.insert(
+            "Game".to_string(),
+            KeyValuesValue::Array(vec![s("a"), s("b")]),
+        );
+
+        let entries = object.entries();
+        assert_eq!(
+            entries,
+            vec![
+                ("Game", &s("a")),
+                ("Mod", &s("core")),
+                ("Game", &s("b")),
+                ("Write", &s("citadel")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_growing_a_repeated_key_places_new_values_after_its_last_occurrence() {
+        let mut object = search_paths();
+        object.append("Tail".to_string(), s("end"));
+
+        if let Some(KeyValuesValue::Array(games)) = object.get_mut("Game") {
+            games.push(s("extra"));
+        }
+
+        assert_eq!(
+            entry_keys(&object),
+            ["Game", "Mod", "Game", "Write", "Game", "Game", "Tail"]
+        );
+    }
+
+    #[test]
+    fn test_remove_drops_every_occurrence() {
+        let mut object = search_paths();
+
+        assert!(object.remove("Game").is_some());
+        assert_eq!(entry_keys(&object), ["Mod", "Write"]);
+        assert_eq!(object.get("Write"), Some(&s("citadel")));
+    }
+
+    #[test]
+    fn test_single_occurrences_can_be_inserted_and_removed() {
+        let mut object = search_paths();
+
+        let at = object.entry_index("Game", 1).unwrap();
+        object.insert_entry(at, "Game".to_string(), s("citadel/win"));
+        assert_eq!(
+            entry_keys(&object),
+            ["Game", "Mod", "Game", "Game", "Write", "Game"]
+        );
+        assert_eq!(object.get_occurrence("Game", 1), Some(&s("citadel/win")));
+        assert_eq!(object.get_occurrence("Game", 2), Some(&s("citadel")));
+
+        assert_eq!(
+            object.remove_occurrence("Game", 0),
+            Some(s("citadel/addons"))
+        );
+        assert_eq!(object.remove_occurrence("Game", 2), Some(s("core")));
+        assert_eq!(object.remove_occurrence("Game", 1), Some(s("citadel")));
+        assert_eq!(entry_keys(&object), ["Mod", "Game", "Write"]);
+        assert_eq!(object.get("Game"), Some(&s("citadel/win")));
+    }
+
+    #[test]
+    fn test_json_keeps_key_order() {
+        let json = r#"{"zeta":"1","alpha":"2","mid":["3","4"]}"#;
+        let object: KeyValuesObject = serde_json::from_str(json).unwrap();
+
+        assert_eq!(entry_keys(&object), ["zeta", "alpha", "mid", "mid"]);
+        assert_eq!(serde_json::to_string(&object).unwrap(), json);
+    }
+}
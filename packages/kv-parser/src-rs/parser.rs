@@ -1,16 +1,27 @@
 use crate::ast::*;
 use crate::error::{KvError, Result};
 use crate::tokenizer::Tokenizer;
-use crate::types::{KeyValuesObject, KeyValuesValue, ParseOptions, ParseResult, Token, TokenType};
+use crate::types::{
+    Diagnostic, DiagnosticFix, DiagnosticSeverity, KeyValuesObject, KeyValuesValue, ParseOptions,
+    ParseReport, ParseResult, Token, TokenType,
+};
 
 pub struct Parser {
     tokens: Vec<Token>,
     pos: usize,
+    /// Report problems as diagnostics instead of failing
+    recover: bool,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+        Self {
+            tokens,
+            pos: 0,
+            recover: false,
+            diagnostics: Vec::new(),
+        }
     }
 
     pub fn parse(input: &str, options: ParseOptions) -> Result<ParseResult> {
@@ -28,6 +39,99 @@ impl Parser {
         Ok(ParseResult { data, ast })
     }
 
+    /// Parse without stopping at the first error. Every problem is reported
+    /// as a diagnostic and the AST covers as much of the input as could be
+    /// recovered: keys without a value get an empty string and unclosed
+    /// objects are closed at the end of the input.
+    pub fn parse_with_diagnostics(input: &str, options: ParseOptions) -> ParseReport {
+        let mut tokenizer = Tokenizer::with_options(
+            input,
+            options.allow_escape_sequences,
+            options.allow_conditionals,
+            options.allow_includes,
+        );
+        let (tokens, mut diagnostics) = tokenizer.tokenize_recovering();
+        let mut parser = Parser::new(tokens);
+        parser.recover = true;
+        let ast = parser
+            .parse_document()
+            .expect("a recovering parse never fails");
+        let data = parser.ast_to_data(&ast);
+
+        diagnostics.append(&mut parser.diagnostics);
+        diagnostics.sort_by_key(|d| d.start.offset);
+
+        ParseReport {
+            data,
+            ast,
+            diagnostics,
+        }
+    }
+
+    fn report(
+        &mut self,
+        severity: DiagnosticSeverity,
+        start: Position,
+        end: Position,
+        message: String,
+        fix: Option<DiagnosticFix>,
+    ) {
+        if self.recover {
+            self.diagnostics.push(Diagnostic {
+                severity,
+                start,
+                end,
+                message,
+                fix,
+            });
+        }
+    }
+
+    /// Report a token that has no place where it appears. Error tokens were
+    /// already reported by the tokenizer.
+    fn report_stray_token(&mut self, in_object: bool) {
+        let token = self.current_token().clone();
+        let start = self.make_position(&token);
+        let end = end_of(start, &token.raw);
+
+        match token.token_type {
+            TokenType::CloseBrace => {
+                let fix = DiagnosticFix {
+                    description: "Remove the brace".to_string(),
+                    start,
+                    end,
+                    replacement: String::new(),
+                };
+                self.report(
+                    DiagnosticSeverity::Error,
+                    start,
+                    end,
+                    "Unexpected '}' with no matching '{'".to_string(),
+                    Some(fix),
+                );
+            }
+            TokenType::OpenBrace => {
+                self.report(
+                    DiagnosticSeverity::Error,
+                    start,
+                    end,
+                    "Object has no key".to_string(),
+                    None,
+                );
+            }
+            TokenType::Include | TokenType::Base if in_object => {
+                self.report(
+                    DiagnosticSeverity::Warning,
+                    start,
+                    end,
+                    "Directives are only read at the top level".to_string(),
+                    None,
+                );
+            }
+            _ => {}
+        }
+    }
+
     fn current_token(&self) -> &Token {
         if self.pos < self.tokens.len() {
             &self.tokens[self.pos]
@@ -75,10 +179,10 @@ impl Parser {
                     children.push(AstNode::Conditional(self.parse_conditional()?));
                 }
                 TokenType::Include | TokenType::Base => {
-                    // Skip includes for now
-                    self.advance();
+                    children.push(AstNode::Directive(self.parse_directive()));
                 }
                 _ => {
+                    self.report_stray_token(false);
                     self.advance();
                 }
             }
@@ -120,11 +224,36 @@ impl Parser {
                 .and_then(|m| m.quoted)
                 .unwrap_or(false);
 
-            if !is_quoted && token.value.parse::<f64>().is_ok() && !token.value.trim().is_empty() {
+            let value = if !is_quoted
+                && token.value.parse::<f64>().is_ok()
+                && !token.value.trim().is_empty()
+            {
                 ValueNode::Number(self.parse_number()?)
             } else {
                 ValueNode::String(self.parse_string()?)
+            };
+
+            if separator.as_ref().is_some_and(|ws| ws.value.contains('\n')) {
+                let (start, raw) = match &value {
+                    ValueNode::Number(n) => (n.start, &n.raw),
+                    ValueNode::String(s) => (s.start, &s.raw),
+                    ValueNode::Object(_) => unreachable!(),
+                };
+                let end = end_of(start, raw);
+                self.report(
+                    DiagnosticSeverity::Warning,
+                    start,
+                    end,
+                    format!(
+                        "Value of '{}' is not on the same line as its key",
+                        key.value
+                    ),
+                    None,
+                );
             }
+            value
+        } else if self.recover {
+            self.missing_value(&key)
         } else {
             return Err(KvError::ExpectedToken {
                 expected: "value".to_string(),
@@ -226,12 +355,13 @@ impl Parser {
                     children.push(AstNode::Conditional(self.parse_conditional()?));
                 }
                 _ => {
+                    self.report_stray_token(true);
                     self.advance();
                 }
             }
         }
 
-        if self.current_token().token_type != TokenType::CloseBrace {
+        if self.current_token().token_type != TokenType::CloseBrace && !self.recover {
             return Err(KvError::ExpectedToken {
                 expected: "}".to_string(),
                 line: self.current_token().line,
@@ -240,6 +370,25 @@ impl Parser {
         }
 
         let close_token = self.current_token().clone();
+        let closed = close_token.token_type == TokenType::CloseBrace;
+        if !closed {
+            // Close the object at the end of the input with an empty brace so
+            // the AST still serializes back to the original text
+            let eof = self.make_position(&close_token);
+            let fix = DiagnosticFix {
+                description: "Close the object".to_string(),
+                start: eof,
+                end: eof,
+                replacement: "}".to_string(),
+            };
+            self.report(
+                DiagnosticSeverity::Error,
+                open_brace.start,
+                end_of(open_brace.start, "{"),
+                "Unclosed '{'".to_string(),
+                Some(fix),
+            );
+        }
         let close_brace = TokenNode {
             node_type: NodeType::Token,
             start: self.make_position(&close_token),
@@ -249,7 +398,9 @@ impl Parser {
             value: "}".to_string(),
         };
 
-        self.advance(); // Skip }
+        if closed {
+            self.advance(); // Skip }
+        }
 
         Ok(ObjectNode {
             node_type: NodeType::Object,
@@ -262,6 +413,59 @@ impl Parser {
         })
     }
 
+    /// Stand-in for the value of a key that has none: an empty string with
+    /// no raw text, so the AST still serializes back to the original text
+    fn missing_value(&mut self, key: &StringNode) -> ValueNode {
+        let key_end = end_of(key.start, &key.raw);
+        let fix = DiagnosticFix {
+            description: "Insert an empty value".to_string(),
+            start: key_end,
+            end: key_end,
+            replacement: " \"\"".to_string(),
+        };
+        self.report(
+            DiagnosticSeverity::Error,
+            key.start,
+            key_end,
+            format!("Key '{}' has no value", key.value),
+            Some(fix),
+        );
+
+        ValueNode::String(StringNode {
+            node_type: NodeType::String,
+            start: key_end,
+            end: key_end,
+            raw: String::new(),
+            value: String::new(),
+            quoted: true,
+            quote_char: None,
+        })
+    }
+
+    /// Directives stay in the AST so the document round-trips; resolving
+    /// them is left to `IncludeResolver`
+    fn parse_directive(&mut self) -> DirectiveNode {
+        let token = self.current_token();
+        let kind = if token.token_type == TokenType::Base {
+            DirectiveKind::Base
+        } else {
+            DirectiveKind::Include
+        };
+
+        let node = DirectiveNode {
+            node_type: NodeType::Directive,
+            start: self.make_position(token),
+            end: self.make_position(token),
+            raw: token.raw.clone(),
+            kind,
+            path: token.value.clone(),
+            resolved: None,
+        };
+
+        self.advance();
+        node
+    }
+
     fn parse_string(&mut self) -> Result<StringNode> {
         let token = self.current_token();
 
@@ -423,48 +627,64 @@ impl Parser {
     }
 
     fn ast_to_data(&self, doc: &DocumentNode) -> KeyValuesObject {
-        self.extract_data_from_children(&doc.children)
+        Self::ast_to_data_where(doc, &|_| true)
     }
 
-    fn extract_data_from_children(&self, children: &[AstNode]) -> KeyValuesObject {
+    /// Extract data from an AST, dropping key-values (and everything nested
+    /// under them) that `keep` rejects
+    pub(crate) fn ast_to_data_where(
+        doc: &DocumentNode,
+        keep: &dyn Fn(&KeyValueNode) -> bool,
+    ) -> KeyValuesObject {
+        Self::extract_data_from_children(&doc.children, keep)
+    }
+
+    fn extract_data_from_children(
+        children: &[AstNode],
+        keep: &dyn Fn(&KeyValueNode) -> bool,
+    ) -> KeyValuesObject {
         let mut result = KeyValuesObject::new();
 
         for child in children {
-            if let AstNode::KeyValue(kv) = child {
+            if let AstNode::KeyValue(kv) = child
+                && keep(kv)
+            {
                 let key = kv.key.value.clone();
-                let value = self.extract_value(&kv.value);
-
-                // Handle duplicate keys
-                if let Some(existing) = result.get_mut(&key) {
-                    match existing {
-                        KeyValuesValue::Array(arr) => {
-                            arr.push(value);
-                        }
-                        _ => {
-                            let old_value = existing.clone();
-                            *existing = KeyValuesValue::Array(vec![old_value, value]);
-                        }
-                    }
-                } else {
-                    result.insert(key, value);
-                }
+                let value = Self::extract_value(&kv.value, keep);
+
+                // Duplicate keys are grouped into an array, in document order
+                result.append(key, value);
             }
         }
 
         result
     }
 
-    fn extract_value(&self, value: &ValueNode) -> KeyValuesValue {
+    fn extract_value(value: &ValueNode, keep: &dyn Fn(&KeyValueNode) -> bool) -> KeyValuesValue {
         match value {
             ValueNode::String(s) => KeyValuesValue::String(s.value.clone()),
             ValueNode::Number(n) => KeyValuesValue::Number(n.value),
             ValueNode::Object(o) => {
-                KeyValuesValue::Object(self.extract_data_from_children(&o.children))
+                KeyValuesValue::Object(Self::extract_data_from_children(&o.children, keep))
             }
         }
     }
 }
 
+/// Position just past `raw` when it starts at `start`
+fn end_of(start: Position, raw: &str) -> Position {
+    raw.chars().fold(start, |mut end, ch| {
+        end.offset += 1;
+        if ch == '\n' {
+            end.line += 1;
+            end.column = 1;
+        } else {
+            end.column += 1;
+        }
+        end
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -627,4 +847,71 @@ Root
             panic!("Expected first child to be KeyValue");
         }
     }
+
+    #[test]
+    fn test_parse_with_diagnostics_reports_every_error() {
+        let input = "\"Root\"\n{\n    \"Name\" \"Deadlock\"\n    \"Broken\"\n}\n}\n#import \"x\"\n\"Tail\"\n{\n    \"Key\" \"Value\"\n";
+        let report = Parser::parse_with_diagnostics(input, ParseOptions::default());
+
+        let messages: Vec<_> = report
+            .diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Key 'Broken' has no value",
+                "Unexpected '}' with no matching '{'",
+                "Unknown directive '#import'",
+                "Unclosed '{'",
+            ]
+        );
+        assert!(report.has_errors());
+        assert_eq!(report.diagnostics[0].start.line, 4);
+        assert_eq!(report.diagnostics[3].start.line, 9);
+
+        let Some(KeyValuesValue::Object(root)) = report.data.get("Root") else {
+            panic!("Expected Root to be an object");
+        };
+        assert!(matches!(root.get("Name"), Some(KeyValuesValue::String(s)) if s == "Deadlock"));
+        assert!(matches!(root.get("Broken"), Some(KeyValuesValue::String(s)) if s.is_empty()));
+        let Some(KeyValuesValue::Object(tail)) = report.data.get("Tail") else {
+            panic!("Expected Tail to be an object");
+        };
+        assert!(tail.contains_key("Key"));
+    }
+
+    #[test]
+    fn test_parse_with_diagnostics_fixes_apply() {
+        let input = "\"Root\"\n{\n    \"Key\"\n";
+        let report = Parser::parse_with_diagnostics(input, ParseOptions::default());
+
+        let mut fixed = input.to_string();
+        let mut fixes: Vec<_> = report
+            .diagnostics
+            .iter()
+            .filter_map(|d| d.fix.as_ref())
+            .collect();
+        fixes.sort_by_key(|f| std::cmp::Reverse(f.start.offset));
+        for fix in fixes {
+            fixed.replace_range(fix.start.offset..fix.end.offset, &fix.replacement);
+        }
+
+        assert_eq!(fixed, "\"Root\"\n{\n    \"Key\" \"\"\n}");
+        assert!(Parser::parse(&fixed, ParseOptions::default()).is_ok());
+        assert_eq!(crate::Serializer::serialize_ast(&report.ast), input);
+    }
+
+    #[test]
+    fn test_parse_with_diagnostics_warns_on_split_pair() {
+        let input = "\"Root\"\n{\n    \"Key\"\n    \"Value\"\n}\n";
+        let report = Parser::parse_with_diagnostics(input, ParseOptions::default());
+
+        assert!(!report.has_errors());
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].severity, DiagnosticSeverity::Warning);
+        assert_eq!(report.diagnostics[0].start.line, 4);
+        assert!(Parser::parse(input, ParseOptions::default()).is_ok());
+    }
 }
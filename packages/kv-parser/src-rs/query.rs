@@ -0,0 +1,894 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::diff::DiffApplicator;
+use crate::error::{KvError, Result};
+use crate::types::{
+    AstNode, DocumentNode, InsertPosition, KeyValueNode, KeyValuesObject, KeyValuesValue, NodeType,
+    Position, QueryMatch, StringNode, ValueNode, WhitespaceNode,
+};
+
+/// A path query over KeyValues data or ASTs
+///
+/// Queries extend the dotted paths `KvDocument::get` takes:
+/// - `a.b` selects every `b` directly inside every `a`
+/// - `a..b` selects every `b` at any depth below `a`; a leading `..` searches
+///   the whole document
+/// - `*` matches any key, and keys containing `.`, `[`, `]`, `"` or spaces
+///   can be written quoted: `"my key"`
+/// - `[N]` picks the N-th match within each parent, counting from zero;
+///   negative indexes count from the end
+/// - `[value="..."]` and `[value!="..."]` keep matches whose value is (or is
+///   not) the given string or number
+///
+/// Filters apply left to right, so `Game[value!="core"][0]` is the first
+/// `Game` that is not `core`. For example
+/// `GameInfo.FileSystem.SearchPaths.Game[value="citadel/addons"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvQuery {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    /// Matches at any depth instead of only direct children
+    recursive: bool,
+    /// Key to match, `None` for `*`
+    name: Option<String>,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Index(isize),
+    Value { literal: String, negated: bool },
+}
+
+/// One step from a parent to a key-value inside it
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    key: String,
+    /// Which occurrence of `key` within the parent
+    occurrence: usize,
+    /// Whether `key` appears more than once within the parent
+    repeated: bool,
+    /// Position among all key-values of the parent
+    position: usize,
+}
+
+type Location = Vec<Step>;
+
+/// Something a query can walk: a data object or an AST node list
+trait QueryNode<'a>: Copy {
+    /// Key-values directly inside this node, in document order
+    fn children(self) -> Vec<(&'a str, Self)>;
+    fn value_equals(self, literal: &str) -> bool;
+}
+
+#[derive(Clone, Copy)]
+enum DataNode<'a> {
+    Object(&'a KeyValuesObject),
+    Value(&'a KeyValuesValue),
+}
+
+impl<'a> QueryNode<'a> for DataNode<'a> {
+    fn children(self) -> Vec<(&'a str, Self)> {
+        let obj = match self {
+            DataNode::Object(obj) | DataNode::Value(KeyValuesValue::Object(obj)) => obj,
+            DataNode::Value(_) => return Vec::new(),
+        };
+        obj.entries()
+            .into_iter()
+            .map(|(key, value)| (key, DataNode::Value(value)))
+            .collect()
+    }
+
+    fn value_equals(self, literal: &str) -> bool {
+        match self {
+            DataNode::Value(KeyValuesValue::String(s)) => s == literal,
+            DataNode::Value(KeyValuesValue::Number(n)) => {
+                literal.parse::<f64>().is_ok_and(|l| l == *n)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum AstRef<'a> {
+    Children(&'a [AstNode]),
+    KeyValue(&'a KeyValueNode),
+}
+
+impl<'a> QueryNode<'a> for AstRef<'a> {
+    fn children(self) -> Vec<(&'a str, Self)> {
+        let children = match self {
+            AstRef::Children(children) => children,
+            AstRef::KeyValue(kv) => match &kv.value {
+                ValueNode::Object(obj) => &obj.children[..],
+                _ => return Vec::new(),
+            },
+        };
+        children
+            .iter()
+            .filter_map(|child| match child {
+                AstNode::KeyValue(kv) => Some((kv.key.value.as_str(), AstRef::KeyValue(kv))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn value_equals(self, literal: &str) -> bool {
+        let AstRef::KeyValue(kv) = self else {
+            return false;
+        };
+        match &kv.value {
+            ValueNode::String(s) => s.value == literal,
+            ValueNode::Number(n) => {
+                n.raw == literal || literal.parse::<f64>().is_ok_and(|l| l == n.value)
+            }
+            ValueNode::Object(_) => false,
+        }
+    }
+}
+
+impl KvQuery {
+    pub fn parse(query: &str) -> Result<Self> {
+        QueryParser::new(query).parse()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Every key-value the query selects, in document order
+    pub fn select(&self, data: &KeyValuesObject) -> Vec<QueryMatch> {
+        self.evaluate(DataNode::Object(data))
+            .into_iter()
+            .filter_map(|(location, node)| match node {
+                DataNode::Value(value) => Some(QueryMatch {
+                    path: format_location(&location),
+                    value: value.clone(),
+                }),
+                DataNode::Object(_) => None,
+            })
+            .collect()
+    }
+
+    /// Every key-value node the query selects, in document order
+    pub fn select_ast<'a>(&self, ast: &'a DocumentNode) -> Vec<&'a KeyValueNode> {
+        self.evaluate(AstRef::Children(&ast.children))
+            .into_iter()
+            .filter_map(|(_, node)| match node {
+                AstRef::KeyValue(kv) => Some(kv),
+                AstRef::Children(_) => None,
+            })
+            .collect()
+    }
+
+    /// Replace the value of every match, returning how many there were
+    pub fn set(&self, data: &mut KeyValuesObject, value: &KeyValuesValue) -> Result<usize> {
+        reject_array(value)?;
+        self.edit_data(data, |parent, step| {
+            if let Some(slot) = parent.get_occurrence_mut(&step.key, step.occurrence) {
+                *slot = value.clone();
+            }
+        })
+    }
+
+    /// Add `key` next to every match, returning how many there were
+    pub fn insert(
+        &self,
+        data: &mut KeyValuesObject,
+        position: InsertPosition,
+        key: &str,
+        value: &KeyValuesValue,
+    ) -> Result<usize> {
+        reject_array(value)?;
+        self.edit_data(data, |parent, step| {
+            if let Some(index) = parent.entry_index(&step.key, step.occurrence) {
+                let index = match position {
+                    InsertPosition::Before => index,
+                    InsertPosition::After => index + 1,
+                };
+                parent.insert_entry(index, key.to_string(), value.clone());
+            }
+        })
+    }
+
+    /// Remove every match, returning how many there were
+    pub fn delete(&self, data: &mut KeyValuesObject) -> Result<usize> {
+        self.edit_data(data, |parent, step| {
+            parent.remove_occurrence(&step.key, step.occurrence);
+        })
+    }
+
+    /// Replace the value of every matching node, keeping the surrounding
+    /// formatting
+    pub fn set_ast(&self, ast: &mut DocumentNode, value: &KeyValuesValue) -> Result<usize> {
+        let value = DiffApplicator::create_value_node(value)?;
+        self.edit_ast(ast, |children, index| {
+            if let AstNode::KeyValue(kv) = &mut children[index] {
+                kv.value = value.clone();
+            }
+            Ok(())
+        })
+    }
+
+    /// Add `key` next to every matching node, indented like the match
+    pub fn insert_ast(
+        &self,
+        ast: &mut DocumentNode,
+        position: InsertPosition,
+        key: &str,
+        value: &KeyValuesValue,
+    ) -> Result<usize> {
+        let value = DiffApplicator::create_value_node(value)?;
+        self.edit_ast(ast, |children, index| {
+            let AstNode::KeyValue(target) = &children[index] else {
+                return Ok(());
+            };
+            let indent = match index.checked_sub(1).map(|i| &children[i]) {
+                Some(AstNode::Whitespace(ws)) => line_indent(&ws.raw),
+                _ => "\n".to_string(),
+            };
+            let node = AstNode::KeyValue(Box::new(KeyValueNode {
+                node_type: NodeType::KeyValue,
+                start: ORIGIN,
+                end: ORIGIN,
+                raw: String::new(),
+                key: quoted_string(key),
+                value: value.clone(),
+                separator: Some(
+                    target
+                        .separator
+                        .clone()
+                        .unwrap_or_else(|| whitespace("    ")),
+                ),
+                conditional_separator: None,
+                conditional: None,
+            }));
+            let indent = AstNode::Whitespace(whitespace(&indent));
+
+            match position {
+                InsertPosition::Before => {
+                    children.insert(index, indent);
+                    children.insert(index, node);
+                }
+                InsertPosition::After => {
+                    children.insert(index + 1, node);
+                    children.insert(index + 1, indent);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove every matching node along with the whitespace in front of it
+    pub fn delete_ast(&self, ast: &mut DocumentNode) -> Result<usize> {
+        self.edit_ast(ast, |children, index| {
+            children.remove(index);
+            if index > 0 && matches!(children[index - 1], AstNode::Whitespace(_)) {
+                children.remove(index - 1);
+            }
+            Ok(())
+        })
+    }
+
+    fn evaluate<'a, N: QueryNode<'a>>(&self, root: N) -> Vec<(Location, N)> {
+        let mut current = vec![(Vec::new(), root)];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for (location, node) in &current {
+                if segment.recursive {
+                    segment.descend(*node, location, &mut next);
+                } else {
+                    segment.select(*node, location, &mut next);
+                }
+            }
+            // Recursive segments can reach the same key-value twice
+            next.sort_by(|(a, _), (b, _)| {
+                a.iter()
+                    .map(|step| step.position)
+                    .cmp(b.iter().map(|step| step.position))
+            });
+            next.dedup_by(|(a, _), (b, _)| a == b);
+            current = next;
+        }
+        current
+    }
+
+    fn locations<'a, N: QueryNode<'a>>(&self, root: N) -> Vec<Location> {
+        self.evaluate(root)
+            .into_iter()
+            .map(|(location, _)| location)
+            .collect()
+    }
+
+    /// Run `edit` on the parent of every match. Later matches are edited
+    /// first so earlier locations stay valid.
+    fn edit_data(
+        &self,
+        data: &mut KeyValuesObject,
+        mut edit: impl FnMut(&mut KeyValuesObject, &Step),
+    ) -> Result<usize> {
+        let locations = self.locations(DataNode::Object(data));
+        for location in locations.iter().rev() {
+            let (step, parents) = location.split_last().expect("matches are never the root");
+            edit(data_parent(data, parents)?, step);
+        }
+        Ok(locations.len())
+    }
+
+    fn edit_ast(
+        &self,
+        ast: &mut DocumentNode,
+        mut edit: impl FnMut(&mut Vec<AstNode>, usize) -> Result<()>,
+    ) -> Result<usize> {
+        let locations = self.locations(AstRef::Children(&ast.children));
+        for location in locations.iter().rev() {
+            let (step, parents) = location.split_last().expect("matches are never the root");
+            let children = ast_parent(&mut ast.children, parents)?;
+            let index = key_value_index(children, step).ok_or_else(|| KvError::PathNotFound {
+                path: format_location(location),
+            })?;
+            edit(children, index)?;
+        }
+        Ok(locations.len())
+    }
+}
+
+impl Segment {
+    /// Children of `node` this segment matches
+    fn select<'a, N: QueryNode<'a>>(
+        &self,
+        node: N,
+        location: &Location,
+        out: &mut Vec<(Location, N)>,
+    ) {
+        let mut candidates: Vec<(Step, N)> = child_steps(node)
+            .into_iter()
+            .filter(|(step, _)| self.name.as_ref().is_none_or(|name| *name == step.key))
+            .collect();
+
+        for filter in &self.filters {
+            candidates = match filter {
+                Filter::Index(index) => {
+                    let len = candidates.len() as isize;
+                    let index = if *index < 0 { len + index } else { *index };
+                    if (0..len).contains(&index) {
+                        vec![candidates.swap_remove(index as usize)]
+                    } else {
+                        Vec::new()
+                    }
+                }
+                Filter::Value { literal, negated } => candidates
+                    .into_iter()
+                    .filter(|(_, child)| child.value_equals(literal) != *negated)
+                    .collect(),
+            };
+        }
+
+        out.extend(candidates.into_iter().map(|(step, child)| {
+            let mut location = location.clone();
+            location.push(step);
+            (location, child)
+        }));
+    }
+
+    /// Descendants of `node` at any depth this segment matches
+    fn descend<'a, N: QueryNode<'a>>(
+        &self,
+        node: N,
+        location: &Location,
+        out: &mut Vec<(Location, N)>,
+    ) {
+        self.select(node, location, out);
+        for (step, child) in child_steps(node) {
+            let mut location = location.clone();
+            location.push(step);
+            self.descend(child, &location, out);
+        }
+    }
+}
+
+fn child_steps<'a, N: QueryNode<'a>>(node: N) -> Vec<(Step, N)> {
+    let children = node.children();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (key, _) in &children {
+        *counts.entry(key).or_default() += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    children
+        .into_iter()
+        .enumerate()
+        .map(|(position, (key, child))| {
+            let occurrence = seen.entry(key).or_default();
+            let step = Step {
+                key: key.to_string(),
+                occurrence: *occurrence,
+                repeated: counts[key] > 1,
+                position,
+            };
+            *occurrence += 1;
+            (step, child)
+        })
+        .collect()
+}
+
+fn data_parent<'d>(
+    data: &'d mut KeyValuesObject,
+    steps: &[Step],
+) -> Result<&'d mut KeyValuesObject> {
+    let mut current = data;
+    for step in steps {
+        match current.get_occurrence_mut(&step.key, step.occurrence) {
+            Some(KeyValuesValue::Object(obj)) => current = obj,
+            _ => {
+                return Err(KvError::PathNotObject {
+                    path: format_location(steps),
+                    part: step.key.clone(),
+                });
+            }
+        }
+    }
+    Ok(current)
+}
+
+fn ast_parent<'d>(children: &'d mut Vec<AstNode>, steps: &[Step]) -> Result<&'d mut Vec<AstNode>> {
+    let mut current = children;
+    for step in steps {
+        let index = key_value_index(current, step);
+        match index.map(|index| &mut current[index]) {
+            Some(AstNode::KeyValue(kv)) => match &mut kv.value {
+                ValueNode::Object(obj) => current = &mut obj.children,
+                _ => {
+                    return Err(KvError::PathNotObject {
+                        path: format_location(steps),
+                        part: step.key.clone(),
+                    });
+                }
+            },
+            _ => {
+                return Err(KvError::PathNotFound {
+                    path: format_location(steps),
+                });
+            }
+        }
+    }
+    Ok(current)
+}
+
+/// Index in `children` of the key-value a step points at
+fn key_value_index(children: &[AstNode], step: &Step) -> Option<usize> {
+    children
+        .iter()
+        .enumerate()
+        .filter(|(_, child)| matches!(child, AstNode::KeyValue(kv) if kv.key.value == step.key))
+        .nth(step.occurrence)
+        .map(|(index, _)| index)
+}
+
+fn reject_array(value: &KeyValuesValue) -> Result<()> {
+    match value {
+        KeyValuesValue::Array(_) => Err(KvError::ArraySerializationError),
+        _ => Ok(()),
+    }
+}
+
+/// A query that selects exactly the key-value at `location`
+fn format_location(location: &[Step]) -> String {
+    location
+        .iter()
+        .map(|step| {
+            let key = format_key(&step.key);
+            if step.repeated {
+                format!("{key}[{}]", step.occurrence)
+            } else {
+                key
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn format_key(key: &str) -> String {
+    let plain = !key.is_empty()
+        && key != "*"
+        && !key
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '.' | '[' | ']' | '"' | '\\'));
+    if plain {
+        key.to_string()
+    } else {
+        format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// The line break and indentation at the end of a whitespace run
+fn line_indent(raw: &str) -> String {
+    match raw.rfind('\n') {
+        Some(start) if raw[..start].ends_with('\r') => raw[start - 1..].to_string(),
+        Some(start) => raw[start..].to_string(),
+        None => "\n".to_string(),
+    }
+}
+
+const ORIGIN: Position = Position {
+    offset: 0,
+    line: 0,
+    column: 0,
+};
+
+fn whitespace(raw: &str) -> WhitespaceNode {
+    WhitespaceNode {
+        node_type: NodeType::Whitespace,
+        start: ORIGIN,
+        end: ORIGIN,
+        raw: raw.to_string(),
+        value: raw.to_string(),
+    }
+}
+
+fn quoted_string(value: &str) -> StringNode {
+    StringNode {
+        node_type: NodeType::String,
+        start: ORIGIN,
+        end: ORIGIN,
+        raw: format!("\"{value}\""),
+        value: value.to_string(),
+        quoted: true,
+        quote_char: Some("\"".to_string()),
+    }
+}
+
+impl FromStr for KvQuery {
+    type Err = KvError;
+
+    fn from_str(query: &str) -> Result<Self> {
+        Self::parse(query)
+    }
+}
+
+impl fmt::Display for KvQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+struct QueryParser<'q> {
+    source: &'q str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'q> QueryParser<'q> {
+    fn new(source: &'q str) -> Self {
+        Self {
+            source,
+            chars: source.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn parse(mut self) -> Result<KvQuery> {
+        let mut segments = Vec::new();
+        while segments.is_empty() || !self.at_end() {
+            let recursive = if self.eat_str("..") {
+                true
+            } else if segments.is_empty() || self.eat('.') {
+                false
+            } else {
+                return Err(self.error("expected '.' or '..'"));
+            };
+
+            let name = self.parse_name()?;
+            let mut filters = Vec::new();
+            while self.eat('[') {
+                filters.push(self.parse_filter()?);
+            }
+            segments.push(Segment {
+                recursive,
+                name,
+                filters,
+            });
+        }
+
+        Ok(KvQuery {
+            source: self.source.to_string(),
+            segments,
+        })
+    }
+
+    fn parse_name(&mut self) -> Result<Option<String>> {
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Ok(None)
+            }
+            Some('"') => self.parse_quoted().map(Some),
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| !matches!(c, '.' | '[' | ']' | '"'))
+                {
+                    self.pos += 1;
+                }
+                if start == self.pos {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(Some(self.chars[start..self.pos].iter().collect()))
+            }
+        }
+    }
+
+    fn parse_quoted(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next() {
+                    Some(c) => value.push(c),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => value.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// The part of a filter after `[`
+    fn parse_filter(&mut self) -> Result<Filter> {
+        self.skip_whitespace();
+        let filter = if self.peek().is_some_and(|c| c == '-' || c.is_ascii_digit()) {
+            let start = self.pos;
+            self.pos += 1;
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+            let digits: String = self.chars[start..self.pos].iter().collect();
+            let index = digits
+                .parse()
+                .map_err(|_| self.error(&format!("invalid index '{digits}'")))?;
+            Filter::Index(index)
+        } else if self.eat_str("value") {
+            self.skip_whitespace();
+            let negated = if self.eat_str("!=") {
+                true
+            } else if self.eat('=') {
+                false
+            } else {
+                return Err(self.error("expected '=' or '!='"));
+            };
+            self.skip_whitespace();
+            let literal = if self.peek() == Some('"') {
+                self.parse_quoted()?
+            } else {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != ']') {
+                    self.pos += 1;
+                }
+                self.chars[start..self.pos]
+                    .iter()
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            };
+            Filter::Value { literal, negated }
+        } else {
+            return Err(self.error("expected an index or a value filter"));
+        };
+
+        self.skip_whitespace();
+        if !self.eat(']') {
+            return Err(self.error("expected ']'"));
+        }
+        Ok(filter)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_str(&mut self, expected: &str) -> bool {
+        let len = expected.chars().count();
+        let matches = self.chars.len() >= self.pos + len
+            && self.chars[self.pos..self.pos + len]
+                .iter()
+                .copied()
+                .eq(expected.chars());
+        if matches {
+            self.pos += len;
+        }
+        matches
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> KvError {
+        KvError::InvalidQuery {
+            query: self.source.to_string(),
+            message: format!("{message} at position {}", self.pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::KvDocument;
+
+    const GAMEINFO: &str = r#""GameInfo"
+{
+    "game"    "citadel"
+    "FileSystem"
+    {
+        "SearchPaths"
+        {
+            "Game"    "citadel/addons"
+            "Mod"    "citadel"
+            "Game"    "citadel"
+            "Game"    "core"
+        }
+    }
+    "Mods"
+    {
+        "Mod"    "first"
+    }
+}
+"#;
+
+    fn s(value: &str) -> KeyValuesValue {
+        KeyValuesValue::String(value.to_string())
+    }
+
+    fn doc() -> KvDocument {
+        let mut doc = KvDocument::new();
+        doc.load_from_string(GAMEINFO).unwrap();
+        doc
+    }
+
+    fn paths(matches: &[QueryMatch]) -> Vec<&str> {
+        matches.iter().map(|m| m.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_indexes_wildcards_and_recursive_descent() {
+        let doc = doc();
+
+        let third = doc
+            .query("GameInfo.FileSystem.SearchPaths.Game[2]")
+            .unwrap();
+        assert_eq!(third[0].value, s("core"));
+        assert_eq!(
+            doc.query("GameInfo.FileSystem.SearchPaths.Game[-1]")
+                .unwrap(),
+            third
+        );
+
+        let mods = doc.query("..Mod").unwrap();
+        assert_eq!(
+            paths(&mods),
+            ["GameInfo.FileSystem.SearchPaths.Mod", "GameInfo.Mods.Mod"]
+        );
+
+        let all = doc.query("GameInfo.FileSystem.SearchPaths.*").unwrap();
+        assert_eq!(
+            paths(&all),
+            [
+                "GameInfo.FileSystem.SearchPaths.Game[0]",
+                "GameInfo.FileSystem.SearchPaths.Mod",
+                "GameInfo.FileSystem.SearchPaths.Game[1]",
+                "GameInfo.FileSystem.SearchPaths.Game[2]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_value_predicates() {
+        let doc = doc();
+
+        let addons = doc
+            .query(r#"GameInfo.FileSystem.SearchPaths.Game[value="citadel/addons"]"#)
+            .unwrap();
+        assert_eq!(paths(&addons), ["GameInfo.FileSystem.SearchPaths.Game[0]"]);
+
+        let others = doc.query(r#"..Game[value!="citadel/addons"][0]"#).unwrap();
+        assert_eq!(others[0].value, s("citadel"));
+    }
+
+    #[test]
+    fn test_edits_keep_formatting() {
+        let mut doc = doc();
+
+        let inserted = doc
+            .query_insert(
+                r#"..SearchPaths.Game[value="citadel"]"#,
+                InsertPosition::Before,
+                "Game",
+                s("citadel/win"),
+            )
+            .unwrap();
+        assert_eq!(inserted, 1);
+        assert_eq!(doc.query_set("..Mod", s("changed")).unwrap(), 2);
+        assert_eq!(
+            doc.query_delete("GameInfo.FileSystem.SearchPaths.Game[-1]")
+                .unwrap(),
+            1
+        );
+
+        let expected = GAMEINFO
+            .replace(
+                "            \"Game\"    \"citadel\"\n",
+                "            \"Game\"    \"citadel/win\"\n            \"Game\"    \"citadel\"\n",
+            )
+            .replace("\"Mod\"    \"citadel\"", "\"Mod\"    \"changed\"")
+            .replace("\"Mod\"    \"first\"", "\"Mod\"    \"changed\"")
+            .replace("\n            \"Game\"    \"core\"", "");
+        assert_eq!(doc.serialize().unwrap(), expected);
+        assert_eq!(
+            doc.get_all("GameInfo.FileSystem.SearchPaths.Game"),
+            vec![&s("citadel/addons"), &s("citadel/win"), &s("citadel")]
+        );
+    }
+
+    #[test]
+    fn test_data_layer_edits() {
+        let mut data = doc().get_data().clone();
+        let query = KvQuery::parse("GameInfo.FileSystem.SearchPaths.Game[1]").unwrap();
+
+        query
+            .insert(&mut data, InsertPosition::After, "Write", &s("citadel"))
+            .unwrap();
+        query.delete(&mut data).unwrap();
+
+        let search = KvQuery::parse("..SearchPaths.*").unwrap().select(&data);
+        let entries: Vec<&str> = paths(&search)
+            .into_iter()
+            .map(|path| path.rsplit('.').next().unwrap())
+            .collect();
+        assert_eq!(entries, ["Game[0]", "Mod", "Write", "Game[1]"]);
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        for query in ["", "a.", "a[", "a[x]", "a[value~\"x\"]", "a\"b", "\"open"] {
+            assert!(
+                matches!(KvQuery::parse(query), Err(KvError::InvalidQuery { .. })),
+                "{query} should not parse"
+            );
+        }
+        assert_eq!(
+            KvQuery::parse(r#""my.key"..*[0]"#).unwrap().segments.len(),
+            2
+        );
+    }
+}
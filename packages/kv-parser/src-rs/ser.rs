@@ -0,0 +1,530 @@
+//! Serializing typed values to KeyValues text with serde.
+//!
+//! Everything is written as a quoted string the way Steam writes its own
+//! files: numbers in their decimal form and booleans as `"1"` / `"0"`. A
+//! sequence becomes its key repeated once per item, an empty one an empty
+//! block, and `None` leaves the key out altogether.
+
+use serde::Serialize;
+use serde::ser::{
+    self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+
+use crate::error::{KvError, Result};
+use crate::object::KeyValuesObject;
+use crate::serializer::Serializer;
+use crate::types::{KeyValuesValue, SerializeOptions};
+
+/// Serialize `value` under a root key named after its type, e.g. a
+/// `struct GameInfo` is written as `"GameInfo" { ... }`
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    let mut name = None;
+    let data = value.serialize(ValueSerializer {
+        name: Some(&mut name),
+    })?;
+    let name = name.ok_or_else(|| error("Only named structs can be written without a root key"))?;
+    write_root(name, data)
+}
+
+/// Serialize `value` under the root key `key`
+pub fn to_string_with_key<T: Serialize + ?Sized>(value: &T, key: &str) -> Result<String> {
+    write_root(key, value.serialize(ValueSerializer { name: None })?)
+}
+
+fn write_root(key: &str, data: Option<KeyValuesValue>) -> Result<String> {
+    let Some(value @ KeyValuesValue::Object(_)) = data else {
+        return Err(error("The root value must be a struct or a map"));
+    };
+    let mut root = KeyValuesObject::new();
+    root.insert(key.to_string(), value);
+
+    Serializer::new(SerializeOptions {
+        use_tabs: true,
+        quote_all_strings: true,
+        ..SerializeOptions::default()
+    })
+    .serialize_data(&root)
+}
+
+fn error(message: &str) -> KvError {
+    KvError::Data {
+        message: message.to_string(),
+        position: None,
+    }
+}
+
+fn string(value: impl ToString) -> Result<Option<KeyValuesValue>> {
+    Ok(Some(KeyValuesValue::String(value.to_string())))
+}
+
+/// Turns a value into the data model. `None` stands for a value that is
+/// left out, so an `Option` field with nothing in it writes no key at all.
+struct ValueSerializer<'a> {
+    /// Where the root value reports its type name, for `to_string`
+    name: Option<&'a mut Option<&'static str>>,
+}
+
+impl ValueSerializer<'_> {
+    fn nested() -> Self {
+        ValueSerializer { name: None }
+    }
+
+    fn named(self, name: &'static str) {
+        if let Some(slot) = self.name {
+            *slot = Some(name);
+        }
+    }
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = ObjectSerializer;
+    type SerializeStruct = ObjectSerializer;
+    type SerializeStructVariant = ObjectSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        string(if v { "1" } else { "0" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        string(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        Err(error("Byte arrays cannot be written as KeyValues"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        string("")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        string("")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        string(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let data = value.serialize(ValueSerializer::nested())?;
+        self.named(name);
+        Ok(data)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let mut object = KeyValuesObject::new();
+        if let Some(data) = value.serialize(ValueSerializer::nested())? {
+            object.insert(variant.to_string(), data);
+        }
+        Ok(Some(KeyValuesValue::Object(object)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(ObjectSerializer::new(None))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.named(name);
+        Ok(ObjectSerializer::new(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(ObjectSerializer::new(Some(variant)))
+    }
+}
+
+/// Wrap a variant's content in an object keyed by the variant name
+fn in_variant(variant: Option<&'static str>, value: KeyValuesValue) -> KeyValuesValue {
+    match variant {
+        Some(variant) => {
+            let mut object = KeyValuesObject::new();
+            object.insert(variant.to_string(), value);
+            KeyValuesValue::Object(object)
+        }
+        None => value,
+    }
+}
+
+struct SeqSerializer {
+    items: Vec<KeyValuesValue>,
+    variant: Option<&'static str>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match value.serialize(ValueSerializer::nested())? {
+            Some(KeyValuesValue::Array(_)) => {
+                Err(error("Nested sequences cannot be written as KeyValues"))
+            }
+            Some(item) => {
+                self.items.push(item);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// An empty sequence has no key to repeat, so it is written as an empty
+    /// block, which reads back as an empty list
+    fn finish(self) -> Result<Option<KeyValuesValue>> {
+        let value = if self.items.is_empty() {
+            KeyValuesValue::Object(KeyValuesObject::new())
+        } else {
+            KeyValuesValue::Array(self.items)
+        };
+        Ok(Some(in_variant(self.variant, value)))
+    }
+}
+
+impl SerializeSeq for SeqSerializer {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for SeqSerializer {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleVariant for SeqSerializer {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+struct ObjectSerializer {
+    object: KeyValuesObject,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl ObjectSerializer {
+    fn new(variant: Option<&'static str>) -> Self {
+        Self {
+            object: KeyValuesObject::new(),
+            key: None,
+            variant,
+        }
+    }
+
+    /// An array value stands for the key repeated once per item
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        if let Some(data) = value.serialize(ValueSerializer::nested())? {
+            self.object.insert(key, data);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<KeyValuesValue>> {
+        Ok(Some(in_variant(
+            self.variant,
+            KeyValuesValue::Object(self.object),
+        )))
+    }
+}
+
+impl SerializeMap for ObjectSerializer {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer::nested())? {
+            Some(KeyValuesValue::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(error("Map keys must be strings, numbers or unit variants")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value is called after serialize_key");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeStruct for ObjectSerializer {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeStructVariant for ObjectSerializer {
+    type Ok = Option<KeyValuesValue>;
+    type Error = KvError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::de::from_str;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct SearchPaths {
+        game: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        write: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct GameInfo {
+        game: String,
+        nomodels: bool,
+        #[serde(rename = "SearchPaths")]
+        search_paths: SearchPaths,
+        hidden_maps: BTreeMap<String, u32>,
+    }
+
+    fn game_info() -> GameInfo {
+        GameInfo {
+            game: "citadel".to_string(),
+            nomodels: true,
+            search_paths: SearchPaths {
+                game: vec!["citadel/addons".to_string(), "core".to_string()],
+                write: None,
+            },
+            hidden_maps: BTreeMap::from([("test_speakers".to_string(), 1)]),
+        }
+    }
+
+    #[test]
+    fn test_struct_under_its_name_with_repeated_keys() {
+        let text = to_string(&game_info()).unwrap();
+
+        assert_eq!(
+            text,
+            "\"GameInfo\"\n{\n\t\"game\"    \"citadel\"\n\t\"nomodels\"    \"1\"\n\t\"SearchPaths\"\n\t{\n\t\t\"Game\"    \"citadel/addons\"\n\t\t\"Game\"    \"core\"\n\t}\n\t\"hidden_maps\"\n\t{\n\t\t\"test_speakers\"    \"1\"\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_through_from_str() {
+        let text = to_string_with_key(&game_info(), "GameInfo").unwrap();
+
+        assert_eq!(from_str::<GameInfo>(&text).unwrap(), game_info());
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Root {
+        item: Vec<Item>,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_vec_round_trip_by_length() {
+        for len in 0..=2 {
+            let root = Root {
+                item: (0..len)
+                    .map(|i| Item {
+                        name: format!("item{i}"),
+                    })
+                    .collect(),
+                tags: (0..len).map(|i| format!("tag{i}")).collect(),
+            };
+
+            let text = to_string(&root).unwrap();
+
+            assert_eq!(
+                from_str::<Root>(&text).unwrap(),
+                root,
+                "{len} items:\n{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_values_without_a_root_object() {
+        assert!(to_string(&vec![1, 2]).is_err());
+        assert!(to_string_with_key("text", "root").is_err());
+        assert!(to_string_with_key(&vec![vec![1]], "root").is_err());
+    }
+}
@@ -30,6 +30,7 @@ impl Serializer {
             AstNode::Comment(c) => c.raw.clone(),
             AstNode::Whitespace(w) => w.raw.clone(),
             AstNode::Conditional(c) => c.raw.clone(),
+            AstNode::Directive(d) => d.raw.clone(),
         }
     }
 
@@ -102,12 +103,14 @@ impl Serializer {
         let next_indent_str = self.get_indent(indent + 1);
         let mut result = String::from("{\n");
 
-        for (key, value) in obj {
+        // Entries come back in document order with repeated keys expanded,
+        // so interleaved duplicates keep their original positions
+        for (key, value) in obj.entries() {
             let formatted_key = self.format_value(key);
 
             match value {
                 KeyValuesValue::Array(arr) => {
-                    // Handle duplicate keys
+                    // Arrays nested inside an array value
                     for item in arr {
                         if matches!(item, KeyValuesValue::Object(_)) {
                             result.push_str(&format!("{}{}\n", next_indent_str, formatted_key));
@@ -166,7 +169,7 @@ impl Serializer {
         }
     }
 
-    fn needs_quotes(&self, value: &str) -> bool {
+    pub(crate) fn needs_quotes(&self, value: &str) -> bool {
         if self.options.quote_all_strings {
             return true;
         }
@@ -191,7 +194,7 @@ impl Serializer {
         }
     }
 
-    fn escape_string(&self, value: &str) -> String {
+    pub(crate) fn escape_string(&self, value: &str) -> String {
         value
             .replace('\\', "\\\\")
             .replace('"', "\\\"")
@@ -315,6 +318,49 @@ mod tests {
         assert!(result.contains("\"Value\""));
     }
 
+    #[test]
+    fn test_data_round_trip_keeps_order_and_duplicates() {
+        let input = r#"
+"GameInfo"
+{
+    "Zeta" "1"
+    "SearchPaths"
+    {
+        "Game" "citadel/addons"
+        "Mod" "citadel"
+        "Write" "citadel"
+        "Game" "citadel"
+    }
+    "Alpha" "2"
+}
+"#;
+        let result = Parser::parse(input, ParseOptions::default()).unwrap();
+        let serializer = Serializer::new(SerializeOptions::default());
+        let serialized = serializer.serialize_data(&result.data).unwrap();
+
+        let keys: Vec<&str> = serialized
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|token| !matches!(*token, "{" | "}"))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "GameInfo",
+                "Zeta",
+                "SearchPaths",
+                "Game",
+                "Mod",
+                "Write",
+                "Game",
+                "Alpha"
+            ]
+        );
+
+        let reparsed = Parser::parse(&serialized, ParseOptions::default()).unwrap();
+        assert_eq!(reparsed.data, result.data);
+    }
+
     #[test]
     fn test_round_trip() {
         let input = r#"
@@ -1,5 +1,8 @@
 use crate::error::{KvError, Result};
-use crate::types::{CommentStyle, Token, TokenMetadata, TokenType};
+use crate::types::{
+    CommentStyle, Diagnostic, DiagnosticFix, DiagnosticSeverity, Position, Token, TokenMetadata,
+    TokenType,
+};
 
 const MAX_TOKEN_LENGTH: usize = 1024;
 
@@ -508,6 +511,173 @@ impl Tokenizer {
 
         Ok(tokens)
     }
+
+    /// Tokenize without stopping at the first error. Lexical errors are
+    /// reported as diagnostics: an unterminated string is closed at the end
+    /// of its line, anything else turns the rest of the line into an
+    /// `Error` token so the parser can pick up again on the next one.
+    pub fn tokenize_recovering(&mut self) -> (Vec<Token>, Vec<Diagnostic>) {
+        let mut tokens = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        loop {
+            let start = self.position();
+            let token = match self.next_token() {
+                Ok(token) => {
+                    if token.token_type == TokenType::String && token.raw.contains('\n') {
+                        diagnostics.push(Self::multiline_string(start, &token.raw));
+                    }
+                    if token.token_type == TokenType::Error {
+                        let end = self.position();
+                        diagnostics.push(Diagnostic {
+                            severity: DiagnosticSeverity::Error,
+                            start,
+                            end,
+                            message: token.value.clone(),
+                            fix: Some(DiagnosticFix {
+                                description: "Close the comment".to_string(),
+                                start: end,
+                                end,
+                                replacement: "*/".to_string(),
+                            }),
+                        });
+                    }
+                    token
+                }
+                Err(error) => {
+                    self.rewind(start);
+                    let (token, diagnostic) = self.recover_line(&error);
+                    diagnostics.push(diagnostic);
+                    token
+                }
+            };
+
+            let is_eof = token.token_type == TokenType::Eof;
+            tokens.push(token);
+            if is_eof {
+                break;
+            }
+        }
+
+        (tokens, diagnostics)
+    }
+
+    /// A quoted string that spans lines is valid, but far more often a
+    /// missing closing quote that swallowed the following lines
+    fn multiline_string(start: Position, raw: &str) -> Diagnostic {
+        let first_line = raw.split('\n').next().unwrap_or_default();
+        let first_line = first_line.strip_suffix('\r').unwrap_or(first_line);
+        let line_end = Position {
+            offset: start.offset + first_line.chars().count(),
+            line: start.line,
+            column: start.column + first_line.chars().count(),
+        };
+        Diagnostic {
+            severity: DiagnosticSeverity::Warning,
+            start,
+            end: line_end,
+            message: "String continues onto the next line".to_string(),
+            fix: Some(DiagnosticFix {
+                description: "Close the string".to_string(),
+                start: line_end,
+                end: line_end,
+                replacement: "\"".to_string(),
+            }),
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            offset: self.pos,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn rewind(&mut self, to: Position) {
+        self.pos = to.offset;
+        self.line = to.line;
+        self.column = to.column;
+    }
+
+    /// Consume up to the end of the current line after `error` was raised
+    /// at the current position
+    fn recover_line(&mut self, error: &KvError) -> (Token, Diagnostic) {
+        let start = self.position();
+        let unterminated = matches!(
+            error,
+            KvError::UnterminatedString { .. } | KvError::UnexpectedEof { .. }
+        ) && self.current_char() == Some('"');
+
+        let mut raw = String::new();
+        while let Some(ch) = self.current_char() {
+            if ch == '\n' || ch == '\r' {
+                break;
+            }
+            raw.push(ch);
+            self.advance();
+        }
+        let end = self.position();
+
+        let (token_type, value, message, fix) = if unterminated {
+            let fix = DiagnosticFix {
+                description: "Close the string".to_string(),
+                start: end,
+                end,
+                replacement: "\"".to_string(),
+            };
+            let value = raw[1..].to_string();
+            (
+                TokenType::String,
+                value,
+                "Unterminated string".to_string(),
+                Some(fix),
+            )
+        } else {
+            (TokenType::Error, raw.clone(), Self::describe(error), None)
+        };
+
+        let metadata = unterminated.then(|| TokenMetadata {
+            quoted: Some(true),
+            quote_char: Some("\"".to_string()),
+            comment_style: None,
+        });
+        let token = Token {
+            token_type,
+            value,
+            line: start.line,
+            column: start.column,
+            offset: start.offset,
+            raw,
+            metadata,
+        };
+        let diagnostic = Diagnostic {
+            severity: DiagnosticSeverity::Error,
+            start,
+            end,
+            message,
+            fix,
+        };
+        (token, diagnostic)
+    }
+
+    /// Diagnostic message for a lexical error, without the position that
+    /// `KvError`'s display adds
+    fn describe(error: &KvError) -> String {
+        match error {
+            KvError::TokenTooLong { max_length, .. } => {
+                format!("Token is longer than {max_length} characters")
+            }
+            KvError::UnknownDirective { directive, .. } => {
+                format!("Unknown directive '#{directive}'")
+            }
+            KvError::UnexpectedCharacter { character, .. } => {
+                format!("Unexpected character '{character}'")
+            }
+            KvError::UnexpectedEof { .. } => "Unexpected end of input".to_string(),
+            other => other.to_string(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -641,4 +811,36 @@ mod tests {
             _ => panic!("Expected TokenTooLong error"),
         }
     }
+
+    #[test]
+    fn test_tokenize_recovering() {
+        let input = "#import \"x\"\n\"Key\" \"Value";
+        let mut tokenizer = Tokenizer::new(input);
+        let (tokens, diagnostics) = tokenizer.tokenize_recovering();
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "Unknown directive '#import'");
+        assert_eq!(diagnostics[1].message, "Unterminated string");
+        assert_eq!(diagnostics[1].start.line, 2);
+        assert_eq!(diagnostics[1].fix.as_ref().unwrap().start.column, 13);
+
+        assert_eq!(tokens[0].token_type, TokenType::Error);
+        assert_eq!(tokens[0].raw, "#import \"x\"");
+        assert_eq!(tokens[2].value, "Key");
+        assert_eq!(tokens[4].token_type, TokenType::String);
+        assert_eq!(tokens[4].value, "Value");
+        assert_eq!(tokens[5].token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_tokenize_recovering_warns_on_multiline_string() {
+        let input = "\"Key\" \"Value\n\"Next\" \"1\"";
+        let mut tokenizer = Tokenizer::new(input);
+        let (tokens, diagnostics) = tokenizer.tokenize_recovering();
+
+        assert_eq!(tokens[2].value, "Value\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostics[0].end.column, 13);
+    }
 }
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use ts_rs::TS;
 
+pub use crate::object::KeyValuesObject;
+
 /// KeyValues value type - can be string, number, object, or array
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../src/generated/")]
@@ -9,15 +11,12 @@ use ts_rs::TS;
 pub enum KeyValuesValue {
     String(String),
     Number(f64),
-    Object(KeyValuesObject),
+    Object(#[ts(as = "HashMap<String, KeyValuesValue>")] KeyValuesObject),
     Array(Vec<KeyValuesValue>),
 }
 
-/// KeyValues object - a map of string keys to values
-pub type KeyValuesObject = HashMap<String, KeyValuesValue>;
-
 /// Position in source file
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub struct Position {
@@ -122,6 +121,9 @@ pub struct SerializeOptions {
     pub quote_all_strings: bool,
     #[serde(default = "default_true")]
     pub minimize_quotes: bool,
+    /// Line up the values of consecutive keys (used by the formatter)
+    #[serde(default)]
+    pub align_values: bool,
 }
 
 impl Default for SerializeOptions {
@@ -131,6 +133,7 @@ impl Default for SerializeOptions {
             use_tabs: false,
             quote_all_strings: false,
             minimize_quotes: default_true(),
+            align_values: false,
         }
     }
 }
@@ -144,8 +147,66 @@ fn default_indent_size() -> usize {
 #[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub struct ParseResult {
+    #[ts(as = "HashMap<String, KeyValuesValue>")]
+    pub data: KeyValuesObject,
+    pub ast: DocumentNode,
+}
+
+/// How serious a parse diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/")]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSeverity {
+    /// The text is not valid KeyValues
+    Error,
+    /// Valid, but probably not what was meant
+    Warning,
+}
+
+/// A text edit that fixes a diagnostic: replace `start..end` with
+/// `replacement`. Insertions have `start == end`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticFix {
+    pub description: String,
+    pub start: Position,
+    pub end: Position,
+    pub replacement: String,
+}
+
+/// A problem found by `Parser::parse_with_diagnostics`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    pub start: Position,
+    pub end: Position,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub fix: Option<DiagnosticFix>,
+}
+
+/// Best-effort parse of a document that may contain errors
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct ParseReport {
+    #[ts(as = "HashMap<String, KeyValuesValue>")]
     pub data: KeyValuesObject,
     pub ast: DocumentNode,
+    /// Every problem found, in document order
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ParseReport {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == DiagnosticSeverity::Error)
+    }
 }
 
 /// Diff operation type
@@ -211,6 +272,136 @@ pub struct DiffStats {
     pub modified: usize,
 }
 
+/// A key changed differently on both sides of a three-way merge
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    /// Dot-separated key path
+    pub path: String,
+    /// Value in the common ancestor, if the key existed there
+    pub base: Option<KeyValuesValue>,
+    /// Value in our document; this is what the merged document keeps
+    pub ours: Option<KeyValuesValue>,
+    /// Value in their document
+    pub theirs: Option<KeyValuesValue>,
+}
+
+/// Serialized result of a three-way merge
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct MergeOutput {
+    /// Merged document, formatted like our document
+    pub merged: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// A key-value selected by a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct QueryMatch {
+    /// Query that selects only this key-value, e.g.
+    /// `GameInfo.FileSystem.SearchPaths.Game[2]`
+    pub path: String,
+    pub value: KeyValuesValue,
+}
+
+/// Where `query_insert` places the new key-value relative to each match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/")]
+#[serde(rename_all = "lowercase")]
+pub enum InsertPosition {
+    Before,
+    After,
+}
+
+/// An edit applied to every key-value a query selects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum QueryEdit {
+    Set {
+        query: String,
+        value: KeyValuesValue,
+    },
+    Insert {
+        query: String,
+        position: InsertPosition,
+        key: String,
+        value: KeyValuesValue,
+    },
+    Delete {
+        query: String,
+    },
+}
+
+/// Serialized result of a query edit
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct QueryEditOutput {
+    /// Edited document, formatted like the input
+    pub content: String,
+    /// Number of key-values the query selected
+    pub count: usize,
+}
+
+/// Text produced by `kv_format` and `kv_serialize_kv3`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct TextOutput {
+    /// Whether `text` holds the result; when false, `error` says why
+    pub ok: bool,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub error: Option<String>,
+}
+
+impl TextOutput {
+    pub fn from_result(result: std::result::Result<String, String>) -> Self {
+        match result {
+            Ok(text) => Self {
+                ok: true,
+                text,
+                error: None,
+            },
+            Err(error) => Self {
+                ok: false,
+                text: String::new(),
+                error: Some(error),
+            },
+        }
+    }
+}
+
+/// Binary KeyValues produced by `kv_serialize_binary`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct BinaryOutput {
+    #[serde(with = "base64_bytes")]
+    #[ts(type = "string")]
+    pub bytes: Vec<u8>, // base64 in JSON
+}
+
+pub(crate) mod base64_bytes {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(D::Error::custom)
+    }
+}
+
 // AST Node types
 
 /// Base node type
@@ -227,6 +418,7 @@ pub enum NodeType {
     Whitespace,
     Conditional,
     Token,
+    Directive,
 }
 
 /// Document node (root)
@@ -356,6 +548,95 @@ pub struct ConditionalNode {
     pub negated: bool,
 }
 
+/// Key whose presence depends on a `[$SYMBOL]` conditional
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalKey {
+    /// Dot-separated key path
+    pub path: String,
+    /// Conditional as written, e.g. `[$LINUX||$OSX]`
+    pub condition: String,
+}
+
+/// Kind of file directive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/")]
+#[serde(rename_all = "lowercase")]
+pub enum DirectiveKind {
+    /// `#include` - the file's keys are appended after the including file's
+    Include,
+    /// `#base` - the file's keys fill in whatever the including file leaves out
+    Base,
+}
+
+/// `#include` / `#base` directive node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct DirectiveNode {
+    #[ts(rename = "type")]
+    #[serde(rename = "type")]
+    pub node_type: NodeType,
+    pub start: Position,
+    pub end: Position,
+    pub raw: String,
+    pub kind: DirectiveKind,
+    /// Path as written in the directive
+    pub path: String,
+    /// The referenced file, once an include resolver has loaded it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub resolved: Option<Box<IncludedDocument>>,
+}
+
+/// A file pulled in by a directive, with its own AST
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct IncludedDocument {
+    /// Resolved path of the file
+    pub file: String,
+    pub ast: DocumentNode,
+}
+
+/// File a key of a resolved document was read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct KeySource {
+    /// Dot-separated key path in the resolved data
+    pub path: String,
+    pub file: String,
+}
+
+/// Document with its `#include` and `#base` directives resolved
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedDocument {
+    /// Path of the root file
+    pub file: String,
+    /// Merged data of the root file and everything it pulls in
+    #[ts(as = "HashMap<String, KeyValuesValue>")]
+    pub data: KeyValuesObject,
+    /// AST of the root file; directive nodes carry the included files' ASTs
+    pub ast: DocumentNode,
+    /// Source file of every key in `data`, in the order they were read
+    pub sources: Vec<KeySource>,
+}
+
+impl ResolvedDocument {
+    /// File the key at a dot-separated path came from. A repeated key reports
+    /// the file of its first occurrence.
+    pub fn source_of(&self, path: &str) -> Option<&str> {
+        self.sources
+            .iter()
+            .find(|source| source.path == path)
+            .map(|source| source.file.as_str())
+    }
+}
+
 /// Token node
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
@@ -390,4 +671,5 @@ pub enum AstNode {
     Comment(CommentNode),
     Whitespace(WhitespaceNode),
     Conditional(ConditionalNode),
+    Directive(DirectiveNode),
 }
@@ -3,12 +3,22 @@ import { CString, dlopen, FFIType, type Pointer, ptr } from "bun:ffi";
 import { dirname, join } from "node:path";
 import { fileURLToPath } from "node:url";
 import type {
+  BinaryOutput,
+  ConditionalKey,
   DiffStats,
   DocumentDiff,
   KeyValuesObject,
+  Kv3Document,
+  MergeOutput,
   ParseOptions,
+  ParseReport,
   ParseResult,
+  QueryEdit,
+  QueryEditOutput,
+  QueryMatch,
+  ResolvedDocument,
   SerializeOptions,
+  TextOutput,
 } from "./types";
 
 const suffix =
@@ -34,6 +44,14 @@ const lib = dlopen(libPath, {
     args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr],
     returns: FFIType.cstring,
   },
+  kv_parse_file: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_parse_with_diagnostics: {
+    args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
   kv_serialize_ast: {
     args: [FFIType.ptr],
     returns: FFIType.cstring,
@@ -42,6 +60,10 @@ const lib = dlopen(libPath, {
     args: [FFIType.ptr, FFIType.ptr],
     returns: FFIType.cstring,
   },
+  kv_format: {
+    args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
   kv_diff: {
     args: [FFIType.ptr, FFIType.ptr],
     returns: FFIType.cstring,
@@ -54,6 +76,42 @@ const lib = dlopen(libPath, {
     args: [FFIType.ptr],
     returns: FFIType.cstring,
   },
+  kv_merge3: {
+    args: [FFIType.ptr, FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_evaluate_conditionals: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_conditional_keys: {
+    args: [FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_query: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_query_edit: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_parse_binary: {
+    args: [FFIType.ptr, FFIType.uint64_t],
+    returns: FFIType.cstring,
+  },
+  kv_serialize_binary: {
+    args: [FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_parse_kv3: {
+    args: [FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  kv_serialize_kv3: {
+    args: [FFIType.ptr],
+    returns: FFIType.cstring,
+  },
   kv_free_string: {
     args: [FFIType.ptr],
     returns: FFIType.void,
@@ -136,6 +194,53 @@ export function parseKv(content: string, options?: ParseOptions): ParseResult {
   );
 }
 
+/**
+ * Parse a KeyValues file from disk, resolving its #include and #base
+ * directives relative to the file that contains them
+ */
+export function parseKvFile(
+  filePath: string,
+  options?: ParseOptions,
+): ResolvedDocument {
+  const pathBuffer = Buffer.from(`${filePath}\0`, "utf8");
+  const optionsJson = JSON.stringify({
+    allowEscapeSequences: options?.allowEscapeSequences ?? true,
+    allowConditionals: options?.allowConditionals ?? true,
+    allowIncludes: options?.allowIncludes ?? true,
+  });
+  const optionsBuffer = Buffer.from(`${optionsJson}\0`, "utf8");
+
+  return callNativeFunction<ResolvedDocument>(
+    lib.symbols.kv_parse_file,
+    ptr(pathBuffer),
+    ptr(optionsBuffer),
+  );
+}
+
+/**
+ * Parse KeyValues without stopping at the first error. Returns every problem
+ * found together with a best-effort AST, e.g. to underline them in an editor
+ */
+export function parseKvWithDiagnostics(
+  content: string,
+  options?: ParseOptions,
+): ParseReport {
+  const buffer = Buffer.from(content, "utf8");
+  const optionsJson = JSON.stringify({
+    allowEscapeSequences: options?.allowEscapeSequences ?? true,
+    allowConditionals: options?.allowConditionals ?? true,
+    allowIncludes: options?.allowIncludes ?? true,
+  });
+  const optionsBuffer = Buffer.from(`${optionsJson}\0`, "utf8");
+
+  return callNativeFunction<ParseReport>(
+    lib.symbols.kv_parse_with_diagnostics,
+    ptr(buffer),
+    buffer.length,
+    ptr(optionsBuffer),
+  );
+}
+
 /**
  * Extracts a string result from various FFI return types
  */
@@ -183,6 +288,7 @@ export function serializeData(
     useTabs: options?.useTabs ?? false,
     quoteAllStrings: options?.quoteAllStrings ?? false,
     minimizeQuotes: options?.minimizeQuotes ?? true,
+    alignValues: options?.alignValues ?? false,
   });
   const optionsBuffer = Buffer.from(`${optionsJson}\0`, "utf8");
 
@@ -194,6 +300,32 @@ export function serializeData(
   return extractStringResult(result);
 }
 
+/**
+ * Reformat KeyValues text canonically (indentation, quoting, alignment)
+ * while keeping comments, conditionals and blank lines between groups
+ */
+export function formatKv(
+  content: string,
+  options?: Partial<SerializeOptions>,
+): string {
+  const buffer = Buffer.from(content, "utf8");
+  const optionsJson = JSON.stringify({
+    indentSize: options?.indentSize ?? 4,
+    useTabs: options?.useTabs ?? false,
+    quoteAllStrings: options?.quoteAllStrings ?? false,
+    minimizeQuotes: options?.minimizeQuotes ?? true,
+    alignValues: options?.alignValues ?? false,
+  });
+  const optionsBuffer = Buffer.from(`${optionsJson}\0`, "utf8");
+
+  return callNativeFunction<TextOutput>(
+    lib.symbols.kv_format,
+    ptr(buffer),
+    buffer.length,
+    ptr(optionsBuffer),
+  ).text;
+}
+
 /**
  * Generate diff between two data objects
  */
@@ -245,6 +377,143 @@ export function getDiffStats(diff: DocumentDiff): DiffStats {
   );
 }
 
+/**
+ * Three-way merge of KeyValues text: applies the changes `theirs` made to
+ * `base` on top of `ours`, keeping our comments and formatting. Keys both
+ * sides changed differently keep our value and are listed as conflicts.
+ */
+export function mergeKv(
+  base: string,
+  ours: string,
+  theirs: string,
+): MergeOutput {
+  const baseBuffer = Buffer.from(`${base}\0`, "utf8");
+  const oursBuffer = Buffer.from(`${ours}\0`, "utf8");
+  const theirsBuffer = Buffer.from(`${theirs}\0`, "utf8");
+
+  return callNativeFunction<MergeOutput>(
+    lib.symbols.kv_merge3,
+    ptr(baseBuffer),
+    ptr(oursBuffer),
+    ptr(theirsBuffer),
+  );
+}
+
+/**
+ * Data view of a parsed document on a platform. Key-values whose conditional
+ * (e.g. `[$WIN32]`, `[!$X360]`, `[$LINUX||$OSX]`) is false for the given
+ * symbols are left out. Symbols are matched case-insensitively, with or
+ * without the leading `$`.
+ */
+export function evaluateConditionals(
+  ast: ParseResult["ast"],
+  symbols: string[],
+): KeyValuesObject {
+  const astBuffer = Buffer.from(`${JSON.stringify(ast)}\0`, "utf8");
+  const symbolsBuffer = Buffer.from(`${JSON.stringify(symbols)}\0`, "utf8");
+
+  return callNativeFunction<KeyValuesObject>(
+    lib.symbols.kv_evaluate_conditionals,
+    ptr(astBuffer),
+    ptr(symbolsBuffer),
+  );
+}
+
+/**
+ * List the keys of a parsed document whose presence depends on a conditional
+ */
+export function getConditionalKeys(
+  ast: ParseResult["ast"],
+): ConditionalKey[] {
+  const astBuffer = Buffer.from(`${JSON.stringify(ast)}\0`, "utf8");
+
+  return callNativeFunction<ConditionalKey[]>(
+    lib.symbols.kv_conditional_keys,
+    ptr(astBuffer),
+  );
+}
+
+/**
+ * Select key-values from KeyValues text with a path query such as
+ * `GameInfo.FileSystem.SearchPaths.Game[2]`, `..Mod` or
+ * `SearchPaths.Game[value="citadel/addons"]`
+ */
+export function queryKv(content: string, query: string): QueryMatch[] {
+  const contentBuffer = Buffer.from(`${content}\0`, "utf8");
+  const queryBuffer = Buffer.from(`${query}\0`, "utf8");
+
+  return callNativeFunction<QueryMatch[]>(
+    lib.symbols.kv_query,
+    ptr(contentBuffer),
+    ptr(queryBuffer),
+  );
+}
+
+/**
+ * Set, insert next to or delete every key-value a query selects, keeping the
+ * rest of the text's formatting
+ */
+export function editKv(content: string, edit: QueryEdit): QueryEditOutput {
+  const contentBuffer = Buffer.from(`${content}\0`, "utf8");
+  const editBuffer = Buffer.from(`${JSON.stringify(edit)}\0`, "utf8");
+
+  return callNativeFunction<QueryEditOutput>(
+    lib.symbols.kv_query_edit,
+    ptr(contentBuffer),
+    ptr(editBuffer),
+  );
+}
+
+/**
+ * Parse binary KeyValues such as Steam's `shortcuts.vdf`. Integers and floats
+ * become numbers; 64-bit integers above 2^53 lose precision.
+ */
+export function parseBinaryKv(buffer: Uint8Array): KeyValuesObject {
+  return callNativeFunction<KeyValuesObject>(
+    lib.symbols.kv_parse_binary,
+    ptr(buffer),
+    buffer.length,
+  );
+}
+
+/**
+ * Serialize data as binary KeyValues. Whole numbers that fit are written as
+ * 32-bit integers, larger ones as 64-bit integers and the rest as floats.
+ */
+export function serializeBinaryKv(data: KeyValuesObject): Buffer {
+  const dataBuffer = Buffer.from(`${JSON.stringify(data)}\0`, "utf8");
+
+  const output = callNativeFunction<BinaryOutput>(
+    lib.symbols.kv_serialize_binary,
+    ptr(dataBuffer),
+  );
+  return Buffer.from(output.bytes, "base64");
+}
+
+/**
+ * Parse KV3 text (`<!-- kv3 ... -->` files such as `.vdata`)
+ */
+export function parseKv3(content: string): Kv3Document {
+  const contentBuffer = Buffer.from(`${content}\0`, "utf8");
+
+  return callNativeFunction<Kv3Document>(
+    lib.symbols.kv_parse_kv3,
+    ptr(contentBuffer),
+  );
+}
+
+/**
+ * Serialize a KV3 document to text, laid out the way Valve's tools write it
+ */
+export function serializeKv3(document: Kv3Document): string {
+  const documentBuffer = Buffer.from(`${JSON.stringify(document)}\0`, "utf8");
+
+  return callNativeFunction<TextOutput>(
+    lib.symbols.kv_serialize_kv3,
+    ptr(documentBuffer),
+  ).text;
+}
+
 /**
  * Get the version of the native library
  */
@@ -1,6 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { CommentNode } from "./CommentNode";
 import type { ConditionalNode } from "./ConditionalNode";
+import type { DirectiveNode } from "./DirectiveNode";
 import type { KeyValueNode } from "./KeyValueNode";
 import type { WhitespaceNode } from "./WhitespaceNode";
 
@@ -11,4 +12,5 @@ export type AstNode =
   | KeyValueNode
   | CommentNode
   | WhitespaceNode
-  | ConditionalNode;
+  | ConditionalNode
+  | DirectiveNode;
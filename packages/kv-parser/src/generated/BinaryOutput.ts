@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Binary KeyValues produced by `kv_serialize_binary`
+ */
+export type BinaryOutput = { bytes: string };
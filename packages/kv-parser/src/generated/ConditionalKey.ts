@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Key whose presence depends on a `[$SYMBOL]` conditional
+ */
+export type ConditionalKey = {
+  /**
+   * Dot-separated key path
+   */
+  path: string;
+  /**
+   * Conditional as written, e.g. `[$LINUX||$OSX]`
+   */
+  condition: string;
+};
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DiagnosticFix } from "./DiagnosticFix";
+import type { DiagnosticSeverity } from "./DiagnosticSeverity";
+import type { Position } from "./Position";
+
+/**
+ * A problem found by `Parser::parse_with_diagnostics`
+ */
+export type Diagnostic = {
+  severity: DiagnosticSeverity;
+  start: Position;
+  end: Position;
+  message: string;
+  fix?: DiagnosticFix;
+};
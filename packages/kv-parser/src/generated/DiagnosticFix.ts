@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Position } from "./Position";
+
+/**
+ * A text edit that fixes a diagnostic: replace `start..end` with
+ * `replacement`. Insertions have `start == end`.
+ */
+export type DiagnosticFix = {
+  description: string;
+  start: Position;
+  end: Position;
+  replacement: string;
+};
@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DirectiveKind } from "./DirectiveKind";
+import type { IncludedDocument } from "./IncludedDocument";
+import type { NodeType } from "./NodeType";
+import type { Position } from "./Position";
+
+/**
+ * `#include` / `#base` directive node
+ */
+export type DirectiveNode = {
+  type: NodeType;
+  start: Position;
+  end: Position;
+  raw: string;
+  kind: DirectiveKind;
+  /**
+   * Path as written in the directive
+   */
+  path: string;
+  /**
+   * The referenced file, once an include resolver has loaded it
+   */
+  resolved?: IncludedDocument;
+};
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DocumentNode } from "./DocumentNode";
+
+/**
+ * A file pulled in by a directive, with its own AST
+ */
+export type IncludedDocument = {
+  /**
+   * Resolved path of the file
+   */
+  file: string;
+  ast: DocumentNode;
+};
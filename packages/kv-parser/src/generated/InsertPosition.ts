@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where `query_insert` places the new key-value relative to each match
+ */
+export type InsertPosition = "before" | "after";
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * File a key of a resolved document was read from
+ */
+export type KeySource = {
+  /**
+   * Dot-separated key path in the resolved data
+   */
+  path: string;
+  file: string;
+};
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Kv3Header } from "./Kv3Header";
+import type { Kv3Value } from "./Kv3Value";
+
+/**
+ * A KV3 text document as plain data
+ */
+export type Kv3Document = { header: Kv3Header; root: Kv3Value };
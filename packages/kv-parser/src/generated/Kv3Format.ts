@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * One `name:version{guid}` part of the header
+ */
+export type Kv3Format = { name: string; version: string };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Kv3Format } from "./Kv3Format";
+
+/**
+ * `<!-- kv3 encoding:text:version{...} format:generic:version{...} -->`
+ */
+export type Kv3Header = { encoding: Kv3Format; format: Kv3Format };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A KV3 value. Mirrors the binary KV3 value tree, plus the flags
+ * (`resource:`, `soundevent:`, `subclass:`, ...) text KV3 can attach to a
+ * value.
+ */
+export type Kv3Value =
+  | { type: "null" }
+  | { type: "bool"; value: boolean }
+  | { type: "int"; value: number }
+  | { type: "uInt"; value: number }
+  | { type: "double"; value: number }
+  | { type: "string"; value: string }
+  | { type: "binary"; value: string }
+  | { type: "array"; value: Array<Kv3Value> }
+  | { type: "object"; value: Array<[string, Kv3Value]> }
+  | { type: "flagged"; value: { flag: string; value: Kv3Value } };
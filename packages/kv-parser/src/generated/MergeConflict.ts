@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { KeyValuesValue } from "./KeyValuesValue";
+
+/**
+ * A key changed differently on both sides of a three-way merge
+ */
+export type MergeConflict = {
+  /**
+   * Dot-separated key path
+   */
+  path: string;
+  /**
+   * Value in the common ancestor, if the key existed there
+   */
+  base: KeyValuesValue | null;
+  /**
+   * Value in our document; this is what the merged document keeps
+   */
+  ours: KeyValuesValue | null;
+  /**
+   * Value in their document
+   */
+  theirs: KeyValuesValue | null;
+};
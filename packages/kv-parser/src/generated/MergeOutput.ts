@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MergeConflict } from "./MergeConflict";
+
+/**
+ * Serialized result of a three-way merge
+ */
+export type MergeOutput = {
+  /**
+   * Merged document, formatted like our document
+   */
+  merged: string;
+  conflicts: Array<MergeConflict>;
+};
@@ -12,4 +12,5 @@ export type NodeType =
   | "comment"
   | "whitespace"
   | "conditional"
-  | "token";
+  | "token"
+  | "directive";
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Diagnostic } from "./Diagnostic";
+import type { DocumentNode } from "./DocumentNode";
+import type { KeyValuesValue } from "./KeyValuesValue";
+
+/**
+ * Best-effort parse of a document that may contain errors
+ */
+export type ParseReport = {
+  data: { [key in string]?: KeyValuesValue };
+  ast: DocumentNode;
+  /**
+   * Every problem found, in document order
+   */
+  diagnostics: Array<Diagnostic>;
+};
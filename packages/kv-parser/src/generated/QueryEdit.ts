@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InsertPosition } from "./InsertPosition";
+import type { KeyValuesValue } from "./KeyValuesValue";
+
+/**
+ * An edit applied to every key-value a query selects
+ */
+export type QueryEdit =
+  | { op: "set"; query: string; value: KeyValuesValue }
+  | {
+      op: "insert";
+      query: string;
+      position: InsertPosition;
+      key: string;
+      value: KeyValuesValue;
+    }
+  | { op: "delete"; query: string };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Serialized result of a query edit
+ */
+export type QueryEditOutput = {
+  /**
+   * Edited document, formatted like the input
+   */
+  content: string;
+  /**
+   * Number of key-values the query selected
+   */
+  count: number;
+};
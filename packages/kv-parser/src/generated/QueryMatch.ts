@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { KeyValuesValue } from "./KeyValuesValue";
+
+/**
+ * A key-value selected by a query
+ */
+export type QueryMatch = {
+  /**
+   * Query that selects only this key-value, e.g.
+   * `GameInfo.FileSystem.SearchPaths.Game[2]`
+   */
+  path: string;
+  value: KeyValuesValue;
+};
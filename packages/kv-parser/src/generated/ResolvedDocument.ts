@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DocumentNode } from "./DocumentNode";
+import type { KeySource } from "./KeySource";
+import type { KeyValuesValue } from "./KeyValuesValue";
+
+/**
+ * Document with its `#include` and `#base` directives resolved
+ */
+export type ResolvedDocument = {
+  /**
+   * Path of the root file
+   */
+  file: string;
+  /**
+   * Merged data of the root file and everything it pulls in
+   */
+  data: { [key in string]?: KeyValuesValue };
+  /**
+   * AST of the root file; directive nodes carry the included files' ASTs
+   */
+  ast: DocumentNode;
+  /**
+   * Source file of every key in `data`, in the order they were read
+   */
+  sources: Array<KeySource>;
+};
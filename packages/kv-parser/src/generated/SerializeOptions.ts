@@ -8,4 +8,8 @@ export type SerializeOptions = {
   useTabs: boolean;
   quoteAllStrings: boolean;
   minimizeQuotes: boolean;
+  /**
+   * Line up the values of consecutive keys (used by the formatter)
+   */
+  alignValues: boolean;
 };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Text produced by `kv_format` and `kv_serialize_kv3`
+ */
+export type TextOutput = {
+  /**
+   * Whether `text` holds the result; when false, `error` says why
+   */
+  ok: boolean;
+  text: string;
+  error?: string;
+};
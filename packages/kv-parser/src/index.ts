@@ -1,12 +1,24 @@
 // Export FFI functions
 export {
   applyDiff,
+  editKv,
+  evaluateConditionals,
+  formatKv,
   generateDiff,
+  getConditionalKeys,
   getDiffStats,
   getVersion,
+  mergeKv,
+  parseBinaryKv,
   parseKv,
+  parseKv3,
+  parseKvFile,
+  parseKvWithDiagnostics,
+  queryKv,
   serializeAst,
+  serializeBinaryKv,
   serializeData,
+  serializeKv3,
 } from "./ffi";
 // Re-export for convenience
 export type { ParseResult } from "./types";
@@ -2,24 +2,46 @@ import type { KeyValuesValue } from "./generated/KeyValuesValue";
 
 // Re-export generated types from Rust
 export type { AstNode } from "./generated/AstNode";
+export type { BinaryOutput } from "./generated/BinaryOutput";
 export type { CommentNode } from "./generated/CommentNode";
 export type { CommentStyle } from "./generated/CommentStyle";
+export type { ConditionalKey } from "./generated/ConditionalKey";
 export type { ConditionalNode } from "./generated/ConditionalNode";
+export type { Diagnostic } from "./generated/Diagnostic";
+export type { DiagnosticFix } from "./generated/DiagnosticFix";
+export type { DiagnosticSeverity } from "./generated/DiagnosticSeverity";
 export type { DiffEntry } from "./generated/DiffEntry";
 export type { DiffOp } from "./generated/DiffOp";
 export type { DiffStats } from "./generated/DiffStats";
+export type { DirectiveKind } from "./generated/DirectiveKind";
+export type { DirectiveNode } from "./generated/DirectiveNode";
 export type { DocumentDiff } from "./generated/DocumentDiff";
 export type { DocumentNode } from "./generated/DocumentNode";
+export type { IncludedDocument } from "./generated/IncludedDocument";
+export type { InsertPosition } from "./generated/InsertPosition";
+export type { KeySource } from "./generated/KeySource";
 export type { KeyValueNode } from "./generated/KeyValueNode";
 export type { KeyValuesValue } from "./generated/KeyValuesValue";
+export type { Kv3Document } from "./generated/Kv3Document";
+export type { Kv3Format } from "./generated/Kv3Format";
+export type { Kv3Header } from "./generated/Kv3Header";
+export type { Kv3Value } from "./generated/Kv3Value";
+export type { MergeConflict } from "./generated/MergeConflict";
+export type { MergeOutput } from "./generated/MergeOutput";
 export type { NodeType } from "./generated/NodeType";
 export type { NumberNode } from "./generated/NumberNode";
 export type { ObjectNode } from "./generated/ObjectNode";
 export type { ParseOptions } from "./generated/ParseOptions";
+export type { ParseReport } from "./generated/ParseReport";
 export type { ParseResult } from "./generated/ParseResult";
 export type { Position } from "./generated/Position";
+export type { QueryEdit } from "./generated/QueryEdit";
+export type { QueryEditOutput } from "./generated/QueryEditOutput";
+export type { QueryMatch } from "./generated/QueryMatch";
+export type { ResolvedDocument } from "./generated/ResolvedDocument";
 export type { SerializeOptions } from "./generated/SerializeOptions";
 export type { StringNode } from "./generated/StringNode";
+export type { TextOutput } from "./generated/TextOutput";
 export type { Token } from "./generated/Token";
 export type { TokenMetadata } from "./generated/TokenMetadata";
 export type { TokenNode } from "./generated/TokenNode";
@@ -1,6 +1,7 @@
 mod common;
 
 use kv_parser::{KvDocument, KeyValuesValue, ParseOptions, Parser};
+use serde::Deserialize;
 
 #[test]
 fn test_parse_gameinfo_structure() {
@@ -176,3 +177,38 @@ fn test_round_trip_preserves_search_paths() {
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SearchPaths {
+    game: Vec<String>,
+    #[serde(rename = "Mod")]
+    mods: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FileSystem {
+    search_paths: SearchPaths,
+}
+
+#[derive(Debug, Deserialize)]
+struct GameInfo {
+    game: String,
+    nomodels: bool,
+    #[serde(rename = "FileSystem")]
+    file_system: FileSystem,
+}
+
+#[test]
+fn test_deserialize_gameinfo_into_typed_struct() {
+    let content = common::load_gameinfo();
+    let game_info: GameInfo = kv_parser::from_str(&content).expect("Failed to deserialize gameinfo.gi");
+
+    assert_eq!(game_info.game, "citadel");
+    assert!(game_info.nomodels);
+    assert_eq!(
+        game_info.file_system.search_paths.game,
+        ["citadel/addons", "citadel", "core"]
+    );
+    assert_eq!(game_info.file_system.search_paths.mods, ["citadel", "core"]);
+}
@@ -85,7 +85,7 @@ impl VpkArchive {
             file_path: vpk_path.to_string_lossy().to_string(),
             ..Default::default()
         };
-        let parsed = VpkParser::parse(buffer.clone(), options)
+        let parsed = VpkParser::parse_slice(&buffer, options)
             .map_err(|e| Source2Error::Vpk(format!("failed to parse VPK: {e}")))?;
 
         let entries: Vec<EntryInfo> = parsed
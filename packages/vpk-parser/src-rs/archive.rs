@@ -0,0 +1,137 @@
+use crate::{
+    error::{Result, VpkError},
+    types::VpkEntry,
+};
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Archive index of entries whose data lives in the directory file itself,
+/// right after the directory tree.
+pub const INLINE_ARCHIVE_INDEX: u16 = 0x7fff;
+
+/// Size of the fixed VPK header for a given format version.
+pub fn header_size(version: u32) -> usize {
+    if version >= 2 { 28 } else { 12 }
+}
+
+/// Path of the `_NNN.vpk` companion archive that holds the entries stored under
+/// `archive_index` for the directory file at `dir_path`.
+///
+/// `pak01_dir.vpk` with index 3 resolves to `pak01_003.vpk` in the same folder.
+/// A directory file without the `_dir` suffix keeps its full stem.
+pub fn companion_archive_path(dir_path: &Path, archive_index: u16) -> PathBuf {
+    let stem = dir_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let base = stem.strip_suffix("_dir").unwrap_or(stem);
+    dir_path.with_file_name(format!("{base}_{archive_index:03}.vpk"))
+}
+
+/// Resolves entry payloads across a directory file and its companions.
+///
+/// Companion archives are memory-mapped on first use and kept open for the
+/// lifetime of the reader, so walking every entry of a multi-part VPK maps each
+/// `_NNN.vpk` once and pages its data in on demand.
+pub(crate) struct ArchiveReader<'a> {
+    dir_buffer: &'a [u8],
+    data_start: usize,
+    dir_path: Option<PathBuf>,
+    companions: HashMap<u16, Mmap>,
+}
+
+impl<'a> ArchiveReader<'a> {
+    pub(crate) fn new(
+        dir_buffer: &'a [u8],
+        version: u32,
+        tree_length: u32,
+        dir_path: Option<&Path>,
+    ) -> Self {
+        Self {
+            dir_buffer,
+            data_start: header_size(version) + tree_length as usize,
+            dir_path: dir_path.map(Path::to_path_buf),
+            companions: HashMap::new(),
+        }
+    }
+
+    /// The whole memory-mapped companion archive for `archive_index`.
+    pub(crate) fn companion(&mut self, archive_index: u16) -> Result<&[u8]> {
+        if !self.companions.contains_key(&archive_index) {
+            let path = self
+                .dir_path
+                .as_deref()
+                .map(|dir_path| companion_archive_path(dir_path, archive_index))
+                .ok_or_else(|| VpkError::Validation {
+                    message: format!(
+                        "Cannot locate companion archive {archive_index:03} without the directory file's path"
+                    ),
+                })?;
+            let file = File::open(&path).map_err(|_| VpkError::MissingArchive {
+                archive_index,
+                path: path.to_string_lossy().to_string(),
+            })?;
+            // SAFETY: the map is read-only and owned by this reader.
+            let mmap = unsafe { Mmap::map(&file)? };
+            self.companions.insert(archive_index, mmap);
+        }
+
+        Ok(&self.companions[&archive_index])
+    }
+
+    /// Maps every listed companion archive and returns them in the same order.
+    pub(crate) fn companions(&mut self, archive_indices: &[u16]) -> Result<Vec<&[u8]>> {
+        for &index in archive_indices {
+            self.companion(index)?;
+        }
+        Ok(archive_indices
+            .iter()
+            .map(|index| &self.companions[index][..])
+            .collect())
+    }
+
+    /// The preload bytes stored in the tree, followed by the bytes stored in
+    /// the data section or companion archive. Either part may be empty.
+    pub(crate) fn entry_parts(
+        &mut self,
+        entry: &VpkEntry,
+        preload_offset: usize,
+    ) -> Result<(&'a [u8], &[u8])> {
+        let preload = self
+            .dir_buffer
+            .get(preload_offset..preload_offset + entry.preload_bytes as usize)
+            .ok_or(VpkError::CursorOverrun {
+                cursor: preload_offset,
+                requested: entry.preload_bytes as usize,
+                buffer_size: self.dir_buffer.len(),
+            })?;
+
+        if entry.entry_length == 0 {
+            return Ok((preload, &[]));
+        }
+
+        let (source, start) = if entry.archive_index == INLINE_ARCHIVE_INDEX {
+            (
+                self.dir_buffer,
+                self.data_start + entry.entry_offset as usize,
+            )
+        } else {
+            (
+                self.companion(entry.archive_index)?,
+                entry.entry_offset as usize,
+            )
+        };
+
+        let length = entry.entry_length as usize;
+        let body = source
+            .get(start..start + length)
+            .ok_or(VpkError::CursorOverrun {
+                cursor: start,
+                requested: length,
+                buffer_size: source.len(),
+            })?;
+        Ok((preload, body))
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{extract::normalize_entry_path, parser::VpkParser, types::*};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+fn entry_size(entry: &VpkEntry) -> u64 {
+    u64::from(entry.preload_bytes) + u64::from(entry.entry_length)
+}
+
+fn content_key(entry: &VpkEntry) -> (String, u64) {
+    (entry.crc32_hex.to_ascii_lowercase(), entry_size(entry))
+}
+
+impl VpkParser<'_> {
+    /// Compares the entries of two parsed VPKs by path, CRC32 and size.
+    /// Paths match case-insensitively and ignore the separator style.
+    ///
+    /// Removed and added entries with identical content are paired up as
+    /// renames, oldest path first. Empty entries all share the same CRC32, so
+    /// they are never treated as renames.
+    pub fn diff(old: &VpkParsed, new: &VpkParsed) -> VpkDiff {
+        let old_entries: BTreeMap<String, &VpkEntry> = old
+            .entries
+            .iter()
+            .map(|entry| (normalize_entry_path(&entry.full_path), entry))
+            .collect();
+        let new_entries: BTreeMap<String, &VpkEntry> = new
+            .entries
+            .iter()
+            .map(|entry| (normalize_entry_path(&entry.full_path), entry))
+            .collect();
+
+        let mut diff = VpkDiff::default();
+        let mut removed = Vec::new();
+        for (path, old_entry) in &old_entries {
+            match new_entries.get(path) {
+                None => removed.push(*old_entry),
+                Some(new_entry) if content_key(old_entry) == content_key(new_entry) => {
+                    diff.unchanged_count += 1;
+                }
+                Some(new_entry) => diff.modified.push(VpkEntryChange {
+                    full_path: new_entry.full_path.clone(),
+                    old_crc32_hex: old_entry.crc32_hex.clone(),
+                    new_crc32_hex: new_entry.crc32_hex.clone(),
+                    old_size: entry_size(old_entry),
+                    new_size: entry_size(new_entry),
+                }),
+            }
+        }
+
+        let mut added: HashMap<(String, u64), VecDeque<&VpkEntry>> = HashMap::new();
+        let mut added_order = Vec::new();
+        for (path, new_entry) in &new_entries {
+            if old_entries.contains_key(path) {
+                continue;
+            }
+            added_order.push(*new_entry);
+            if entry_size(new_entry) > 0 {
+                added
+                    .entry(content_key(new_entry))
+                    .or_default()
+                    .push_back(new_entry);
+            }
+        }
+
+        for old_entry in removed {
+            let counterpart = (entry_size(old_entry) > 0)
+                .then(|| added.get_mut(&content_key(old_entry)))
+                .flatten()
+                .and_then(VecDeque::pop_front);
+            match counterpart {
+                Some(new_entry) => diff.renamed.push(VpkEntryRename {
+                    old_path: old_entry.full_path.clone(),
+                    new_path: new_entry.full_path.clone(),
+                    crc32_hex: new_entry.crc32_hex.clone(),
+                    size: entry_size(new_entry),
+                }),
+                None => diff.removed.push(old_entry.clone()),
+            }
+        }
+
+        let renamed_to: HashSet<&str> = diff
+            .renamed
+            .iter()
+            .map(|rename| rename.new_path.as_str())
+            .collect();
+        diff.added = added_order
+            .into_iter()
+            .filter(|entry| !renamed_to.contains(&entry.full_path.as_str()))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestVpk;
+
+    fn entries_only() -> VpkParseOptions {
+        VpkParseOptions {
+            include_entries: true,
+            ..Default::default()
+        }
+    }
+
+    fn parse(vpk: TestVpk) -> VpkParsed {
+        VpkParser::parse(vpk.build().dir, entries_only()).unwrap()
+    }
+
+    #[test]
+    fn reports_added_removed_modified_and_unchanged_entries() {
+        let old = parse(
+            TestVpk::new()
+                .inline("scripts/keep.vdata_c", b"same bytes")
+                .inline("scripts/change.vdata_c", b"version one")
+                .inline("scripts/drop.vdata_c", b"dropped"),
+        );
+        let new = parse(
+            TestVpk::new()
+                .inline("scripts/keep.vdata_c", b"same bytes")
+                .inline("scripts/change.vdata_c", b"version two!")
+                .inline("scripts/new.vdata_c", b"brand new"),
+        );
+
+        let diff = VpkParser::diff(&old, &new);
+
+        assert_eq!(diff.unchanged_count, 1);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].full_path, "scripts/new.vdata_c");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].full_path, "scripts/drop.vdata_c");
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].old_size, 11);
+        assert_eq!(diff.modified[0].new_size, 12);
+        assert!(diff.renamed.is_empty());
+    }
+
+    #[test]
+    fn pairs_moved_entries_by_content() {
+        let old = parse(
+            TestVpk::new()
+                .inline("sounds/old/cast.vsnd_c", b"cast sound")
+                .inline("sounds/old/empty.txt", b""),
+        );
+        let new = parse(
+            TestVpk::new()
+                .preload("sounds/new/cast_renamed.vsnd_c", b"cast sound", 4)
+                .inline("sounds/new/empty.txt", b""),
+        );
+
+        let diff = VpkParser::diff(&old, &new);
+
+        assert_eq!(diff.renamed.len(), 1);
+        assert_eq!(diff.renamed[0].old_path, "sounds/old/cast.vsnd_c");
+        assert_eq!(diff.renamed[0].new_path, "sounds/new/cast_renamed.vsnd_c");
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed.len(), 1);
+    }
+
+    #[test]
+    fn identical_archives_have_no_changes() {
+        let parsed = VpkParser::parse(
+            std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/data/pak95_dir.vpk")).unwrap(),
+            entries_only(),
+        )
+        .unwrap();
+
+        let diff = VpkParser::diff(&parsed, &parsed);
+
+        assert_eq!(diff.unchanged_count, 3);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert!(diff.modified.is_empty() && diff.renamed.is_empty());
+    }
+
+    #[test]
+    fn sizes_past_u32_do_not_overflow() {
+        let old = parse(TestVpk::new().inline("scripts/big.vdata_c", b"small"));
+        let mut new = old.clone();
+        new.entries[0].preload_bytes = u16::MAX;
+        new.entries[0].entry_length = u32::MAX;
+
+        let diff = VpkParser::diff(&old, &new);
+
+        assert_eq!(
+            diff.modified[0].new_size,
+            u64::from(u16::MAX) + u64::from(u32::MAX)
+        );
+    }
+}
@@ -23,6 +23,19 @@ pub enum VpkError {
     #[error("Invalid entry terminator: expected 0xFFFF, got 0x{actual:04x}")]
     InvalidTerminator { actual: u16 },
 
+    #[error("Companion archive {archive_index:03} not found: {path}")]
+    MissingArchive { archive_index: u16, path: String },
+
+    #[error("Entry not found: {path}")]
+    EntryNotFound { path: String },
+
+    #[error("CRC32 mismatch for {path}: expected {expected}, got {actual}")]
+    CrcMismatch {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("UTF-8 conversion error: {0}")]
     Utf8(#[from] std::string::FromUtf8Error),
 
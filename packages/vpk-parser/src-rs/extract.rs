@@ -0,0 +1,191 @@
+use crate::{
+    archive::ArchiveReader,
+    error::{Result, VpkError},
+    parser::VpkParser,
+    types::*,
+};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+pub(crate) fn normalize_entry_path(path: &str) -> String {
+    path.replace('\\', "/").to_ascii_lowercase()
+}
+
+impl VpkParser<'_> {
+    /// Reads one entry's bytes from a VPK held in memory. `dir_path` locates
+    /// the `_NNN.vpk` companions for entries stored outside the directory file.
+    pub fn extract_entry(
+        buffer: &[u8],
+        dir_path: Option<&Path>,
+        entry_path: &str,
+    ) -> Result<Vec<u8>> {
+        let mut contents =
+            VpkParser::read_entries(buffer, dir_path, Some(&[entry_path.to_string()]))?;
+        Ok(contents.remove(0).data)
+    }
+
+    /// Reads one entry's bytes from a memory-mapped VPK file on disk.
+    pub fn extract_entry_from_file(path: &Path, entry_path: &str) -> Result<Vec<u8>> {
+        let mut contents =
+            VpkParser::read_entries_from_file(path, Some(&[entry_path.to_string()]))?;
+        Ok(contents.remove(0).data)
+    }
+
+    /// Reads the named entries (or every entry, for `None`) from a VPK held in
+    /// memory, in request order. Paths match case-insensitively and accept
+    /// either separator. Every entry is checked against its stored CRC32.
+    pub fn read_entries(
+        buffer: &[u8],
+        dir_path: Option<&Path>,
+        entry_paths: Option<&[String]>,
+    ) -> Result<Vec<VpkEntryContent>> {
+        let mut parser = VpkParser::new(buffer);
+        let (header, entries) = parser.parse_tree()?;
+        let mut archives = ArchiveReader::new(buffer, header.version, header.tree_length, dir_path);
+
+        let selected: Vec<usize> = match entry_paths {
+            None => (0..entries.len()).collect(),
+            Some(paths) => {
+                let mut index: HashMap<String, usize> = HashMap::new();
+                for (position, entry) in entries.iter().enumerate() {
+                    index
+                        .entry(normalize_entry_path(&entry.full_path))
+                        .or_insert(position);
+                }
+                paths
+                    .iter()
+                    .map(|path| {
+                        index
+                            .get(&normalize_entry_path(path))
+                            .copied()
+                            .ok_or_else(|| VpkError::EntryNotFound { path: path.clone() })
+                    })
+                    .collect::<Result<_>>()?
+            }
+        };
+
+        selected
+            .into_iter()
+            .map(|position| {
+                let entry = &entries[position];
+                let (preload, body) =
+                    archives.entry_parts(entry, parser.preload_offsets()[position])?;
+
+                let mut digest = CRC32.digest();
+                digest.update(preload);
+                digest.update(body);
+                let actual = format!("{:08x}", digest.finalize());
+                if actual != entry.crc32_hex {
+                    return Err(VpkError::CrcMismatch {
+                        path: entry.full_path.clone(),
+                        expected: entry.crc32_hex.clone(),
+                        actual,
+                    });
+                }
+
+                let mut data = Vec::with_capacity(preload.len() + body.len());
+                data.extend_from_slice(preload);
+                data.extend_from_slice(body);
+                Ok(VpkEntryContent {
+                    full_path: entry.full_path.clone(),
+                    crc32_hex: entry.crc32_hex.clone(),
+                    size: data.len(),
+                    data,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the named entries (or every entry) from a memory-mapped VPK file,
+    /// resolving companions next to it.
+    pub fn read_entries_from_file(
+        path: &Path,
+        entry_paths: Option<&[String]>,
+    ) -> Result<Vec<VpkEntryContent>> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read-only and dropped before this function returns.
+        let mmap = unsafe { Mmap::map(&file)? };
+        VpkParser::read_entries(&mmap, Some(path), entry_paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::companion_archive_path;
+    use crate::test_support::{TestVpk, scratch_dir};
+
+    fn sample() -> TestVpk {
+        TestVpk::new()
+            .inline("scripts/abilities.vdata_c", b"inline ability data")
+            .preload("panorama/layout.xml", b"<root>preloaded</root>", 6)
+            .preload("materials/tiny.vmat_c", b"tiny", 4)
+            .companion("sounds/cast.vsnd_c", b"companion sound data", 0)
+    }
+
+    #[test]
+    fn extracts_inline_preload_and_companion_entries() {
+        let scratch = scratch_dir("extract-all");
+        let dir = scratch.path();
+        let path = sample().write_to(dir, "pak01");
+
+        let contents = VpkParser::read_entries_from_file(&path, None).unwrap();
+        let by_path: HashMap<_, _> = contents
+            .iter()
+            .map(|content| (content.full_path.as_str(), content.data.as_slice()))
+            .collect();
+
+        assert_eq!(by_path["scripts/abilities.vdata_c"], b"inline ability data");
+        assert_eq!(by_path["panorama/layout.xml"], b"<root>preloaded</root>");
+        assert_eq!(by_path["materials/tiny.vmat_c"], b"tiny");
+        assert_eq!(by_path["sounds/cast.vsnd_c"], b"companion sound data");
+    }
+
+    #[test]
+    fn bundled_sample_entries_pass_their_crc() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/pak95_dir.vpk");
+        let contents = VpkParser::read_entries_from_file(&path, None).unwrap();
+        assert_eq!(contents.len(), 3);
+        assert!(contents.iter().all(|content| content.size > 0));
+    }
+
+    #[test]
+    fn entry_lookup_ignores_case_and_separators() {
+        let built = sample().build();
+        let data =
+            VpkParser::extract_entry(&built.dir, None, "Scripts\\Abilities.VDATA_C").unwrap();
+        assert_eq!(data, b"inline ability data");
+    }
+
+    #[test]
+    fn corrupted_companion_data_fails_the_crc_check() {
+        let scratch = scratch_dir("extract-crc");
+        let dir = scratch.path();
+        let path = sample().write_to(dir, "pak01");
+        let companion = companion_archive_path(&path, 0);
+        let mut bytes = std::fs::read(&companion).unwrap();
+        bytes[3] ^= 0x20;
+        std::fs::write(&companion, bytes).unwrap();
+
+        let error = VpkParser::extract_entry_from_file(&path, "sounds/cast.vsnd_c").unwrap_err();
+        assert!(matches!(error, VpkError::CrcMismatch { .. }));
+    }
+
+    #[test]
+    fn unknown_entry_is_reported_by_path() {
+        let built = sample().build();
+        let error = VpkParser::extract_entry(&built.dir, None, "missing/file.txt").unwrap_err();
+        assert!(matches!(error, VpkError::EntryNotFound { path } if path == "missing/file.txt"));
+    }
+
+    #[test]
+    fn companion_entry_needs_a_directory_path() {
+        let built = sample().build();
+        assert!(VpkParser::extract_entry(&built.dir, None, "sounds/cast.vsnd_c").is_err());
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    error::{Result, VpkError},
+    parser::VpkParser,
+    types::*,
+};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the on-disk layout changes, so an index written by an older
+/// build is rebuilt rather than misread.
+const INDEX_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexedEntry {
+    full_path: String,
+    archive_index: u16,
+    entry_offset: u32,
+    entry_length: u32,
+    preload_bytes: u16,
+    crc32_hex: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexedArchive {
+    modified_nanos: u128,
+    size: u64,
+    entries: Vec<IndexedEntry>,
+}
+
+/// A persistent index of the entries of many VPKs, for finding which archive
+/// holds a file without parsing every directory tree again.
+///
+/// Each archive is keyed by its path, nanosecond modification time and size,
+/// so a same-second rewrite of the same length is still noticed. This is in the
+/// spirit of `hero_parser::VpkEntryCache`; `refresh` only re-parses the
+/// archives whose key changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VpkEntryIndex {
+    format_version: u32,
+    archives: BTreeMap<PathBuf, IndexedArchive>,
+}
+
+impl Default for VpkEntryIndex {
+    fn default() -> Self {
+        Self {
+            format_version: INDEX_FORMAT_VERSION,
+            archives: BTreeMap::new(),
+        }
+    }
+}
+
+fn file_key(path: &Path) -> Option<(u128, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified_nanos = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    Some((modified_nanos, metadata.len()))
+}
+
+impl VpkEntryIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads an index saved with `save`. A missing, unreadable or outdated
+    /// file yields an empty index, which the next `refresh` fills in.
+    pub fn load(path: &Path) -> Self {
+        fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok())
+            .filter(|index| index.format_version == INDEX_FORMAT_VERSION)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Written next to the target and renamed over it, so a crash mid-write
+        // leaves the previous index intact.
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Brings the index in line with `vpk_paths`: parses new or changed
+    /// archives and forgets ones that are no longer listed. Archives that fail
+    /// to parse are left out and reported in `failed`.
+    pub fn refresh(&mut self, vpk_paths: &[PathBuf]) -> VpkIndexRefresh {
+        let mut refresh = VpkIndexRefresh::default();
+        let listed: HashSet<&PathBuf> = vpk_paths.iter().collect();
+        let before = self.archives.len();
+        self.archives.retain(|path, _| listed.contains(path));
+        refresh.removed = before - self.archives.len();
+
+        for vpk_path in listed {
+            let Some((modified_nanos, size)) = file_key(vpk_path) else {
+                self.archives.remove(vpk_path);
+                refresh.failed.push(vpk_path.to_string_lossy().to_string());
+                continue;
+            };
+            if self.archives.get(vpk_path).is_some_and(|archive| {
+                archive.modified_nanos == modified_nanos && archive.size == size
+            }) {
+                refresh.reused += 1;
+                continue;
+            }
+
+            match VpkParser::parse_directory_from_file(vpk_path) {
+                Ok(entries) => {
+                    let entries = entries
+                        .into_iter()
+                        .map(|entry| IndexedEntry {
+                            full_path: entry.full_path,
+                            archive_index: entry.archive_index,
+                            entry_offset: entry.entry_offset,
+                            entry_length: entry.entry_length,
+                            preload_bytes: entry.preload_bytes,
+                            crc32_hex: entry.crc32_hex,
+                        })
+                        .collect();
+                    self.archives.insert(
+                        vpk_path.clone(),
+                        IndexedArchive {
+                            modified_nanos,
+                            size,
+                            entries,
+                        },
+                    );
+                    refresh.parsed += 1;
+                }
+                Err(_) => {
+                    self.archives.remove(vpk_path);
+                    refresh.failed.push(vpk_path.to_string_lossy().to_string());
+                }
+            }
+        }
+
+        refresh.failed.sort();
+        refresh
+    }
+
+    pub fn archive_count(&self) -> usize {
+        self.archives.len()
+    }
+
+    /// Entries matching `query`, ordered by archive path and then by their
+    /// position in the archive, stopping after `limit` hits if one is given.
+    pub fn search(&self, query: &VpkIndexQuery, limit: Option<usize>) -> Result<Vec<VpkIndexHit>> {
+        let matcher = Matcher::new(query)?;
+        Ok(self
+            .archives
+            .iter()
+            .flat_map(|(vpk_path, archive)| {
+                archive.entries.iter().map(move |entry| (vpk_path, entry))
+            })
+            .filter(|(_, entry)| matcher.matches(&entry.full_path))
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(vpk_path, entry)| VpkIndexHit {
+                vpk_path: vpk_path.to_string_lossy().to_string(),
+                full_path: entry.full_path.clone(),
+                archive_index: entry.archive_index,
+                entry_offset: entry.entry_offset,
+                entry_length: entry.entry_length,
+                preload_bytes: entry.preload_bytes,
+                crc32_hex: entry.crc32_hex.clone(),
+            })
+            .collect())
+    }
+}
+
+enum Matcher {
+    Glob(Pattern),
+    Substring(String),
+    Extension(String),
+}
+
+impl Matcher {
+    fn new(query: &VpkIndexQuery) -> Result<Self> {
+        Ok(match query {
+            VpkIndexQuery::Glob(pattern) => {
+                Matcher::Glob(Pattern::new(&pattern.replace('\\', "/")).map_err(|e| {
+                    VpkError::Validation {
+                        message: format!("Invalid glob pattern {pattern:?}: {e}"),
+                    }
+                })?)
+            }
+            VpkIndexQuery::Substring(needle) => {
+                Matcher::Substring(needle.replace('\\', "/").to_lowercase())
+            }
+            VpkIndexQuery::Extension(ext) => {
+                Matcher::Extension(format!(".{}", ext.trim_start_matches('.').to_lowercase()))
+            }
+        })
+    }
+
+    fn matches(&self, full_path: &str) -> bool {
+        match self {
+            Matcher::Glob(pattern) => pattern.matches_with(
+                full_path,
+                MatchOptions {
+                    case_sensitive: false,
+                    require_literal_separator: true,
+                    require_literal_leading_dot: false,
+                },
+            ),
+            Matcher::Substring(needle) => full_path.to_lowercase().contains(needle),
+            Matcher::Extension(ext) => full_path.to_lowercase().ends_with(ext),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TestVpk, scratch_dir};
+    use tempfile::TempDir;
+
+    fn fixture() -> (TempDir, PathBuf, PathBuf) {
+        let dir = scratch_dir("entry-index");
+        let first = TestVpk::new()
+            .inline("materials/heroes/haze/haze_color.vtex_c", b"haze")
+            .inline("materials/heroes/haze/haze.vmat_c", b"material")
+            .write_to(dir.path(), "pak01");
+        let second = TestVpk::new()
+            .companion("sounds/haze/Haze_Cast.vsnd_c", b"sound", 0)
+            .write_to(dir.path(), "pak02");
+        (dir, first, second)
+    }
+
+    fn paths(hits: &[VpkIndexHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.full_path.as_str()).collect()
+    }
+
+    #[test]
+    fn glob_substring_and_extension_queries() {
+        let (_dir, first, second) = fixture();
+        let mut index = VpkEntryIndex::new();
+        index.refresh(&[first.clone(), second]);
+
+        let glob = index
+            .search(
+                &VpkIndexQuery::Glob("materials/**/HAZE_*.vtex_c".into()),
+                None,
+            )
+            .unwrap();
+        assert_eq!(paths(&glob), ["materials/heroes/haze/haze_color.vtex_c"]);
+        assert_eq!(glob[0].vpk_path, first.to_string_lossy());
+
+        let shallow = index
+            .search(&VpkIndexQuery::Glob("materials/*.vtex_c".into()), None)
+            .unwrap();
+        assert!(shallow.is_empty());
+
+        let substring = index
+            .search(&VpkIndexQuery::Substring("haze_cast".into()), None)
+            .unwrap();
+        assert_eq!(paths(&substring), ["sounds/haze/Haze_Cast.vsnd_c"]);
+        assert_eq!(substring[0].archive_index, 0);
+
+        let extension = index
+            .search(&VpkIndexQuery::Extension(".VMAT_C".into()), None)
+            .unwrap();
+        assert_eq!(paths(&extension), ["materials/heroes/haze/haze.vmat_c"]);
+
+        let limited = index
+            .search(&VpkIndexQuery::Substring("haze".into()), Some(2))
+            .unwrap();
+        assert_eq!(limited.len(), 2);
+
+        assert!(
+            index
+                .search(&VpkIndexQuery::Glob("[".into()), None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn refresh_reuses_unchanged_archives_and_drops_missing_ones() {
+        let (dir, first, second) = fixture();
+        let mut index = VpkEntryIndex::new();
+
+        let initial = index.refresh(&[first.clone(), second.clone()]);
+        assert_eq!((initial.parsed, initial.reused), (2, 0));
+
+        let again = index.refresh(&[first.clone(), second.clone()]);
+        assert_eq!((again.parsed, again.reused), (0, 2));
+
+        TestVpk::new()
+            .inline("materials/other.vmat_c", b"a larger replacement archive")
+            .write_to(dir.path(), "pak01");
+        let changed = index.refresh(&[first]);
+        assert_eq!((changed.parsed, changed.reused, changed.removed), (1, 0, 1));
+        let hits = index
+            .search(&VpkIndexQuery::Extension("vmat_c".into()), None)
+            .unwrap();
+        assert_eq!(paths(&hits), ["materials/other.vmat_c"]);
+    }
+
+    #[test]
+    fn refresh_notices_same_second_rewrites_of_the_same_length() {
+        let (dir, first, _second) = fixture();
+        let second_start = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let touch = |nanos: u64| {
+            fs::File::options()
+                .write(true)
+                .open(&first)
+                .unwrap()
+                .set_modified(second_start + std::time::Duration::from_nanos(nanos))
+                .unwrap();
+        };
+
+        touch(100);
+        let mut index = VpkEntryIndex::new();
+        index.refresh(std::slice::from_ref(&first));
+
+        let before = fs::metadata(&first).unwrap().len();
+        TestVpk::new()
+            .inline("materials/heroes/haze/haze_color.vtex_c", b"HAZE")
+            .inline("materials/heroes/haze/haze.vmat_c", b"MATERIAL")
+            .write_to(dir.path(), "pak01");
+        assert_eq!(fs::metadata(&first).unwrap().len(), before);
+        touch(200);
+
+        let changed = index.refresh(std::slice::from_ref(&first));
+        assert_eq!((changed.parsed, changed.reused), (1, 0));
+    }
+
+    #[test]
+    fn index_round_trips_through_disk() {
+        let (dir, first, second) = fixture();
+        let index_path = dir.path().join("cache/entry-index.json");
+        let mut index = VpkEntryIndex::new();
+        index.refresh(&[first.clone(), second.clone()]);
+        index.save(&index_path).unwrap();
+
+        let mut loaded = VpkEntryIndex::load(&index_path);
+        assert_eq!(loaded.archive_count(), 2);
+        assert_eq!(loaded.refresh(&[first, second]).reused, 2);
+
+        fs::write(&index_path, b"{ not json").unwrap();
+        assert_eq!(VpkEntryIndex::load(&index_path).archive_count(), 0);
+    }
+
+    #[test]
+    fn unreadable_archives_are_reported() {
+        let scratch = scratch_dir("entry-index-failed");
+        let dir = scratch.path();
+        let broken = dir.join("pak01_dir.vpk");
+        fs::write(&broken, b"not a vpk").unwrap();
+
+        let mut index = VpkEntryIndex::new();
+        let refresh = index.refresh(&[broken.clone(), dir.join("missing_dir.vpk")]);
+
+        assert_eq!(refresh.failed.len(), 2);
+        assert_eq!(index.archive_count(), 0);
+    }
+}
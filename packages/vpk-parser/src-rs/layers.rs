@@ -0,0 +1,278 @@
+//! Resolves game paths through a stack of VPKs the way the engine mounts
+//! them from the gameinfo.gi `SearchPaths` block.
+//!
+//! Search paths are consulted in the order they are listed, and within one
+//! search path `pak01_dir.vpk` is mounted ahead of `pak02_dir.vpk`, so the
+//! first VPK in that combined order that contains a file supplies it.
+
+use crate::{error::Result, extract::normalize_entry_path, parser::VpkParser};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One `Game` search path and the VPKs mounted from it, highest priority
+/// first.
+#[derive(Debug, Clone)]
+pub struct SearchPath {
+    pub name: String,
+    pub vpks: Vec<PathBuf>,
+}
+
+impl SearchPath {
+    /// A search path whose VPKs are already in mount order.
+    pub fn new(name: impl Into<String>, vpks: Vec<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            vpks,
+        }
+    }
+
+    /// Collects the `pakNN_dir.vpk` files in `dir`, ordered by `NN`. A missing
+    /// folder mounts nothing, like a search path the engine cannot find.
+    pub fn scan(name: impl Into<String>, dir: &Path) -> Result<Self> {
+        let mut numbered = Vec::new();
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if let Some(number) = pak_number(&path) {
+                    numbered.push((number, path));
+                }
+            }
+        }
+        numbered.sort();
+
+        Ok(Self::new(
+            name,
+            numbered.into_iter().map(|(_, path)| path).collect(),
+        ))
+    }
+}
+
+/// The `NN` of a `pakNN_dir.vpk` file name.
+fn pak_number(path: &Path) -> Option<u32> {
+    path.file_name()?
+        .to_str()?
+        .to_ascii_lowercase()
+        .strip_prefix("pak")?
+        .strip_suffix("_dir.vpk")?
+        .parse()
+        .ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VpkLayer {
+    pub search_path: String,
+    pub vpk_path: PathBuf,
+}
+
+/// Which VPK supplies a game path, and the lower-priority VPKs that also
+/// contain it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedFile {
+    pub game_path: String,
+    pub winner: VpkLayer,
+    pub shadowed: Vec<VpkLayer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnreadableLayer {
+    pub layer: VpkLayer,
+    pub error: String,
+}
+
+/// Every file reachable through a stack of search paths.
+pub struct LayeredResolver {
+    layers: Vec<VpkLayer>,
+    /// Normalized game path to its spelling in the archive and the indices of
+    /// the layers that contain it, in priority order.
+    files: BTreeMap<String, (String, Vec<usize>)>,
+    unreadable: Vec<UnreadableLayer>,
+}
+
+impl LayeredResolver {
+    /// Reads the directory tree of every VPK in `search_paths`. A VPK that
+    /// fails to parse is left out of the stack and listed in `unreadable`
+    /// instead of failing the whole resolution. A VPK mounted by more than
+    /// one search path only counts at its first, highest-priority mount.
+    pub fn build(search_paths: &[SearchPath]) -> Self {
+        let mut resolver = Self {
+            layers: Vec::new(),
+            files: BTreeMap::new(),
+            unreadable: Vec::new(),
+        };
+        let mut mounted = HashSet::new();
+
+        for search_path in search_paths {
+            for vpk_path in &search_path.vpks {
+                if !mounted.insert(vpk_path.clone()) {
+                    continue;
+                }
+                let layer = VpkLayer {
+                    search_path: search_path.name.clone(),
+                    vpk_path: vpk_path.clone(),
+                };
+
+                match VpkParser::parse_directory_from_file(vpk_path) {
+                    Ok(entries) => {
+                        let index = resolver.layers.len();
+                        resolver.layers.push(layer);
+                        for entry in entries {
+                            let (_, layers) = resolver
+                                .files
+                                .entry(normalize_entry_path(&entry.full_path))
+                                .or_insert_with(|| (entry.full_path, Vec::new()));
+                            if layers.last() != Some(&index) {
+                                layers.push(index);
+                            }
+                        }
+                    }
+                    Err(error) => resolver.unreadable.push(UnreadableLayer {
+                        layer,
+                        error: error.to_string(),
+                    }),
+                }
+            }
+        }
+
+        resolver
+    }
+
+    /// Every mounted VPK, highest priority first.
+    pub fn layers(&self) -> &[VpkLayer] {
+        &self.layers
+    }
+
+    pub fn unreadable(&self) -> &[UnreadableLayer] {
+        &self.unreadable
+    }
+
+    /// The VPK that supplies `game_path`, matched case-insensitively with
+    /// either separator, or `None` if no mounted VPK contains it.
+    pub fn resolve(&self, game_path: &str) -> Option<ResolvedFile> {
+        self.files
+            .get(&normalize_entry_path(game_path))
+            .map(|(game_path, layers)| self.to_resolved(game_path, layers))
+    }
+
+    /// Every game path supplied by more than one VPK, sorted by path.
+    pub fn overridden(&self) -> Vec<ResolvedFile> {
+        self.files
+            .values()
+            .filter(|(_, layers)| layers.len() > 1)
+            .map(|(game_path, layers)| self.to_resolved(game_path, layers))
+            .collect()
+    }
+
+    fn to_resolved(&self, game_path: &str, layers: &[usize]) -> ResolvedFile {
+        ResolvedFile {
+            game_path: game_path.to_string(),
+            winner: self.layers[layers[0]].clone(),
+            shadowed: layers[1..]
+                .iter()
+                .map(|&index| self.layers[index].clone())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TestVpk, scratch_dir};
+
+    fn install(dir: &Path, name: &str, vpk: TestVpk) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        vpk.write_to(dir, name)
+    }
+
+    #[test]
+    fn lower_pak_numbers_and_earlier_search_paths_win() {
+        let scratch = scratch_dir("layers-priority");
+        let root = scratch.path();
+        let addons = root.join("citadel/addons");
+        let base = root.join("citadel");
+        install(
+            &addons,
+            "pak02",
+            TestVpk::new()
+                .inline("materials/haze.vtex_c", b"second mod")
+                .inline("sounds/only_here.vsnd_c", b"unique"),
+        );
+        install(
+            &addons,
+            "pak01",
+            TestVpk::new().inline("materials/haze.vtex_c", b"first mod"),
+        );
+        install(
+            &base,
+            "pak01",
+            TestVpk::new()
+                .inline("materials/haze.vtex_c", b"vanilla")
+                .inline("scripts/heroes.vdata_c", b"vanilla heroes"),
+        );
+
+        let resolver = LayeredResolver::build(&[
+            SearchPath::scan("citadel/addons", &addons).unwrap(),
+            SearchPath::scan("citadel", &base).unwrap(),
+        ]);
+
+        let haze = resolver.resolve("Materials\\Haze.vtex_c").unwrap();
+        assert_eq!(haze.game_path, "materials/haze.vtex_c");
+        assert_eq!(haze.winner.vpk_path, addons.join("pak01_dir.vpk"));
+        let shadowed: Vec<&Path> = haze.shadowed.iter().map(|l| l.vpk_path.as_path()).collect();
+        assert_eq!(
+            shadowed,
+            [addons.join("pak02_dir.vpk"), base.join("pak01_dir.vpk")]
+        );
+
+        let heroes = resolver.resolve("scripts/heroes.vdata_c").unwrap();
+        assert_eq!(heroes.winner.search_path, "citadel");
+        assert!(heroes.shadowed.is_empty());
+        assert!(resolver.resolve("missing/file.txt").is_none());
+
+        let overridden: Vec<String> = resolver
+            .overridden()
+            .into_iter()
+            .map(|file| file.game_path)
+            .collect();
+        assert_eq!(overridden, ["materials/haze.vtex_c"]);
+    }
+
+    #[test]
+    fn unreadable_vpks_are_reported_and_skipped() {
+        let scratch = scratch_dir("layers-unreadable");
+        let root = scratch.path();
+        install(root, "pak02", TestVpk::new().inline("a/b.txt", b"ok"));
+        fs::write(root.join("pak01_dir.vpk"), b"not a vpk").unwrap();
+        fs::write(root.join("readme.txt"), b"ignored").unwrap();
+
+        let resolver = LayeredResolver::build(&[SearchPath::scan("addons", root).unwrap()]);
+
+        assert_eq!(resolver.layers().len(), 1);
+        assert_eq!(resolver.unreadable().len(), 1);
+        assert_eq!(
+            resolver.unreadable()[0].layer.vpk_path,
+            root.join("pak01_dir.vpk")
+        );
+        assert!(resolver.resolve("a/b.txt").is_some());
+    }
+
+    #[test]
+    fn a_vpk_mounted_twice_only_counts_once() {
+        let scratch = scratch_dir("layers-duplicate");
+        let root = scratch.path();
+        install(root, "pak01", TestVpk::new().inline("a/b.txt", b"ok"));
+
+        let resolver = LayeredResolver::build(&[
+            SearchPath::scan("citadel", root).unwrap(),
+            SearchPath::scan("citadel", root).unwrap(),
+        ]);
+
+        assert_eq!(resolver.layers().len(), 1);
+        assert!(resolver.overridden().is_empty());
+    }
+}
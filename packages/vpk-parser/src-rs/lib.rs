@@ -1,13 +1,29 @@
+mod archive;
+mod diff;
 mod error;
+mod extract;
+mod index;
+mod layers;
 mod parser;
+mod similarity;
+#[cfg(test)]
+mod test_support;
 mod types;
+mod verify;
 
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::path::Path;
 use std::slice;
 
+use serde::Serialize;
+
+pub use archive::{INLINE_ARCHIVE_INDEX, companion_archive_path, header_size};
 pub use error::{Result, VpkError};
+pub use index::VpkEntryIndex;
+pub use layers::{LayeredResolver, ResolvedFile, SearchPath, UnreadableLayer, VpkLayer};
 pub use parser::VpkParser;
+pub use similarity::SIMILARITY_SLOTS;
 pub use types::*;
 
 // Helper function to convert Rust string to C string
@@ -18,9 +34,93 @@ fn to_c_string(s: String) -> *mut c_char {
     }
 }
 
-// Helper function to free C string
+// Helper function to serialize a parse result, or its error, as JSON
+fn to_json_result<T: Serialize>(result: Result<T>) -> *mut c_char {
+    match result {
+        Ok(value) => match serde_json::to_string(&value) {
+            Ok(json) => to_c_string(json),
+            Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
+        },
+        Err(e) => to_c_string(format!(r#"{{"error": "{e}"}}"#)),
+    }
+}
+
+// Helper function to read parse options from a nullable JSON string
+fn read_options(options_json: *const c_char) -> std::result::Result<VpkParseOptions, String> {
+    if options_json.is_null() {
+        return Ok(VpkParseOptions::default());
+    }
+
+    let options_str = unsafe {
+        CStr::from_ptr(options_json)
+            .to_str()
+            .map_err(|_| r#"{"error": "Invalid options string"}"#.to_string())?
+    };
+
+    serde_json::from_str::<VpkParseOptions>(options_str)
+        .map_err(|e| format!(r#"{{"error": "Invalid options JSON: {e}"}}"#))
+}
+
+// Helper function to read an optional JSON array of entry paths; null selects every entry
+fn read_entry_paths(
+    entry_paths_json: *const c_char,
+) -> std::result::Result<Option<Vec<String>>, String> {
+    let json = read_optional_str(entry_paths_json);
+    if json.is_empty() {
+        return Ok(None);
+    }
+
+    serde_json::from_str::<Vec<String>>(&json)
+        .map(Some)
+        .map_err(|e| format!(r#"{{"error": "Invalid entry paths JSON: {e}"}}"#))
+}
+
+// Helper function to read a nullable C string, falling back to an empty string
+fn read_optional_str(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(ptr).to_str().unwrap_or_default().to_string() }
+}
+
+fn hash_options(file_path: String) -> VpkParseOptions {
+    VpkParseOptions {
+        include_full_file_hash: true,
+        include_merkle: true,
+        file_path,
+        last_modified: None,
+        include_entries: true,
+        hash_archive_data: false,
+        lenient: false,
+    }
+}
+
+fn info_options() -> VpkParseOptions {
+    VpkParseOptions {
+        include_full_file_hash: false,
+        include_merkle: false,
+        file_path: String::new(),
+        last_modified: None,
+        include_entries: true,
+        hash_archive_data: false,
+        lenient: false,
+    }
+}
+
+fn to_info(parsed: VpkParsed) -> VpkInfo {
+    VpkInfo {
+        version: parsed.version,
+        file_count: parsed.entries.len(),
+        manifest_sha256: parsed.manifest_sha256,
+        fast_hash: parsed.fingerprint.fast_hash,
+    }
+}
+
+/// # Safety
+/// The caller must ensure `ptr` is a valid pointer returned by `to_c_string()`.
 #[unsafe(no_mangle)]
-pub extern "C" fn vpk_free_string(ptr: *mut c_char) {
+pub unsafe extern "C" fn vpk_free_string(ptr: *mut c_char) {
     if !ptr.is_null() {
         unsafe {
             let _ = CString::from_raw(ptr);
@@ -28,9 +128,12 @@ pub extern "C" fn vpk_free_string(ptr: *mut c_char) {
     }
 }
 
-// Parse VPK from buffer with options as JSON string
+/// Parse VPK from buffer with options as JSON string
+///
+/// # Safety
+/// The caller must ensure `buffer` is a valid pointer to `buffer_len` bytes and `options_json` is null or a valid C string.
 #[unsafe(no_mangle)]
-pub extern "C" fn vpk_parse(
+pub unsafe extern "C" fn vpk_parse(
     buffer: *const u8,
     buffer_len: usize,
     options_json: *const c_char,
@@ -40,38 +143,43 @@ pub extern "C" fn vpk_parse(
     }
 
     let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
-    let buffer_vec = buffer_slice.to_vec();
-
-    // Parse options from JSON
-    let options = if options_json.is_null() {
-        VpkParseOptions::default()
-    } else {
-        let options_str = unsafe {
-            match CStr::from_ptr(options_json).to_str() {
-                Ok(s) => s,
-                Err(_) => return to_c_string(r#"{"error": "Invalid options string"}"#.to_string()),
-            }
-        };
-
-        match serde_json::from_str::<VpkParseOptions>(options_str) {
-            Ok(opts) => opts,
-            Err(e) => return to_c_string(format!(r#"{{"error": "Invalid options JSON: {e}"}}"#)),
-        }
+
+    let options = match read_options(options_json) {
+        Ok(opts) => opts,
+        Err(error_json) => return to_c_string(error_json),
     };
 
-    // Parse the VPK
-    match VpkParser::parse(buffer_vec, options) {
-        Ok(parsed) => match serde_json::to_string(&parsed) {
-            Ok(json) => to_c_string(json),
-            Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
-        },
-        Err(e) => to_c_string(format!(r#"{{"error": "{e}"}}"#)),
+    to_json_result(VpkParser::parse_slice(buffer_slice, options))
+}
+
+/// Parse VPK from a file path (memory-mapped) with options as JSON string
+///
+/// # Safety
+/// The caller must ensure `file_path` and `options_json` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_parse_file(
+    file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let path = read_optional_str(file_path);
+    if path.is_empty() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
     }
+
+    let options = match read_options(options_json) {
+        Ok(opts) => opts,
+        Err(error_json) => return to_c_string(error_json),
+    };
+
+    to_json_result(VpkParser::parse_file(Path::new(&path), options))
 }
 
-// Get VPK hashes only (faster)
+/// Get VPK hashes only (faster)
+///
+/// # Safety
+/// The caller must ensure `buffer` is a valid pointer to `buffer_len` bytes and `file_path` is null or a valid C string.
 #[unsafe(no_mangle)]
-pub extern "C" fn vpk_get_hashes(
+pub unsafe extern "C" fn vpk_get_hashes(
     buffer: *const u8,
     buffer_len: usize,
     file_path: *const c_char,
@@ -81,69 +189,248 @@ pub extern "C" fn vpk_get_hashes(
     }
 
     let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
-    let buffer_vec = buffer_slice.to_vec();
+    let options = hash_options(read_optional_str(file_path));
 
-    let path = if file_path.is_null() {
-        String::new()
-    } else {
-        unsafe {
-            match CStr::from_ptr(file_path).to_str() {
-                Ok(s) => s.to_string(),
-                Err(_) => String::new(),
-            }
-        }
-    };
+    to_json_result(VpkParser::parse_slice(buffer_slice, options).map(|parsed| parsed.fingerprint))
+}
 
-    let options = VpkParseOptions {
-        include_full_file_hash: true,
-        include_merkle: true,
-        file_path: path,
-        last_modified: None,
-        include_entries: true,
+/// Get VPK hashes from a file path (memory-mapped)
+///
+/// # Safety
+/// The caller must ensure `file_path` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_get_hashes_file(file_path: *const c_char) -> *mut c_char {
+    let path = read_optional_str(file_path);
+    if path.is_empty() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
+    }
+
+    let options = hash_options(path.clone());
+    to_json_result(
+        VpkParser::parse_file(Path::new(&path), options).map(|parsed| parsed.fingerprint),
+    )
+}
+
+/// Get basic VPK info (fastest)
+///
+/// # Safety
+/// The caller must ensure `buffer` is a valid pointer to `buffer_len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_get_info(buffer: *const u8, buffer_len: usize) -> *mut c_char {
+    if buffer.is_null() || buffer_len == 0 {
+        return to_c_string(r#"{"error": "Invalid buffer"}"#.to_string());
+    }
+
+    let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
+
+    to_json_result(VpkParser::parse_slice(buffer_slice, info_options()).map(to_info))
+}
+
+/// Get basic VPK info from a file path (memory-mapped)
+///
+/// # Safety
+/// The caller must ensure `file_path` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_get_info_file(file_path: *const c_char) -> *mut c_char {
+    let path = read_optional_str(file_path);
+    if path.is_empty() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
+    }
+
+    to_json_result(VpkParser::parse_file(Path::new(&path), info_options()).map(to_info))
+}
+
+/// Extract one entry's contents (base64 in JSON) from a VPK held in a buffer
+///
+/// # Safety
+/// The caller must ensure `buffer` is a valid pointer to `buffer_len` bytes and `file_path` and `entry_path` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_extract_entry(
+    buffer: *const u8,
+    buffer_len: usize,
+    file_path: *const c_char,
+    entry_path: *const c_char,
+) -> *mut c_char {
+    if buffer.is_null() || buffer_len == 0 {
+        return to_c_string(r#"{"error": "Invalid buffer"}"#.to_string());
+    }
+
+    let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
+    let path = read_optional_str(file_path);
+    let dir_path = (!path.is_empty()).then(|| Path::new(&path));
+    let entry_paths = [read_optional_str(entry_path)];
+
+    to_json_result(
+        VpkParser::read_entries(buffer_slice, dir_path, Some(&entry_paths))
+            .map(|mut contents| contents.remove(0)),
+    )
+}
+
+/// Extract one entry's contents (base64 in JSON) from a VPK file (memory-mapped)
+///
+/// # Safety
+/// The caller must ensure `file_path` and `entry_path` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_extract_entry_file(
+    file_path: *const c_char,
+    entry_path: *const c_char,
+) -> *mut c_char {
+    let path = read_optional_str(file_path);
+    if path.is_empty() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
+    }
+
+    let entry_paths = [read_optional_str(entry_path)];
+    to_json_result(
+        VpkParser::read_entries_from_file(Path::new(&path), Some(&entry_paths))
+            .map(|mut contents| contents.remove(0)),
+    )
+}
+
+/// Read several entries (a JSON array of paths, or null for all) from a VPK held in a buffer
+///
+/// # Safety
+/// The caller must ensure `buffer` is a valid pointer to `buffer_len` bytes and `file_path` and `entry_paths_json` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_read_entries(
+    buffer: *const u8,
+    buffer_len: usize,
+    file_path: *const c_char,
+    entry_paths_json: *const c_char,
+) -> *mut c_char {
+    if buffer.is_null() || buffer_len == 0 {
+        return to_c_string(r#"{"error": "Invalid buffer"}"#.to_string());
+    }
+
+    let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
+    let path = read_optional_str(file_path);
+    let dir_path = (!path.is_empty()).then(|| Path::new(&path));
+    let entry_paths = match read_entry_paths(entry_paths_json) {
+        Ok(paths) => paths,
+        Err(error_json) => return to_c_string(error_json),
     };
 
-    match VpkParser::parse(buffer_vec, options) {
-        Ok(parsed) => match serde_json::to_string(&parsed.fingerprint) {
-            Ok(json) => to_c_string(json),
-            Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
-        },
-        Err(e) => to_c_string(format!(r#"{{"error": "{e}"}}"#)),
+    to_json_result(VpkParser::read_entries(
+        buffer_slice,
+        dir_path,
+        entry_paths.as_deref(),
+    ))
+}
+
+/// Read several entries (a JSON array of paths, or null for all) from a VPK file (memory-mapped)
+///
+/// # Safety
+/// The caller must ensure `file_path` and `entry_paths_json` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_read_entries_file(
+    file_path: *const c_char,
+    entry_paths_json: *const c_char,
+) -> *mut c_char {
+    let path = read_optional_str(file_path);
+    if path.is_empty() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
     }
+
+    let entry_paths = match read_entry_paths(entry_paths_json) {
+        Ok(paths) => paths,
+        Err(error_json) => return to_c_string(error_json),
+    };
+
+    to_json_result(VpkParser::read_entries_from_file(
+        Path::new(&path),
+        entry_paths.as_deref(),
+    ))
 }
 
-// Get basic VPK info (fastest)
+/// Verify the MD5 and signature sections of a VPK held in a buffer
+///
+/// # Safety
+/// The caller must ensure `buffer` is a valid pointer to `buffer_len` bytes and `file_path` is null or a valid C string.
 #[unsafe(no_mangle)]
-pub extern "C" fn vpk_get_info(buffer: *const u8, buffer_len: usize) -> *mut c_char {
+pub unsafe extern "C" fn vpk_verify(
+    buffer: *const u8,
+    buffer_len: usize,
+    file_path: *const c_char,
+) -> *mut c_char {
     if buffer.is_null() || buffer_len == 0 {
         return to_c_string(r#"{"error": "Invalid buffer"}"#.to_string());
     }
 
     let buffer_slice = unsafe { slice::from_raw_parts(buffer, buffer_len) };
-    let buffer_vec = buffer_slice.to_vec();
+    let path = read_optional_str(file_path);
+    let dir_path = (!path.is_empty()).then(|| Path::new(&path));
 
-    let options = VpkParseOptions {
-        include_full_file_hash: false,
-        include_merkle: false,
-        file_path: String::new(),
-        last_modified: None,
-        include_entries: true,
+    to_json_result(VpkParser::verify_slice(buffer_slice, dir_path))
+}
+
+/// Verify the MD5 and signature sections of a VPK file (memory-mapped)
+///
+/// # Safety
+/// The caller must ensure `file_path` is null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_verify_file(file_path: *const c_char) -> *mut c_char {
+    let path = read_optional_str(file_path);
+    if path.is_empty() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
+    }
+
+    to_json_result(VpkParser::verify_file(Path::new(&path)))
+}
+
+/// Diff two parsed VPKs, each passed as the JSON returned by `vpk_parse` with entries included
+///
+/// # Safety
+/// The caller must ensure `old_json` and `new_json` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_diff(old_json: *const c_char, new_json: *const c_char) -> *mut c_char {
+    let parse = |json: *const c_char| {
+        serde_json::from_str::<VpkParsed>(&read_optional_str(json))
+            .map_err(|e| format!(r#"{{"error": "Invalid parsed VPK JSON: {e}"}}"#))
+    };
+    let (old, new) = match (parse(old_json), parse(new_json)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(error_json), _) | (_, Err(error_json)) => return to_c_string(error_json),
     };
 
-    match VpkParser::parse(buffer_vec, options) {
-        Ok(parsed) => {
-            let info = VpkInfo {
-                version: parsed.version,
-                file_count: parsed.entries.len(),
-                manifest_sha256: parsed.manifest_sha256,
-                fast_hash: parsed.fingerprint.fast_hash,
-            };
-            match serde_json::to_string(&info) {
-                Ok(json) => to_c_string(json),
-                Err(e) => to_c_string(format!(r#"{{"error": "Serialization failed: {e}"}}"#)),
-            }
-        }
-        Err(e) => to_c_string(format!(r#"{{"error": "{e}"}}"#)),
+    to_json_result(Ok(VpkParser::diff(&old, &new)))
+}
+
+/// Diff two VPK files on disk (memory-mapped)
+///
+/// # Safety
+/// The caller must ensure `old_path` and `new_path` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_diff_files(
+    old_path: *const c_char,
+    new_path: *const c_char,
+) -> *mut c_char {
+    let old_path = read_optional_str(old_path);
+    let new_path = read_optional_str(new_path);
+    if old_path.is_empty() || new_path.is_empty() {
+        return to_c_string(r#"{"error": "Invalid file path"}"#.to_string());
     }
+
+    to_json_result(
+        VpkParser::parse_file(Path::new(&old_path), info_options()).and_then(|old| {
+            VpkParser::parse_file(Path::new(&new_path), info_options())
+                .map(|new| VpkParser::diff(&old, &new))
+        }),
+    )
+}
+
+/// Score how alike two VPKs are (0.0 to 1.0) from their fingerprints' similarity signatures
+///
+/// # Safety
+/// The caller must ensure `signature_a` and `signature_b` are null or valid C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vpk_similarity(
+    signature_a: *const c_char,
+    signature_b: *const c_char,
+) -> *mut c_char {
+    to_json_result(VpkParser::similarity(
+        &read_optional_str(signature_a),
+        &read_optional_str(signature_b),
+    ))
 }
 
 // Get library version
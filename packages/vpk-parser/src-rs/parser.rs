@@ -1,10 +1,13 @@
 use crate::{
+    archive::{ArchiveReader, INLINE_ARCHIVE_INDEX, header_size},
     error::{Result, VpkError},
+    similarity::is_junk_entry,
     types::*,
 };
 use chrono::{DateTime, Utc};
+use memmap2::Mmap;
 use sha2::{Digest, Sha256};
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 use std::hash::Hasher;
 use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
@@ -12,19 +15,93 @@ use twox_hash::XxHash64;
 
 const VPK_SIGNATURE: u32 = 0x55aa1234;
 
-pub struct VpkParser {
-    buffer: Vec<u8>,
+/// Whole-file hashes are fed in slices of this size so that a memory-mapped
+/// archive is paged through once, with both hashers sharing each pass.
+const HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Parses a VPK directory file that lives in a caller-owned buffer. The parser
+/// only ever borrows the bytes, so a slice handed over FFI or a memory-mapped
+/// file is read in place without being copied.
+pub struct VpkParser<'a> {
+    buffer: &'a [u8],
     cursor: usize,
+    /// Buffer offset of each parsed entry's preload bytes, in entry order.
+    preload_offsets: Vec<usize>,
+    /// Record tree errors in `diagnostics` and keep the entries read so far.
+    lenient: bool,
+    diagnostics: Vec<VpkDiagnostic>,
+}
+
+/// How far the tree walk got, so a lenient parse can say where it stopped.
+#[derive(Default)]
+struct TreePosition {
+    ext: String,
+    path: String,
+    filename: String,
+    record_start: usize,
+}
+
+impl TreePosition {
+    fn path_so_far(&self) -> String {
+        let ext = if self.ext.is_empty() { "*" } else { &self.ext };
+        let file = if self.filename.is_empty() {
+            "*"
+        } else {
+            &self.filename
+        };
+        match self.path.as_str() {
+            "" | " " => format!("{file}.{ext}"),
+            path => format!("{path}/{file}.{ext}"),
+        }
+    }
+}
+
+struct FileHashes {
+    fast_hash: String,
+    sha256: String,
 }
 
-impl VpkParser {
-    pub fn new(buffer: Vec<u8>) -> Self {
-        Self { buffer, cursor: 0 }
+impl<'a> VpkParser<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self {
+            buffer,
+            cursor: 0,
+            preload_offsets: Vec::new(),
+            lenient: false,
+            diagnostics: Vec::new(),
+        }
     }
 
     pub fn parse(buffer: Vec<u8>, options: VpkParseOptions) -> Result<VpkParsed> {
-        let mut parser = Self::new(buffer);
-        parser.parse_internal(options)
+        VpkParser::parse_slice(&buffer, options)
+    }
+
+    /// Parses a VPK held in a borrowed slice without copying it. When
+    /// `hash_archive_data` is set, companion archives are resolved relative to
+    /// `options.file_path`.
+    pub fn parse_slice(buffer: &[u8], options: VpkParseOptions) -> Result<VpkParsed> {
+        let dir_path = (!options.file_path.is_empty()).then(|| Path::new(&options.file_path));
+        let mut parser = VpkParser::new(buffer);
+        parser.parse_internal(&options, dir_path)
+    }
+
+    /// Parses a VPK file by memory-mapping it, so multi-GB directory files are
+    /// paged in by the OS instead of being read into a heap buffer.
+    ///
+    /// The mapping is only valid while nobody truncates the file underneath it;
+    /// callers scanning folders that another process writes to should expect
+    /// the usual I/O errors rather than a consistent snapshot.
+    pub fn parse_file(path: &Path, options: VpkParseOptions) -> Result<VpkParsed> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the map is read-only and dropped before this function returns.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let mut parser = VpkParser::new(&mmap);
+        parser.parse_internal(&options, Some(path))
+    }
+
+    /// Reads only the fixed-size header at the start of a VPK buffer.
+    pub fn read_header(buffer: &[u8]) -> Result<VpkHeader> {
+        VpkParser::new(buffer).parse_header()
     }
 
     /// Reads only the VPK header + directory tree from a file on disk,
@@ -62,28 +139,58 @@ impl VpkParser {
         file.seek(SeekFrom::Start(0))?;
         file.read_exact(&mut tree_buf)?;
 
-        let mut parser = Self::new(tree_buf);
+        let mut parser = VpkParser::new(&tree_buf);
         parser.cursor = header_size;
         parser.parse_directory_tree(header_size, tree_length as usize)
     }
 
-    fn parse_internal(&mut self, options: VpkParseOptions) -> Result<VpkParsed> {
+    /// Parses the header and directory tree, leaving the preload offsets of
+    /// the returned entries in `preload_offsets`.
+    pub(crate) fn parse_tree(&mut self) -> Result<(VpkHeader, Vec<VpkEntry>)> {
         let header = self.parse_header()?;
         let tree_start = self.cursor;
         let entries = self.parse_directory_tree(tree_start, header.tree_length as usize)?;
+        Ok((header, entries))
+    }
+
+    pub(crate) fn preload_offsets(&self) -> &[usize] {
+        &self.preload_offsets
+    }
+
+    fn parse_internal(
+        &mut self,
+        options: &VpkParseOptions,
+        dir_path: Option<&Path>,
+    ) -> Result<VpkParsed> {
+        self.lenient = options.lenient;
+        let (header, entries) = self.parse_tree()?;
+        if self.lenient {
+            self.check_entry_bounds(&header, &entries);
+        }
         let manifest_sha256 = self.generate_manifest_hash(&entries);
 
+        let mut archives = options
+            .hash_archive_data
+            .then(|| ArchiveReader::new(self.buffer, header.version, header.tree_length, dir_path));
+
         let fingerprint = self.generate_fingerprint(
             &entries,
             &options.file_path,
             options.last_modified,
             options.include_merkle,
+            archives.as_mut(),
         )?;
 
-        let dir_sha256 = if options.include_full_file_hash {
-            Some(self.generate_sha256_hash()?)
-        } else {
-            None
+        // Unless companions were folded in, the fingerprint already hashed the
+        // whole file; reuse it rather than walking a potentially multi-GB buffer
+        // a second time.
+        let dir_sha256 = match (
+            options.include_full_file_hash,
+            fingerprint.includes_archive_data,
+        ) {
+            (false, _) => None,
+            (true, false) => Some(fingerprint.sha256.clone()),
+            (true, true) => Some(Self::hash_slices([self.buffer]).sha256),
         };
 
         // Only include entries if requested
@@ -104,6 +211,8 @@ impl VpkParser {
             manifest_sha256,
             dir_sha256,
             fingerprint,
+            partial: !self.diagnostics.is_empty(),
+            diagnostics: std::mem::take(&mut self.diagnostics),
         })
     }
 
@@ -162,28 +271,79 @@ impl VpkParser {
         tree_length: usize,
     ) -> Result<Vec<VpkEntry>> {
         let mut entries = Vec::new();
-        let tree_end = tree_start + tree_length;
+        let mut tree_end = tree_start + tree_length;
+        if self.lenient && tree_end > self.buffer.len() {
+            self.diagnostics.push(VpkDiagnostic {
+                offset: self.buffer.len(),
+                path: String::new(),
+                reason: format!(
+                    "Directory tree ends at byte {tree_end}, past the end of the {}-byte file",
+                    self.buffer.len()
+                ),
+            });
+            tree_end = self.buffer.len();
+        }
 
+        let mut position = TreePosition::default();
+        match self.walk_directory_tree(tree_end, &mut entries, &mut position) {
+            Ok(()) => Ok(entries),
+            Err(error) if self.lenient => {
+                // The tree has no record boundaries to resynchronise on, so
+                // everything after the first malformed record is lost.
+                self.preload_offsets.truncate(entries.len());
+                self.diagnostics.push(VpkDiagnostic {
+                    offset: position.record_start,
+                    path: position.path_so_far(),
+                    reason: error.to_string(),
+                });
+                Ok(entries)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn walk_directory_tree(
+        &mut self,
+        tree_end: usize,
+        entries: &mut Vec<VpkEntry>,
+        position: &mut TreePosition,
+    ) -> Result<()> {
         while self.cursor < tree_end {
-            let ext = self.read_null_terminated_string()?;
-            if ext.is_empty() {
+            position.record_start = self.cursor;
+            position.ext = self.read_null_terminated_string()?;
+            if position.ext.is_empty() {
                 break;
             }
 
             while self.cursor < tree_end {
-                let path = self.read_null_terminated_string()?;
-                if path.is_empty() {
+                position.record_start = self.cursor;
+                position.path = self.read_null_terminated_string()?;
+                if position.path.is_empty() {
                     break;
                 }
 
                 while self.cursor < tree_end {
-                    let filename = self.read_null_terminated_string()?;
-                    if filename.is_empty() {
+                    position.record_start = self.cursor;
+                    position.filename = self.read_null_terminated_string()?;
+                    if position.filename.is_empty() {
                         break;
                     }
 
-                    let entry = self.parse_entry(&ext, &path, &filename)?;
-                    entries.push(entry.clone());
+                    let entry =
+                        self.parse_entry(&position.ext, &position.path, &position.filename)?;
+                    if self.lenient && entry.terminator != 0xffff {
+                        // The record length is fixed, so the walk can carry on
+                        // past a damaged terminator without losing its place.
+                        self.diagnostics.push(VpkDiagnostic {
+                            offset: position.record_start,
+                            path: position.path_so_far(),
+                            reason: VpkError::InvalidTerminator {
+                                actual: entry.terminator,
+                            }
+                            .to_string(),
+                        });
+                    }
+                    self.preload_offsets.push(self.cursor);
 
                     if entry.preload_bytes > 0 {
                         self.cursor += entry.preload_bytes as usize;
@@ -195,11 +355,37 @@ impl VpkParser {
                             });
                         }
                     }
+
+                    entries.push(entry);
                 }
+                position.filename.clear();
             }
+            position.path.clear();
         }
 
-        Ok(entries)
+        Ok(())
+    }
+
+    /// Flags inline entries whose data would run past the end of the
+    /// directory file, as happens with a truncated download.
+    fn check_entry_bounds(&mut self, header: &VpkHeader, entries: &[VpkEntry]) {
+        let data_start = header_size(header.version) + header.tree_length as usize;
+        for entry in entries {
+            if entry.archive_index != INLINE_ARCHIVE_INDEX {
+                continue;
+            }
+            let data_end = data_start + entry.entry_offset as usize + entry.entry_length as usize;
+            if data_end > self.buffer.len() {
+                self.diagnostics.push(VpkDiagnostic {
+                    offset: data_start + entry.entry_offset as usize,
+                    path: entry.full_path.clone(),
+                    reason: format!(
+                        "Entry data ends at byte {data_end}, past the end of the {}-byte file",
+                        self.buffer.len()
+                    ),
+                });
+            }
+        }
     }
 
     fn parse_entry(&mut self, ext: &str, path: &str, filename: &str) -> Result<VpkEntry> {
@@ -266,16 +452,22 @@ impl VpkParser {
         file_path: &str,
         last_modified: Option<DateTime<Utc>>,
         include_merkle: bool,
+        mut archives: Option<&mut ArchiveReader<'_>>,
     ) -> Result<VpkFingerprint> {
         let has_multiparts = self.detect_multiparts(entries);
         let has_inline_data = self.detect_inline_data(entries);
 
-        let fast_hash = self.generate_fast_hash()?;
-        let sha256 = self.generate_sha256_hash()?;
+        let FileHashes { fast_hash, sha256 } = match archives.as_deref_mut() {
+            Some(archives) => self.generate_archive_hashes(entries, archives)?,
+            None => Self::hash_slices([self.buffer]),
+        };
         let content_signature = self.generate_content_signature(entries);
 
         let (merkle_root, merkle_leaves) = if include_merkle {
-            let merkle_data = self.generate_merkle_hash(entries);
+            let merkle_data = match archives.as_deref_mut() {
+                Some(archives) => self.generate_content_merkle_hash(entries, archives)?,
+                None => self.generate_merkle_hash(entries),
+            };
             (Some(merkle_data.root), Some(merkle_data.leaves))
         } else {
             (None, None)
@@ -294,38 +486,53 @@ impl VpkParser {
             has_inline_data,
             merkle_root,
             merkle_leaves,
+            includes_archive_data: archives.is_some(),
+            similarity_signature: Self::similarity_signature(entries),
         })
     }
 
-    fn generate_fast_hash(&self) -> Result<String> {
-        let mut hasher = XxHash64::with_seed(0);
-        hasher.write(&self.buffer);
-        let hash = hasher.finish();
-        Ok(format!("{:016x}", hash))
-    }
+    fn hash_slices<'b>(slices: impl IntoIterator<Item = &'b [u8]>) -> FileHashes {
+        let mut fast_hasher = XxHash64::with_seed(0);
+        let mut sha_hasher = Sha256::new();
+        for chunk in slices
+            .into_iter()
+            .flat_map(|slice| slice.chunks(HASH_CHUNK_SIZE))
+        {
+            fast_hasher.write(chunk);
+            sha_hasher.update(chunk);
+        }
 
-    fn generate_sha256_hash(&self) -> Result<String> {
-        let mut hasher = Sha256::new();
-        hasher.update(&self.buffer);
-        Ok(format!("{:x}", hasher.finalize()))
+        FileHashes {
+            fast_hash: format!("{:016x}", fast_hasher.finish()),
+            sha256: format!("{:x}", sha_hasher.finalize()),
+        }
     }
 
-    fn generate_content_signature(&self, entries: &[VpkEntry]) -> String {
-        let junk_files: HashSet<&str> = ["thumbs.db", ".ds_store", "desktop.ini", ".tmp", ".temp"]
+    /// Hashes the directory file followed by every companion archive its
+    /// entries reference, in archive-index order.
+    fn generate_archive_hashes(
+        &self,
+        entries: &[VpkEntry],
+        archives: &mut ArchiveReader<'_>,
+    ) -> Result<FileHashes> {
+        let archive_indices: Vec<u16> = entries
             .iter()
-            .copied()
+            .map(|entry| entry.archive_index)
+            .filter(|&index| index != INLINE_ARCHIVE_INDEX)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
             .collect();
 
+        let companions = archives.companions(&archive_indices)?;
+        Ok(Self::hash_slices(
+            std::iter::once(self.buffer).chain(companions),
+        ))
+    }
+
+    fn generate_content_signature(&self, entries: &[VpkEntry]) -> String {
         let filtered_entries: Vec<&VpkEntry> = entries
             .iter()
-            .filter(|entry| {
-                let filename = entry.filename.to_lowercase();
-                let full_path = entry.full_path.to_lowercase();
-                if junk_files.contains(filename.as_str()) {
-                    return false;
-                }
-                !junk_files.iter().any(|junk| full_path.contains(junk))
-            })
+            .filter(|entry| !is_junk_entry(entry))
             .collect();
 
         let mut tuples: Vec<String> = filtered_entries
@@ -361,6 +568,32 @@ impl VpkParser {
             })
             .collect();
 
+        Self::merkle_from_leaves(leaves)
+    }
+
+    /// Merkle tree whose leaves hash each entry's path and actual bytes
+    /// (preload data followed by its archive data), so two archives only share
+    /// a root when every file in them is byte-identical.
+    fn generate_content_merkle_hash(
+        &self,
+        entries: &[VpkEntry],
+        archives: &mut ArchiveReader<'_>,
+    ) -> Result<MerkleData> {
+        let mut leaves = Vec::with_capacity(entries.len());
+        for (entry, &preload_offset) in entries.iter().zip(&self.preload_offsets) {
+            let (preload, body) = archives.entry_parts(entry, preload_offset)?;
+            let mut hasher = Sha256::new();
+            hasher.update(entry.full_path.as_bytes());
+            hasher.update(b"|");
+            hasher.update(preload);
+            hasher.update(body);
+            leaves.push(format!("{:x}", hasher.finalize()));
+        }
+
+        Ok(Self::merkle_from_leaves(leaves))
+    }
+
+    fn merkle_from_leaves(leaves: Vec<String>) -> MerkleData {
         let mut sorted_leaves = leaves.clone();
         sorted_leaves.sort();
 
@@ -447,3 +680,223 @@ impl VpkParser {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::companion_archive_path;
+    use crate::test_support::{TestVpk, scratch_dir};
+
+    fn archive_options(dir_path: &Path) -> VpkParseOptions {
+        VpkParseOptions {
+            file_path: dir_path.to_string_lossy().to_string(),
+            include_merkle: true,
+            include_entries: true,
+            hash_archive_data: true,
+            ..Default::default()
+        }
+    }
+
+    fn multipart() -> TestVpk {
+        TestVpk::new()
+            .inline("scripts/mod.vdata_c", b"inline")
+            .companion("materials/a.vtex_c", b"first companion", 0)
+            .companion("materials/b.vtex_c", b"second companion", 1)
+    }
+
+    #[test]
+    fn archive_fingerprint_sees_companion_changes_the_dir_fingerprint_misses() {
+        let scratch = scratch_dir("fingerprint-companions");
+        let dir = scratch.path();
+        let path = multipart().write_to(dir, "pak01");
+
+        let dir_only = VpkParseOptions {
+            include_merkle: true,
+            ..Default::default()
+        };
+        let before = VpkParser::parse_file(&path, dir_only.clone()).unwrap();
+        let before_full = VpkParser::parse_file(&path, archive_options(&path)).unwrap();
+
+        let companion = companion_archive_path(&path, 1);
+        let mut bytes = std::fs::read(&companion).unwrap();
+        bytes[0] ^= 0xff;
+        std::fs::write(&companion, bytes).unwrap();
+
+        let after = VpkParser::parse_file(&path, dir_only).unwrap();
+        let after_full = VpkParser::parse_file(&path, archive_options(&path)).unwrap();
+
+        assert_eq!(before.fingerprint.sha256, after.fingerprint.sha256);
+        assert_eq!(
+            before.fingerprint.merkle_root,
+            after.fingerprint.merkle_root
+        );
+        assert!(!after.fingerprint.includes_archive_data);
+
+        assert!(after_full.fingerprint.includes_archive_data);
+        assert_ne!(
+            before_full.fingerprint.sha256,
+            after_full.fingerprint.sha256
+        );
+        assert_ne!(
+            before_full.fingerprint.fast_hash,
+            after_full.fingerprint.fast_hash
+        );
+        assert_ne!(
+            before_full.fingerprint.merkle_root,
+            after_full.fingerprint.merkle_root
+        );
+    }
+
+    #[test]
+    fn dir_sha256_stays_a_hash_of_the_directory_file() {
+        let scratch = scratch_dir("fingerprint-dir-sha");
+        let dir = scratch.path();
+        let path = multipart().write_to(dir, "pak01");
+
+        let options = VpkParseOptions {
+            include_full_file_hash: true,
+            ..archive_options(&path)
+        };
+        let parsed = VpkParser::parse_file(&path, options).unwrap();
+
+        let expected = format!("{:x}", Sha256::digest(std::fs::read(&path).unwrap()));
+        assert_eq!(parsed.dir_sha256.as_deref(), Some(expected.as_str()));
+        assert_ne!(parsed.fingerprint.sha256, expected);
+    }
+
+    #[test]
+    fn content_leaves_cover_preload_and_inline_bytes() {
+        let built = TestVpk::new()
+            .preload("panorama/layout.xml", b"<root>preloaded</root>", 6)
+            .build();
+        let parsed = VpkParser::parse_slice(
+            &built.dir,
+            VpkParseOptions {
+                include_merkle: true,
+                hash_archive_data: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let expected = format!(
+            "{:x}",
+            Sha256::digest(b"panorama/layout.xml|<root>preloaded</root>")
+        );
+        assert_eq!(parsed.fingerprint.merkle_leaves, Some(vec![expected]));
+    }
+
+    #[test]
+    fn missing_companion_fails_archive_fingerprinting() {
+        let scratch = scratch_dir("fingerprint-missing");
+        let dir = scratch.path();
+        let path = multipart().write_to(dir, "pak01");
+        std::fs::remove_file(companion_archive_path(&path, 0)).unwrap();
+
+        let error = VpkParser::parse_file(&path, archive_options(&path)).unwrap_err();
+        assert!(matches!(
+            error,
+            VpkError::MissingArchive {
+                archive_index: 0,
+                ..
+            }
+        ));
+    }
+
+    fn lenient_options() -> VpkParseOptions {
+        VpkParseOptions {
+            include_entries: true,
+            lenient: true,
+            ..Default::default()
+        }
+    }
+
+    fn three_scripts() -> Vec<u8> {
+        TestVpk::new()
+            .inline("scripts/a.txt", b"first")
+            .inline("scripts/b.txt", b"second")
+            .inline("scripts/c.txt", b"third")
+            .build()
+            .dir
+    }
+
+    #[test]
+    fn lenient_parse_of_an_intact_archive_is_not_partial() {
+        let parsed = VpkParser::parse(three_scripts(), lenient_options()).unwrap();
+
+        assert!(!parsed.partial);
+        assert!(parsed.diagnostics.is_empty());
+        assert_eq!(parsed.entries.len(), 3);
+    }
+
+    #[test]
+    fn lenient_parse_keeps_entries_before_a_truncated_record() {
+        // Header (28) + "txt\0scripts\0" (12) + two 20-byte records, then 5
+        // bytes into the third record.
+        let mut dir = three_scripts();
+        dir.truncate(28 + 12 + 40 + 5);
+
+        assert!(VpkParser::parse(dir.clone(), VpkParseOptions::default()).is_err());
+
+        let parsed = VpkParser::parse(dir, lenient_options()).unwrap();
+        assert!(parsed.partial);
+        let paths: Vec<&str> = parsed
+            .entries
+            .iter()
+            .map(|e| e.full_path.as_str())
+            .collect();
+        assert_eq!(paths, ["scripts/a.txt", "scripts/b.txt"]);
+
+        // The data section is gone too, so the surviving entries are flagged
+        // alongside the record the tree walk stopped at.
+        let failure = parsed
+            .diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.path == "scripts/c.txt")
+            .unwrap();
+        assert_eq!(failure.offset, 28 + 12 + 40);
+        assert!(failure.reason.contains("Cursor overrun"));
+    }
+
+    #[test]
+    fn lenient_parse_keeps_entries_past_a_corrupt_terminator() {
+        let mut dir = three_scripts();
+        // Terminator of the second record: its last two bytes.
+        let terminator = 28 + 12 + 20 + 18;
+        dir[terminator] = 0;
+
+        assert!(VpkParser::parse(dir.clone(), VpkParseOptions::default()).is_ok());
+
+        let parsed = VpkParser::parse(dir, lenient_options()).unwrap();
+
+        assert!(parsed.partial);
+        let paths: Vec<&str> = parsed
+            .entries
+            .iter()
+            .map(|e| e.full_path.as_str())
+            .collect();
+        assert_eq!(paths, ["scripts/a.txt", "scripts/b.txt", "scripts/c.txt"]);
+        assert_eq!(parsed.entries[2].terminator, 0xffff);
+        assert_eq!(
+            parsed.diagnostics,
+            [VpkDiagnostic {
+                offset: 28 + 12 + 20,
+                path: "scripts/b.txt".to_string(),
+                reason: "Invalid entry terminator: expected 0xFFFF, got 0xff00".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn lenient_parse_flags_inline_data_cut_off_by_truncation() {
+        let mut dir = three_scripts();
+        dir.truncate(dir.len() - 2);
+
+        let parsed = VpkParser::parse(dir, lenient_options()).unwrap();
+
+        assert_eq!(parsed.entries.len(), 3);
+        assert!(parsed.partial);
+        assert_eq!(parsed.diagnostics.len(), 1);
+        assert_eq!(parsed.diagnostics[0].path, "scripts/c.txt");
+    }
+}
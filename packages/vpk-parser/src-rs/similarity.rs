@@ -0,0 +1,132 @@
+use crate::{
+    error::{Result, VpkError},
+    parser::VpkParser,
+    types::VpkEntry,
+};
+use std::hash::Hasher;
+use twox_hash::XxHash64;
+
+/// Number of MinHash slots in a similarity signature. The standard error of
+/// the Jaccard estimate is about `1 / sqrt(64)`, i.e. ±12.5 percentage points
+/// in the worst case and much tighter near 0% and 100%.
+pub const SIMILARITY_SLOTS: usize = 64;
+
+/// Entries that packing tools and file managers leave behind, which say
+/// nothing about a mod's content.
+const JUNK_FILES: [&str; 5] = ["thumbs.db", ".ds_store", "desktop.ini", ".tmp", ".temp"];
+
+pub(crate) fn is_junk_entry(entry: &VpkEntry) -> bool {
+    let filename = entry.filename.to_lowercase();
+    let full_path = entry.full_path.to_lowercase();
+    JUNK_FILES.contains(&filename.as_str())
+        || JUNK_FILES.iter().any(|junk| full_path.contains(junk))
+}
+
+/// SplitMix64 finalizer, used to derive every MinHash slot from one base hash
+/// per entry instead of rehashing the entry for each slot.
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
+
+fn decode_signature(signature: &str) -> Result<Vec<u64>> {
+    let invalid = || VpkError::Validation {
+        message: format!(
+            "Similarity signature must be {} hex characters",
+            SIMILARITY_SLOTS * 16
+        ),
+    };
+    if signature.len() != SIMILARITY_SLOTS * 16 || !signature.is_ascii() {
+        return Err(invalid());
+    }
+    (0..SIMILARITY_SLOTS)
+        .map(|slot| {
+            u64::from_str_radix(&signature[slot * 16..(slot + 1) * 16], 16).map_err(|_| invalid())
+        })
+        .collect()
+}
+
+impl VpkParser<'_> {
+    /// MinHash signature over the set of `(path, crc32)` pairs of an archive,
+    /// hex-encoded. Two signatures agree in a fraction of slots that estimates
+    /// the Jaccard similarity of the two sets, so a re-pack with the same files
+    /// scores 1.0 and editing a few files lowers the score proportionally.
+    /// Paths are compared case-insensitively and junk files are ignored.
+    pub fn similarity_signature(entries: &[VpkEntry]) -> String {
+        let mut slots = [u64::MAX; SIMILARITY_SLOTS];
+        for entry in entries.iter().filter(|entry| !is_junk_entry(entry)) {
+            let mut hasher = XxHash64::with_seed(0);
+            hasher.write(entry.full_path.to_lowercase().replace('\\', "/").as_bytes());
+            hasher.write(&[0]);
+            hasher.write(entry.crc32_hex.to_ascii_lowercase().as_bytes());
+            let base = hasher.finish();
+
+            for (seed, slot) in slots.iter_mut().enumerate() {
+                *slot = (*slot).min(mix(base ^ mix(seed as u64 + 1)));
+            }
+        }
+
+        slots.iter().map(|slot| format!("{slot:016x}")).collect()
+    }
+
+    /// Estimated share of `(path, crc32)` pairs two archives have in common,
+    /// from 0.0 to 1.0, given their `similarity_signature`s.
+    pub fn similarity(a: &str, b: &str) -> Result<f64> {
+        let a = decode_signature(a)?;
+        let b = decode_signature(b)?;
+        let matching = a.iter().zip(&b).filter(|(a, b)| a == b).count();
+        Ok(matching as f64 / SIMILARITY_SLOTS as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestVpk;
+    use crate::types::VpkParseOptions;
+
+    fn signature(vpk: TestVpk) -> String {
+        VpkParser::parse(vpk.build().dir, VpkParseOptions::default())
+            .unwrap()
+            .fingerprint
+            .similarity_signature
+    }
+
+    fn files(count: usize, edited: usize) -> TestVpk {
+        (0..count).fold(TestVpk::new(), |vpk, index| {
+            let data = if index < edited {
+                format!("edited {index}")
+            } else {
+                format!("original {index}")
+            };
+            vpk.inline(&format!("materials/file{index}.vmat_c"), data.as_bytes())
+        })
+    }
+
+    #[test]
+    fn repacked_archive_is_identical() {
+        let original = signature(files(40, 0));
+        let repacked = signature(files(40, 0).inline("Thumbs.db", b"junk"));
+
+        assert_eq!(VpkParser::similarity(&original, &repacked).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn lightly_edited_archive_scores_high_and_unrelated_scores_low() {
+        let original = signature(files(100, 0));
+        let edited = signature(files(100, 5));
+        let unrelated = signature(TestVpk::new().inline("sounds/other.vsnd_c", b"other"));
+
+        let close = VpkParser::similarity(&original, &edited).unwrap();
+        assert!(close > 0.75 && close < 1.0, "{close}");
+        assert_eq!(VpkParser::similarity(&original, &unrelated).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn malformed_signature_is_rejected() {
+        let original = signature(files(1, 0));
+
+        assert!(VpkParser::similarity(&original, "abc").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Builds small VPK v2 archives in memory for unit tests.
+
+use crate::archive::{INLINE_ARCHIVE_INDEX, companion_archive_path};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use md5::{Digest, Md5};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+struct TestEntry {
+    path: String,
+    data: Vec<u8>,
+    preload: usize,
+    archive_index: u16,
+}
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+type Tree<'a> = BTreeMap<String, BTreeMap<String, Vec<(String, &'a TestEntry)>>>;
+
+pub struct BuiltVpk {
+    pub dir: Vec<u8>,
+    pub companions: BTreeMap<u16, Vec<u8>>,
+}
+
+#[derive(Default)]
+pub struct TestVpk {
+    entries: Vec<TestEntry>,
+    with_checksums: bool,
+}
+
+impl TestVpk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An entry stored in the directory file's data section.
+    pub fn inline(self, path: &str, data: &[u8]) -> Self {
+        self.entry(path, data, 0, INLINE_ARCHIVE_INDEX)
+    }
+
+    /// An entry whose first `preload` bytes live in the directory tree.
+    pub fn preload(self, path: &str, data: &[u8], preload: usize) -> Self {
+        self.entry(path, data, preload, INLINE_ARCHIVE_INDEX)
+    }
+
+    /// An entry stored in the `_NNN.vpk` companion with `archive_index`.
+    pub fn companion(self, path: &str, data: &[u8], archive_index: u16) -> Self {
+        self.entry(path, data, 0, archive_index)
+    }
+
+    /// Emit archive-MD5 and other-MD5 sections covering every chunk.
+    pub fn with_checksums(mut self) -> Self {
+        self.with_checksums = true;
+        self
+    }
+
+    fn entry(mut self, path: &str, data: &[u8], preload: usize, archive_index: u16) -> Self {
+        self.entries.push(TestEntry {
+            path: path.to_string(),
+            data: data.to_vec(),
+            preload,
+            archive_index,
+        });
+        self
+    }
+
+    pub fn build(&self) -> BuiltVpk {
+        let mut tree: Tree = BTreeMap::new();
+        for entry in &self.entries {
+            let (dir, file) = entry.path.rsplit_once('/').unwrap_or((" ", &entry.path));
+            let (stem, ext) = file
+                .rsplit_once('.')
+                .expect("test entry needs an extension");
+            tree.entry(ext.to_string())
+                .or_default()
+                .entry(dir.to_string())
+                .or_default()
+                .push((stem.to_string(), entry));
+        }
+
+        let mut inline_data = Vec::new();
+        let mut companions: BTreeMap<u16, Vec<u8>> = BTreeMap::new();
+        let mut tree_bytes = Vec::new();
+        for (ext, dirs) in &tree {
+            push_cstring(&mut tree_bytes, ext);
+            for (dir, files) in dirs {
+                push_cstring(&mut tree_bytes, dir);
+                for (stem, entry) in files {
+                    let (preloaded, rest) = entry.data.split_at(entry.preload);
+                    let target = if entry.archive_index == INLINE_ARCHIVE_INDEX {
+                        &mut inline_data
+                    } else {
+                        companions.entry(entry.archive_index).or_default()
+                    };
+                    let offset = target.len() as u32;
+                    target.extend_from_slice(rest);
+
+                    push_cstring(&mut tree_bytes, stem);
+                    tree_bytes.extend_from_slice(&CRC32.checksum(&entry.data).to_le_bytes());
+                    tree_bytes.extend_from_slice(&(entry.preload as u16).to_le_bytes());
+                    tree_bytes.extend_from_slice(&entry.archive_index.to_le_bytes());
+                    tree_bytes.extend_from_slice(&offset.to_le_bytes());
+                    tree_bytes.extend_from_slice(&(rest.len() as u32).to_le_bytes());
+                    tree_bytes.extend_from_slice(&0xffffu16.to_le_bytes());
+                    tree_bytes.extend_from_slice(preloaded);
+                }
+                tree_bytes.push(0);
+            }
+            tree_bytes.push(0);
+        }
+        tree_bytes.push(0);
+
+        let mut archive_md5 = Vec::new();
+        if self.with_checksums {
+            let chunks = companions
+                .iter()
+                .map(|(index, data)| (*index, data))
+                .chain(std::iter::once((INLINE_ARCHIVE_INDEX, &inline_data)));
+            for (index, data) in chunks.filter(|(_, data)| !data.is_empty()) {
+                archive_md5.extend_from_slice(&u32::from(index).to_le_bytes());
+                archive_md5.extend_from_slice(&0u32.to_le_bytes());
+                archive_md5.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                archive_md5.extend_from_slice(&Md5::digest(data));
+            }
+        }
+        let other_md5_size: u32 = if self.with_checksums { 48 } else { 0 };
+
+        let mut dir = Vec::new();
+        dir.extend_from_slice(&0x55aa1234u32.to_le_bytes());
+        dir.extend_from_slice(&2u32.to_le_bytes());
+        dir.extend_from_slice(&(tree_bytes.len() as u32).to_le_bytes());
+        dir.extend_from_slice(&(inline_data.len() as u32).to_le_bytes());
+        dir.extend_from_slice(&(archive_md5.len() as u32).to_le_bytes());
+        dir.extend_from_slice(&other_md5_size.to_le_bytes());
+        dir.extend_from_slice(&0u32.to_le_bytes());
+        dir.extend_from_slice(&tree_bytes);
+        dir.extend_from_slice(&inline_data);
+        dir.extend_from_slice(&archive_md5);
+
+        if self.with_checksums {
+            let tree_md5 = Md5::digest(&tree_bytes);
+            let archive_md5_md5 = Md5::digest(&archive_md5);
+            dir.extend_from_slice(&tree_md5);
+            dir.extend_from_slice(&archive_md5_md5);
+            let whole_file_md5 = Md5::digest(&dir);
+            dir.extend_from_slice(&whole_file_md5);
+        }
+
+        BuiltVpk { dir, companions }
+    }
+
+    /// Writes `<name>_dir.vpk` and its companions into `dir`, returning the
+    /// directory file's path.
+    pub fn write_to(&self, dir: &Path, name: &str) -> PathBuf {
+        let built = self.build();
+        let dir_path = dir.join(format!("{name}_dir.vpk"));
+        fs::write(&dir_path, &built.dir).expect("write dir vpk");
+        for (index, data) in &built.companions {
+            fs::write(companion_archive_path(&dir_path, *index), data).expect("write companion");
+        }
+        dir_path
+    }
+}
+
+/// A temporary directory that is removed when dropped
+pub fn scratch_dir(label: &str) -> TempDir {
+    tempfile::Builder::new()
+        .prefix(&format!("vpk-parser-{label}-"))
+        .tempdir()
+        .expect("create scratch dir")
+}
+
+fn push_cstring(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(value.as_bytes());
+    out.push(0);
+}
@@ -34,6 +34,14 @@ pub struct VpkFingerprint {
     pub has_inline_data: bool,
     pub merkle_root: Option<String>,
     pub merkle_leaves: Option<Vec<String>>,
+    /// True when `fast_hash`, `sha256` and the Merkle leaves cover the
+    /// companion `_NNN.vpk` archives and real entry bytes, not just `_dir.vpk`.
+    #[serde(default)]
+    pub includes_archive_data: bool,
+    /// Hex-encoded MinHash over the entries' `(path, crc32)` pairs; compare two
+    /// with `VpkParser::similarity`.
+    #[serde(default)]
+    pub similarity_signature: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -50,6 +58,23 @@ pub struct VpkParsed {
     pub manifest_sha256: String,
     pub dir_sha256: Option<String>,
     pub fingerprint: VpkFingerprint,
+    /// Set when a lenient parse skipped part of the archive.
+    #[serde(default)]
+    pub partial: bool,
+    #[serde(default)]
+    pub diagnostics: Vec<VpkDiagnostic>,
+}
+
+/// A problem a lenient parse recovered from. `path` is as much of the entry
+/// path as had been read when parsing stopped, e.g. `materials/*.vmat_c` when
+/// the failure hit before the file name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkDiagnostic {
+    pub offset: usize,
+    pub path: String,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
@@ -61,6 +86,14 @@ pub struct VpkParseOptions {
     pub last_modified: Option<DateTime<Utc>>,
     pub include_merkle: bool,
     pub include_entries: bool,
+    /// Stream every companion archive referenced by the entries into the
+    /// fingerprint. Companions are resolved next to `file_path`.
+    #[serde(default)]
+    pub hash_archive_data: bool,
+    /// Recover every entry that precedes a malformed part of the directory
+    /// tree instead of failing, recording what went wrong in `diagnostics`.
+    #[serde(default)]
+    pub lenient: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -91,3 +124,173 @@ pub struct MerkleData {
     pub root: String,
     pub leaves: Vec<String>,
 }
+
+/// The bytes of one VPK entry, CRC32-checked against the directory tree.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkEntryContent {
+    pub full_path: String,
+    pub crc32_hex: String,
+    pub size: usize,
+    #[serde(with = "base64_bytes")]
+    #[ts(type = "string")]
+    pub data: Vec<u8>, // base64 in JSON
+}
+
+mod base64_bytes {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub enum VpkCheckStatus {
+    Valid,
+    Mismatch,
+    Unreadable,
+}
+
+/// Overall verdict of a verification pass. `Corrupted` means a stored MD5 no
+/// longer matches its bytes; `Tampered` means every MD5 matches but the RSA
+/// signature does not, i.e. someone rewrote the archive and its checksums.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub enum VpkIntegrity {
+    Unchecked,
+    Intact,
+    Corrupted,
+    Tampered,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkChecksumCheck {
+    pub expected: String,
+    pub actual: Option<String>,
+    pub status: VpkCheckStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkChunkCheck {
+    pub archive_index: u16,
+    pub offset: u32,
+    pub length: u32,
+    pub expected: String,
+    pub actual: Option<String>,
+    pub status: VpkCheckStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkSignatureCheck {
+    pub public_key: String, // hex-encoded DER SubjectPublicKeyInfo
+    pub signature: String,  // hex-encoded RSA signature
+    pub status: VpkCheckStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkVerificationReport {
+    pub version: u32,
+    pub tree_checksum: Option<VpkChecksumCheck>,
+    pub archive_md5_checksum: Option<VpkChecksumCheck>,
+    pub whole_file_checksum: Option<VpkChecksumCheck>,
+    pub chunks: Vec<VpkChunkCheck>,
+    pub signature: Option<VpkSignatureCheck>,
+    pub integrity: VpkIntegrity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkEntryChange {
+    pub full_path: String,
+    pub old_crc32_hex: String,
+    pub new_crc32_hex: String,
+    #[ts(type = "number")]
+    pub old_size: u64, // preload bytes + archive bytes
+    #[ts(type = "number")]
+    pub new_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkEntryRename {
+    pub old_path: String,
+    pub new_path: String,
+    pub crc32_hex: String,
+    #[ts(type = "number")]
+    pub size: u64,
+}
+
+/// Entry-level changes between two parsed VPKs. An entry that disappeared
+/// from one path and reappeared with the same CRC32 and size at another is
+/// reported once in `renamed` (covering moves between directories) rather
+/// than in `removed` and `added`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkDiff {
+    pub added: Vec<VpkEntry>,
+    pub removed: Vec<VpkEntry>,
+    pub modified: Vec<VpkEntryChange>,
+    pub renamed: Vec<VpkEntryRename>,
+    pub unchanged_count: usize,
+}
+
+/// A search over a `VpkEntryIndex`. Every kind matches case-insensitively.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/")]
+#[serde(tag = "kind", content = "value", rename_all = "camelCase")]
+pub enum VpkIndexQuery {
+    /// A glob over the full entry path, e.g. `materials/**/haze_*.vtex_c`.
+    /// `*` stays within one directory; `**` crosses directories.
+    Glob(String),
+    Substring(String),
+    /// An extension with or without its leading dot, e.g. `vtex_c`.
+    Extension(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkIndexHit {
+    pub vpk_path: String, // the `_dir.vpk` listing the entry
+    pub full_path: String,
+    pub archive_index: u16,
+    pub entry_offset: u32,
+    pub entry_length: u32,
+    pub preload_bytes: u16,
+    pub crc32_hex: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../src/generated/", rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct VpkIndexRefresh {
+    pub parsed: usize,
+    pub reused: usize,
+    pub removed: usize,
+    pub failed: Vec<String>,
+}
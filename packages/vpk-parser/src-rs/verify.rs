@@ -0,0 +1,500 @@
+//! Verification of the VPK v2 integrity sections that follow the file data:
+//! the archive-MD5 chunk table, the "other" MD5 block (tree, chunk table and
+//! whole-file checksums) and the RSA signature.
+
+use crate::{
+    archive::{INLINE_ARCHIVE_INDEX, companion_archive_path, header_size},
+    error::{Result, VpkError},
+    parser::VpkParser,
+    types::*,
+};
+use md5::{Digest, Md5};
+use memmap2::Mmap;
+use rsa::{Pkcs1v15Sign, RsaPublicKey, pkcs8::DecodePublicKey};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const ARCHIVE_MD5_ENTRY_SIZE: usize = 28;
+const OTHER_MD5_SECTION_SIZE: usize = 48;
+
+struct ArchiveMd5Entry {
+    archive_index: u32,
+    offset: u32,
+    length: u32,
+    checksum: [u8; 16],
+}
+
+/// Byte ranges of the sections in a v2 directory file.
+struct SectionLayout {
+    data_start: usize,
+    archive_md5_start: usize,
+    archive_md5_size: usize,
+    other_md5_start: usize,
+    other_md5_size: usize,
+    signature_start: usize,
+    signature_size: usize,
+}
+
+impl VpkParser<'_> {
+    /// Verifies a VPK file on disk, resolving `_NNN.vpk` companions next to it.
+    pub fn verify_file(path: &Path) -> Result<VpkVerificationReport> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read-only and dropped before this function returns.
+        let mmap = unsafe { Mmap::map(&file)? };
+        VpkParser::verify_slice(&mmap, Some(path))
+    }
+
+    /// Verifies a VPK held in memory. `dir_path` is where the directory file
+    /// lives on disk and is only used to locate companion archives; without it,
+    /// chunks stored outside the directory file are reported as unreadable.
+    pub fn verify_slice(buffer: &[u8], dir_path: Option<&Path>) -> Result<VpkVerificationReport> {
+        let header = VpkParser::read_header(buffer)?;
+        if header.version < 2 {
+            return Ok(VpkVerificationReport {
+                version: header.version,
+                tree_checksum: None,
+                archive_md5_checksum: None,
+                whole_file_checksum: None,
+                chunks: Vec::new(),
+                signature: None,
+                integrity: VpkIntegrity::Unchecked,
+            });
+        }
+
+        let layout = section_layout(&header, buffer.len())?;
+
+        let archive_md5_section =
+            &buffer[layout.archive_md5_start..layout.archive_md5_start + layout.archive_md5_size];
+        let archive_md5_entries = parse_archive_md5_entries(archive_md5_section)?;
+        let chunks = verify_chunks(buffer, &layout, &archive_md5_entries, dir_path);
+
+        let (tree_checksum, archive_md5_checksum, whole_file_checksum) =
+            if layout.other_md5_size >= OTHER_MD5_SECTION_SIZE {
+                let other = &buffer[layout.other_md5_start..];
+                let tree_end = layout.data_start;
+                let tree_start = header_size(header.version);
+                (
+                    Some(check_md5(&other[0..16], &buffer[tree_start..tree_end])),
+                    Some(check_md5(&other[16..32], archive_md5_section)),
+                    // The whole-file checksum covers everything up to and
+                    // including the first two checksums of this section.
+                    Some(check_md5(
+                        &other[32..48],
+                        &buffer[..layout.other_md5_start + 32],
+                    )),
+                )
+            } else {
+                (None, None, None)
+            };
+
+        let signature = if layout.signature_size > 0 {
+            let section =
+                &buffer[layout.signature_start..layout.signature_start + layout.signature_size];
+            Some(verify_signature(section, &buffer[..layout.signature_start]))
+        } else {
+            None
+        };
+
+        let integrity = summarize(
+            [&tree_checksum, &archive_md5_checksum, &whole_file_checksum]
+                .into_iter()
+                .flatten()
+                .map(|check| check.status)
+                .chain(chunks.iter().map(|chunk| chunk.status)),
+            signature.as_ref().map(|signature| signature.status),
+        );
+
+        Ok(VpkVerificationReport {
+            version: header.version,
+            tree_checksum,
+            archive_md5_checksum,
+            whole_file_checksum,
+            chunks,
+            signature,
+            integrity,
+        })
+    }
+}
+
+fn section_layout(header: &VpkHeader, buffer_size: usize) -> Result<SectionLayout> {
+    let data_start = header_size(header.version) + header.tree_length as usize;
+    let archive_md5_start = data_start + header.file_data_section_size.unwrap_or(0) as usize;
+    let archive_md5_size = header.archive_md5_section_size.unwrap_or(0) as usize;
+    let other_md5_start = archive_md5_start + archive_md5_size;
+    let other_md5_size = header.other_md5_section_size.unwrap_or(0) as usize;
+    let signature_start = other_md5_start + other_md5_size;
+    let signature_size = header.signature_section_size.unwrap_or(0) as usize;
+
+    let sections_end = signature_start + signature_size;
+    if sections_end > buffer_size {
+        return Err(VpkError::Validation {
+            message: format!(
+                "VPK is truncated: integrity sections end at byte {sections_end}, file is {buffer_size} bytes"
+            ),
+        });
+    }
+
+    if !archive_md5_size.is_multiple_of(ARCHIVE_MD5_ENTRY_SIZE) {
+        return Err(VpkError::Validation {
+            message: format!(
+                "Archive MD5 section size {archive_md5_size} is not a multiple of {ARCHIVE_MD5_ENTRY_SIZE}"
+            ),
+        });
+    }
+
+    Ok(SectionLayout {
+        data_start,
+        archive_md5_start,
+        archive_md5_size,
+        other_md5_start,
+        other_md5_size,
+        signature_start,
+        signature_size,
+    })
+}
+
+fn read_u32_at(buffer: &[u8], offset: usize) -> Result<u32> {
+    buffer
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or(VpkError::CursorOverrun {
+            cursor: offset,
+            requested: 4,
+            buffer_size: buffer.len(),
+        })
+}
+
+fn parse_archive_md5_entries(section: &[u8]) -> Result<Vec<ArchiveMd5Entry>> {
+    section
+        .chunks_exact(ARCHIVE_MD5_ENTRY_SIZE)
+        .map(|raw| {
+            let mut checksum = [0u8; 16];
+            checksum.copy_from_slice(&raw[12..28]);
+            Ok(ArchiveMd5Entry {
+                archive_index: read_u32_at(raw, 0)?,
+                offset: read_u32_at(raw, 4)?,
+                length: read_u32_at(raw, 8)?,
+                checksum,
+            })
+        })
+        .collect()
+}
+
+fn check_md5(expected: &[u8], data: &[u8]) -> VpkChecksumCheck {
+    let actual = Md5::digest(data);
+    VpkChecksumCheck {
+        expected: to_hex(expected),
+        actual: Some(to_hex(&actual)),
+        status: if actual[..] == *expected {
+            VpkCheckStatus::Valid
+        } else {
+            VpkCheckStatus::Mismatch
+        },
+        error: None,
+    }
+}
+
+fn verify_chunks(
+    buffer: &[u8],
+    layout: &SectionLayout,
+    entries: &[ArchiveMd5Entry],
+    dir_path: Option<&Path>,
+) -> Vec<VpkChunkCheck> {
+    let mut companions: HashMap<u16, File> = HashMap::new();
+
+    entries
+        .iter()
+        .map(|entry| {
+            let archive_index = u16::try_from(entry.archive_index).unwrap_or(u16::MAX);
+            let digest = if archive_index == INLINE_ARCHIVE_INDEX {
+                let start = layout.data_start + entry.offset as usize;
+                let end = start + entry.length as usize;
+                buffer
+                    .get(start..end)
+                    .map(Md5::digest)
+                    .ok_or_else(|| format!("chunk {start}..{end} lies outside the directory file"))
+            } else {
+                digest_companion_chunk(&mut companions, dir_path, archive_index, entry)
+            };
+
+            let expected = to_hex(&entry.checksum);
+            match digest {
+                Ok(actual) => VpkChunkCheck {
+                    archive_index,
+                    offset: entry.offset,
+                    length: entry.length,
+                    status: if actual[..] == entry.checksum {
+                        VpkCheckStatus::Valid
+                    } else {
+                        VpkCheckStatus::Mismatch
+                    },
+                    expected,
+                    actual: Some(to_hex(&actual)),
+                    error: None,
+                },
+                Err(error) => VpkChunkCheck {
+                    archive_index,
+                    offset: entry.offset,
+                    length: entry.length,
+                    expected,
+                    actual: None,
+                    status: VpkCheckStatus::Unreadable,
+                    error: Some(error),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Streams a chunk of a companion archive through MD5, so a bogus length in
+/// the chunk table never turns into an allocation.
+fn digest_companion_chunk(
+    companions: &mut HashMap<u16, File>,
+    dir_path: Option<&Path>,
+    archive_index: u16,
+    entry: &ArchiveMd5Entry,
+) -> std::result::Result<md5::digest::Output<Md5>, String> {
+    let dir_path =
+        dir_path.ok_or_else(|| "no directory file path to resolve companions from".to_string())?;
+
+    let file = match companions.entry(archive_index) {
+        std::collections::hash_map::Entry::Occupied(file) => file.into_mut(),
+        std::collections::hash_map::Entry::Vacant(slot) => {
+            let path = companion_archive_path(dir_path, archive_index);
+            let file = File::open(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+            slot.insert(file)
+        }
+    };
+
+    let mut hasher = Md5::new();
+    file.seek(SeekFrom::Start(u64::from(entry.offset)))
+        .and_then(|_| std::io::copy(&mut (&*file).take(u64::from(entry.length)), &mut hasher))
+        .map_err(|e| format!("archive {archive_index:03}: {e}"))
+        .and_then(|read| {
+            if read == u64::from(entry.length) {
+                Ok(hasher.finalize())
+            } else {
+                Err(format!(
+                    "archive {archive_index:03}: chunk {}..{} runs past the end of the file",
+                    entry.offset,
+                    u64::from(entry.offset) + u64::from(entry.length)
+                ))
+            }
+        })
+}
+
+/// Checks the signature section. A section that cannot be parsed is reported
+/// as unreadable rather than failing the whole verification.
+fn verify_signature(section: &[u8], signed_data: &[u8]) -> VpkSignatureCheck {
+    match check_signature(section, signed_data) {
+        Ok(check) => check,
+        Err(e) => VpkSignatureCheck {
+            public_key: String::new(),
+            signature: String::new(),
+            status: VpkCheckStatus::Unreadable,
+            error: Some(format!("Malformed signature section: {e}")),
+        },
+    }
+}
+
+fn check_signature(section: &[u8], signed_data: &[u8]) -> Result<VpkSignatureCheck> {
+    let key_size = read_u32_at(section, 0)? as usize;
+    let key_end = 4 + key_size;
+    let public_key = section.get(4..key_end).ok_or(VpkError::CursorOverrun {
+        cursor: 4,
+        requested: key_size,
+        buffer_size: section.len(),
+    })?;
+
+    let signature_size = read_u32_at(section, key_end)? as usize;
+    let signature_start = key_end + 4;
+    let signature = section
+        .get(signature_start..signature_start + signature_size)
+        .ok_or(VpkError::CursorOverrun {
+            cursor: signature_start,
+            requested: signature_size,
+            buffer_size: section.len(),
+        })?;
+
+    let mut check = VpkSignatureCheck {
+        public_key: to_hex(public_key),
+        signature: to_hex(signature),
+        status: VpkCheckStatus::Unreadable,
+        error: None,
+    };
+
+    match RsaPublicKey::from_public_key_der(public_key) {
+        Ok(key) => {
+            let digest = Sha256::digest(signed_data);
+            check.status = match key.verify(Pkcs1v15Sign::new::<Sha256>(), &digest, signature) {
+                Ok(()) => VpkCheckStatus::Valid,
+                Err(_) => VpkCheckStatus::Mismatch,
+            };
+        }
+        Err(e) => check.error = Some(format!("Invalid public key: {e}")),
+    }
+
+    Ok(check)
+}
+
+fn summarize(
+    checksums: impl Iterator<Item = VpkCheckStatus>,
+    signature: Option<VpkCheckStatus>,
+) -> VpkIntegrity {
+    let mut checked_any = false;
+    for status in checksums {
+        checked_any = true;
+        if status != VpkCheckStatus::Valid {
+            return VpkIntegrity::Corrupted;
+        }
+    }
+
+    match signature {
+        Some(VpkCheckStatus::Valid) => VpkIntegrity::Intact,
+        Some(VpkCheckStatus::Mismatch) => VpkIntegrity::Tampered,
+        Some(VpkCheckStatus::Unreadable) => VpkIntegrity::Corrupted,
+        None if checked_any => VpkIntegrity::Intact,
+        None => VpkIntegrity::Unchecked,
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TestVpk, scratch_dir};
+
+    fn sample() -> TestVpk {
+        TestVpk::new()
+            .inline("materials/hud/icon.vtex_c", b"inline texture bytes")
+            .companion("sounds/hero/cast.vsnd_c", b"companion sound bytes", 0)
+            .with_checksums()
+    }
+
+    #[test]
+    fn untouched_archive_is_intact() {
+        let scratch = scratch_dir("verify-intact");
+        let dir = scratch.path();
+        let path = sample().write_to(dir, "pak01");
+
+        let report = VpkParser::verify_file(&path).expect("verify");
+        assert_eq!(report.integrity, VpkIntegrity::Intact);
+        assert_eq!(report.chunks.len(), 2);
+        assert!(
+            report
+                .chunks
+                .iter()
+                .all(|c| c.status == VpkCheckStatus::Valid)
+        );
+        assert_eq!(
+            report.whole_file_checksum.map(|c| c.status),
+            Some(VpkCheckStatus::Valid)
+        );
+    }
+
+    #[test]
+    fn damaged_companion_chunk_is_reported_as_corrupted() {
+        let scratch = scratch_dir("verify-companion");
+        let dir = scratch.path();
+        let path = sample().write_to(dir, "pak01");
+        let companion = companion_archive_path(&path, 0);
+        let mut bytes = std::fs::read(&companion).unwrap();
+        bytes[0] ^= 0xff;
+        std::fs::write(&companion, bytes).unwrap();
+
+        let report = VpkParser::verify_file(&path).expect("verify");
+        assert_eq!(report.integrity, VpkIntegrity::Corrupted);
+        let chunk = report.chunks.iter().find(|c| c.archive_index == 0).unwrap();
+        assert_eq!(chunk.status, VpkCheckStatus::Mismatch);
+    }
+
+    #[test]
+    fn missing_companion_is_unreadable() {
+        let built = sample().build();
+        let report = VpkParser::verify_slice(&built.dir, None).expect("verify");
+
+        let chunk = report.chunks.iter().find(|c| c.archive_index == 0).unwrap();
+        assert_eq!(chunk.status, VpkCheckStatus::Unreadable);
+        assert_eq!(report.integrity, VpkIntegrity::Corrupted);
+    }
+
+    #[test]
+    fn edited_tree_fails_the_tree_checksum() {
+        let mut dir = sample().build().dir;
+        // First byte of the tree is the first extension name.
+        dir[28] = b'x';
+
+        let report = VpkParser::verify_slice(&dir, None).expect("verify");
+        assert_eq!(
+            report.tree_checksum.map(|c| c.status),
+            Some(VpkCheckStatus::Mismatch)
+        );
+    }
+
+    #[test]
+    fn bundled_sample_passes_its_checksums() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/pak95_dir.vpk");
+        let report = VpkParser::verify_file(&path).expect("verify");
+        assert_eq!(report.integrity, VpkIntegrity::Intact);
+        assert!(report.tree_checksum.is_some());
+    }
+
+    #[test]
+    fn archive_without_integrity_sections_is_unchecked() {
+        let dir = TestVpk::new().inline("a/b.txt", b"hello").build().dir;
+        let report = VpkParser::verify_slice(&dir, None).expect("verify");
+        assert_eq!(report.integrity, VpkIntegrity::Unchecked);
+    }
+
+    #[test]
+    fn malformed_signature_section_is_unreadable() {
+        let mut dir = sample().build().dir;
+        // Declare a 4-byte signature section whose key size overruns it.
+        dir.extend_from_slice(&u32::MAX.to_le_bytes());
+        dir[24..28].copy_from_slice(&4u32.to_le_bytes());
+
+        let report = VpkParser::verify_slice(&dir, None).expect("verify");
+        let signature = report.signature.expect("signature check");
+        assert_eq!(signature.status, VpkCheckStatus::Unreadable);
+        assert!(signature.error.is_some());
+        assert_eq!(
+            report.tree_checksum.map(|c| c.status),
+            Some(VpkCheckStatus::Valid)
+        );
+        assert_eq!(report.chunks.len(), 2);
+    }
+
+    #[test]
+    fn oversized_companion_chunk_is_unreadable() {
+        let scratch = scratch_dir("verify-oversized");
+        let dir = scratch.path();
+        let path = sample().write_to(dir, "pak01");
+        let mut bytes = std::fs::read(&path).unwrap();
+        let header = VpkParser::read_header(&bytes).unwrap();
+        let layout = section_layout(&header, bytes.len()).unwrap();
+        // Make the companion chunk claim 4GB.
+        let entry_at = (0..layout.archive_md5_size / ARCHIVE_MD5_ENTRY_SIZE)
+            .map(|i| layout.archive_md5_start + i * ARCHIVE_MD5_ENTRY_SIZE)
+            .find(|&at| read_u32_at(&bytes, at).unwrap() == 0)
+            .unwrap();
+        let length_at = entry_at + 8;
+        bytes[length_at..length_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let report = VpkParser::verify_slice(&bytes, Some(&path)).expect("verify");
+        let chunk = report.chunks.iter().find(|c| c.archive_index == 0).unwrap();
+        assert_eq!(chunk.status, VpkCheckStatus::Unreadable);
+    }
+
+    #[test]
+    fn truncated_sections_are_an_error() {
+        let dir = sample().build().dir;
+        let truncated = &dir[..dir.len() - 10];
+        assert!(VpkParser::verify_slice(truncated, None).is_err());
+    }
+}
@@ -1,13 +1,17 @@
 import { RuntimeError } from "@deadlock-mods/common";
 import { CString, dlopen, FFIType, type Pointer, ptr } from "bun:ffi";
-import { readFileSync, statSync } from "node:fs";
+import { statSync } from "node:fs";
 import { dirname, join } from "node:path";
 import { fileURLToPath } from "node:url";
 import type {
+  VpkDiff,
+  VpkEntryContent,
+  VpkEntryData,
   VpkFingerprint,
   VpkInfo,
   VpkParsed,
   VpkParseOptionsInput,
+  VpkVerificationReport,
 } from "./types";
 
 const suffix =
@@ -33,14 +37,62 @@ const lib = dlopen(libPath, {
     args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr],
     returns: FFIType.cstring,
   },
+  vpk_parse_file: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
   vpk_get_hashes: {
     args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr],
     returns: FFIType.cstring,
   },
+  vpk_get_hashes_file: {
+    args: [FFIType.ptr],
+    returns: FFIType.cstring,
+  },
   vpk_get_info: {
     args: [FFIType.ptr, FFIType.uint64_t],
     returns: FFIType.cstring,
   },
+  vpk_get_info_file: {
+    args: [FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_extract_entry: {
+    args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_extract_entry_file: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_read_entries: {
+    args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_read_entries_file: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_diff: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_diff_files: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_similarity: {
+    args: [FFIType.ptr, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_verify: {
+    args: [FFIType.ptr, FFIType.uint64_t, FFIType.ptr],
+    returns: FFIType.cstring,
+  },
+  vpk_verify_file: {
+    args: [FFIType.ptr],
+    returns: FFIType.cstring,
+  },
   vpk_free_string: {
     args: [FFIType.ptr],
     returns: FFIType.void,
@@ -101,35 +153,50 @@ function callNativeFunction<T>(
 }
 
 /**
- * Parse a VPK buffer with optional parsing configuration.
- * @param buffer - The VPK file data as a Buffer
- * @param options - Parsing options including hash calculation and file metadata
- * @returns Parsed VPK data including header, entries, and fingerprint
+ * Encodes a JS string as a null-terminated UTF-8 buffer for the native side.
  */
-export function parseVpk(
-  buffer: Buffer,
-  options: VpkParseOptionsInput = {},
-): VpkParsed {
+function toCStringBuffer(value: string): Buffer {
+  return Buffer.from(`${value}\0`, "utf8");
+}
+
+/**
+ * Serializes parse options into the JSON shape expected by the native parser.
+ */
+function serializeParseOptions(options: VpkParseOptionsInput): Buffer {
   const optionsJson = JSON.stringify({
     includeFullFileHash: options.includeFullFileHash || false,
     includeMerkle: options.includeMerkle || false,
     filePath: options.filePath || "",
     lastModified: options.lastModified?.toISOString() || null,
     includeEntries: options.includeEntries !== false, // Default to true for backward compatibility
+    hashArchiveData: options.hashArchiveData || false,
+    lenient: options.lenient || false,
   });
 
-  const optionsBuffer = Buffer.from(`${optionsJson}\0`, "utf8");
+  return toCStringBuffer(optionsJson);
+}
 
+/**
+ * Parse a VPK buffer with optional parsing configuration.
+ * @param buffer - The VPK file data as a Buffer
+ * @param options - Parsing options including hash calculation and file metadata
+ * @returns Parsed VPK data including header, entries, and fingerprint
+ */
+export function parseVpk(
+  buffer: Buffer,
+  options: VpkParseOptionsInput = {},
+): VpkParsed {
   return callNativeFunction<VpkParsed>(
     lib.symbols.vpk_parse,
     ptr(buffer),
     buffer.length,
-    ptr(optionsBuffer),
+    ptr(serializeParseOptions(options)),
   );
 }
 
 /**
- * Parse a VPK file directly from the filesystem.
+ * Parse a VPK file directly from the filesystem. The file is memory-mapped by
+ * the native library, so it is never copied into the JS heap.
  * @param filePath - Path to the VPK file
  * @param options - Parsing options (file path and modification time are automatically set)
  * @returns Parsed VPK data including header, entries, and fingerprint
@@ -138,14 +205,18 @@ export function parseVpkFile(
   filePath: string,
   options: VpkParseOptionsInput = {},
 ): VpkParsed {
-  const buffer = readFileSync(filePath);
   const stats = statSync(filePath);
-
-  return parseVpk(buffer, {
+  const optionsBuffer = serializeParseOptions({
     ...options,
     filePath: filePath,
     lastModified: stats.mtime,
   });
+
+  return callNativeFunction<VpkParsed>(
+    lib.symbols.vpk_parse_file,
+    ptr(toCStringBuffer(filePath)),
+    ptr(optionsBuffer),
+  );
 }
 
 /**
@@ -158,9 +229,7 @@ export function getVpkHashes(
   buffer: Buffer,
   filePath: string = "",
 ): VpkFingerprint {
-  const pathBuffer = filePath
-    ? Buffer.from(`${filePath}\0`, "utf8")
-    : Buffer.from("\0");
+  const pathBuffer = toCStringBuffer(filePath);
 
   return callNativeFunction<VpkFingerprint>(
     lib.symbols.vpk_get_hashes,
@@ -171,13 +240,16 @@ export function getVpkHashes(
 }
 
 /**
- * Generate cryptographic hashes for a VPK file from filesystem.
+ * Generate cryptographic hashes for a VPK file from filesystem. The file is
+ * memory-mapped by the native library instead of being read into a Buffer.
  * @param filePath - Path to the VPK file
  * @returns Fingerprint containing various hashes and metadata flags
  */
 export function getVpkHashesFromFile(filePath: string): VpkFingerprint {
-  const buffer = readFileSync(filePath);
-  return getVpkHashes(buffer, filePath);
+  return callNativeFunction<VpkFingerprint>(
+    lib.symbols.vpk_get_hashes_file,
+    ptr(toCStringBuffer(filePath)),
+  );
 }
 
 /**
@@ -199,8 +271,186 @@ export function getVpkInfo(buffer: Buffer): VpkInfo {
  * @returns Basic VPK information including version, file count, and quick hashes
  */
 export function getVpkInfoFromFile(filePath: string): VpkInfo {
-  const buffer = readFileSync(filePath);
-  return getVpkInfo(buffer);
+  return callNativeFunction<VpkInfo>(
+    lib.symbols.vpk_get_info_file,
+    ptr(toCStringBuffer(filePath)),
+  );
+}
+
+/**
+ * Decodes the base64 payload returned by the native entry readers.
+ */
+function toEntryData(content: VpkEntryContent): VpkEntryData {
+  return { ...content, data: Buffer.from(content.data, "base64") };
+}
+
+/**
+ * Serializes an optional list of entry paths; `undefined` selects every entry.
+ */
+function serializeEntryPaths(entryPaths?: string[]): Buffer | null {
+  return entryPaths ? toCStringBuffer(JSON.stringify(entryPaths)) : null;
+}
+
+/**
+ * Extract a single entry's bytes from a VPK, verified against its stored CRC32.
+ * @param buffer - The VPK directory file data as a Buffer
+ * @param entryPath - Path of the entry inside the VPK (case-insensitive)
+ * @param filePath - Optional path of the directory file, used to locate `_NNN.vpk` companions
+ * @returns The entry's bytes and metadata
+ */
+export function extractVpkEntry(
+  buffer: Buffer,
+  entryPath: string,
+  filePath: string = "",
+): VpkEntryData {
+  const content = callNativeFunction<VpkEntryContent>(
+    lib.symbols.vpk_extract_entry,
+    ptr(buffer),
+    buffer.length,
+    ptr(toCStringBuffer(filePath)),
+    ptr(toCStringBuffer(entryPath)),
+  );
+  return toEntryData(content);
+}
+
+/**
+ * Extract a single entry's bytes from a VPK file on disk, including entries
+ * stored in its `_NNN.vpk` companion archives.
+ * @param filePath - Path to the `_dir.vpk` file
+ * @param entryPath - Path of the entry inside the VPK (case-insensitive)
+ * @returns The entry's bytes and metadata
+ */
+export function extractVpkEntryFromFile(
+  filePath: string,
+  entryPath: string,
+): VpkEntryData {
+  const content = callNativeFunction<VpkEntryContent>(
+    lib.symbols.vpk_extract_entry_file,
+    ptr(toCStringBuffer(filePath)),
+    ptr(toCStringBuffer(entryPath)),
+  );
+  return toEntryData(content);
+}
+
+/**
+ * Read several entries from a VPK, each verified against its stored CRC32.
+ * @param buffer - The VPK directory file data as a Buffer
+ * @param entryPaths - Entries to read, in order; omit to read every entry
+ * @param filePath - Optional path of the directory file, used to locate `_NNN.vpk` companions
+ * @returns The entries' bytes and metadata
+ */
+export function readVpkEntries(
+  buffer: Buffer,
+  entryPaths?: string[],
+  filePath: string = "",
+): VpkEntryData[] {
+  const pathsBuffer = serializeEntryPaths(entryPaths);
+  const contents = callNativeFunction<VpkEntryContent[]>(
+    lib.symbols.vpk_read_entries,
+    ptr(buffer),
+    buffer.length,
+    ptr(toCStringBuffer(filePath)),
+    pathsBuffer ? ptr(pathsBuffer) : null,
+  );
+  return contents.map(toEntryData);
+}
+
+/**
+ * Read several entries from a VPK file on disk.
+ * @param filePath - Path to the `_dir.vpk` file
+ * @param entryPaths - Entries to read, in order; omit to read every entry
+ * @returns The entries' bytes and metadata
+ */
+export function readVpkEntriesFromFile(
+  filePath: string,
+  entryPaths?: string[],
+): VpkEntryData[] {
+  const pathsBuffer = serializeEntryPaths(entryPaths);
+  const contents = callNativeFunction<VpkEntryContent[]>(
+    lib.symbols.vpk_read_entries_file,
+    ptr(toCStringBuffer(filePath)),
+    pathsBuffer ? ptr(pathsBuffer) : null,
+  );
+  return contents.map(toEntryData);
+}
+
+/**
+ * Compare two parsed VPKs entry by entry.
+ * Neither may have been parsed with `includeEntries: false`.
+ * @param oldParsed - The earlier version of the VPK
+ * @param newParsed - The later version of the VPK
+ * @returns Added, removed, modified and renamed entries
+ */
+export function diffVpks(oldParsed: VpkParsed, newParsed: VpkParsed): VpkDiff {
+  return callNativeFunction<VpkDiff>(
+    lib.symbols.vpk_diff,
+    ptr(toCStringBuffer(JSON.stringify(oldParsed))),
+    ptr(toCStringBuffer(JSON.stringify(newParsed))),
+  );
+}
+
+/**
+ * Compare two VPK files on disk entry by entry.
+ * @param oldPath - Path to the earlier version of the VPK
+ * @param newPath - Path to the later version of the VPK
+ * @returns Added, removed, modified and renamed entries
+ */
+export function diffVpkFiles(oldPath: string, newPath: string): VpkDiff {
+  return callNativeFunction<VpkDiff>(
+    lib.symbols.vpk_diff_files,
+    ptr(toCStringBuffer(oldPath)),
+    ptr(toCStringBuffer(newPath)),
+  );
+}
+
+/**
+ * Estimate how alike two VPKs are from their fingerprints' similarity signatures.
+ * Re-packed or renamed copies score 1; editing a few files lowers the score
+ * roughly by the share of files changed.
+ * @param signatureA - `similaritySignature` of the first VPK's fingerprint
+ * @param signatureB - `similaritySignature` of the second VPK's fingerprint
+ * @returns Estimated share of (path, CRC32) pairs in common, from 0 to 1
+ */
+export function getVpkSimilarity(
+  signatureA: string,
+  signatureB: string,
+): number {
+  return callNativeFunction<number>(
+    lib.symbols.vpk_similarity,
+    ptr(toCStringBuffer(signatureA)),
+    ptr(toCStringBuffer(signatureB)),
+  );
+}
+
+/**
+ * Verify the archive-MD5, tree/whole-file MD5 and RSA signature sections of a VPK.
+ * @param buffer - The VPK directory file data as a Buffer
+ * @param filePath - Optional path of the directory file, used to locate `_NNN.vpk` companions
+ * @returns Per-section verification report and an overall integrity verdict
+ */
+export function verifyVpk(
+  buffer: Buffer,
+  filePath: string = "",
+): VpkVerificationReport {
+  return callNativeFunction<VpkVerificationReport>(
+    lib.symbols.vpk_verify,
+    ptr(buffer),
+    buffer.length,
+    ptr(toCStringBuffer(filePath)),
+  );
+}
+
+/**
+ * Verify the integrity sections of a VPK file on disk, including chunks stored
+ * in its `_NNN.vpk` companion archives.
+ * @param filePath - Path to the `_dir.vpk` file
+ * @returns Per-section verification report and an overall integrity verdict
+ */
+export function verifyVpkFile(filePath: string): VpkVerificationReport {
+  return callNativeFunction<VpkVerificationReport>(
+    lib.symbols.vpk_verify_file,
+    ptr(toCStringBuffer(filePath)),
+  );
 }
 
 /**
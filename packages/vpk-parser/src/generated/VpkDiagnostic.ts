@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A problem a lenient parse recovered from. `path` is as much of the entry
+ * path as had been read when parsing stopped, e.g. `materials/*.vmat_c` when
+ * the failure hit before the file name.
+ */
+export type VpkDiagnostic = { offset: number, path: string, reason: string, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { VpkEntry } from "./VpkEntry";
+import type { VpkEntryChange } from "./VpkEntryChange";
+import type { VpkEntryRename } from "./VpkEntryRename";
+
+/**
+ * Entry-level changes between two parsed VPKs. An entry that disappeared
+ * from one path and reappeared with the same CRC32 and size at another is
+ * reported once in `renamed` (covering moves between directories) rather
+ * than in `removed` and `added`.
+ */
+export type VpkDiff = { added: Array<VpkEntry>, removed: Array<VpkEntry>, modified: Array<VpkEntryChange>, renamed: Array<VpkEntryRename>, unchangedCount: number, };
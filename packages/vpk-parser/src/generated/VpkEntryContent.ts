@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The bytes of one VPK entry, CRC32-checked against the directory tree.
+ */
+export type VpkEntryContent = { fullPath: string, crc32Hex: string, size: number, data: string, };
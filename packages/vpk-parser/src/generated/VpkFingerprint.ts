@@ -1,3 +1,13 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type VpkFingerprint = { filePath: string, fileSize: number, lastModified: string | null, fastHash: string, sha256: string, contentSignature: string, vpkVersion: number, fileCount: number, hasMultiparts: boolean, hasInlineData: boolean, merkleRoot: string | null, merkleLeaves: Array<string> | null, };
+export type VpkFingerprint = { filePath: string, fileSize: number, lastModified: string | null, fastHash: string, sha256: string, contentSignature: string, vpkVersion: number, fileCount: number, hasMultiparts: boolean, hasInlineData: boolean, merkleRoot: string | null, merkleLeaves: Array<string> | null, 
+/**
+ * True when `fast_hash`, `sha256` and the Merkle leaves cover the
+ * companion `_NNN.vpk` archives and real entry bytes, not just `_dir.vpk`.
+ */
+includesArchiveData: boolean, 
+/**
+ * Hex-encoded MinHash over the entries' `(path, crc32)` pairs; compare two
+ * with `VpkParser::similarity`.
+ */
+similaritySignature: string, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type VpkIndexHit = { vpkPath: string, fullPath: string, archiveIndex: number, entryOffset: number, entryLength: number, preloadBytes: number, crc32Hex: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A search over a `VpkEntryIndex`. Every kind matches case-insensitively.
+ */
+export type VpkIndexQuery = { "kind": "glob", "value": string } | { "kind": "substring", "value": string } | { "kind": "extension", "value": string };
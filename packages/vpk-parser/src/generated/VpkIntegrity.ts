@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Overall verdict of a verification pass. `Corrupted` means a stored MD5 no
+ * longer matches its bytes; `Tampered` means every MD5 matches but the RSA
+ * signature does not, i.e. someone rewrote the archive and its checksums.
+ */
+export type VpkIntegrity = "unchecked" | "intact" | "corrupted" | "tampered";
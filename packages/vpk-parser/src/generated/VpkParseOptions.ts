@@ -1,3 +1,13 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type VpkParseOptions = { includeFullFileHash: boolean, filePath: string, lastModified: string | null, includeMerkle: boolean, includeEntries: boolean, };
+export type VpkParseOptions = { includeFullFileHash: boolean, filePath: string, lastModified: string | null, includeMerkle: boolean, includeEntries: boolean, 
+/**
+ * Stream every companion archive referenced by the entries into the
+ * fingerprint. Companions are resolved next to `file_path`.
+ */
+hashArchiveData: boolean, 
+/**
+ * Recover every entry that precedes a malformed part of the directory
+ * tree instead of failing, recording what went wrong in `diagnostics`.
+ */
+lenient: boolean, };
@@ -1,5 +1,10 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { VpkDiagnostic } from "./VpkDiagnostic";
 import type { VpkEntry } from "./VpkEntry";
 import type { VpkFingerprint } from "./VpkFingerprint";
 
-export type VpkParsed = { version: number, treeLength: number, fileDataSectionSize: number | null, archiveMd5SectionSize: number | null, otherMd5SectionSize: number | null, signatureSectionSize: number | null, entries: Array<VpkEntry>, manifestSha256: string, dirSha256: string | null, fingerprint: VpkFingerprint, };
+export type VpkParsed = { version: number, treeLength: number, fileDataSectionSize: number | null, archiveMd5SectionSize: number | null, otherMd5SectionSize: number | null, signatureSectionSize: number | null, entries: Array<VpkEntry>, manifestSha256: string, dirSha256: string | null, fingerprint: VpkFingerprint, 
+/**
+ * Set when a lenient parse skipped part of the archive.
+ */
+partial: boolean, diagnostics: Array<VpkDiagnostic>, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { VpkChecksumCheck } from "./VpkChecksumCheck";
+import type { VpkChunkCheck } from "./VpkChunkCheck";
+import type { VpkIntegrity } from "./VpkIntegrity";
+import type { VpkSignatureCheck } from "./VpkSignatureCheck";
+
+export type VpkVerificationReport = { version: number, treeChecksum: VpkChecksumCheck | null, archiveMd5Checksum: VpkChecksumCheck | null, wholeFileChecksum: VpkChecksumCheck | null, chunks: Array<VpkChunkCheck>, signature: VpkSignatureCheck | null, integrity: VpkIntegrity, };
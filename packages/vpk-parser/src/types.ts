@@ -1,9 +1,25 @@
+import type { VpkEntryContent } from "./generated/VpkEntryContent";
+
+export type { VpkCheckStatus } from "./generated/VpkCheckStatus";
+export type { VpkDiagnostic } from "./generated/VpkDiagnostic";
+export type { VpkChecksumCheck } from "./generated/VpkChecksumCheck";
+export type { VpkChunkCheck } from "./generated/VpkChunkCheck";
+export type { VpkDiff } from "./generated/VpkDiff";
 export type { VpkEntry } from "./generated/VpkEntry";
+export type { VpkEntryChange } from "./generated/VpkEntryChange";
+export type { VpkEntryContent } from "./generated/VpkEntryContent";
+export type { VpkEntryRename } from "./generated/VpkEntryRename";
 export type { VpkFingerprint } from "./generated/VpkFingerprint";
 export type { VpkHeader } from "./generated/VpkHeader";
+export type { VpkIndexHit } from "./generated/VpkIndexHit";
+export type { VpkIndexQuery } from "./generated/VpkIndexQuery";
+export type { VpkIndexRefresh } from "./generated/VpkIndexRefresh";
 export type { VpkInfo } from "./generated/VpkInfo";
+export type { VpkIntegrity } from "./generated/VpkIntegrity";
 export type { VpkParsed } from "./generated/VpkParsed";
 export type { VpkParseOptions } from "./generated/VpkParseOptions";
+export type { VpkSignatureCheck } from "./generated/VpkSignatureCheck";
+export type { VpkVerificationReport } from "./generated/VpkVerificationReport";
 
 export interface VpkParseOptionsInput {
   includeFullFileHash?: boolean;
@@ -11,4 +27,13 @@ export interface VpkParseOptionsInput {
   filePath?: string;
   lastModified?: Date;
   includeEntries?: boolean;
+  /** Fold `_NNN.vpk` companion archives and real entry bytes into the fingerprint. */
+  hashArchiveData?: boolean;
+  /** Keep the entries before a malformed part of the tree instead of throwing. */
+  lenient?: boolean;
+}
+
+/** A VPK entry's bytes, decoded from the native library's base64 payload. */
+export interface VpkEntryData extends Omit<VpkEntryContent, "data"> {
+  data: Buffer;
 }
@@ -1,14 +1,22 @@
 import { beforeAll, describe, expect, it } from "bun:test";
 import { existsSync, readFileSync } from "node:fs";
 import {
+  diffVpkFiles,
+  diffVpks,
+  extractVpkEntry,
+  extractVpkEntryFromFile,
   getVersion,
   getVpkHashes,
   getVpkHashesFromFile,
   getVpkInfo,
   getVpkInfoFromFile,
+  getVpkSimilarity,
   parseVpk,
   parseVpkFile,
+  readVpkEntriesFromFile,
   VpkParser,
+  verifyVpk,
+  verifyVpkFile,
 } from "../src/index";
 
 describe("VPK Parser FFI", () => {
@@ -109,6 +117,20 @@ describe("VPK Parser FFI", () => {
         hasInlineData: expect.any(Boolean),
       });
     });
+
+    it("should hash a memory-mapped file identically to its buffer", () => {
+      const fromBuffer = getVpkHashes(testBuffer, testVpkPath);
+      const fromFile = getVpkHashesFromFile(testVpkPath);
+
+      expect(fromFile.fastHash).toBe(fromBuffer.fastHash);
+      expect(fromFile.sha256).toBe(fromBuffer.sha256);
+      expect(fromFile.contentSignature).toBe(fromBuffer.contentSignature);
+      expect(fromFile.merkleRoot).toBe(fromBuffer.merkleRoot);
+    });
+
+    it("should report an error for a missing file", () => {
+      expect(() => getVpkHashesFromFile("data/missing_dir.vpk")).toThrow();
+    });
   });
 
   describe("VPK Parsing", () => {
@@ -150,6 +172,116 @@ describe("VPK Parser FFI", () => {
     });
   });
 
+  describe("Entry Extraction", () => {
+    const entryPath = "sounds/abilities/doorman/doorman_hotel_cast_01.vsnd_c";
+
+    it("should read every entry from a file", () => {
+      const entries = readVpkEntriesFromFile(testVpkPath);
+
+      expect(entries).toHaveLength(3);
+      for (const entry of entries) {
+        expect(entry.data).toBeInstanceOf(Buffer);
+        expect(entry.data.length).toBe(entry.size);
+      }
+    });
+
+    it("should extract the same bytes from a buffer and a file", () => {
+      const fromBuffer = extractVpkEntry(testBuffer, entryPath);
+      const fromFile = extractVpkEntryFromFile(testVpkPath, entryPath);
+
+      expect(fromBuffer.crc32Hex).toBe(fromFile.crc32Hex);
+      expect(fromBuffer.data.equals(fromFile.data)).toBe(true);
+    });
+
+    it("should throw for an unknown entry", () => {
+      expect(() => extractVpkEntry(testBuffer, "missing/file.txt")).toThrow();
+    });
+  });
+
+  describe("Lenient Parsing", () => {
+    it("should throw on a truncated tree by default", () => {
+      const truncated = testBuffer.subarray(0, 64);
+      expect(() => parseVpk(truncated)).toThrow();
+    });
+
+    it("should salvage a truncated tree in lenient mode", () => {
+      const truncated = testBuffer.subarray(0, 64);
+      const parsed = parseVpk(truncated, { lenient: true });
+
+      expect(parsed.partial).toBe(true);
+      expect(parsed.diagnostics.length).toBeGreaterThan(0);
+      expect(typeof parsed.diagnostics[0].reason).toBe("string");
+    });
+
+    it("should not flag an intact archive", () => {
+      const parsed = parseVpk(testBuffer, { lenient: true });
+
+      expect(parsed.partial).toBe(false);
+      expect(parsed.diagnostics).toHaveLength(0);
+    });
+  });
+
+  describe("VPK Similarity", () => {
+    it("should score an archive as identical to itself", () => {
+      const { similaritySignature } = getVpkHashes(testBuffer);
+      expect(
+        getVpkSimilarity(similaritySignature, similaritySignature),
+      ).toBe(1);
+    });
+
+    it("should reject malformed signatures", () => {
+      const { similaritySignature } = getVpkHashes(testBuffer);
+      expect(() => getVpkSimilarity(similaritySignature, "abc")).toThrow();
+    });
+  });
+
+  describe("VPK Diff", () => {
+    it("should report no changes between identical archives", () => {
+      const parsed = parseVpk(testBuffer, { includeEntries: true });
+      const diff = diffVpks(parsed, parsed);
+
+      expect(diff.unchangedCount).toBe(parsed.entries.length);
+      expect(diff.added).toHaveLength(0);
+      expect(diff.removed).toHaveLength(0);
+      expect(diff.modified).toHaveLength(0);
+      expect(diff.renamed).toHaveLength(0);
+    });
+
+    it("should report removed entries against a trimmed copy", () => {
+      const parsed = parseVpk(testBuffer, { includeEntries: true });
+      const trimmed = { ...parsed, entries: parsed.entries.slice(1) };
+      const diff = diffVpks(parsed, trimmed);
+
+      expect(diff.removed.map((entry) => entry.fullPath)).toEqual([
+        parsed.entries[0].fullPath,
+      ]);
+    });
+
+    it("should diff files on disk", () => {
+      const diff = diffVpkFiles(testVpkPath, testVpkPath);
+      expect(diff.unchangedCount).toBe(3);
+    });
+  });
+
+  describe("VPK Verification", () => {
+    it("should verify the tree and whole-file checksums", () => {
+      const report = verifyVpkFile(testVpkPath);
+
+      expect(report.version).toBe(2);
+      expect(report.integrity).toBe("intact");
+      expect(report.treeChecksum?.status).toBe("valid");
+      expect(report.wholeFileChecksum?.status).toBe("valid");
+    });
+
+    it("should flag a modified buffer as corrupted", () => {
+      const modified = Buffer.from(testBuffer);
+      modified[40] = (modified[40] ?? 0) ^ 0xff;
+
+      const report = verifyVpk(modified);
+      expect(report.integrity).toBe("corrupted");
+    });
+  });
+
   describe("VpkParser Class", () => {
     it("should parse VPK using static parse method", () => {
       const parsed = VpkParser.parse(testBuffer, {
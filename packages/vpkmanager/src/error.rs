@@ -10,6 +10,8 @@ pub enum VpkManagerError {
     Decode(#[from] DecodeError),
     #[error("failed to write Source 2 asset: {0}")]
     Encode(#[from] EncodeError),
+    #[error("invalid KV3 text: {0}")]
+    Kv3Text(#[from] kv_parser::KvError),
     #[error("unsupported image: {0}")]
     Image(#[from] image::ImageError),
     #[error("unsupported audio: {0}")]
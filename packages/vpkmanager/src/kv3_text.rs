@@ -0,0 +1,228 @@
+//! Converting binary KV3 to and from its text form.
+//!
+//! `kv-parser` reads and writes KV3 text (`<!-- kv3 ... -->`); this maps its
+//! [`Kv3Value`] onto the binary codec's [`Value`] so a compiled resource's
+//! `DATA` block can be dumped as text, edited by hand and encoded again.
+//!
+//! Value flags (`resource:`, `soundevent:`, ...) exist only on the text side
+//! here: they are dropped on the way to binary, as the binary writer does not
+//! model them, and never produced on the way back.
+//!
+//! Text KV3 has no unsigned integer syntax, so the text round trip is lossy
+//! for [`Value::UInt`]: values up to `i64::MAX` come back as [`Value::Int`],
+//! and only larger ones stay unsigned.
+
+use kv_parser::{KV3_FORMAT_GENERIC, Kv3Document, Kv3Format, Kv3Header, Kv3Value};
+
+use crate::error::{Result, VpkManagerError};
+use crate::source2::kv3::{self, Format, Value};
+
+/// Convert a binary KV3 tree to the text model.
+#[must_use]
+pub fn to_kv3_value(value: &Value) -> Kv3Value {
+    match value {
+        Value::Null => Kv3Value::Null,
+        Value::Bool(b) => Kv3Value::Bool(*b),
+        Value::Int(i) => Kv3Value::Int(*i),
+        Value::UInt(u) => Kv3Value::UInt(*u),
+        Value::Double(d) => Kv3Value::Double(*d),
+        Value::String(s) => Kv3Value::String(s.clone()),
+        Value::Binary(bytes) => Kv3Value::Binary(bytes.clone()),
+        Value::Array(items) => Kv3Value::Array(items.iter().map(to_kv3_value).collect()),
+        Value::Object(pairs) => Kv3Value::Object(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.clone(), to_kv3_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a text KV3 tree to the binary model, dropping value flags.
+#[must_use]
+pub fn from_kv3_value(value: &Kv3Value) -> Value {
+    match value {
+        Kv3Value::Null => Value::Null,
+        Kv3Value::Bool(b) => Value::Bool(*b),
+        Kv3Value::Int(i) => Value::Int(*i),
+        Kv3Value::UInt(u) => Value::UInt(*u),
+        Kv3Value::Double(d) => Value::Double(*d),
+        Kv3Value::String(s) => Value::String(s.clone()),
+        Kv3Value::Binary(bytes) => Value::Binary(bytes.clone()),
+        Kv3Value::Array(items) => Value::Array(items.iter().map(from_kv3_value).collect()),
+        Kv3Value::Object(pairs) => Value::Object(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.clone(), from_kv3_value(value)))
+                .collect(),
+        ),
+        Kv3Value::Flagged { value, .. } => from_kv3_value(value),
+    }
+}
+
+/// Write `value` as KV3 text whose header carries `format`.
+#[must_use]
+pub fn to_text(value: &Value, format: &Format) -> String {
+    let version = format_guid(format);
+    // Binary KV3 stores only the GUID; the name is for people reading the text
+    let name = if version == KV3_FORMAT_GENERIC {
+        "generic"
+    } else {
+        "unknown"
+    };
+    let document = Kv3Document {
+        header: Kv3Header {
+            format: Kv3Format {
+                name: name.to_string(),
+                version,
+            },
+            ..Kv3Header::default()
+        },
+        root: to_kv3_value(value),
+    };
+    document.to_text()
+}
+
+/// Parse KV3 text into a binary KV3 tree and the format GUID from its header.
+pub fn from_text(text: &str) -> Result<(Value, Format)> {
+    let document = Kv3Document::parse(text)?;
+    let guid = &document.header.format.version;
+    let format = parse_format_guid(guid).ok_or_else(|| {
+        VpkManagerError::Invalid(format!("KV3 header has an invalid format GUID: {guid}"))
+    })?;
+    Ok((from_kv3_value(&document.root), format))
+}
+
+/// Decode a binary KV3 DATA payload straight to text.
+pub fn decode_to_text(data: &[u8]) -> Result<String> {
+    let format = Format::from_payload(data)?;
+    let value = kv3::decode(data)?;
+    Ok(to_text(&value, &format))
+}
+
+/// Encode KV3 text as an uncompressed binary KV3 DATA payload.
+pub fn encode_text(text: &str) -> Result<Vec<u8>> {
+    let (value, format) = from_text(text)?;
+    Ok(kv3::encode(&value, &format))
+}
+
+/// The format GUID as text. The binary header stores it the way .NET lays out
+/// a `Guid`: the first three groups little-endian, the last two as-is.
+fn format_guid(format: &Format) -> String {
+    let b = &format.0;
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        u16::from_le_bytes([b[4], b[5]]),
+        u16::from_le_bytes([b[6], b[7]]),
+        b[8],
+        b[9],
+        b[10],
+        b[11],
+        b[12],
+        b[13],
+        b[14],
+        b[15],
+    )
+}
+
+fn parse_format_guid(guid: &str) -> Option<Format> {
+    let groups: Vec<&str> = guid.split('-').collect();
+    let [d1, d2, d3, d4, d5] = groups.as_slice() else {
+        return None;
+    };
+    if [d1.len(), d2.len(), d3.len(), d4.len(), d5.len()] != [8, 4, 4, 4, 12] {
+        return None;
+    }
+
+    let mut bytes = [0u8; 16];
+    bytes[0..4].copy_from_slice(&u32::from_str_radix(d1, 16).ok()?.to_le_bytes());
+    bytes[4..6].copy_from_slice(&u16::from_str_radix(d2, 16).ok()?.to_le_bytes());
+    bytes[6..8].copy_from_slice(&u16::from_str_radix(d3, 16).ok()?.to_le_bytes());
+    let tail = format!("{d4}{d5}");
+    for (i, byte) in bytes[8..].iter_mut().enumerate() {
+        *byte = u8::from_str_radix(tail.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(Format(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_guid_round_trips_in_dotnet_byte_order() {
+        let format = parse_format_guid(KV3_FORMAT_GENERIC).unwrap();
+
+        assert_eq!(&format.0[..4], &[0x7c, 0x16, 0x12, 0x74]);
+        assert_eq!(format.0[8], 0xaf);
+        assert_eq!(format_guid(&format), KV3_FORMAT_GENERIC);
+        assert!(parse_format_guid("7412167c-06e9-4698-aff2").is_none());
+    }
+
+    #[test]
+    fn text_round_trip_keeps_values_and_format() {
+        let format = Format([
+            0x3c, 0xec, 0x42, 0x7c, 0xfa, 0x0c, 0x91, 0x43, 0xa5, 0x8c, 0x4a, 0x77, 0x3d, 0x54,
+            0x7c, 0x50,
+        ]);
+        let value = Value::Object(vec![
+            ("m_nCount".to_string(), Value::Int(-4)),
+            ("m_flScale".to_string(), Value::Double(0.5)),
+            ("m_bEnabled".to_string(), Value::Bool(true)),
+            (
+                "m_Children".to_string(),
+                Value::Array(vec![Value::String("a".to_string()), Value::Null]),
+            ),
+            ("m_Blob".to_string(), Value::Binary(vec![0xde, 0xad])),
+        ]);
+
+        let text = to_text(&value, &format);
+        let (parsed, parsed_format) = from_text(&text).unwrap();
+
+        assert!(text.contains("format:unknown:version{"));
+        assert_eq!(parsed, value);
+        assert_eq!(parsed_format, format);
+    }
+
+    #[test]
+    fn unsigned_integers_that_fit_i64_come_back_signed() {
+        let format = parse_format_guid(KV3_FORMAT_GENERIC).unwrap();
+        let value = Value::Object(vec![
+            ("m_nSmall".to_string(), Value::UInt(5)),
+            ("m_nLarge".to_string(), Value::UInt(u64::MAX)),
+        ]);
+
+        let (parsed, _) = from_text(&to_text(&value, &format)).unwrap();
+
+        assert_eq!(parsed.get("m_nSmall"), Some(&Value::Int(5)));
+        assert_eq!(parsed.get("m_nLarge"), Some(&Value::UInt(u64::MAX)));
+    }
+
+    #[test]
+    fn deeply_nested_text_is_rejected() {
+        let text = format!(
+            "{}\n{}1{}\n",
+            Kv3Header::default(),
+            "[".repeat(10_000),
+            "]".repeat(10_000)
+        );
+
+        assert!(from_text(&text).is_err());
+    }
+
+    #[test]
+    fn flags_are_dropped_on_the_way_to_binary() {
+        let text = format!(
+            "{}\n{{\n\tm_hModel = resource_name:\"models/a.vmdl\"\n}}\n",
+            Kv3Header::default()
+        );
+
+        let (value, _) = from_text(&text).unwrap();
+
+        assert_eq!(
+            value.get("m_hModel").and_then(Value::as_str),
+            Some("models/a.vmdl")
+        );
+    }
+}
@@ -13,6 +13,7 @@
 
 pub mod audio;
 pub mod error;
+pub mod kv3_text;
 pub mod pack;
 pub mod particle_edit;
 pub mod pattern;